@@ -14,10 +14,10 @@ use std::process::Command;
 use glam::UVec2;
 
 use kiln_rhi::{
-    ColorAttachment, CommandBuffer, DepthAttachment, Device, DeviceDesc, Format, GpuAllocation,
-    LoadOp, MAX_FRAMES_IN_FLIGHT, MemoryType, RenderPassDesc, RenderTarget, SampleCount,
-    ShaderModule, ShaderModuleDesc, ShaderStage, StoreOp, Surface, SurfaceDesc, Swapchain,
-    SwapchainDesc, Texture, TextureDesc, TextureDimension, TextureUsage,
+    ColorAttachment, CommandBuffer, DepthAttachment, DepthResolveMode, Device, DeviceDesc, Format,
+    GpuAllocation, LoadOp, MAX_FRAMES_IN_FLIGHT, MemoryType, RenderPassDesc, RenderTarget,
+    SampleCount, ShaderModule, ShaderModuleDesc, ShaderStage, StoreOp, Surface, SurfaceDesc,
+    Swapchain, SwapchainDesc, Texture, TextureDesc, TextureDimension, TextureUsage,
 };
 use raw_window_handle::{HasDisplayHandle, HasWindowHandle};
 use winit::application::ApplicationHandler;
@@ -83,6 +83,7 @@ fn make_depth(device: &Device, format: Format, w: u32, h: u32) -> (Texture, GpuA
         sample_count: SampleCount::S1,
         usage: TextureUsage::DEPTH_STENCIL_ATTACHMENT,
         label: Some("depth".into()),
+        export: None,
     };
     let sa = device.texture_size_align(&desc).expect("depth size_align");
     let mem = device
@@ -180,6 +181,7 @@ impl<E: Example> App<E> {
                 load_op: LoadOp::Clear,
                 store_op: StoreOp::Store,
                 clear_color: self.clear,
+                resolve_target: None,
             }],
             depth_attachment: self.depth.as_ref().map(|(tex, _)| DepthAttachment {
                 target: RenderTarget::Texture(tex.id()),
@@ -187,8 +189,11 @@ impl<E: Example> App<E> {
                 store_op: StoreOp::DontCare, // depth is transient; never read back
                 clear_depth: 1.0,
                 clear_stencil: 0,
+                resolve_target: None,
+                resolve_mode: DepthResolveMode::SampleZero,
             }),
             render_area: [0, 0, extent.x, extent.y],
+            view_mask: 0,
         });
         cmd.set_viewport(0.0, 0.0, extent.x as f32, extent.y as f32, 0.0, 1.0);
         cmd.set_scissor(0, 0, extent.x, extent.y);
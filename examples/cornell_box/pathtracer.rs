@@ -40,34 +40,8 @@ pub const DEFAULT_SAMPLES_PER_FRAME: u32 = 16;
 /// Bytes of transient root data each frame slot may allocate.
 const FRAME_ARENA_SIZE: u64 = 4096;
 
-/// Zero the accumulation buffer on the GPU. Recorded ahead of the trace pass
-/// whenever the film resets, so history invalidation needs no CPU writes and
-/// stays ordered against in-flight frames by queue submission order.
-const CLEAR_SOURCE: &str = /*slang*/
-    r#"
-[shader("compute")]
-[numthreads(256, 1, 1)]
-void clearMain(uint3 tid : SV_DispatchThreadID, uniform ClearRoot* r)
-{
-    if (tid.x < r.count) {
-        r.accum[tid.x] = float4(0.0);
-    }
-}
-"#;
-
-const CLEAR_THREADS: u32 = 256;
-
-gpu_struct! {
-    struct ClearRoot {
-        accum: GpuAddress as "float4*",
-        count: u32 as "uint",
-        _pad: u32 as "uint",
-    }
-}
-
 pub struct PathTracer {
     trace_pso: ComputePso,
-    clear_pso: ComputePso,
     display_pso: GraphicsPso,
     /// One transient-argument arena per frame in flight, reset when its slot records.
     frame_arenas: [BumpAllocator; MAX_FRAMES_IN_FLIGHT],
@@ -110,17 +84,6 @@ impl PathTracer {
             &trace_shader,
         )?;
 
-        let clear_src = format!("{}{}", ClearRoot::SLANG, CLEAR_SOURCE);
-        let clear_shader = common::compile(device, &clear_src, "clearMain", ShaderStage::Compute);
-        let clear_pso = device.create_compute_pso(
-            &ComputePsoDesc {
-                root_constant_size: std::mem::size_of::<GpuAddress>() as u32,
-                threads_per_threadgroup: [CLEAR_THREADS, 1, 1],
-                label: Some("cornell-film-clear".into()),
-            },
-            &clear_shader,
-        )?;
-
         let display_src = format!("{}{}", DisplayRoot::SLANG, display::SOURCE);
         let display_vs = common::compile(device, &display_src, "displayVs", ShaderStage::Vertex);
         let display_fs = common::compile(device, &display_src, "displayFs", ShaderStage::Pixel);
@@ -147,6 +110,7 @@ impl PathTracer {
                         size: FRAME_ARENA_SIZE,
                         memory: MemoryType::Default,
                         label: Some(format!("cornell-frame-arena-{slot}")),
+                        export: None,
                     })
                     .expect("create frame arena"),
             )
@@ -154,7 +118,6 @@ impl PathTracer {
 
         Ok(Self {
             trace_pso,
-            clear_pso,
             display_pso,
             frame_arenas,
             film: Film::new(),
@@ -181,7 +144,7 @@ impl PathTracer {
         let camera = CameraGpu::from_scene(scene, ctx.extent);
         let film_key = camera.film_key(self.target_spp as u64);
         if self.film.prepare(ctx.device, ctx.extent, film_key) {
-            self.record_film_clear(ctx, cmd);
+            self.record_film_clear(cmd);
             eprintln!(
                 "cornell path tracer reset: {}x{}, target spp={}, samples/frame={}, materials={}, lights={}",
                 ctx.extent.x,
@@ -301,23 +264,13 @@ impl PathTracer {
         self.film.tonemapped_rgba8(display::tonemap_channel)
     }
 
-    /// Record the GPU zero of the film's accumulation buffer.
-    fn record_film_clear(&mut self, ctx: &FrameCtx, cmd: &mut CommandBuffer) {
+    /// Record the GPU zero of the film's accumulation buffer. Recorded ahead of
+    /// the trace pass whenever the film resets, so history invalidation needs no
+    /// CPU writes and stays ordered against in-flight frames by queue order.
+    fn record_film_clear(&self, cmd: &mut CommandBuffer) {
         let accum = self.film.accum().expect("film prepared");
-        let pixel_count = ctx.extent.x * ctx.extent.y;
-        let root = self.frame_arenas[ctx.slot]
-            .alloc(std::mem::size_of::<ClearRoot>() as u64, 16)
-            .expect("clear root from frame arena");
-        root.upload(&ClearRoot {
-            accum: accum.gpu(),
-            count: pixel_count,
-            _pad: 0,
-        })
-        .expect("upload clear root");
-
-        cmd.set_compute_pipeline(&self.clear_pso);
-        cmd.dispatch(root.gpu, pixel_count.div_ceil(CLEAR_THREADS), 1, 1);
-        cmd.barrier(StageFlags::COMPUTE, StageFlags::COMPUTE);
+        cmd.fill(accum.gpu(), accum.size(), 0);
+        cmd.barrier(StageFlags::TRANSFER, StageFlags::COMPUTE);
     }
 
     fn log_progress(&self) {
@@ -115,6 +115,7 @@ impl RasterPreview {
                     support_dual_source_blending: false,
                     blendstate: None,
                     root_constant_size: 16,
+                    view_mask: 0,
                     label: Some("cornell-box".into()),
                 },
                 &ms,
@@ -141,6 +142,7 @@ impl RasterPreview {
                         size: 4096,
                         memory: MemoryType::Default,
                         label: Some(format!("cornell-raster-arena-{slot}")),
+                        export: None,
                     })
                     .expect("create raster arena"),
             )
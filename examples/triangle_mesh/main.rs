@@ -62,6 +62,7 @@ impl Example for TriangleMesh {
                     support_dual_source_blending: false,
                     blendstate: None,
                     root_constant_size: 16,
+                    view_mask: 0,
                     label: Some("triangle-mesh".into()),
                 },
                 &ms,
@@ -33,6 +33,10 @@ pub struct MetalAccelerationStructure {
     pub(crate) scratch_buffer: Option<Retained<ProtocolObject<dyn MTLBuffer>>>,
 }
 
+// SAFETY: Metal resources are thread-safe, and nothing here is mutated after the build.
+unsafe impl Send for MetalAccelerationStructure {}
+unsafe impl Sync for MetalAccelerationStructure {}
+
 enum MetalBlasGeometryDescriptor {
     Triangle(Retained<MTL4AccelerationStructureTriangleGeometryDescriptor>),
     Aabb(Retained<MTL4AccelerationStructureBoundingBoxGeometryDescriptor>),
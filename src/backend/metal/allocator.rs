@@ -0,0 +1,173 @@
+//! Sub-allocation of Metal placement heaps.
+//!
+//! Each `MemoryType` owns a list of large placement heaps. Buffers are placed into a heap at a
+//! TLSF-chosen offset with `newBufferWithLength:options:offset:`, so a small allocation no
+//! longer costs its own `MTLHeap`. Requests above `DEDICATED_THRESHOLD` still get a heap of
+//! their own.
+
+use std::collections::HashMap;
+
+use objc2::rc::Retained;
+use objc2::runtime::ProtocolObject;
+use objc2_metal::{
+    MTLBuffer, MTLDevice, MTLHeap, MTLHeapDescriptor, MTLHeapType, MTLResourceOptions,
+};
+
+use crate::backend::tlsf::Tlsf;
+use crate::error::{RhiError, RhiResult};
+use crate::memory::MemoryType;
+
+/// Size of each sub-allocated placement heap.
+pub(crate) const BLOCK_SIZE: u64 = 64 << 20;
+/// Requests larger than this bypass the shared heaps and get a dedicated one.
+pub(crate) const DEDICATED_THRESHOLD: u64 = BLOCK_SIZE / 2;
+/// Alignment used by `create_buffer` when the caller does not ask for one.
+pub(crate) const MIN_BUFFER_ALIGNMENT: u64 = 256;
+
+/// A buffer placed in a heap, plus where it sits.
+pub(crate) struct PlacedBuffer {
+    pub buffer: Retained<ProtocolObject<dyn MTLBuffer>>,
+    pub heap: Retained<ProtocolObject<dyn MTLHeap>>,
+    pub heap_offset: u64,
+}
+
+struct HeapBlock {
+    heap: Retained<ProtocolObject<dyn MTLHeap>>,
+    tlsf: Tlsf,
+}
+
+/// Per-`MemoryType` placement heap lists.
+pub(crate) struct HeapAllocator {
+    blocks: HashMap<MemoryType, Vec<HeapBlock>>,
+}
+
+pub(crate) fn resource_options(memory: MemoryType) -> MTLResourceOptions {
+    match memory {
+        MemoryType::Default | MemoryType::Readback => MTLResourceOptions::StorageModeShared,
+        MemoryType::GpuOnly => MTLResourceOptions::StorageModePrivate,
+    }
+}
+
+/// Create a placement heap of `size` bytes for `memory`.
+pub(crate) fn new_placement_heap(
+    device: &ProtocolObject<dyn MTLDevice>,
+    memory: MemoryType,
+    size: u64,
+) -> RhiResult<Retained<ProtocolObject<dyn MTLHeap>>> {
+    let heap_desc = MTLHeapDescriptor::new();
+    heap_desc.setType(MTLHeapType::Placement);
+    heap_desc.setSize(size as usize);
+    heap_desc.setResourceOptions(resource_options(memory));
+    device
+        .newHeapWithDescriptor(&heap_desc)
+        .ok_or_else(|| RhiError::BufferCreation("Metal heap allocation failed".into()))
+}
+
+impl HeapAllocator {
+    pub(crate) fn new() -> Self {
+        Self {
+            blocks: HashMap::new(),
+        }
+    }
+
+    /// Place a `size`-byte buffer aligned to `align` in a shared heap. Returns `Ok(None)`
+    /// when the request is too large for a block or the placed buffer's GPU address does not
+    /// honour `align`; the caller then falls back to a dedicated heap.
+    pub(crate) fn allocate(
+        &mut self,
+        device: &ProtocolObject<dyn MTLDevice>,
+        memory: MemoryType,
+        size: u64,
+        align: u64,
+    ) -> RhiResult<Option<PlacedBuffer>> {
+        if size > DEDICATED_THRESHOLD {
+            return Ok(None);
+        }
+        let options = resource_options(memory);
+        let size_align = device.heapBufferSizeAndAlignWithLength_options(size as usize, options);
+        let placed_size = size_align.size as u64;
+        let align = align.max(size_align.align as u64);
+
+        let blocks = self.blocks.entry(memory).or_default();
+        let mut placement = blocks.iter_mut().enumerate().find_map(|(index, block)| {
+            block
+                .tlsf
+                .allocate(placed_size, align)
+                .map(|offset| (index, offset))
+        });
+        if placement.is_none() {
+            let heap = new_placement_heap(device, memory, BLOCK_SIZE)?;
+            log::debug!(
+                "allocated {} MiB {memory:?} placement heap",
+                BLOCK_SIZE >> 20
+            );
+            let mut block = HeapBlock {
+                heap,
+                tlsf: Tlsf::new(BLOCK_SIZE),
+            };
+            placement = block
+                .tlsf
+                .allocate(placed_size, align)
+                .map(|offset| (blocks.len(), offset));
+            blocks.push(block);
+        }
+        let Some((index, heap_offset)) = placement else {
+            return Ok(None);
+        };
+
+        let block = &mut blocks[index];
+        let buffer = unsafe {
+            block.heap.newBufferWithLength_options_offset(
+                size as usize,
+                options,
+                heap_offset as usize,
+            )
+        };
+        let Some(buffer) = buffer else {
+            block.tlsf.free(heap_offset);
+            return Err(RhiError::BufferCreation(
+                "Metal placed buffer allocation failed".into(),
+            ));
+        };
+        if !buffer.gpuAddress().is_multiple_of(align) {
+            block.tlsf.free(heap_offset);
+            return Ok(None);
+        }
+        Ok(Some(PlacedBuffer {
+            buffer,
+            heap: block.heap.clone(),
+            heap_offset,
+        }))
+    }
+
+    /// Number of shared heaps held for `memory` and their total size.
+    pub(crate) fn block_usage(&self, memory: MemoryType) -> (u64, u64) {
+        let count = self.blocks.get(&memory).map_or(0, Vec::len) as u64;
+        (count, count * BLOCK_SIZE)
+    }
+
+    /// Return a placed buffer's range to its heap. Returns `false` if `heap` is not one of
+    /// the allocator's shared heaps (i.e. the buffer had a dedicated heap).
+    pub(crate) fn free(&mut self, heap: &ProtocolObject<dyn MTLHeap>, heap_offset: u64) -> bool {
+        for blocks in self.blocks.values_mut() {
+            let Some(index) = blocks
+                .iter()
+                .position(|b| std::ptr::eq(b.heap.as_ref(), heap))
+            else {
+                continue;
+            };
+            blocks[index].tlsf.free(heap_offset);
+            // Keep one empty heap per memory type around for reuse; release the rest.
+            if blocks[index].tlsf.allocated() == 0
+                && blocks
+                    .iter()
+                    .enumerate()
+                    .any(|(i, b)| i != index && b.tlsf.allocated() == 0)
+            {
+                blocks.swap_remove(index);
+            }
+            return true;
+        }
+        false
+    }
+}
@@ -0,0 +1,481 @@
+use std::sync::OnceLock;
+
+use objc2::rc::Retained;
+use objc2::runtime::ProtocolObject;
+use objc2_foundation::NSString;
+use objc2_metal::{
+    MTLAllocation, MTLBuffer, MTLComputePipelineState, MTLCullMode, MTLDepthStencilState,
+    MTLDevice, MTLIndexType, MTLIndirectCommandBuffer, MTLIndirectCommandBufferDescriptor,
+    MTLIndirectCommandType, MTLIndirectComputeCommand, MTLIndirectRenderCommand, MTLPrimitiveType,
+    MTLRenderPipelineState, MTLResidencySet, MTLResidencySetDescriptor, MTLResource,
+    MTLResourceOptions, MTLScissorRect, MTLSize, MTLViewport, MTLWinding,
+};
+
+use super::command::new_depth_stencil_state;
+use crate::error::RhiResult;
+use crate::pipeline::{BlendState, ComputePso, DepthStencilState, GraphicsPso};
+use crate::types::GpuAddress;
+
+/// Bytes per root slot in a bundle's roots buffer; each slot holds one root pointer.
+pub(crate) const BUNDLE_ROOT_SLOT_BYTES: u64 = 16;
+
+/// A command as recorded; bundles are encoded into an indirect command buffer when first
+/// executed, where index buffers can be resolved against the allocation registry.
+#[derive(Clone)]
+enum BundleCommand {
+    Pipeline {
+        pipeline: Retained<ProtocolObject<dyn MTLRenderPipelineState>>,
+        cull_mode: MTLCullMode,
+        winding: MTLWinding,
+        topology: MTLPrimitiveType,
+    },
+    DepthStencil {
+        state: Retained<ProtocolObject<dyn MTLDepthStencilState>>,
+        depth_bias: Option<(f32, f32, f32)>,
+    },
+    Viewport(MTLViewport),
+    Scissor(MTLScissorRect),
+    Draw {
+        root: GpuAddress,
+        vertex_count: u32,
+        instance_count: u32,
+        first_vertex: u32,
+        first_instance: u32,
+    },
+    DrawIndexed {
+        root: GpuAddress,
+        indices: GpuAddress,
+        index_count: u32,
+        instance_count: u32,
+    },
+    ComputePipeline {
+        pipeline: Retained<ProtocolObject<dyn MTLComputePipelineState>>,
+        threads_per_threadgroup: MTLSize,
+    },
+    Dispatch {
+        root: GpuAddress,
+        groups: MTLSize,
+    },
+}
+
+impl BundleCommand {
+    fn is_work(&self) -> bool {
+        matches!(
+            self,
+            BundleCommand::Draw { .. }
+                | BundleCommand::DrawIndexed { .. }
+                | BundleCommand::Dispatch { .. }
+        )
+    }
+}
+
+/// Records bundle commands on the CPU. Pipelines are resolved as they are bound, so blend
+/// state behaves as in a command buffer.
+pub struct MetalBundleEncoder {
+    device: Retained<ProtocolObject<dyn MTLDevice>>,
+    compute: bool,
+    commands: Vec<BundleCommand>,
+    current_blend_state: BlendState,
+    texture_heap_slot: bool,
+    sampler_heap_slot: bool,
+    label: Option<String>,
+}
+
+// SAFETY: the encoder holds only Metal device, pipeline and depth-stencil state objects,
+// which Metal documents as thread-safe, plus plain data.
+unsafe impl Send for MetalBundleEncoder {}
+
+impl MetalBundleEncoder {
+    pub(crate) fn new(
+        device: Retained<ProtocolObject<dyn MTLDevice>>,
+        compute: bool,
+        label: Option<String>,
+    ) -> Self {
+        let mut encoder = Self {
+            device,
+            compute,
+            commands: Vec::new(),
+            current_blend_state: BlendState::default(),
+            texture_heap_slot: false,
+            sampler_heap_slot: false,
+            label,
+        };
+        if !compute {
+            // Indirect render commands inherit no depth-stencil state from the pass.
+            encoder.set_depth_stencil_state(&DepthStencilState::default());
+        }
+        encoder
+    }
+
+    pub fn set_graphics_pipeline(&mut self, pso: &GraphicsPso) {
+        let mtl_pso = match &pso.inner {
+            crate::pipeline::GraphicsPsoInner::Metal(p) => p,
+            #[allow(unreachable_patterns)]
+            _ => unreachable!("wrong backend"),
+        };
+        let has_slot = |slot: usize| mtl_pso.graphics_argument_buffer_slots.contains(&slot);
+        self.texture_heap_slot |= has_slot(1);
+        self.sampler_heap_slot |= has_slot(2);
+        self.commands.push(BundleCommand::Pipeline {
+            pipeline: mtl_pso.pipeline_for_blend(&self.current_blend_state),
+            cull_mode: mtl_pso.cull_mode,
+            winding: mtl_pso.winding,
+            topology: mtl_pso.topology,
+        });
+    }
+
+    pub fn set_compute_pipeline(&mut self, pso: &ComputePso) {
+        let mtl_pso = match &pso.inner {
+            crate::pipeline::ComputePsoInner::Metal(p) => p,
+            #[allow(unreachable_patterns)]
+            _ => unreachable!("wrong backend"),
+        };
+        let has_slot = |slot: usize| mtl_pso.compute_argument_buffer_slots.contains(&slot);
+        self.texture_heap_slot |= has_slot(1);
+        self.sampler_heap_slot |= has_slot(2);
+        let [width, height, depth] = mtl_pso.threads_per_threadgroup.map(|n| n as usize);
+        self.commands.push(BundleCommand::ComputePipeline {
+            pipeline: mtl_pso.pipeline.clone(),
+            threads_per_threadgroup: MTLSize {
+                width,
+                height,
+                depth,
+            },
+        });
+    }
+
+    pub fn set_depth_stencil_state(&mut self, state: &DepthStencilState) {
+        if let Some((state, depth_bias)) = new_depth_stencil_state(&self.device, state) {
+            self.commands
+                .push(BundleCommand::DepthStencil { state, depth_bias });
+        }
+    }
+
+    pub fn set_blend_state(&mut self, state: &BlendState) {
+        self.current_blend_state = state.clone();
+    }
+
+    pub fn set_viewport(
+        &mut self,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        min_depth: f32,
+        max_depth: f32,
+    ) {
+        self.commands.push(BundleCommand::Viewport(MTLViewport {
+            originX: x as f64,
+            originY: y as f64,
+            width: width as f64,
+            height: height as f64,
+            znear: min_depth as f64,
+            zfar: max_depth as f64,
+        }));
+    }
+
+    pub fn set_scissor(&mut self, x: i32, y: i32, width: u32, height: u32) {
+        self.commands.push(BundleCommand::Scissor(MTLScissorRect {
+            x: x.max(0) as usize,
+            y: y.max(0) as usize,
+            width: width as usize,
+            height: height as usize,
+        }));
+    }
+
+    pub fn draw(
+        &mut self,
+        vertex_root: GpuAddress,
+        pixel_root: GpuAddress,
+        vertex_count: u32,
+        instance_count: u32,
+        first_vertex: u32,
+        first_instance: u32,
+    ) {
+        self.commands.push(BundleCommand::Draw {
+            root: shared_root(vertex_root, pixel_root),
+            vertex_count,
+            instance_count,
+            first_vertex,
+            first_instance,
+        });
+    }
+
+    pub fn draw_indexed(
+        &mut self,
+        vertex_root: GpuAddress,
+        pixel_root: GpuAddress,
+        indices: GpuAddress,
+        index_count: u32,
+        instance_count: u32,
+    ) {
+        self.commands.push(BundleCommand::DrawIndexed {
+            root: shared_root(vertex_root, pixel_root),
+            indices,
+            index_count,
+            instance_count,
+        });
+    }
+
+    pub fn dispatch(&mut self, root: GpuAddress, x: u32, y: u32, z: u32) {
+        self.commands.push(BundleCommand::Dispatch {
+            root,
+            groups: MTLSize {
+                width: x as usize,
+                height: y as usize,
+                depth: z as usize,
+            },
+        });
+    }
+
+    pub fn finish(self) -> RhiResult<MetalBundle> {
+        Ok(MetalBundle {
+            device: self.device,
+            compute: self.compute,
+            commands: self.commands,
+            texture_heap_slot: self.texture_heap_slot,
+            sampler_heap_slot: self.sampler_heap_slot,
+            label: self.label,
+            encoded: OnceLock::new(),
+        })
+    }
+}
+
+/// Vertex and fragment stages share one root on Metal, as in `set_root_data`.
+fn shared_root(vertex_root: GpuAddress, pixel_root: GpuAddress) -> GpuAddress {
+    if vertex_root.0 != 0 {
+        vertex_root
+    } else {
+        pixel_root
+    }
+}
+
+/// A recorded bundle. Its indirect command buffer is encoded by the first command buffer
+/// executing it and reused from then on.
+pub struct MetalBundle {
+    device: Retained<ProtocolObject<dyn MTLDevice>>,
+    compute: bool,
+    commands: Vec<BundleCommand>,
+    /// Whether any pipeline reads the bindless texture or sampler heap.
+    pub(crate) texture_heap_slot: bool,
+    pub(crate) sampler_heap_slot: bool,
+    label: Option<String>,
+    encoded: OnceLock<EncodedBundle>,
+}
+
+// SAFETY: as for `MetalBundleEncoder`; the encoded Metal objects are never mutated after
+// `OnceLock` publishes them.
+unsafe impl Send for MetalBundle {}
+unsafe impl Sync for MetalBundle {}
+
+/// A step of executing a bundle: encoder state the indirect commands cannot carry, or
+/// indirect command `n`, whose root slot is `n` in the roots buffer.
+pub(crate) enum BundleStep {
+    Viewport(MTLViewport),
+    Scissor(MTLScissorRect),
+    Execute(usize),
+}
+
+/// The indirect command buffer and root slots of a bundle, resident through a residency set
+/// of their own that executing command buffers use.
+pub(crate) struct EncodedBundle {
+    pub(crate) icb: Retained<ProtocolObject<dyn MTLIndirectCommandBuffer>>,
+    pub(crate) roots: Retained<ProtocolObject<dyn MTLBuffer>>,
+    pub(crate) residency_set: Retained<ProtocolObject<dyn MTLResidencySet>>,
+    pub(crate) steps: Vec<BundleStep>,
+}
+
+impl MetalBundle {
+    /// Encode the bundle on first use. `resolve_buffer` maps an index-buffer address to its
+    /// Metal buffer and offset, which indirect render commands take in place of an address.
+    pub(crate) fn encoded(
+        &self,
+        resolve_buffer: impl Fn(GpuAddress, u64) -> (Retained<ProtocolObject<dyn MTLBuffer>>, u64),
+    ) -> &EncodedBundle {
+        self.encoded.get_or_init(|| self.encode(resolve_buffer))
+    }
+
+    fn encode(
+        &self,
+        resolve_buffer: impl Fn(GpuAddress, u64) -> (Retained<ProtocolObject<dyn MTLBuffer>>, u64),
+    ) -> EncodedBundle {
+        let command_count = self.commands.iter().filter(|c| c.is_work()).count().max(1);
+        let icb_desc = MTLIndirectCommandBufferDescriptor::new();
+        if self.compute {
+            icb_desc.setCommandTypes(MTLIndirectCommandType::ConcurrentDispatch);
+            icb_desc.setMaxKernelBufferBindCount(0);
+        } else {
+            icb_desc.setCommandTypes(
+                MTLIndirectCommandType::Draw | MTLIndirectCommandType::DrawIndexed,
+            );
+            icb_desc.setInheritDepthStencilState(false);
+            icb_desc.setInheritDepthBias(false);
+            icb_desc.setInheritCullMode(false);
+            icb_desc.setInheritFrontFacingWinding(false);
+            icb_desc.setMaxVertexBufferBindCount(0);
+            icb_desc.setMaxFragmentBufferBindCount(0);
+        }
+        // Roots come from the argument table, bound per command by the executing encoder.
+        icb_desc.setInheritBuffers(true);
+        icb_desc.setInheritPipelineState(false);
+        let icb = unsafe {
+            self.device
+                .newIndirectCommandBufferWithDescriptor_maxCommandCount_options(
+                    &icb_desc,
+                    command_count,
+                    MTLResourceOptions::StorageModeShared,
+                )
+        }
+        .expect("Failed to create Metal ICB for bundle");
+        let roots = self
+            .device
+            .newBufferWithLength_options(
+                command_count * BUNDLE_ROOT_SLOT_BYTES as usize,
+                MTLResourceOptions::StorageModeShared,
+            )
+            .expect("Failed to allocate Metal bundle roots buffer");
+        if let Some(label) = &self.label {
+            icb.setLabel(Some(&NSString::from_str(label)));
+        }
+
+        let mut steps = Vec::new();
+        let mut render_pipeline = None;
+        let mut depth_stencil = None;
+        let mut compute_pipeline = None;
+        let mut index = 0;
+        for command in &self.commands {
+            let root = match command {
+                BundleCommand::Pipeline { .. } => {
+                    render_pipeline = Some(command);
+                    continue;
+                }
+                BundleCommand::DepthStencil { .. } => {
+                    depth_stencil = Some(command);
+                    continue;
+                }
+                BundleCommand::ComputePipeline { .. } => {
+                    compute_pipeline = Some(command);
+                    continue;
+                }
+                BundleCommand::Viewport(viewport) => {
+                    steps.push(BundleStep::Viewport(*viewport));
+                    continue;
+                }
+                BundleCommand::Scissor(scissor) => {
+                    steps.push(BundleStep::Scissor(*scissor));
+                    continue;
+                }
+                BundleCommand::Draw { root, .. }
+                | BundleCommand::DrawIndexed { root, .. }
+                | BundleCommand::Dispatch { root, .. } => *root,
+            };
+            if self.compute {
+                let Some(BundleCommand::ComputePipeline {
+                    pipeline,
+                    threads_per_threadgroup,
+                }) = compute_pipeline
+                else {
+                    unreachable!("bundle dispatch without a pipeline");
+                };
+                let BundleCommand::Dispatch { groups, .. } = command else {
+                    unreachable!();
+                };
+                let icc = unsafe { icb.indirectComputeCommandAtIndex(index) };
+                icc.setComputePipelineState(pipeline);
+                icc.concurrentDispatchThreadgroups_threadsPerThreadgroup(
+                    *groups,
+                    *threads_per_threadgroup,
+                );
+            } else {
+                let Some(BundleCommand::Pipeline {
+                    pipeline,
+                    cull_mode,
+                    winding,
+                    topology,
+                }) = render_pipeline
+                else {
+                    unreachable!("bundle draw without a pipeline");
+                };
+                let irc = unsafe { icb.indirectRenderCommandAtIndex(index) };
+                irc.setRenderPipelineState(pipeline);
+                irc.setCullMode(*cull_mode);
+                irc.setFrontFacingWinding(*winding);
+                if let Some(BundleCommand::DepthStencil { state, depth_bias }) = depth_stencil {
+                    irc.setDepthStencilState(Some(state));
+                    let (bias, slope, clamp) = depth_bias.unwrap_or((0.0, 0.0, 0.0));
+                    irc.setDepthBias_slopeScale_clamp(bias, slope, clamp);
+                }
+                match *command {
+                    BundleCommand::Draw {
+                        vertex_count,
+                        instance_count,
+                        first_vertex,
+                        first_instance,
+                        ..
+                    } => unsafe {
+                        irc.drawPrimitives_vertexStart_vertexCount_instanceCount_baseInstance(
+                            *topology,
+                            first_vertex as usize,
+                            vertex_count as usize,
+                            instance_count as usize,
+                            first_instance as usize,
+                        );
+                    },
+                    BundleCommand::DrawIndexed {
+                        indices,
+                        index_count,
+                        instance_count,
+                        ..
+                    } => {
+                        // Indirect render commands take the index buffer as a buffer and
+                        // offset rather than a GPU address.
+                        let (index_buffer, offset) =
+                            resolve_buffer(indices, index_count as u64 * 4);
+                        unsafe {
+                            irc.drawIndexedPrimitives_indexCount_indexType_indexBuffer_indexBufferOffset_instanceCount_baseVertex_baseInstance(
+                                *topology,
+                                index_count as usize,
+                                MTLIndexType::UInt32,
+                                &index_buffer,
+                                offset as usize,
+                                instance_count as usize,
+                                0,
+                                0,
+                            );
+                        }
+                    }
+                    _ => unreachable!(),
+                }
+            }
+            unsafe {
+                let slot = (roots.contents().as_ptr() as *mut u8)
+                    .add(index * BUNDLE_ROOT_SLOT_BYTES as usize);
+                std::ptr::write_unaligned(slot as *mut u64, root.0);
+            }
+            steps.push(BundleStep::Execute(index));
+            index += 1;
+        }
+
+        let residency_set = self
+            .device
+            .newResidencySetWithDescriptor_error(&MTLResidencySetDescriptor::new())
+            .expect("Failed to create Metal bundle residency set");
+        let icb_allocation = unsafe {
+            &*(icb.as_ref() as *const ProtocolObject<dyn MTLIndirectCommandBuffer>
+                as *const ProtocolObject<dyn MTLAllocation>)
+        };
+        let roots_allocation = unsafe {
+            &*(roots.as_ref() as *const ProtocolObject<dyn MTLBuffer>
+                as *const ProtocolObject<dyn MTLAllocation>)
+        };
+        residency_set.addAllocation(icb_allocation);
+        residency_set.addAllocation(roots_allocation);
+        residency_set.commit();
+        EncodedBundle {
+            icb,
+            roots,
+            residency_set,
+            steps,
+        }
+    }
+}
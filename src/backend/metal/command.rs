@@ -1,34 +1,51 @@
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+
 use objc2::rc::Retained;
 use objc2::runtime::ProtocolObject;
-use objc2_foundation::NSRange;
+use objc2_foundation::{NSRange, NSString};
 use objc2_metal::{
-    MTL4ArgumentTable, MTL4ArgumentTableDescriptor, MTL4CommandAllocator, MTL4CommandBuffer,
-    MTL4CommandEncoder, MTL4ComputeCommandEncoder, MTL4RenderCommandEncoder,
-    MTL4RenderPassDescriptor, MTL4VisibilityOptions, MTLAllocation, MTLBuffer,
-    MTLComputePipelineState, MTLDepthStencilState, MTLDevice, MTLGPUAddress, MTLIndexType,
+    MTL4ArgumentTable, MTL4ArgumentTableDescriptor, MTL4BufferRange, MTL4CommandAllocator,
+    MTL4CommandBuffer, MTL4CommandEncoder, MTL4ComputeCommandEncoder, MTL4CounterHeap,
+    MTL4RenderCommandEncoder, MTL4RenderPassDescriptor, MTL4TimestampGranularity,
+    MTL4VisibilityOptions, MTLAllocation, MTLBlitOption, MTLBuffer, MTLComputePipelineState,
+    MTLDepthStencilState, MTLDevice, MTLFence, MTLFunction, MTLGPUAddress, MTLIndexType,
     MTLIndirectCommandBuffer, MTLIndirectCommandBufferDescriptor, MTLIndirectCommandType,
-    MTLLoadAction, MTLOrigin, MTLPrimitiveType, MTLRenderPipelineState, MTLRenderStages,
-    MTLResidencySet, MTLResourceOptions, MTLSamplerState, MTLScissorRect, MTLSize, MTLStages,
-    MTLStencilOperation, MTLStoreAction, MTLTexture, MTLViewport,
+    MTLLoadAction, MTLMultisampleDepthResolveFilter, MTLOrigin, MTLPixelFormat, MTLPrimitiveType,
+    MTLRenderPassAttachmentDescriptor, MTLRenderPipelineDescriptor, MTLRenderPipelineState,
+    MTLRenderStages, MTLResourceOptions, MTLSamplerState, MTLScissorRect, MTLSize, MTLStages,
+    MTLStencilOperation, MTLStoreAction, MTLTexture, MTLTextureType,
+    MTLVertexAmplificationViewMapping, MTLViewport, MTLVisibilityResultMode,
+    MTLVisibilityResultType,
 };
 
 use crate::barrier::{HazardFlags, StageFlags};
+use crate::bundle::{BundleInner, ComputeBundle, RenderBundle};
 use crate::command::{
-    DrawIndirectMultiArgs, LoadOp, RenderPassDesc, RenderTarget, SignalValueDesc, StoreOp,
-    WaitValueDesc,
+    BlitTarget, ClearValue, DepthResolveMode, DrawIndirectMultiArgs, LoadOp, RenderPassDesc,
+    RenderTarget, SignalValueDesc, StoreOp, WaitValueDesc,
 };
 use crate::pipeline::{BlendState, ComputePso, DepthStencilState, GraphicsPso, MeshletPso};
-use crate::texture::{Texture, bytes_per_pixel};
+use crate::query::{QUERY_RESULT_SIZE, QueryPool, QueryPoolInner, QueryType};
+use crate::texture::{
+    ResolvedCopyRegion, Texture, TextureCopyRegion, TextureRegion, format_block, format_compression,
+};
 use crate::types::*;
 
-use super::device::{SharedAllocations, SharedSamplers, SharedTextures};
+use super::bundle::{BUNDLE_ROOT_SLOT_BYTES, BundleStep, EncodedBundle, MetalBundle};
+use super::device::{SharedAllocations, SharedResidency, SharedSamplers, SharedTextures};
+use super::query::MetalQueryPool;
+use super::texture::clear_texel_planes;
 
 const ROOT_TABLE_BYTES: usize = 32;
 const ROOT_TABLE_RING_ENTRIES: usize = 65_536;
 const ROOT_TABLE_RING_BYTES: usize = ROOT_TABLE_BYTES * ROOT_TABLE_RING_ENTRIES;
-const METAL_BINDLESS_TEXTURE_CAPACITY: usize = 65_536;
-const METAL_BINDLESS_SAMPLER_CAPACITY: usize = 256;
+pub(crate) const METAL_BINDLESS_TEXTURE_CAPACITY: usize = 65_536;
+pub(crate) const METAL_BINDLESS_SAMPLER_CAPACITY: usize = 256;
 const MDI_ICB_THREADGROUP_SIZE: usize = 64;
+/// Most bytes `fill` and `clear_texture` stage at once; larger writes repeat the chunk.
+const STAGING_CHUNK_BYTES: u64 = 1 << 20;
 
 #[derive(Clone)]
 struct MetalPipelineBinding {
@@ -51,6 +68,85 @@ struct GeneratedMdiIcb {
     icb_arg_buffer: Retained<ProtocolObject<dyn MTLBuffer>>,
 }
 
+/// The downsample kernels `generate_mips` falls back to, compiled once per device.
+#[derive(Clone)]
+pub(crate) struct MipDownsamplePipelines {
+    pub(crate) float: Retained<ProtocolObject<dyn MTLComputePipelineState>>,
+    pub(crate) uint: Retained<ProtocolObject<dyn MTLComputePipelineState>>,
+}
+
+/// The fullscreen-triangle draw `blit_texture` scales with. Its render pipelines are built
+/// per destination pixel format on first use and shared by the device's command buffers.
+#[derive(Clone)]
+pub(crate) struct BlitPipelines {
+    pub(crate) vertex: Retained<ProtocolObject<dyn MTLFunction>>,
+    pub(crate) fragment: Retained<ProtocolObject<dyn MTLFunction>>,
+    pub(crate) by_format:
+        Arc<Mutex<HashMap<MTLPixelFormat, Retained<ProtocolObject<dyn MTLRenderPipelineState>>>>>,
+}
+
+impl BlitPipelines {
+    fn for_format(
+        &self,
+        device: &ProtocolObject<dyn MTLDevice>,
+        format: MTLPixelFormat,
+    ) -> Retained<ProtocolObject<dyn MTLRenderPipelineState>> {
+        self.by_format
+            .lock()
+            .expect("blit pipelines lock poisoned")
+            .entry(format)
+            .or_insert_with(|| {
+                let desc = MTLRenderPipelineDescriptor::new();
+                desc.setVertexFunction(Some(&self.vertex));
+                desc.setFragmentFunction(Some(&self.fragment));
+                unsafe { desc.colorAttachments().objectAtIndexedSubscript(0) }
+                    .setPixelFormat(format);
+                device
+                    .newRenderPipelineStateWithDescriptor_error(&desc)
+                    .unwrap_or_else(|e| {
+                        panic!("Failed to create Metal blit pipeline for {format:?}: {e}")
+                    })
+            })
+            .clone()
+    }
+}
+
+/// Texel type a compute downsample reads and writes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum MipTexel {
+    Float,
+    Uint,
+}
+
+/// How `generate_mips` fills the mip chain of a format.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum MipGeneration {
+    /// `generateMipmapsForTexture`, for filterable color formats.
+    Blit,
+    /// The downsample kernels, for formats the device cannot filter.
+    Compute(MipTexel),
+}
+
+/// `None` for depth and block-compressed formats, which have no GPU mip generation.
+pub(crate) fn mip_generation(
+    device: &ProtocolObject<dyn MTLDevice>,
+    format: Format,
+) -> Option<MipGeneration> {
+    match format {
+        Format::D16Unorm | Format::D32Float | Format::D24UnormS8Uint | Format::D32FloatS8Uint => {
+            None
+        }
+        Format::R16Uint | Format::R32Uint => Some(MipGeneration::Compute(MipTexel::Uint)),
+        Format::R32Float | Format::R32G32Float | Format::R32G32B32A32Float
+            if !device.supports32BitFloatFiltering() =>
+        {
+            Some(MipGeneration::Compute(MipTexel::Float))
+        }
+        _ if !format_compression(format).is_empty() => None,
+        _ => Some(MipGeneration::Blit),
+    }
+}
+
 #[derive(Clone, Copy)]
 struct PendingQueueBarrier {
     after_queue_stages: MTLStages,
@@ -93,10 +189,8 @@ pub struct MetalCommandBuffer {
     pub(crate) samplers: SharedSamplers,
     /// Shared buffer allocation registry for GPU pointer resolution.
     pub(crate) allocations: SharedAllocations,
-    /// Residency set for command-local allocations.
-    residency_set: Retained<ProtocolObject<dyn MTLResidencySet>>,
-    /// Dirty bit for queue residency commits.
-    residency_dirty: std::rc::Rc<std::cell::Cell<bool>>,
+    /// Device residency set, holding command-local allocations too.
+    residency: SharedResidency,
     /// Keep depth-stencil state objects alive for Metal 4 command lifetime.
     depth_stencil_states: Vec<Retained<ProtocolObject<dyn MTLDepthStencilState>>>,
     /// Active blend state used for pipeline selection.
@@ -132,10 +226,31 @@ pub struct MetalCommandBuffer {
     )>,
     current_viewport: Option<MTLViewport>,
     current_scissor: Option<MTLScissorRect>,
+    /// Mode and offset of the active occlusion query, if any.
+    current_visibility: Option<(MTLVisibilityResultMode, usize)>,
+    /// Visibility result buffer of the last occlusion pool queried, bound to every render
+    /// encoder opened since. Querying another pool swaps it, splitting the open pass.
+    visibility_buffer: Option<Retained<ProtocolObject<dyn MTLBuffer>>>,
     mdi_icb_pipeline: Retained<ProtocolObject<dyn MTLComputePipelineState>>,
     mdi_icb_resources: Vec<GeneratedMdiIcb>,
+    mip_downsample: MipDownsamplePipelines,
+    blit: BlitPipelines,
+    /// Per-level views bound by compute `generate_mips` and `blit_texture`, kept for the
+    /// command buffer's life.
+    mip_views: Vec<Retained<ProtocolObject<dyn MTLTexture>>>,
+    /// Shared buffers sourcing `fill`, `update` and `clear_texture` copies, and scratch
+    /// buffers of format-changing `copy_texture`s, kept for the command buffer's life.
+    staging_buffers: Vec<Retained<ProtocolObject<dyn MTLBuffer>>>,
+    /// Fence ordering `resolve_queries` after earlier work, created on first use.
+    resolve_fence: Option<Retained<ProtocolObject<dyn MTLFence>>>,
 }
 
+// SAFETY: a Metal command buffer, its allocator and encoders may move between threads as
+// long as one thread encodes at a time, which `&mut self` guarantees. `root_table_ptr`
+// points into `root_table_buffer`, owned by this command buffer alone, and the shared
+// registries are behind mutexes.
+unsafe impl Send for MetalCommandBuffer {}
+
 impl MetalCommandBuffer {
     fn resolve_buffer(
         &self,
@@ -143,7 +258,7 @@ impl MetalCommandBuffer {
         size: u64,
     ) -> (Retained<ProtocolObject<dyn MTLBuffer>>, u64) {
         let addr_u64 = addr.0;
-        let allocations = self.allocations.borrow();
+        let allocations = self.allocations.lock().expect("allocations lock poisoned");
         if let Some((&base, alloc)) = allocations.range(..=addr_u64).next_back()
             && addr_u64 + size <= base + alloc.size
         {
@@ -158,7 +273,7 @@ impl MetalCommandBuffer {
     /// non-indirect draws pass their addresses through without any lookup.
     fn allocation_remaining(&self, addr: GpuAddress) -> u64 {
         let addr_u64 = addr.0;
-        let allocations = self.allocations.borrow();
+        let allocations = self.allocations.lock().expect("allocations lock poisoned");
         if let Some((&base, alloc)) = allocations.range(..=addr_u64).next_back()
             && addr_u64 < base + alloc.size
         {
@@ -168,7 +283,7 @@ impl MetalCommandBuffer {
     }
 
     fn resolve_texture(&self, id: TextureId) -> Retained<ProtocolObject<dyn MTLTexture>> {
-        let textures = self.textures.borrow();
+        let textures = self.textures.lock().expect("textures lock poisoned");
         textures
             .get(id.0 as usize)
             .and_then(|t| t.as_ref())
@@ -176,6 +291,22 @@ impl MetalCommandBuffer {
             .clone()
     }
 
+    /// The texture behind a color render target, or any off-screen target.
+    fn render_target_texture(
+        &self,
+        target: &RenderTarget,
+    ) -> Retained<ProtocolObject<dyn MTLTexture>> {
+        match target {
+            RenderTarget::SwapchainImage(_) => self
+                .drawable_texture
+                .clone()
+                .expect("swapchain drawable missing"),
+            RenderTarget::Texture(id) => self.resolve_texture(*id),
+            RenderTarget::TextureSubresource { texture, .. }
+            | RenderTarget::TextureLayers { texture, .. } => self.resolve_texture(*texture),
+        }
+    }
+
     fn refresh_argument_table(&mut self) {
         unsafe {
             self.argument_table.setAddress_atIndex(0, 0);
@@ -204,8 +335,7 @@ impl MetalCommandBuffer {
             &*(buffer as *const ProtocolObject<dyn MTLBuffer>
                 as *const ProtocolObject<dyn MTLAllocation>)
         };
-        self.residency_set.addAllocation(allocation);
-        self.residency_dirty.set(true);
+        self.residency.add(allocation);
     }
 
     fn remove_allocation_from_residency(&self, buffer: &ProtocolObject<dyn MTLBuffer>) {
@@ -213,8 +343,7 @@ impl MetalCommandBuffer {
             &*(buffer as *const ProtocolObject<dyn MTLBuffer>
                 as *const ProtocolObject<dyn MTLAllocation>)
         };
-        self.residency_set.removeAllocation(allocation);
-        self.residency_dirty.set(true);
+        self.residency.remove(allocation);
     }
 
     fn make_command_buffer_resource(
@@ -230,6 +359,32 @@ impl MetalCommandBuffer {
         buffer
     }
 
+    /// A shared buffer holding `bytes`, alive until the command buffer is dropped.
+    fn staging_buffer(&mut self, bytes: &[u8]) -> Retained<ProtocolObject<dyn MTLBuffer>> {
+        let buffer =
+            self.make_command_buffer_resource(bytes.len(), MTLResourceOptions::StorageModeShared);
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                bytes.as_ptr(),
+                buffer.contents().as_ptr() as *mut u8,
+                bytes.len(),
+            );
+        }
+        self.staging_buffers.push(buffer.clone());
+        buffer
+    }
+
+    /// Open a compute encoder for copies, after any barrier enqueued before them.
+    fn begin_copy_encoder(&mut self) -> Retained<ProtocolObject<dyn MTL4ComputeCommandEncoder>> {
+        self.end_active_encoders();
+        let encoder = self
+            .command_buffer
+            .computeCommandEncoder()
+            .expect("Failed to create Metal 4 copy encoder");
+        self.apply_pending_queue_barrier_compute(&encoder);
+        encoder
+    }
+
     fn primitive_id(topology: MTLPrimitiveType) -> u32 {
         if topology == MTLPrimitiveType::Point {
             0
@@ -281,8 +436,7 @@ impl MetalCommandBuffer {
             &*(icb.as_ref() as *const ProtocolObject<dyn MTLIndirectCommandBuffer>
                 as *const ProtocolObject<dyn MTLAllocation>)
         };
-        self.residency_set.addAllocation(icb_allocation);
-        self.residency_dirty.set(true);
+        self.residency.add(icb_allocation);
 
         let range_buffer = self.make_command_buffer_resource(
             std::mem::size_of::<objc2_metal::MTLIndirectCommandBufferExecutionRange>(),
@@ -367,8 +521,7 @@ impl MetalCommandBuffer {
     fn ensure_heap_buffer(
         slot: &mut Option<Retained<ProtocolObject<dyn MTLBuffer>>>,
         device: &ProtocolObject<dyn MTLDevice>,
-        residency_set: &ProtocolObject<dyn MTLResidencySet>,
-        residency_dirty: &std::rc::Rc<std::cell::Cell<bool>>,
+        residency: &SharedResidency,
         required_len: usize,
         label: &'static str,
     ) {
@@ -380,8 +533,7 @@ impl MetalCommandBuffer {
                 &*(old.as_ref() as *const ProtocolObject<dyn MTLBuffer>
                     as *const ProtocolObject<dyn MTLAllocation>)
             };
-            residency_set.removeAllocation(old_alloc);
-            residency_dirty.set(true);
+            residency.remove(old_alloc);
         }
         let new_buf = device
             .newBufferWithLength_options(required_len, MTLResourceOptions::StorageModeShared)
@@ -390,8 +542,7 @@ impl MetalCommandBuffer {
             &*(new_buf.as_ref() as *const ProtocolObject<dyn MTLBuffer>
                 as *const ProtocolObject<dyn MTLAllocation>)
         };
-        residency_set.addAllocation(new_alloc);
-        residency_dirty.set(true);
+        residency.add(new_alloc);
         *slot = Some(new_buf);
     }
 
@@ -401,7 +552,7 @@ impl MetalCommandBuffer {
     /// appropriate `texture<...>` / `sampler` handle using Metal 4 syntax.
     fn refresh_bindless_heaps(&mut self, refresh_textures: bool, refresh_samplers: bool) {
         if refresh_textures {
-            let textures = self.textures.borrow();
+            let textures = self.textures.lock().expect("textures lock poisoned");
             assert!(
                 textures.len() <= METAL_BINDLESS_TEXTURE_CAPACITY,
                 "Metal texture heap overflow: {} textures exceed capacity {}",
@@ -411,8 +562,7 @@ impl MetalCommandBuffer {
             Self::ensure_heap_buffer(
                 &mut self.texture_heap_buffer,
                 &self.device,
-                &self.residency_set,
-                &self.residency_dirty,
+                &self.residency,
                 METAL_BINDLESS_TEXTURE_CAPACITY * std::mem::size_of::<u64>(),
                 "texture heap",
             );
@@ -432,7 +582,7 @@ impl MetalCommandBuffer {
         }
 
         if refresh_samplers {
-            let samplers = self.samplers.borrow();
+            let samplers = self.samplers.lock().expect("samplers lock poisoned");
             assert!(
                 samplers.len() <= METAL_BINDLESS_SAMPLER_CAPACITY,
                 "Metal sampler heap overflow: {} samplers exceed capacity {}",
@@ -442,8 +592,7 @@ impl MetalCommandBuffer {
             Self::ensure_heap_buffer(
                 &mut self.sampler_heap_buffer,
                 &self.device,
-                &self.residency_set,
-                &self.residency_dirty,
+                &self.residency,
                 METAL_BINDLESS_SAMPLER_CAPACITY * std::mem::size_of::<u64>(),
                 "sampler heap",
             );
@@ -453,8 +602,11 @@ impl MetalCommandBuffer {
                 .expect("sampler heap buffer must exist after allocation")
                 .contents()
                 .as_ptr() as *mut u64;
-            for (i, sampler) in samplers.iter().enumerate() {
-                let id = sampler.gpuResourceID().to_raw();
+            for (i, sampler_opt) in samplers.iter().enumerate() {
+                let id = sampler_opt
+                    .as_deref()
+                    .map(|s| s.gpuResourceID().to_raw())
+                    .unwrap_or(0);
                 unsafe { std::ptr::write_unaligned(dst.add(i), id) };
             }
         }
@@ -484,15 +636,16 @@ impl MetalCommandBuffer {
         command_buffer: Retained<ProtocolObject<dyn MTL4CommandBuffer>>,
         command_allocator: Retained<ProtocolObject<dyn MTL4CommandAllocator>>,
         device: Retained<ProtocolObject<dyn MTLDevice>>,
-        residency_set: Retained<ProtocolObject<dyn objc2_metal::MTLResidencySet>>,
-        residency_dirty: std::rc::Rc<std::cell::Cell<bool>>,
+        residency: SharedResidency,
         textures: SharedTextures,
         samplers: SharedSamplers,
         allocations: SharedAllocations,
         mdi_icb_pipeline: Retained<ProtocolObject<dyn MTLComputePipelineState>>,
+        mip_downsample: MipDownsamplePipelines,
+        blit: BlitPipelines,
     ) -> crate::error::RhiResult<Self> {
         command_buffer.beginCommandBufferWithAllocator(&command_allocator);
-        command_buffer.useResidencySet(&residency_set);
+        command_buffer.useResidencySet(residency.set());
 
         let root_table_buffer = device
             .newBufferWithLength_options(
@@ -510,12 +663,13 @@ impl MetalCommandBuffer {
             &*(root_table_buffer.as_ref() as *const ProtocolObject<dyn MTLBuffer>
                 as *const ProtocolObject<dyn MTLAllocation>)
         };
-        residency_set.addAllocation(allocation);
-        residency_dirty.set(true);
+        residency.add(allocation);
 
         let desc = MTL4ArgumentTableDescriptor::new();
         desc.setMaxBufferBindCount(6);
-        desc.setMaxTextureBindCount(0);
+        // Two texture slots for the `generate_mips` downsample kernels; `blit_texture` uses
+        // the first.
+        desc.setMaxTextureBindCount(2);
         desc.setMaxSamplerStateBindCount(0);
         desc.setInitializeBindings(true);
         desc.setSupportAttributeStrides(false);
@@ -548,8 +702,7 @@ impl MetalCommandBuffer {
             textures,
             samplers,
             allocations,
-            residency_set,
-            residency_dirty,
+            residency,
             depth_stencil_states: Vec::new(),
             current_blend_state: BlendState::default(),
             current_threads_per_threadgroup: [1, 1, 1],
@@ -577,8 +730,15 @@ impl MetalCommandBuffer {
             current_depth_stencil: None,
             current_viewport: None,
             current_scissor: None,
+            current_visibility: None,
+            visibility_buffer: None,
             mdi_icb_pipeline,
             mdi_icb_resources: Vec::new(),
+            mip_downsample,
+            blit,
+            mip_views: Vec::new(),
+            staging_buffers: Vec::new(),
+            resolve_fence: None,
         };
 
         cmd.refresh_argument_table();
@@ -598,6 +758,7 @@ impl MetalCommandBuffer {
         self.current_depth_stencil = None;
         self.current_viewport = None;
         self.current_scissor = None;
+        self.current_visibility = None;
     }
 
     /// Build a Metal render command encoder for `desc`. When `force_load` is set, every
@@ -622,9 +783,12 @@ impl MetalCommandBuffer {
                         attachment.setTexture(Some(tex));
                     }
                 }
-                RenderTarget::Texture(id) => {
-                    let tex = self.resolve_texture(*id);
+                target => {
+                    let tex = self.render_target_texture(target);
                     attachment.setTexture(Some(&tex));
+                    if let Some((mip, layer)) = subresource_base(target) {
+                        select_subresource(&attachment, &tex, mip, layer);
+                    }
                 }
             }
 
@@ -638,10 +802,14 @@ impl MetalCommandBuffer {
                 }
             });
 
-            attachment.setStoreAction(match color_att.store_op {
-                StoreOp::Store => MTLStoreAction::Store,
-                StoreOp::DontCare => MTLStoreAction::DontCare,
-            });
+            attachment.setStoreAction(initial_store_action(color_att.store_op));
+            if let Some(target) = &color_att.resolve_target {
+                let tex = self.render_target_texture(target);
+                attachment.setResolveTexture(Some(&tex));
+                if let Some((mip, layer)) = subresource_base(target) {
+                    select_resolve_subresource(&attachment, &tex, mip, layer);
+                }
+            }
 
             if !force_load && color_att.load_op == LoadOp::Clear {
                 let c = color_att.clear_color;
@@ -664,9 +832,12 @@ impl MetalCommandBuffer {
                         depth.setTexture(Some(depth_tex));
                     }
                 }
-                RenderTarget::Texture(id) => {
-                    let tex = self.resolve_texture(*id);
+                target => {
+                    let tex = self.render_target_texture(target);
                     depth.setTexture(Some(&tex));
+                    if let Some((mip, layer)) = subresource_base(target) {
+                        select_subresource(&depth, &tex, mip, layer);
+                    }
                 }
             }
 
@@ -680,34 +851,102 @@ impl MetalCommandBuffer {
                 }
             });
 
-            depth.setStoreAction(match depth_att.store_op {
-                StoreOp::Store => MTLStoreAction::Store,
-                StoreOp::DontCare => MTLStoreAction::DontCare,
-            });
+            depth.setStoreAction(initial_store_action(depth_att.store_op));
+            if let Some(target) = &depth_att.resolve_target {
+                let tex = match target {
+                    RenderTarget::SwapchainImage(_) => self
+                        .depth_texture
+                        .clone()
+                        .expect("swapchain depth texture missing"),
+                    target => self.render_target_texture(target),
+                };
+                depth.setResolveTexture(Some(&tex));
+                if let Some((mip, layer)) = subresource_base(target) {
+                    select_resolve_subresource(&depth, &tex, mip, layer);
+                }
+                depth.setDepthResolveFilter(match depth_att.resolve_mode {
+                    DepthResolveMode::SampleZero => MTLMultisampleDepthResolveFilter::Sample0,
+                    DepthResolveMode::Min => MTLMultisampleDepthResolveFilter::Min,
+                    DepthResolveMode::Max => MTLMultisampleDepthResolveFilter::Max,
+                });
+            }
 
             if !force_load && depth_att.load_op == LoadOp::Clear {
                 depth.setClearDepth(depth_att.clear_depth as f64);
             }
         }
 
+        let layer_count = desc.layer_count();
+        if layer_count > 1 {
+            pass_desc.setRenderTargetArrayLength(layer_count as usize);
+        }
+
+        // Occlusion queries add to their results, which `QueryPool::reset` zeroes, so one
+        // query can span a split pass.
+        if let Some(buffer) = &self.visibility_buffer {
+            unsafe { pass_desc.setVisibilityResultBuffer(Some(buffer)) };
+            pass_desc.setVisibilityResultType(MTLVisibilityResultType::Accumulate);
+        }
+
         // Create the render command encoder
         let encoder = self
             .command_buffer
             .renderCommandEncoderWithDescriptor(&pass_desc)
             .expect("Failed to create Metal render command encoder");
 
+        // Multiview: amplify each vertex once per view, sending view `i` to layer `i`.
+        if desc.view_mask != 0 {
+            let mappings: Vec<MTLVertexAmplificationViewMapping> = (0..u32::BITS)
+                .filter(|bit| desc.view_mask & (1 << bit) != 0)
+                .map(|bit| MTLVertexAmplificationViewMapping {
+                    viewportArrayIndexOffset: 0,
+                    renderTargetArrayIndexOffset: bit,
+                })
+                .collect();
+            unsafe {
+                encoder.setVertexAmplificationCount_viewMappings(mappings.len(), mappings.as_ptr());
+            }
+        }
+
         self.apply_pending_queue_barrier_render(&encoder);
 
         encoder
     }
 
     pub fn end_render_pass(&mut self) {
-        if let Some(encoder) = self.render_encoder.take() {
-            encoder.endEncoding();
-        }
+        self.end_render_encoder(false);
         self.render_pass_desc = None;
     }
 
+    /// End the open render encoder, first settling the store actions resolving attachments
+    /// left deferred: resolve at the real end of the pass, but only store the samples when
+    /// `split` reopens the pass afterwards, so the reopened encoder can load them.
+    fn end_render_encoder(&mut self, split: bool) {
+        let Some(encoder) = self.render_encoder.take() else {
+            return;
+        };
+        if let Some(desc) = &self.render_pass_desc {
+            let final_action = |op: StoreOp| {
+                if split {
+                    MTLStoreAction::Store
+                } else {
+                    to_mtl_store_action(op)
+                }
+            };
+            for (i, ca) in desc.color_attachments.iter().enumerate() {
+                if ca.store_op.resolves() {
+                    unsafe { encoder.setColorStoreAction_atIndex(final_action(ca.store_op), i) };
+                }
+            }
+            if let Some(da) = &desc.depth_attachment
+                && da.store_op.resolves()
+            {
+                encoder.setDepthStoreAction(final_action(da.store_op));
+            }
+        }
+        encoder.endEncoding();
+    }
+
     /// Re-apply the tracked render state to a freshly-opened encoder. Used after an MDI
     /// split, where the new encoder starts with no pipeline/depth/viewport/scissor bound.
     fn reapply_render_state(&mut self, encoder: &ProtocolObject<dyn MTL4RenderCommandEncoder>) {
@@ -732,6 +971,9 @@ impl MetalCommandBuffer {
         if let Some(scissor) = self.current_scissor {
             encoder.setScissorRect(scissor);
         }
+        if let Some((mode, offset)) = self.current_visibility {
+            unsafe { encoder.setVisibilityResultMode_offset(mode, offset) };
+        }
     }
 
     pub fn set_graphics_pipeline(&mut self, pso: &GraphicsPso) {
@@ -813,46 +1055,7 @@ impl MetalCommandBuffer {
     }
 
     pub fn set_depth_stencil_state(&mut self, state: &DepthStencilState) {
-        let ds_desc = objc2_metal::MTLDepthStencilDescriptor::new();
-
-        let depth_test = state.depth_mode.contains(DepthFlags::READ);
-        let depth_write = state.depth_mode.contains(DepthFlags::WRITE);
-
-        if depth_test {
-            ds_desc.setDepthCompareFunction(compare_op_to_mtl(state.depth_test));
-        } else {
-            ds_desc.setDepthCompareFunction(objc2_metal::MTLCompareFunction::Always);
-        }
-        ds_desc.setDepthWriteEnabled(depth_write);
-
-        if state.stencil_enabled() {
-            let front = make_stencil_descriptor(
-                &state.stencil_front,
-                state.stencil_read_mask,
-                state.stencil_write_mask,
-            );
-            let back = make_stencil_descriptor(
-                &state.stencil_back,
-                state.stencil_read_mask,
-                state.stencil_write_mask,
-            );
-            ds_desc.setFrontFaceStencil(Some(&front));
-            ds_desc.setBackFaceStencil(Some(&back));
-        } else {
-            ds_desc.setFrontFaceStencil(None);
-            ds_desc.setBackFaceStencil(None);
-        }
-
-        if let Some(ds_state) = self.device.newDepthStencilStateWithDescriptor(&ds_desc) {
-            let depth_bias = if state.depth_bias != 0.0 || state.depth_bias_slope_factor != 0.0 {
-                Some((
-                    state.depth_bias,
-                    state.depth_bias_slope_factor,
-                    state.depth_bias_clamp,
-                ))
-            } else {
-                None
-            };
+        if let Some((ds_state, depth_bias)) = new_depth_stencil_state(&self.device, state) {
             let encoder = self
                 .render_encoder
                 .as_ref()
@@ -1128,9 +1331,7 @@ impl MetalCommandBuffer {
         // The ICB must be generated by a compute pass before the render encoder runs it, so
         // split the pass: end the render encoder, dispatch ICB generation, then reopen with
         // Load actions and execute the generated commands.
-        if let Some(encoder) = self.render_encoder.take() {
-            encoder.endEncoding();
-        }
+        self.end_render_encoder(true);
 
         let compute = self
             .command_buffer
@@ -1191,9 +1392,142 @@ impl MetalCommandBuffer {
         encoder.endEncoding();
     }
 
-    pub fn copy_to_texture(&mut self, texture_gpu: GpuAddress, src: GpuAddress, texture: &Texture) {
-        let (mtl_texture, buffer, offset, size, origin, bytes_per_row, bytes_per_image) =
-            self.prepare_texture_copy(texture_gpu, src, texture, "copy_to_texture");
+    pub fn fill(&mut self, dst: GpuAddress, size: u64, pattern: u32) {
+        let (dst_buffer, dst_offset) = self.resolve_buffer(dst, size);
+        let bytes = pattern.to_le_bytes();
+        // Metal fills bytes; other patterns repeat a staged chunk of them.
+        let staged = (bytes != [bytes[0]; 4]).then(|| {
+            let chunk_len = size.min(STAGING_CHUNK_BYTES) as usize;
+            self.staging_buffer(&bytes.repeat(chunk_len / 4))
+        });
+        let encoder = self.begin_copy_encoder();
+        match staged {
+            None => unsafe {
+                encoder.fillBuffer_range_value(
+                    &dst_buffer,
+                    NSRange::new(dst_offset as usize, size as usize),
+                    bytes[0],
+                );
+            },
+            Some(chunk) => {
+                let mut done = 0;
+                while done < size {
+                    let len = (size - done).min(chunk.length() as u64);
+                    unsafe {
+                        encoder.copyFromBuffer_sourceOffset_toBuffer_destinationOffset_size(
+                            &chunk,
+                            0,
+                            &dst_buffer,
+                            (dst_offset + done) as usize,
+                            len as usize,
+                        );
+                    }
+                    done += len;
+                }
+            }
+        }
+        encoder.endEncoding();
+    }
+
+    pub fn update(&mut self, dst: GpuAddress, data: &[u8]) {
+        let (dst_buffer, dst_offset) = self.resolve_buffer(dst, data.len() as u64);
+        let staged = self.staging_buffer(data);
+        let encoder = self.begin_copy_encoder();
+        unsafe {
+            encoder.copyFromBuffer_sourceOffset_toBuffer_destinationOffset_size(
+                &staged,
+                0,
+                &dst_buffer,
+                dst_offset as usize,
+                data.len(),
+            );
+        }
+        encoder.endEncoding();
+    }
+
+    /// Metal has no texture clear outside a render pass, so this copies a staged buffer of
+    /// the clear texel into every subresource; that needs no attachment or write usage.
+    pub fn clear_texture(
+        &mut self,
+        texture: &Texture,
+        mips: Range<u32>,
+        layers: Range<u32>,
+        value: ClearValue,
+    ) {
+        let desc = texture.desc();
+        let mtl_texture = self.resolve_texture(texture.id());
+        let [base_width, base_height, _] = desc.mip_extent(mips.start);
+        let planes = clear_texel_planes(desc.format, value);
+        let options: &[MTLBlitOption] = if planes.len() == 2 {
+            &[
+                MTLBlitOption::DepthFromDepthStencil,
+                MTLBlitOption::StencilFromDepthStencil,
+            ]
+        } else {
+            &[MTLBlitOption::None]
+        };
+        // A band of whole rows of the largest mip cleared, copied down each subresource in
+        // turn; narrower mips read the start of each row.
+        let staged: Vec<_> = planes
+            .iter()
+            .map(|texel| {
+                let row_bytes = base_width as u64 * texel.len() as u64;
+                let rows = (STAGING_CHUNK_BYTES / row_bytes).clamp(1, base_height as u64);
+                let band = self.staging_buffer(&texel.repeat((base_width as u64 * rows) as usize));
+                (row_bytes as usize, rows as u32, band)
+            })
+            .collect();
+
+        let encoder = self.begin_copy_encoder();
+        for ((row_bytes, band_rows, band), &option) in staged.iter().zip(options) {
+            for mip in mips.clone() {
+                let [width, height, depth] = desc.mip_extent(mip);
+                for layer in layers.clone() {
+                    for z in 0..depth {
+                        for y in (0..height).step_by(*band_rows as usize) {
+                            let rows = (*band_rows).min(height - y);
+                            unsafe {
+                                encoder.copyFromBuffer_sourceOffset_sourceBytesPerRow_sourceBytesPerImage_sourceSize_toTexture_destinationSlice_destinationLevel_destinationOrigin_options(
+                                    band,
+                                    0,
+                                    *row_bytes,
+                                    row_bytes * rows as usize,
+                                    MTLSize {
+                                        width: width as usize,
+                                        height: rows as usize,
+                                        depth: 1,
+                                    },
+                                    &mtl_texture,
+                                    layer as usize,
+                                    mip as usize,
+                                    MTLOrigin {
+                                        x: 0,
+                                        y: y as usize,
+                                        z: z as usize,
+                                    },
+                                    option,
+                                );
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        encoder.endEncoding();
+    }
+
+    pub fn copy_to_texture(
+        &mut self,
+        texture_gpu: GpuAddress,
+        src: GpuAddress,
+        texture: &Texture,
+        regions: &[TextureCopyRegion],
+    ) {
+        if regions.is_empty() {
+            return;
+        }
+        let (mtl_texture, buffer, offset, resolved) =
+            self.prepare_texture_copy(texture_gpu, src, texture, regions, "copy_to_texture");
 
         self.end_active_encoders();
         let encoder = self
@@ -1202,18 +1536,25 @@ impl MetalCommandBuffer {
             .expect("Failed to create Metal 4 copy encoder");
         // Apply any barrier enqueued before this copy (copy encoders are discrete).
         self.apply_pending_queue_barrier_compute(&encoder);
-        unsafe {
-            encoder.copyFromBuffer_sourceOffset_sourceBytesPerRow_sourceBytesPerImage_sourceSize_toTexture_destinationSlice_destinationLevel_destinationOrigin(
-                &buffer,
-                offset as usize,
-                bytes_per_row,
-                bytes_per_image,
-                size,
-                &mtl_texture,
-                0,
-                0,
-                origin,
-            );
+        for region in &resolved {
+            let (size, origin) = size_origin(region.offset, region.extent);
+            for layer in 0..region.layer_count {
+                let layer_offset =
+                    offset + region.buffer_offset + layer as u64 * region.layer_stride;
+                unsafe {
+                    encoder.copyFromBuffer_sourceOffset_sourceBytesPerRow_sourceBytesPerImage_sourceSize_toTexture_destinationSlice_destinationLevel_destinationOrigin(
+                        &buffer,
+                        layer_offset as usize,
+                        region.row_pitch as usize,
+                        region.image_stride as usize,
+                        size,
+                        &mtl_texture,
+                        (region.base_layer + layer) as usize,
+                        region.mip as usize,
+                        origin,
+                    );
+                }
+            }
         }
         encoder.endEncoding();
     }
@@ -1223,9 +1564,13 @@ impl MetalCommandBuffer {
         dst: GpuAddress,
         texture_gpu: GpuAddress,
         texture: &Texture,
+        regions: &[TextureCopyRegion],
     ) {
-        let (mtl_texture, buffer, offset, size, origin, bytes_per_row, bytes_per_image) =
-            self.prepare_texture_copy(texture_gpu, dst, texture, "copy_from_texture");
+        if regions.is_empty() {
+            return;
+        }
+        let (mtl_texture, buffer, offset, resolved) =
+            self.prepare_texture_copy(texture_gpu, dst, texture, regions, "copy_from_texture");
 
         self.end_active_encoders();
         let encoder = self
@@ -1234,74 +1579,369 @@ impl MetalCommandBuffer {
             .expect("Failed to create Metal 4 copy encoder");
         // Apply any barrier enqueued before this copy (copy encoders are discrete).
         self.apply_pending_queue_barrier_compute(&encoder);
-        unsafe {
-            encoder.copyFromTexture_sourceSlice_sourceLevel_sourceOrigin_sourceSize_toBuffer_destinationOffset_destinationBytesPerRow_destinationBytesPerImage(
-                &mtl_texture,
-                0,
-                0,
-                origin,
-                size,
-                &buffer,
-                offset as usize,
-                bytes_per_row,
-                bytes_per_image,
+        for region in &resolved {
+            let (size, origin) = size_origin(region.offset, region.extent);
+            for layer in 0..region.layer_count {
+                let layer_offset =
+                    offset + region.buffer_offset + layer as u64 * region.layer_stride;
+                unsafe {
+                    encoder.copyFromTexture_sourceSlice_sourceLevel_sourceOrigin_sourceSize_toBuffer_destinationOffset_destinationBytesPerRow_destinationBytesPerImage(
+                        &mtl_texture,
+                        (region.base_layer + layer) as usize,
+                        region.mip as usize,
+                        origin,
+                        size,
+                        &buffer,
+                        layer_offset as usize,
+                        region.row_pitch as usize,
+                        region.image_stride as usize,
+                    );
+                }
+            }
+        }
+        encoder.endEncoding();
+    }
+
+    /// Metal copies between textures of one pixel format only, so a copy reinterpreting
+    /// another format's bits bounces through a scratch buffer instead.
+    pub fn copy_texture(
+        &mut self,
+        src: &Texture,
+        src_region: &TextureRegion,
+        dst: &Texture,
+        dst_region: &TextureRegion,
+    ) {
+        let src_texture = self.resolve_texture(src.id());
+        let dst_texture = self.resolve_texture(dst.id());
+        let (size, src_origin) = size_origin(src_region.offset, src_region.extent);
+        let (_, dst_origin) = size_origin(dst_region.offset, dst_region.extent);
+        let src_slice = |layer| (src_region.base_layer + layer) as usize;
+        let dst_slice = |layer| (dst_region.base_layer + layer) as usize;
+        let layers = 0..src_region.layer_count;
+
+        if src.desc().format == dst.desc().format {
+            let encoder = self.begin_copy_encoder();
+            for layer in layers {
+                unsafe {
+                    encoder.copyFromTexture_sourceSlice_sourceLevel_sourceOrigin_sourceSize_toTexture_destinationSlice_destinationLevel_destinationOrigin(
+                        &src_texture,
+                        src_slice(layer),
+                        src_region.mip as usize,
+                        src_origin,
+                        size,
+                        &dst_texture,
+                        dst_slice(layer),
+                        dst_region.mip as usize,
+                        dst_origin,
+                    );
+                }
+            }
+            encoder.endEncoding();
+            return;
+        }
+
+        let block = format_block(src.desc().format).expect("copy_texture formats share a block");
+        let (row_blocks, rows) = block.blocks(src_region.extent[0], src_region.extent[1]);
+        let row_bytes = row_blocks as usize * block.bytes as usize;
+        let image_bytes = row_bytes * rows as usize;
+        let layer_bytes = image_bytes * size.depth;
+        let scratch = self.make_command_buffer_resource(
+            layer_bytes * layers.len(),
+            MTLResourceOptions::StorageModePrivate,
+        );
+        self.staging_buffers.push(scratch.clone());
+
+        let encoder = self.begin_copy_encoder();
+        for layer in layers.clone() {
+            unsafe {
+                encoder.copyFromTexture_sourceSlice_sourceLevel_sourceOrigin_sourceSize_toBuffer_destinationOffset_destinationBytesPerRow_destinationBytesPerImage(
+                    &src_texture,
+                    src_slice(layer),
+                    src_region.mip as usize,
+                    src_origin,
+                    size,
+                    &scratch,
+                    layer as usize * layer_bytes,
+                    row_bytes,
+                    image_bytes,
+                );
+            }
+        }
+        encoder.barrierAfterEncoderStages_beforeEncoderStages_visibilityOptions(
+            MTLStages::Blit,
+            MTLStages::Blit,
+            MTL4VisibilityOptions::Device,
+        );
+        for layer in layers {
+            unsafe {
+                encoder.copyFromBuffer_sourceOffset_sourceBytesPerRow_sourceBytesPerImage_sourceSize_toTexture_destinationSlice_destinationLevel_destinationOrigin(
+                    &scratch,
+                    layer as usize * layer_bytes,
+                    row_bytes,
+                    image_bytes,
+                    size,
+                    &dst_texture,
+                    dst_slice(layer),
+                    dst_region.mip as usize,
+                    dst_origin,
+                );
+            }
+        }
+        encoder.endEncoding();
+    }
+
+    /// Metal has no scaling copy, so this draws a triangle over the destination region of
+    /// each layer, sampling the source mip level through a 2D-array view. The draws run in
+    /// the fragment stage, so barriers naming transfers are widened to it on either side.
+    pub fn blit_texture(
+        &mut self,
+        src: &Texture,
+        src_region: &TextureRegion,
+        dst: BlitTarget,
+        dst_region: &TextureRegion,
+        filter: FilterMode,
+    ) {
+        let desc = src.desc();
+        let src_texture = self.resolve_texture(src.id());
+        let src_view = unsafe {
+            src_texture
+                .newTextureViewWithPixelFormat_textureType_levels_slices(
+                    src_texture.pixelFormat(),
+                    MTLTextureType::Type2DArray,
+                    NSRange::new(src_region.mip as usize, 1),
+                    NSRange::new(0, desc.layer_count() as usize),
+                )
+                .expect("Failed to create Metal blit source view")
+        };
+        let (dst_texture, dst_region, load_action) = match dst {
+            BlitTarget::Texture(texture) => (
+                self.resolve_texture(texture.id()),
+                *dst_region,
+                MTLLoadAction::Load,
+            ),
+            BlitTarget::SwapchainImage(_) => {
+                let drawable = self
+                    .drawable_texture
+                    .clone()
+                    .expect("blit_texture into a swapchain image needs a swapchain command buffer");
+                let extent = [drawable.width() as u32, drawable.height() as u32, 1];
+                (
+                    drawable,
+                    dst_region.resolve_in(extent, "blit_texture destination"),
+                    MTLLoadAction::DontCare,
+                )
+            }
+        };
+        // Formats the device cannot filter are read nearest, as Vulkan blits them.
+        let linear = filter == FilterMode::Linear
+            && mip_generation(&self.device, desc.format) == Some(MipGeneration::Blit);
+        let pipeline = self
+            .blit
+            .for_format(&self.device, dst_texture.pixelFormat());
+        let [width, height, _] = desc.mip_extent(src_region.mip);
+        let uv = [
+            src_region.offset[0] as f32 / width as f32,
+            src_region.offset[1] as f32 / height as f32,
+            src_region.extent[0] as f32 / width as f32,
+            src_region.extent[1] as f32 / height as f32,
+        ];
+
+        self.end_active_encoders();
+        if let Some(pending) = self.pending_queue_barrier.as_mut() {
+            pending.before_stages |= MTLStages::Fragment;
+        }
+        for layer in 0..src_region.layer_count {
+            let params: Vec<u8> = uv
+                .iter()
+                .flat_map(|v| v.to_ne_bytes())
+                .chain((src_region.base_layer + layer).to_ne_bytes())
+                .chain((linear as u32).to_ne_bytes())
+                .collect();
+            let params = self.staging_buffer(&params);
+
+            let pass_desc = MTL4RenderPassDescriptor::new();
+            let attachment = unsafe { pass_desc.colorAttachments().objectAtIndexedSubscript(0) };
+            attachment.setTexture(Some(&dst_texture));
+            select_subresource(
+                &attachment,
+                &dst_texture,
+                dst_region.mip,
+                dst_region.base_layer + layer,
             );
+            attachment.setLoadAction(load_action);
+            attachment.setStoreAction(MTLStoreAction::Store);
+            let encoder = self
+                .command_buffer
+                .renderCommandEncoderWithDescriptor(&pass_desc)
+                .expect("Failed to create Metal blit encoder");
+            self.apply_pending_queue_barrier_render(&encoder);
+
+            encoder.setRenderPipelineState(&pipeline);
+            encoder.setViewport(MTLViewport {
+                originX: dst_region.offset[0] as f64,
+                originY: dst_region.offset[1] as f64,
+                width: dst_region.extent[0] as f64,
+                height: dst_region.extent[1] as f64,
+                znear: 0.0,
+                zfar: 1.0,
+            });
+            unsafe {
+                self.argument_table
+                    .setTexture_atIndex(src_view.gpuResourceID(), 0);
+                self.argument_table
+                    .setAddress_atIndex(params.gpuAddress(), 0);
+            }
+            encoder.setArgumentTable_atStages(&self.argument_table, MTLRenderStages::Fragment);
+            unsafe {
+                encoder.drawPrimitives_vertexStart_vertexCount(MTLPrimitiveType::Triangle, 0, 3);
+            }
+            if layer + 1 == src_region.layer_count {
+                encoder.barrierAfterStages_beforeQueueStages_visibilityOptions(
+                    MTLStages::Fragment,
+                    MTLStages::All,
+                    MTL4VisibilityOptions::Device,
+                );
+            }
+            encoder.endEncoding();
+        }
+        self.mip_views.push(src_view);
+    }
+
+    pub fn generate_mips(&mut self, texture: &Texture) {
+        let desc = texture.desc();
+        let generation = mip_generation(&self.device, desc.format).unwrap_or_else(|| {
+            panic!(
+                "generate_mips: {:?} textures have no GPU mip generation",
+                desc.format
+            )
+        });
+        let mtl_texture = self.resolve_texture(texture.id());
+
+        self.end_active_encoders();
+        let encoder = self
+            .command_buffer
+            .computeCommandEncoder()
+            .expect("Failed to create Metal 4 mip encoder");
+        self.apply_pending_queue_barrier_compute(&encoder);
+        match generation {
+            MipGeneration::Blit => unsafe { encoder.generateMipmapsForTexture(&mtl_texture) },
+            MipGeneration::Compute(texel) => {
+                self.encode_mip_downsample(&encoder, &mtl_texture, texture, texel)
+            }
         }
         encoder.endEncoding();
     }
 
-    /// Validate the texture address, resolve the linear buffer, and compute the row /
-    /// image strides for a buffer↔texture copy on the Metal 4 compute encoder.
+    /// Downsample each level of `mtl_texture` into the next with a kernel dispatch, through
+    /// 2D-array views of single levels (cube faces are slices of such a view).
+    fn encode_mip_downsample(
+        &mut self,
+        encoder: &ProtocolObject<dyn MTL4ComputeCommandEncoder>,
+        mtl_texture: &ProtocolObject<dyn MTLTexture>,
+        texture: &Texture,
+        texel: MipTexel,
+    ) {
+        let desc = texture.desc();
+        assert!(
+            matches!(
+                desc.dimension,
+                TextureDimension::D2
+                    | TextureDimension::D2Array
+                    | TextureDimension::Cube
+                    | TextureDimension::CubeArray
+            ),
+            "generate_mips: {:?} {:?} textures need a filterable format on Metal",
+            desc.dimension,
+            desc.format
+        );
+        let pipeline = match texel {
+            MipTexel::Float => &self.mip_downsample.float,
+            MipTexel::Uint => &self.mip_downsample.uint,
+        };
+        let layers = desc.layer_count() as usize;
+        let level_views: Vec<_> = (0..desc.mip_levels as usize)
+            .map(|mip| unsafe {
+                mtl_texture
+                    .newTextureViewWithPixelFormat_textureType_levels_slices(
+                        mtl_texture.pixelFormat(),
+                        MTLTextureType::Type2DArray,
+                        NSRange::new(mip, 1),
+                        NSRange::new(0, layers),
+                    )
+                    .expect("Failed to create Metal mip level view")
+            })
+            .collect();
+
+        encoder.setComputePipelineState(pipeline);
+        for mip in 1..desc.mip_levels {
+            if mip > 1 {
+                encoder.barrierAfterEncoderStages_beforeEncoderStages_visibilityOptions(
+                    MTLStages::Dispatch,
+                    MTLStages::Dispatch,
+                    MTL4VisibilityOptions::Device,
+                );
+            }
+            let [width, height, _] = desc.mip_extent(mip);
+            unsafe {
+                self.argument_table
+                    .setTexture_atIndex(level_views[mip as usize - 1].gpuResourceID(), 0);
+                self.argument_table
+                    .setTexture_atIndex(level_views[mip as usize].gpuResourceID(), 1);
+            }
+            encoder.setArgumentTable(Some(&self.argument_table));
+            encoder.dispatchThreads_threadsPerThreadgroup(
+                MTLSize {
+                    width: width as usize,
+                    height: height as usize,
+                    depth: layers,
+                },
+                MTLSize {
+                    width: 8,
+                    height: 8,
+                    depth: 1,
+                },
+            );
+        }
+        self.mip_views.extend(level_views);
+    }
+
+    /// Validate the texture address and regions, and resolve the linear buffer (which must
+    /// hold every region) for a buffer↔texture copy on the Metal 4 compute encoder.
     #[allow(clippy::type_complexity)]
     fn prepare_texture_copy(
         &self,
         texture_gpu: GpuAddress,
         buffer_gpu: GpuAddress,
         texture: &Texture,
+        regions: &[TextureCopyRegion],
         op: &'static str,
     ) -> (
         Retained<ProtocolObject<dyn MTLTexture>>,
         Retained<ProtocolObject<dyn MTLBuffer>>,
         u64,
-        MTLSize,
-        MTLOrigin,
-        usize,
-        usize,
+        Vec<ResolvedCopyRegion>,
     ) {
         assert_eq!(
             texture_gpu,
             texture.gpu(),
             "{op} texture_gpu must match the address used to create the texture"
         );
+        let resolved: Vec<_> = regions
+            .iter()
+            .map(|r| r.resolve(texture.desc(), op))
+            .collect();
+        let size = resolved
+            .iter()
+            .map(|r| r.buffer_offset + r.byte_size)
+            .max()
+            .unwrap_or(0);
         let mtl_texture = self.resolve_texture(texture.id());
-        let width = texture.desc().width;
-        let height = texture.desc().height;
-        let bpp = bytes_per_pixel(texture.desc().format)
-            .unwrap_or_else(|| panic!("Unsupported texture format for {op}"));
-        let bytes_per_row = width as usize * bpp;
-        let bytes_per_image = bytes_per_row * height as usize;
-        let (buffer, offset) = self.resolve_buffer(buffer_gpu, bytes_per_image as u64);
-        let size = MTLSize {
-            width: width as usize,
-            height: height as usize,
-            depth: 1,
-        };
-        let origin = MTLOrigin { x: 0, y: 0, z: 0 };
-        (
-            mtl_texture,
-            buffer,
-            offset,
-            size,
-            origin,
-            bytes_per_row,
-            bytes_per_image,
-        )
+        let (buffer, offset) = self.resolve_buffer(buffer_gpu, size);
+        (mtl_texture, buffer, offset, resolved)
     }
 
     pub(crate) fn end_active_encoders(&mut self) {
-        if let Some(encoder) = self.render_encoder.take() {
-            encoder.endEncoding();
-        }
+        self.end_render_encoder(false);
         if let Some(encoder) = self.compute_encoder.take() {
             encoder.endEncoding();
         }
@@ -1320,6 +1960,17 @@ impl MetalCommandBuffer {
         self.encode_barrier(src, dst, Some(hazard));
     }
 
+    /// Placement-heap resources are untracked, so a stage barrier is all an alias switch
+    /// needs; the textures' contents are simply undefined afterwards.
+    pub fn alias_barrier(
+        &mut self,
+        src: StageFlags,
+        dst: StageFlags,
+        _textures: &[&crate::texture::Texture],
+    ) {
+        self.encode_barrier(src, dst, None);
+    }
+
     pub fn signal_after(&mut self, src: StageFlags, hazard: HazardFlags) {
         if let Some((pending_src, pending_hazard)) = self.pending_split_barrier.as_mut() {
             *pending_src |= src;
@@ -1475,6 +2126,236 @@ impl MetalCommandBuffer {
         }
     }
 
+    // -- Bundles --
+
+    /// Bundle commands inherit the argument table, so each one is executed on its own after
+    /// binding its root slot at index 0, the way `draw` binds `set_root_data`'s slot.
+    pub fn execute_render_bundles(&mut self, desc: &RenderPassDesc, bundles: &[&RenderBundle]) {
+        self.begin_render_pass(desc);
+        let encoder = self
+            .render_encoder
+            .clone()
+            .expect("No active render encoder");
+        for bundle in bundles {
+            let bundle = metal_bundle(bundle.inner());
+            let encoded = self.prepare_bundle(bundle);
+            for step in &encoded.steps {
+                match *step {
+                    BundleStep::Viewport(viewport) => encoder.setViewport(viewport),
+                    BundleStep::Scissor(scissor) => encoder.setScissorRect(scissor),
+                    BundleStep::Execute(index) => unsafe {
+                        self.argument_table
+                            .setAddress_atIndex(bundle_root_slot(encoded, index), 0);
+                        encoder.setArgumentTable_atStages(
+                            &self.argument_table,
+                            MTLRenderStages::Vertex | MTLRenderStages::Fragment,
+                        );
+                        encoder.executeCommandsInBuffer_withRange(
+                            &encoded.icb,
+                            NSRange::new(index, 1),
+                        );
+                    },
+                }
+            }
+        }
+        self.end_render_pass();
+    }
+
+    pub fn execute_compute_bundles(&mut self, bundles: &[&ComputeBundle]) {
+        self.end_active_encoders();
+        let encoder = self
+            .command_buffer
+            .computeCommandEncoder()
+            .expect("Failed to create Metal compute command encoder");
+        self.apply_pending_queue_barrier_compute(&encoder);
+        for bundle in bundles {
+            let bundle = metal_bundle(bundle.inner());
+            let encoded = self.prepare_bundle(bundle);
+            for step in &encoded.steps {
+                let BundleStep::Execute(index) = *step else {
+                    unreachable!("render state in a compute bundle");
+                };
+                unsafe {
+                    self.argument_table
+                        .setAddress_atIndex(bundle_root_slot(encoded, index), 0);
+                    encoder.setArgumentTable(Some(&self.argument_table));
+                    encoder.executeCommandsInBuffer_withRange(&encoded.icb, NSRange::new(index, 1));
+                }
+            }
+        }
+        encoder.endEncoding();
+    }
+
+    /// Encode `bundle` if no command buffer has yet, make it resident for this one, and
+    /// bind the bindless heaps its pipelines read.
+    fn prepare_bundle<'a>(&mut self, bundle: &'a MetalBundle) -> &'a EncodedBundle {
+        let encoded = bundle.encoded(|addr, size| self.resolve_buffer(addr, size));
+        self.command_buffer.useResidencySet(&encoded.residency_set);
+        self.active_texture_heap_slot_enabled = bundle.texture_heap_slot;
+        self.active_sampler_heap_slot_enabled = bundle.sampler_heap_slot;
+        self.refresh_bindless_heaps(bundle.texture_heap_slot, bundle.sampler_heap_slot);
+        self.refresh_argument_table();
+        encoded
+    }
+
+    // -- Queries --
+
+    /// Inside an encoder the timestamp comes from it (after `stage` for render encoders);
+    /// between encoders from the command buffer, once all earlier work is complete.
+    pub fn write_timestamp(&mut self, stage: StageFlags, pool: &QueryPool, index: u32) {
+        let heap = counter_heap(pool);
+        let index = index as usize;
+        let granularity = MTL4TimestampGranularity::Precise;
+        unsafe {
+            if let Some(encoder) = &self.render_encoder {
+                let after = if stage == StageFlags::VERTEX_SHADER {
+                    MTLRenderStages::Vertex
+                } else {
+                    MTLRenderStages::Fragment
+                };
+                encoder.writeTimestampWithGranularity_afterStage_intoHeap_atIndex(
+                    granularity,
+                    after,
+                    heap,
+                    index,
+                );
+            } else if let Some(encoder) = &self.compute_encoder {
+                encoder.writeTimestampWithGranularity_intoHeap_atIndex(granularity, heap, index);
+            } else {
+                self.command_buffer
+                    .writeTimestampIntoHeap_atIndex(heap, index);
+            }
+        }
+    }
+
+    /// Occlusion queries count into the visibility result buffer of their pool, which is
+    /// bound when the render encoder opens: querying a pool the open encoder lacks splits
+    /// the pass to bind it.
+    pub fn begin_query(&mut self, pool: &QueryPool, index: u32) {
+        let buffer = match metal_query_pool(pool) {
+            MetalQueryPool::Visibility(visibility) => &visibility.buffer,
+            MetalQueryPool::Counters(_) => unreachable!("Metal has only occlusion query pools"),
+        };
+        let desc = self
+            .render_pass_desc
+            .clone()
+            .expect("occlusion queries must be recorded inside a render pass");
+        let bound = self
+            .visibility_buffer
+            .as_ref()
+            .is_some_and(|bound| bound.gpuAddress() == buffer.gpuAddress());
+        if !bound {
+            self.visibility_buffer = Some(buffer.clone());
+            self.end_render_encoder(true);
+            let encoder = self.begin_metal_render_encoder(&desc, true);
+            self.reapply_render_state(&encoder);
+            self.render_encoder = Some(encoder);
+        }
+        let mode = if pool.ty() == QueryType::BinaryOcclusion {
+            MTLVisibilityResultMode::Boolean
+        } else {
+            MTLVisibilityResultMode::Counting
+        };
+        let offset = (index as u64 * QUERY_RESULT_SIZE) as usize;
+        self.current_visibility = Some((mode, offset));
+        let encoder = self
+            .render_encoder
+            .as_ref()
+            .expect("render encoder is open");
+        unsafe { encoder.setVisibilityResultMode_offset(mode, offset) };
+    }
+
+    pub fn end_query(&mut self, _pool: &QueryPool, _index: u32) {
+        self.current_visibility = None;
+        let encoder = self
+            .render_encoder
+            .as_ref()
+            .expect("occlusion queries must be recorded inside a render pass");
+        unsafe { encoder.setVisibilityResultMode_offset(MTLVisibilityResultMode::Disabled, 0) };
+    }
+
+    /// Counter heaps resolve outside any encoder, so an empty one waits for all earlier work
+    /// and updates a fence the resolve waits on. Visibility results are copied once all
+    /// earlier work is done.
+    pub fn resolve_queries(&mut self, pool: &QueryPool, range: Range<u32>, dst: GpuAddress) {
+        if let MetalQueryPool::Visibility(visibility) = metal_query_pool(pool) {
+            let size = range.len() as u64 * QUERY_RESULT_SIZE;
+            let (dst_buffer, dst_offset) = self.resolve_buffer(dst, size);
+            let encoder = self.begin_copy_encoder();
+            encoder.barrierAfterQueueStages_beforeStages_visibilityOptions(
+                MTLStages::All,
+                MTLStages::Blit,
+                MTL4VisibilityOptions::Device,
+            );
+            unsafe {
+                encoder.copyFromBuffer_sourceOffset_toBuffer_destinationOffset_size(
+                    &visibility.buffer,
+                    (range.start as u64 * QUERY_RESULT_SIZE) as usize,
+                    &dst_buffer,
+                    dst_offset as usize,
+                    size as usize,
+                );
+            }
+            encoder.endEncoding();
+            return;
+        }
+        let fence = self
+            .resolve_fence
+            .get_or_insert_with(|| {
+                self.device
+                    .newFence()
+                    .expect("Failed to create Metal query resolve fence")
+            })
+            .clone();
+        let encoder = self.begin_copy_encoder();
+        encoder.barrierAfterQueueStages_beforeStages_visibilityOptions(
+            MTLStages::All,
+            MTLStages::Dispatch,
+            MTL4VisibilityOptions::Device,
+        );
+        encoder.updateFence_afterEncoderStages(&fence, MTLStages::Dispatch);
+        encoder.endEncoding();
+        unsafe {
+            self.command_buffer
+                .resolveCounterHeap_withRange_intoBuffer_waitFence_updateFence(
+                    counter_heap(pool),
+                    NSRange::new(range.start as usize, range.len()),
+                    MTL4BufferRange {
+                        bufferAddress: dst.0,
+                        length: range.len() as u64 * QUERY_RESULT_SIZE,
+                    },
+                    Some(&fence),
+                    None,
+                );
+        }
+    }
+
+    // -- Debug labels --
+
+    /// Groups live on the command buffer, not an encoder, since encoders open and close
+    /// under them. Metal has no group colors.
+    pub fn push_debug_group(&mut self, name: &str, _color: [f32; 4]) {
+        self.command_buffer
+            .pushDebugGroup(&NSString::from_str(name));
+    }
+
+    pub fn pop_debug_group(&mut self) {
+        self.command_buffer.popDebugGroup();
+    }
+
+    /// A signpost in the open encoder, or an empty group between encoders.
+    pub fn insert_debug_marker(&mut self, name: &str) {
+        let name = NSString::from_str(name);
+        if let Some(encoder) = &self.render_encoder {
+            encoder.insertDebugSignpost(&name);
+        } else if let Some(encoder) = &self.compute_encoder {
+            encoder.insertDebugSignpost(&name);
+        } else {
+            self.command_buffer.pushDebugGroup(&name);
+            self.command_buffer.popDebugGroup();
+        }
+    }
+
     // -- Mesh shader (meshlet) pipeline + draws --
 
     /// `gpuSetPipeline` for mesh pipelines — binds PSO, refreshes bindless heaps, binds argument table.
@@ -1648,7 +2529,7 @@ impl MetalCommandBuffer {
             .expect("Failed to create compute encoder for BLAS build");
 
         let scratch_addr = scratch.gpuAddress();
-        let scratch_range = objc2_metal::MTL4BufferRange {
+        let scratch_range = MTL4BufferRange {
             bufferAddress: scratch_addr,
             length: !0u64, // full remaining length
         };
@@ -1688,7 +2569,7 @@ impl MetalCommandBuffer {
 
         let instance_desc = MTL4InstanceAccelerationStructureDescriptor::new();
         unsafe {
-            instance_desc.setInstanceDescriptorBuffer(objc2_metal::MTL4BufferRange {
+            instance_desc.setInstanceDescriptorBuffer(MTL4BufferRange {
                 bufferAddress: desc.instance_buffer.0,
                 // Indirect instance-descriptor layout (see device.write_tlas_instance), not
                 // the Vulkan-shaped TlasInstance.
@@ -1707,7 +2588,7 @@ impl MetalCommandBuffer {
             .expect("Failed to create compute encoder for TLAS build");
 
         let scratch_addr = scratch.gpuAddress();
-        let scratch_range = objc2_metal::MTL4BufferRange {
+        let scratch_range = MTL4BufferRange {
             bufferAddress: scratch_addr,
             length: !0u64,
         };
@@ -1733,9 +2614,140 @@ impl Drop for MetalCommandBuffer {
         if let Some(sampler_heap) = self.sampler_heap_buffer.as_ref() {
             self.remove_allocation_from_residency(sampler_heap.as_ref());
         }
+        for staging in &self.staging_buffers {
+            self.remove_allocation_from_residency(staging.as_ref());
+        }
     }
 }
 
+/// Mip level and first layer a subresource render target starts at.
+fn subresource_base(target: &RenderTarget) -> Option<(u32, u32)> {
+    match *target {
+        RenderTarget::TextureSubresource { mip, layer, .. } => Some((mip, layer)),
+        RenderTarget::TextureLayers {
+            mip, base_layer, ..
+        } => Some((mip, base_layer)),
+        _ => None,
+    }
+}
+
+/// Point `attachment` at one mip level and layer of `texture`; the layer of a 3D texture
+/// is a depth plane.
+fn select_subresource(
+    attachment: &MTLRenderPassAttachmentDescriptor,
+    texture: &ProtocolObject<dyn MTLTexture>,
+    mip: u32,
+    layer: u32,
+) {
+    attachment.setLevel(mip as usize);
+    if texture.textureType() == MTLTextureType::Type3D {
+        attachment.setDepthPlane(layer as usize);
+    } else {
+        attachment.setSlice(layer as usize);
+    }
+}
+
+/// Like `select_subresource`, for the attachment's resolve texture.
+fn select_resolve_subresource(
+    attachment: &MTLRenderPassAttachmentDescriptor,
+    texture: &ProtocolObject<dyn MTLTexture>,
+    mip: u32,
+    layer: u32,
+) {
+    attachment.setResolveLevel(mip as usize);
+    if texture.textureType() == MTLTextureType::Type3D {
+        attachment.setResolveDepthPlane(layer as usize);
+    } else {
+        attachment.setResolveSlice(layer as usize);
+    }
+}
+
+/// Store action an encoder is created with. Resolving attachments are left `Unknown` and
+/// settled by `end_render_encoder`.
+fn initial_store_action(op: StoreOp) -> MTLStoreAction {
+    if op.resolves() {
+        MTLStoreAction::Unknown
+    } else {
+        to_mtl_store_action(op)
+    }
+}
+
+fn to_mtl_store_action(op: StoreOp) -> MTLStoreAction {
+    match op {
+        StoreOp::Store => MTLStoreAction::Store,
+        StoreOp::DontCare => MTLStoreAction::DontCare,
+        StoreOp::Resolve => MTLStoreAction::MultisampleResolve,
+        StoreOp::StoreAndResolve => MTLStoreAction::StoreAndMultisampleResolve,
+    }
+}
+
+fn size_origin(offset: [u32; 3], extent: [u32; 3]) -> (MTLSize, MTLOrigin) {
+    (
+        MTLSize {
+            width: extent[0] as usize,
+            height: extent[1] as usize,
+            depth: extent[2] as usize,
+        },
+        MTLOrigin {
+            x: offset[0] as usize,
+            y: offset[1] as usize,
+            z: offset[2] as usize,
+        },
+    )
+}
+
+/// Build the Metal depth-stencil state for `state`, with the depth bias to set alongside it
+/// (None when the state has no bias).
+pub(crate) fn new_depth_stencil_state(
+    device: &ProtocolObject<dyn MTLDevice>,
+    state: &DepthStencilState,
+) -> Option<(
+    Retained<ProtocolObject<dyn MTLDepthStencilState>>,
+    Option<(f32, f32, f32)>,
+)> {
+    let ds_desc = objc2_metal::MTLDepthStencilDescriptor::new();
+
+    let depth_test = state.depth_mode.contains(DepthFlags::READ);
+    let depth_write = state.depth_mode.contains(DepthFlags::WRITE);
+
+    if depth_test {
+        ds_desc.setDepthCompareFunction(compare_op_to_mtl(state.depth_test));
+    } else {
+        ds_desc.setDepthCompareFunction(objc2_metal::MTLCompareFunction::Always);
+    }
+    ds_desc.setDepthWriteEnabled(depth_write);
+
+    if state.stencil_enabled() {
+        let front = make_stencil_descriptor(
+            &state.stencil_front,
+            state.stencil_read_mask,
+            state.stencil_write_mask,
+        );
+        let back = make_stencil_descriptor(
+            &state.stencil_back,
+            state.stencil_read_mask,
+            state.stencil_write_mask,
+        );
+        ds_desc.setFrontFaceStencil(Some(&front));
+        ds_desc.setBackFaceStencil(Some(&back));
+    } else {
+        ds_desc.setFrontFaceStencil(None);
+        ds_desc.setBackFaceStencil(None);
+    }
+
+    let ds_state = device.newDepthStencilStateWithDescriptor(&ds_desc)?;
+    let depth_bias = if state.depth_bias != 0.0 || state.depth_bias_slope_factor != 0.0 {
+        Some((
+            state.depth_bias,
+            state.depth_bias_slope_factor,
+            state.depth_bias_clamp,
+        ))
+    } else {
+        None
+    };
+    Some((ds_state, depth_bias))
+}
+
 fn make_stencil_descriptor(
     desc: &crate::pipeline::StencilDesc,
     read_mask: u8,
@@ -1777,6 +2789,33 @@ fn compare_op_to_mtl(op: CompareOp) -> objc2_metal::MTLCompareFunction {
     }
 }
 
+fn metal_bundle(bundle: &BundleInner) -> &MetalBundle {
+    match bundle {
+        BundleInner::Metal(b) => b,
+        #[allow(unreachable_patterns)]
+        _ => unreachable!("bundle backend does not match command buffer backend"),
+    }
+}
+
+fn bundle_root_slot(encoded: &EncodedBundle, index: usize) -> MTLGPUAddress {
+    encoded.roots.gpuAddress() + index as u64 * BUNDLE_ROOT_SLOT_BYTES
+}
+
+fn metal_query_pool(pool: &QueryPool) -> &MetalQueryPool {
+    match &pool.inner {
+        QueryPoolInner::Metal(p) => p,
+        #[allow(unreachable_patterns)]
+        _ => unreachable!("query pool backend does not match command buffer backend"),
+    }
+}
+
+fn counter_heap(pool: &QueryPool) -> &ProtocolObject<dyn MTL4CounterHeap> {
+    match metal_query_pool(pool) {
+        MetalQueryPool::Counters(heap) => heap,
+        MetalQueryPool::Visibility(_) => unreachable!("timestamps need a counter heap"),
+    }
+}
+
 fn to_mtl_stages(flags: StageFlags) -> MTLStages {
     let mut stages = MTLStages::empty();
 
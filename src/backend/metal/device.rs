@@ -1,7 +1,8 @@
-use std::cell::{Cell, RefCell};
+use std::cell::RefCell;
 use std::collections::{BTreeMap, HashMap};
 use std::ptr::NonNull;
-use std::rc::Rc;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 
 use objc2::rc::Retained;
 use objc2::runtime::ProtocolObject;
@@ -9,10 +10,11 @@ use objc2_core_foundation::CGSize;
 use objc2_foundation::NSString;
 use objc2_metal::{
     MTL4CommandBuffer, MTL4CommandQueue, MTL4Compiler, MTL4CompilerDescriptor,
-    MTL4ComputePipelineDescriptor, MTL4LibraryFunctionDescriptor, MTL4PipelineDescriptor,
+    MTL4ComputePipelineDescriptor, MTL4CounterHeap, MTL4CounterHeapDescriptor, MTL4CounterHeapType,
+    MTL4IndirectCommandBufferSupportState, MTL4LibraryFunctionDescriptor, MTL4PipelineDescriptor,
     MTL4PipelineOptions, MTL4ShaderReflection, MTLAllocation, MTLBinding, MTLBindingType,
     MTLBuffer, MTLCompileOptions, MTLComputePipelineState, MTLCreateSystemDefaultDevice,
-    MTLCullMode, MTLDevice, MTLDrawable, MTLEvent, MTLHeap, MTLHeapDescriptor, MTLHeapType,
+    MTLCullMode, MTLDevice, MTLDrawable, MTLEvent, MTLFunction, MTLGPUFamily, MTLHeap,
     MTLLanguageVersion, MTLLibrary, MTLPixelFormat, MTLRenderPipelineState, MTLResidencySet,
     MTLResidencySetDescriptor, MTLResourceOptions, MTLSamplerDescriptor, MTLSamplerState,
     MTLSharedEvent, MTLStorageMode, MTLTexture, MTLTextureDescriptor, MTLTextureType,
@@ -22,38 +24,60 @@ use objc2_quartz_core::{CAMetalDrawable, CAMetalLayer};
 use raw_window_handle::RawWindowHandle;
 
 use crate::accel::{AccelInner, AccelerationStructure};
+use crate::bindless::{DescriptorHeapStats, SlotAllocator};
+use crate::bundle::{BundleEncoderInner, ComputeBundleDesc, RenderBundleDesc};
 use crate::command::{CommandBuffer, SignalOp, SignalValueDesc, WaitOp, WaitValueDesc};
 use crate::device::{BindlessMode, DeviceDesc};
 use crate::error::{RhiError, RhiResult};
-use crate::memory::{BufferDesc, GpuBuffer, GpuBufferInner, MemoryType};
+use crate::memory::{
+    BufferDesc, ExternalMemoryHandle, GpuBuffer, GpuBufferInner, MemoryHeapBudget, MemoryStats,
+    MemoryType,
+};
 use crate::pipeline::*;
+use crate::query::{QUERY_RESULT_SIZE, QueryPool, QueryPoolDesc, QueryPoolInner, QueryType};
 use crate::queue::{Queue, QueueInner, SubmitDesc};
 use crate::sampler::{Sampler, SamplerDesc};
 use crate::shader::{ShaderModule, ShaderModuleDesc};
 use crate::surface::{Surface, SurfaceDesc, SurfaceInner};
 use crate::swapchain::{AcquiredImage, Swapchain, SwapchainDesc, SwapchainInner};
 use crate::sync::{TimelineSemaphore, TimelineSemaphoreInner};
-use crate::texture::{Texture, TextureDesc, TextureSizeAlign, TextureUsage};
+use crate::texture::{
+    FormatCaps, Texture, TextureCompression, TextureDesc, TextureSizeAlign, TextureUsage,
+    format_block, format_compression,
+};
 use crate::types::*;
 
-use super::command::MetalCommandBuffer;
+use super::allocator::{
+    HeapAllocator, MIN_BUFFER_ALIGNMENT, PlacedBuffer, new_placement_heap, resource_options,
+};
+use super::bundle::MetalBundleEncoder;
+use super::command::{
+    BlitPipelines, METAL_BINDLESS_SAMPLER_CAPACITY, METAL_BINDLESS_TEXTURE_CAPACITY,
+    MetalCommandBuffer, MipDownsamplePipelines, MipGeneration, mip_generation,
+};
 use super::memory::MetalBuffer;
 use super::pipeline::{MetalComputePso, MetalGraphicsPso};
+use super::query::{MetalQueryPool, VisibilityBuffer};
 use super::shader::MetalShaderModule;
 use super::surface::MetalSurface;
 use super::swapchain::MetalSwapchain;
 use super::sync::MetalTimelineSemaphore;
 use super::texture::{format_to_mtl, mtl_to_format};
 
-type FrameFenceValues = Rc<RefCell<[u64; MAX_FRAMES_IN_FLIGHT]>>;
-type InFlightFrameCommands = Rc<RefCell<Vec<Option<MetalCommandBuffer>>>>;
-type PendingSubmissions = Rc<RefCell<Vec<(u64, MetalCommandBuffer)>>>;
-pub(crate) type SharedTextures = Rc<RefCell<Vec<Option<Retained<ProtocolObject<dyn MTLTexture>>>>>>;
-pub(crate) type SharedSamplers = Rc<RefCell<Vec<Retained<ProtocolObject<dyn MTLSamplerState>>>>>;
+type FrameFenceValues = Arc<Mutex<[u64; MAX_FRAMES_IN_FLIGHT]>>;
+type InFlightFrameCommands = Mutex<Vec<Option<MetalCommandBuffer>>>;
+type PendingSubmissions = Mutex<Vec<(u64, MetalCommandBuffer)>>;
+pub(crate) type SharedTextures = Arc<Mutex<Vec<Option<Retained<ProtocolObject<dyn MTLTexture>>>>>>;
+pub(crate) type SharedSamplers =
+    Arc<Mutex<Vec<Option<Retained<ProtocolObject<dyn MTLSamplerState>>>>>>;
 /// Buffer allocations keyed by GPU base address, enabling O(log n) address->buffer
 /// resolution for blit copies and indirect draws instead of a linear scan.
-pub(crate) type SharedAllocations = Rc<RefCell<BTreeMap<u64, BufferAllocation>>>;
-type ValueSyncMap = RefCell<HashMap<u64, MetalValueSyncState>>;
+pub(crate) type SharedAllocations = Arc<Mutex<BTreeMap<u64, BufferAllocation>>>;
+/// CPU-mapped buffers keyed by mapped base address: `(size, GPU base)`. The reverse index of
+/// `SharedAllocations`, so host→device pointer translation is a range lookup too.
+type MappedRanges = Mutex<BTreeMap<usize, (u64, GpuAddress)>>;
+type ValueSyncMap = Mutex<HashMap<u64, MetalValueSyncState>>;
+pub(crate) type SharedResidency = Arc<Residency>;
 type MetalEventWaits = Vec<(Retained<ProtocolObject<dyn MTLSharedEvent>>, u64)>;
 
 fn shared_event_as_event(
@@ -65,6 +89,51 @@ fn shared_event_as_event(
     }
 }
 
+/// The device-wide residency set, shared by the device, the queue, command buffers and
+/// query pools. A residency set must not be modified from several threads at once, so
+/// changes take a lock, which also records whether the queue has a commit pending.
+pub(crate) struct Residency {
+    set: Retained<ProtocolObject<dyn MTLResidencySet>>,
+    dirty: Mutex<bool>,
+}
+
+// SAFETY: `set` is only modified and committed under the `dirty` lock.
+unsafe impl Send for Residency {}
+unsafe impl Sync for Residency {}
+
+impl Residency {
+    fn new(set: Retained<ProtocolObject<dyn MTLResidencySet>>) -> Self {
+        Self {
+            set,
+            dirty: Mutex::new(false),
+        }
+    }
+
+    pub(crate) fn set(&self) -> &ProtocolObject<dyn MTLResidencySet> {
+        &self.set
+    }
+
+    pub(crate) fn add(&self, allocation: &ProtocolObject<dyn MTLAllocation>) {
+        let mut dirty = self.dirty.lock().expect("residency lock poisoned");
+        self.set.addAllocation(allocation);
+        *dirty = true;
+    }
+
+    pub(crate) fn remove(&self, allocation: &ProtocolObject<dyn MTLAllocation>) {
+        let mut dirty = self.dirty.lock().expect("residency lock poisoned");
+        self.set.removeAllocation(allocation);
+        *dirty = true;
+    }
+
+    /// Commit the changes made since the last commit, if any.
+    fn commit(&self) {
+        let mut dirty = self.dirty.lock().expect("residency lock poisoned");
+        if std::mem::take(&mut *dirty) {
+            self.set.commit();
+        }
+    }
+}
+
 // Metal 4 argument tables carry root and bindless-heap buffer addresses.
 
 #[derive(Clone)]
@@ -73,41 +142,64 @@ pub(crate) struct BufferAllocation {
     pub size: u64,
     pub buffer: Retained<ProtocolObject<dyn MTLBuffer>>,
     pub heap: Option<Retained<ProtocolObject<dyn MTLHeap>>>,
+    /// Offset of `base` inside `heap`.
+    pub heap_offset: u64,
+    pub memory_type: MemoryType,
     pub mapped_ptr: Option<*mut u8>,
 }
 
+// Metal buffers and heaps may be used from any thread; the mapped pointer is only read
+// behind the shared allocation mutex.
+unsafe impl Send for BufferAllocation {}
+
 pub struct MetalDevice {
     device: Retained<ProtocolObject<dyn MTLDevice>>,
     rhi_queue: Queue,
-    residency_set: Retained<ProtocolObject<dyn MTLResidencySet>>,
-    residency_dirty: Rc<Cell<bool>>,
+    residency: SharedResidency,
     textures: SharedTextures,
     samplers: SharedSamplers,
+    texture_slots: Mutex<SlotAllocator>,
+    sampler_slots: Mutex<SlotAllocator>,
     allocations: SharedAllocations,
+    mapped_ranges: MappedRanges,
+    /// Sub-allocator placing buffers into large per-`MemoryType` placement heaps.
+    heap_allocator: Mutex<HeapAllocator>,
     /// Per-frame fence values for swapchain acquisition.
     frame_fence_values: FrameFenceValues,
     /// Shared event for per-frame synchronization.
     frame_event: Retained<ProtocolObject<dyn MTLSharedEvent>>,
     bindless_mode: BindlessMode,
     /// Monotonic counter for AccelerationStructureId assignment.
-    accel_counter: RefCell<u32>,
+    accel_counter: AtomicU32,
     mdi_icb_pipeline: Retained<ProtocolObject<dyn MTLComputePipelineState>>,
+    mip_downsample: MipDownsamplePipelines,
+    blit: BlitPipelines,
 }
 
 pub struct MetalQueue {
     queue: Retained<ProtocolObject<dyn MTL4CommandQueue>>,
     #[allow(dead_code)]
     device: Retained<ProtocolObject<dyn MTLDevice>>,
-    residency_set: Retained<ProtocolObject<dyn MTLResidencySet>>,
-    residency_dirty: Rc<Cell<bool>>,
+    residency: SharedResidency,
+    /// Held across a whole submission, so queue event waits, commits and signals of
+    /// concurrent submits never interleave and fence values reach the queue in order.
+    submit_lock: Mutex<()>,
     frame_fence_values: FrameFenceValues,
-    frame_fence_next: Rc<Cell<u64>>,
+    frame_fence_next: AtomicU64,
     frame_event: Retained<ProtocolObject<dyn MTLSharedEvent>>,
     in_flight_frame_commands: InFlightFrameCommands,
     pending_submissions: PendingSubmissions,
     value_sync: ValueSyncMap,
 }
 
+// SAFETY: Metal devices, queues and resources are thread-safe; the non-`Send` objects held
+// here (the heap allocator's heaps, allocation buffers, command buffers kept alive for the
+// GPU) are only touched behind the device's and queue's mutexes.
+unsafe impl Send for MetalDevice {}
+unsafe impl Sync for MetalDevice {}
+unsafe impl Send for MetalQueue {}
+unsafe impl Sync for MetalQueue {}
+
 #[derive(Clone)]
 struct MetalValueSyncState {
     event: Retained<ProtocolObject<dyn MTLSharedEvent>>,
@@ -141,7 +233,7 @@ impl MetalQueue {
     }
 
     fn collect_value_waits(&self, waits: &[WaitValueDesc]) -> RhiResult<MetalEventWaits> {
-        let mut map = self.value_sync.borrow_mut();
+        let mut map = self.value_sync.lock().expect("value sync lock poisoned");
         let mut merged: HashMap<u64, (Retained<ProtocolObject<dyn MTLSharedEvent>>, u64)> =
             HashMap::new();
         for wait in waits {
@@ -189,7 +281,7 @@ impl MetalQueue {
     }
 
     fn collect_value_signals(&self, signals: &[SignalValueDesc]) -> RhiResult<MetalEventWaits> {
-        let mut map = self.value_sync.borrow_mut();
+        let mut map = self.value_sync.lock().expect("value sync lock poisoned");
         let mut merged: HashMap<u64, (Retained<ProtocolObject<dyn MTLSharedEvent>>, u64)> =
             HashMap::new();
         for signal in signals {
@@ -216,11 +308,28 @@ impl MetalQueue {
         cmd: MetalCommandBuffer,
         desc: &SubmitDesc<'_>,
     ) -> RhiResult<()> {
-        let value_waits = self.collect_value_waits(&cmd.pending_value_waits)?;
-        let value_signals = self.collect_value_signals(&cmd.pending_value_signals)?;
-        if self.residency_dirty.replace(false) {
-            self.residency_set.commit();
-        }
+        self.submit_batch(vec![cmd], desc)
+    }
+
+    /// Commit `cmds` together, in order. Their value waits all happen before the first one
+    /// starts and their value signals after the last one finishes.
+    pub fn submit_batch(
+        &self,
+        mut cmds: Vec<MetalCommandBuffer>,
+        desc: &SubmitDesc<'_>,
+    ) -> RhiResult<()> {
+        let _submit = self.submit_lock.lock().expect("submit lock poisoned");
+        let value_waits: Vec<WaitValueDesc> = cmds
+            .iter()
+            .flat_map(|cmd| cmd.pending_value_waits.iter().copied())
+            .collect();
+        let value_signals: Vec<SignalValueDesc> = cmds
+            .iter()
+            .flat_map(|cmd| cmd.pending_value_signals.iter().copied())
+            .collect();
+        let value_waits = self.collect_value_waits(&value_waits)?;
+        let value_signals = self.collect_value_signals(&value_signals)?;
+        self.residency.commit();
         self.reclaim_completed_submissions();
 
         for (semaphore, value) in desc.wait_semaphores {
@@ -242,9 +351,10 @@ impl MetalQueue {
                 .waitForEvent_value(shared_event_as_event(&event), value);
         }
 
-        let mut cmd = cmd;
-        cmd.finish();
-        self.commit_single(&cmd.command_buffer);
+        for cmd in &mut cmds {
+            cmd.finish();
+        }
+        self.commit(&cmds);
 
         for (semaphore, value) in desc.signal_semaphores {
             match &semaphore.inner {
@@ -268,7 +378,10 @@ impl MetalQueue {
         let value = self.next_fence_value();
         self.queue
             .signalEvent_value(shared_event_as_event(&self.frame_event), value);
-        self.pending_submissions.borrow_mut().push((value, cmd));
+        self.pending_submissions
+            .lock()
+            .expect("pending submissions lock poisoned")
+            .extend(cmds.into_iter().map(|cmd| (value, cmd)));
         Ok(())
     }
 
@@ -279,11 +392,10 @@ impl MetalQueue {
         frame_index: usize,
         _image_index: u32,
     ) -> RhiResult<()> {
+        let _submit = self.submit_lock.lock().expect("submit lock poisoned");
         let value_waits = self.collect_value_waits(&cmd.pending_value_waits)?;
         let value_signals = self.collect_value_signals(&cmd.pending_value_signals)?;
-        if self.residency_dirty.replace(false) {
-            self.residency_set.commit();
-        }
+        self.residency.commit();
         self.reclaim_completed_submissions();
 
         for (event, value) in value_waits {
@@ -293,7 +405,7 @@ impl MetalQueue {
 
         let mut cmd = cmd;
         cmd.finish();
-        self.commit_single(&cmd.command_buffer);
+        self.commit(std::slice::from_ref(&cmd));
 
         for (event, value) in value_signals {
             self.queue
@@ -302,7 +414,9 @@ impl MetalQueue {
 
         // Signal a per-frame fence value after GPU work completes.
         let value = self.next_fence_value();
-        self.frame_fence_values.borrow_mut()[frame_index] = value;
+        self.frame_fence_values
+            .lock()
+            .expect("frame fence values lock poisoned")[frame_index] = value;
         self.queue
             .signalEvent_value(shared_event_as_event(&self.frame_event), value);
 
@@ -317,7 +431,10 @@ impl MetalQueue {
         let _ = sc.current_drawable_texture.borrow_mut().take();
 
         // Keep the command buffer and associated resources alive until this frame slot completes.
-        let mut frame_cmds = self.in_flight_frame_commands.borrow_mut();
+        let mut frame_cmds = self
+            .in_flight_frame_commands
+            .lock()
+            .expect("frame commands lock poisoned");
         if frame_index >= frame_cmds.len() {
             return Err(RhiError::Backend(
                 "invalid frame index for Metal queue submission".into(),
@@ -339,14 +456,22 @@ impl MetalQueue {
         self.reclaim_completed_submissions();
 
         // Wait for previous GPU work on this frame slot to finish.
-        let value = self.frame_fence_values.borrow()[frame_index];
+        let value = self
+            .frame_fence_values
+            .lock()
+            .expect("frame fence values lock poisoned")[frame_index];
         if value != 0 {
             let _ = self
                 .frame_event
                 .waitUntilSignaledValue_timeoutMS(value, u64::MAX);
         }
-        if frame_index < self.in_flight_frame_commands.borrow().len() {
-            self.in_flight_frame_commands.borrow_mut()[frame_index] = None;
+        if let Some(slot) = self
+            .in_flight_frame_commands
+            .lock()
+            .expect("frame commands lock poisoned")
+            .get_mut(frame_index)
+        {
+            *slot = None;
         }
 
         let drawable = sc
@@ -386,38 +511,62 @@ impl MetalQueue {
     }
 
     pub fn wait_idle(&self) {
+        let _submit = self.submit_lock.lock().expect("submit lock poisoned");
         let value = self.next_fence_value();
         self.queue
             .signalEvent_value(shared_event_as_event(&self.frame_event), value);
         let _ = self
             .frame_event
             .waitUntilSignaledValue_timeoutMS(value, u64::MAX);
-        self.pending_submissions.borrow_mut().clear();
-        for slot in self.in_flight_frame_commands.borrow_mut().iter_mut() {
+        self.pending_submissions
+            .lock()
+            .expect("pending submissions lock poisoned")
+            .clear();
+        for slot in self
+            .in_flight_frame_commands
+            .lock()
+            .expect("frame commands lock poisoned")
+            .iter_mut()
+        {
             *slot = None;
         }
     }
 
+    pub fn submitted_serial(&self) -> u64 {
+        self.frame_fence_next.load(Ordering::Acquire)
+    }
+
+    pub fn completed_serial(&self) -> u64 {
+        self.frame_event.signaledValue()
+    }
+
+    pub fn wait_for_serial(&self, serial: u64) {
+        let _ = self
+            .frame_event
+            .waitUntilSignaledValue_timeoutMS(serial, u64::MAX);
+    }
+
     fn next_fence_value(&self) -> u64 {
-        let value = self.frame_fence_next.get().wrapping_add(1);
-        self.frame_fence_next.set(value);
-        value
+        self.frame_fence_next.fetch_add(1, Ordering::AcqRel) + 1
     }
 
     fn reclaim_completed_submissions(&self) {
         let completed = self.frame_event.signaledValue();
         self.pending_submissions
-            .borrow_mut()
+            .lock()
+            .expect("pending submissions lock poisoned")
             .retain(|(value, _)| *value > completed);
     }
 
-    fn commit_single(&self, cmd: &Retained<ProtocolObject<dyn MTL4CommandBuffer>>) {
-        let cmd_ptr = NonNull::from(cmd.as_ref());
-        let mut bufs = [cmd_ptr];
+    fn commit(&self, cmds: &[MetalCommandBuffer]) {
+        let mut bufs: Vec<NonNull<ProtocolObject<dyn MTL4CommandBuffer>>> = cmds
+            .iter()
+            .map(|cmd| NonNull::from(cmd.command_buffer.as_ref()))
+            .collect();
         unsafe {
             let ptr =
                 NonNull::new(bufs.as_mut_ptr()).expect("command buffer array pointer is null");
-            self.queue.commit_count(ptr, 1);
+            self.queue.commit_count(ptr, bufs.len());
         }
     }
 }
@@ -489,6 +638,87 @@ kernel void rhi_encode_mdi_icb(
 }
 "#;
 
+const METAL_MIP_DOWNSAMPLE_SOURCE: &str = r#"
+#include <metal_stdlib>
+using namespace metal;
+
+// Compute fallback for `generate_mips` on formats Metal cannot filter. Each dispatch writes
+// one mip level of every slice from the level above it, bound as 2D-array views.
+
+// 2x2 box filter; the last row and column of an odd-sized level are clamped to.
+kernel void rhi_downsample_float(
+    texture2d_array<float, access::read> src [[texture(0)]],
+    texture2d_array<float, access::write> dst [[texture(1)]],
+    uint3 tid [[thread_position_in_grid]])
+{
+    if (tid.x >= dst.get_width() || tid.y >= dst.get_height()) {
+        return;
+    }
+    uint2 last = uint2(src.get_width() - 1, src.get_height() - 1);
+    uint2 base = tid.xy * 2;
+    float4 sum = src.read(min(base, last), tid.z)
+        + src.read(min(base + uint2(1, 0), last), tid.z)
+        + src.read(min(base + uint2(0, 1), last), tid.z)
+        + src.read(min(base + uint2(1, 1), last), tid.z);
+    dst.write(sum * 0.25, tid.xy, tid.z);
+}
+
+// Integer texels are not averaged: each texel takes the top-left texel of its 2x2 footprint,
+// as a nearest-filtered blit would.
+kernel void rhi_downsample_uint(
+    texture2d_array<uint, access::read> src [[texture(0)]],
+    texture2d_array<uint, access::write> dst [[texture(1)]],
+    uint3 tid [[thread_position_in_grid]])
+{
+    if (tid.x >= dst.get_width() || tid.y >= dst.get_height()) {
+        return;
+    }
+    dst.write(src.read(tid.xy * 2, tid.z), tid.xy, tid.z);
+}
+"#;
+
+const METAL_BLIT_SOURCE: &str = r#"
+#include <metal_stdlib>
+using namespace metal;
+
+// `blit_texture`: one triangle covering the viewport, which is set to the destination region,
+// sampling the source region through a 2D-array view of the source mip level.
+
+struct BlitParams {
+    float2 uv_origin;   // source region corner, normalized to the source mip level
+    float2 uv_size;     // source region size, normalized likewise
+    uint layer;
+    uint linear_filter;
+};
+
+struct BlitVertex {
+    float4 position [[position]];
+    float2 uv;
+};
+
+vertex BlitVertex rhi_blit_vertex(uint vid [[vertex_id]])
+{
+    float2 corner = float2((vid << 1) & 2, vid & 2);
+    BlitVertex out;
+    out.position = float4(corner * float2(2.0, -2.0) + float2(-1.0, 1.0), 0.0, 1.0);
+    out.uv = corner;
+    return out;
+}
+
+fragment float4 rhi_blit_fragment(
+    BlitVertex in [[stage_in]],
+    texture2d_array<float> src [[texture(0)]],
+    constant BlitParams& params [[buffer(0)]])
+{
+    constexpr sampler nearest(filter::nearest, address::clamp_to_edge);
+    constexpr sampler linear(filter::linear, address::clamp_to_edge);
+    float2 uv = params.uv_origin + in.uv * params.uv_size;
+    return params.linear_filter != 0
+        ? src.sample(linear, uv, params.layer, level(0))
+        : src.sample(nearest, uv, params.layer, level(0));
+}
+"#;
+
 /// Translate the unified `Cull` value into Metal's `(cull_mode, front-face winding)` pair.
 /// All variants imply CCW as the front-face convention. `Cull::All` is approximated as
 /// Back + CW since Metal has no FRONT_AND_BACK cull mode.
@@ -501,29 +731,46 @@ fn cull_to_mtl(cull: Cull) -> (MTLCullMode, MTLWinding) {
     }
 }
 
-fn create_mdi_icb_pipeline(
+/// Compile one of the backend's own MSL sources. `label` names it in errors.
+fn create_builtin_library(
     device: &ProtocolObject<dyn MTLDevice>,
-) -> RhiResult<Retained<ProtocolObject<dyn MTLComputePipelineState>>> {
+    source: &str,
+    label: &str,
+) -> RhiResult<Retained<ProtocolObject<dyn MTLLibrary>>> {
     let options = MTLCompileOptions::new();
     options.setLanguageVersion(MTLLanguageVersion::Version4_0);
-    let source = NSString::from_str(METAL_MDI_ICB_SOURCE);
-    let library = device
+    let source = NSString::from_str(source);
+    device
         .newLibraryWithSource_options_error(&source, Some(&options))
         .map_err(|e| {
-            RhiError::PipelineCreation(format!(
-                "Metal MDI ICB encoder library compilation failed: {e}"
-            ))
-        })?;
-    let function_name = NSString::from_str("rhi_encode_mdi_icb");
-    let function = library.newFunctionWithName(&function_name).ok_or_else(|| {
-        RhiError::PipelineCreation("Metal MDI ICB encoder function was not found".into())
-    })?;
+            RhiError::PipelineCreation(format!("Metal {label} library compilation failed: {e}"))
+        })
+}
+
+fn builtin_function(
+    library: &ProtocolObject<dyn MTLLibrary>,
+    function_name: &str,
+    label: &str,
+) -> RhiResult<Retained<ProtocolObject<dyn MTLFunction>>> {
+    let function_name = NSString::from_str(function_name);
+    library
+        .newFunctionWithName(&function_name)
+        .ok_or_else(|| RhiError::PipelineCreation(format!("Metal {label} function was not found")))
+}
+
+/// Compile one of the backend's own MSL kernels. `label` names it in errors.
+fn create_builtin_pipeline(
+    device: &ProtocolObject<dyn MTLDevice>,
+    source: &str,
+    function_name: &str,
+    label: &str,
+) -> RhiResult<Retained<ProtocolObject<dyn MTLComputePipelineState>>> {
+    let library = create_builtin_library(device, source, label)?;
+    let function = builtin_function(&library, function_name, label)?;
     device
         .newComputePipelineStateWithFunction_error(&function)
         .map_err(|e| {
-            RhiError::PipelineCreation(format!(
-                "Metal MDI ICB encoder pipeline creation failed: {e}"
-            ))
+            RhiError::PipelineCreation(format!("Metal {label} pipeline creation failed: {e}"))
         })
 }
 
@@ -552,31 +799,31 @@ impl MetalDevice {
             .newSharedEvent()
             .ok_or_else(|| RhiError::DeviceCreation("Failed to create MTLSharedEvent".into()))?;
         let frame_fence_values: FrameFenceValues =
-            Rc::new(RefCell::new([0u64; MAX_FRAMES_IN_FLIGHT]));
-        let frame_fence_next = Rc::new(Cell::new(0u64));
-        let in_flight_frame_commands: InFlightFrameCommands = Rc::new(RefCell::new(
+            Arc::new(Mutex::new([0u64; MAX_FRAMES_IN_FLIGHT]));
+        let in_flight_frame_commands: InFlightFrameCommands = Mutex::new(
             std::iter::repeat_with(|| None)
                 .take(MAX_FRAMES_IN_FLIGHT)
                 .collect(),
-        ));
-        let pending_submissions: PendingSubmissions = Rc::new(RefCell::new(Vec::new()));
+        );
+        let pending_submissions: PendingSubmissions = Mutex::new(Vec::new());
 
-        let residency_dirty = Rc::new(Cell::new(false));
+        let residency = Arc::new(Residency::new(residency_set));
         let metal_queue = MetalQueue {
             queue: queue.clone(),
             device: device.clone(),
-            residency_set: residency_set.clone(),
-            residency_dirty: residency_dirty.clone(),
+            residency: residency.clone(),
+            submit_lock: Mutex::new(()),
             frame_fence_values: frame_fence_values.clone(),
-            frame_fence_next: frame_fence_next.clone(),
+            frame_fence_next: AtomicU64::new(0),
             frame_event: frame_event.clone(),
             in_flight_frame_commands,
             pending_submissions,
-            value_sync: RefCell::new(HashMap::new()),
+            value_sync: Mutex::new(HashMap::new()),
         };
 
         let rhi_queue = Queue {
             inner: QueueInner::Metal(Box::new(metal_queue)),
+            graveyard: Default::default(),
         };
 
         log::info!("Metal device created: {}", device.name());
@@ -587,21 +834,52 @@ impl MetalDevice {
             ));
         }
         let bindless_mode = BindlessMode::ArgumentTable;
-        let mdi_icb = create_mdi_icb_pipeline(device.as_ref())?;
+        let mdi_icb = create_builtin_pipeline(
+            device.as_ref(),
+            METAL_MDI_ICB_SOURCE,
+            "rhi_encode_mdi_icb",
+            "MDI ICB encoder",
+        )?;
+        let mip_downsample = MipDownsamplePipelines {
+            float: create_builtin_pipeline(
+                device.as_ref(),
+                METAL_MIP_DOWNSAMPLE_SOURCE,
+                "rhi_downsample_float",
+                "mip downsample",
+            )?,
+            uint: create_builtin_pipeline(
+                device.as_ref(),
+                METAL_MIP_DOWNSAMPLE_SOURCE,
+                "rhi_downsample_uint",
+                "mip downsample",
+            )?,
+        };
+
+        let blit_library = create_builtin_library(device.as_ref(), METAL_BLIT_SOURCE, "blit")?;
+        let blit = BlitPipelines {
+            vertex: builtin_function(&blit_library, "rhi_blit_vertex", "blit")?,
+            fragment: builtin_function(&blit_library, "rhi_blit_fragment", "blit")?,
+            by_format: Default::default(),
+        };
 
         let device = Self {
             device,
             rhi_queue,
-            residency_set,
-            residency_dirty,
-            textures: Rc::new(RefCell::new(Vec::new())),
-            samplers: Rc::new(RefCell::new(Vec::new())),
-            allocations: Rc::new(RefCell::new(BTreeMap::new())),
+            residency,
+            textures: Arc::new(Mutex::new(Vec::new())),
+            samplers: Arc::new(Mutex::new(Vec::new())),
+            texture_slots: Mutex::new(SlotAllocator::new(METAL_BINDLESS_TEXTURE_CAPACITY as u32)),
+            sampler_slots: Mutex::new(SlotAllocator::new(METAL_BINDLESS_SAMPLER_CAPACITY as u32)),
+            allocations: Arc::new(Mutex::new(BTreeMap::new())),
+            mapped_ranges: Mutex::new(BTreeMap::new()),
+            heap_allocator: Mutex::new(HeapAllocator::new()),
             frame_fence_values,
             frame_event,
             bindless_mode,
-            accel_counter: RefCell::new(0),
+            accel_counter: AtomicU32::new(0),
             mdi_icb_pipeline: mdi_icb,
+            mip_downsample,
+            blit,
         };
 
         Ok(device)
@@ -615,6 +893,83 @@ impl MetalDevice {
         self.bindless_mode
     }
 
+    /// BC on Macs that have it (all Apple silicon and recent AMD/Intel GPUs); ETC2 and ASTC on
+    /// Apple GPUs.
+    pub fn texture_compression(&self) -> TextureCompression {
+        let mut compression = TextureCompression::empty();
+        compression.set(
+            TextureCompression::BC,
+            self.device.supportsBCTextureCompression(),
+        );
+        let apple_gpu = self.device.supportsFamily(MTLGPUFamily::Apple2);
+        compression.set(TextureCompression::ETC2, apple_gpu);
+        compression.set(TextureCompression::ASTC, apple_gpu);
+        compression
+    }
+
+    /// Metal has no per-format query; this follows Apple's pixel format capability tables,
+    /// using the device's feature queries where the tables split by GPU family.
+    pub fn format_caps(&self, format: Format) -> FormatCaps {
+        let device = &self.device;
+        if !self
+            .texture_compression()
+            .contains(format_compression(format))
+        {
+            return FormatCaps::empty();
+        }
+        let mut msaa = FormatCaps::empty();
+        for (count, cap) in [
+            (2, FormatCaps::MSAA_2),
+            (4, FormatCaps::MSAA_4),
+            (8, FormatCaps::MSAA_8),
+            (16, FormatCaps::MSAA_16),
+        ] {
+            msaa.set(cap, device.supportsTextureSampleCount(count));
+        }
+        let renderable = FormatCaps::COLOR_ATTACHMENT | FormatCaps::BLEND | msaa;
+        let filterable = FormatCaps::SAMPLED | FormatCaps::FILTER_LINEAR;
+
+        match format {
+            Format::D24UnormS8Uint if !device.isDepth24Stencil8PixelFormatSupported() => {
+                FormatCaps::empty()
+            }
+            Format::D16Unorm
+            | Format::D32Float
+            | Format::D24UnormS8Uint
+            | Format::D32FloatS8Uint => {
+                FormatCaps::SAMPLED | FormatCaps::DEPTH_STENCIL_ATTACHMENT | msaa
+            }
+            Format::R32Float | Format::R32G32Float | Format::R32G32B32A32Float => {
+                let mut caps = FormatCaps::SAMPLED
+                    | FormatCaps::STORAGE
+                    | FormatCaps::COLOR_ATTACHMENT
+                    | FormatCaps::BLEND;
+                caps.set(
+                    FormatCaps::FILTER_LINEAR,
+                    device.supports32BitFloatFiltering(),
+                );
+                if device.supports32BitMSAA() || format == Format::R32Float {
+                    caps |= msaa;
+                }
+                caps
+            }
+            Format::R16Uint | Format::R32Uint => {
+                FormatCaps::SAMPLED | FormatCaps::STORAGE | FormatCaps::COLOR_ATTACHMENT | msaa
+            }
+            // Shader writes to sRGB textures need an Apple GPU.
+            Format::R8G8B8A8Srgb | Format::B8G8R8A8Srgb => {
+                let mut caps = filterable | renderable;
+                caps.set(
+                    FormatCaps::STORAGE,
+                    device.supportsFamily(MTLGPUFamily::Apple2),
+                );
+                caps
+            }
+            _ if !format_compression(format).is_empty() => filterable,
+            _ => filterable | FormatCaps::STORAGE | renderable,
+        }
+    }
+
     pub fn wait_idle(&self) {
         match &self.rhi_queue.inner {
             QueueInner::Metal(q) => q.wait_idle(),
@@ -624,7 +979,10 @@ impl MetalDevice {
     }
 
     pub fn wait_for_frame(&self, frame_index: usize) {
-        let value = self.frame_fence_values.borrow()[frame_index];
+        let value = self
+            .frame_fence_values
+            .lock()
+            .expect("frame fence values lock poisoned")[frame_index];
         if value != 0 {
             let _ = self
                 .frame_event
@@ -635,8 +993,13 @@ impl MetalDevice {
         match &self.rhi_queue.inner {
             #[cfg(feature = "metal")]
             QueueInner::Metal(q) => {
-                if frame_index < q.in_flight_frame_commands.borrow().len() {
-                    q.in_flight_frame_commands.borrow_mut()[frame_index] = None;
+                if let Some(slot) = q
+                    .in_flight_frame_commands
+                    .lock()
+                    .expect("frame commands lock poisoned")
+                    .get_mut(frame_index)
+                {
+                    *slot = None;
                 }
                 q.reclaim_completed_submissions();
             }
@@ -732,37 +1095,55 @@ impl MetalDevice {
     }
 
     pub fn create_buffer(&self, desc: &BufferDesc) -> RhiResult<GpuBuffer> {
-        let options = match desc.memory {
-            MemoryType::Default | MemoryType::Readback => MTLResourceOptions::StorageModeShared,
-            MemoryType::GpuOnly => MTLResourceOptions::StorageModePrivate,
-        };
-
-        let buffer_size = self
-            .device
-            .heapBufferSizeAndAlignWithLength_options(desc.size as usize, options);
-        let heap_desc = MTLHeapDescriptor::new();
-        heap_desc.setType(MTLHeapType::Placement);
-        heap_desc.setSize(buffer_size.size);
-        heap_desc.setResourceOptions(options);
-
-        let heap = self
-            .device
-            .newHeapWithDescriptor(&heap_desc)
-            .ok_or_else(|| RhiError::BufferCreation("Metal heap allocation failed".into()))?;
+        self.create_buffer_aligned(desc, MIN_BUFFER_ALIGNMENT)
+    }
 
-        let buffer =
-            unsafe { heap.newBufferWithLength_options_offset(desc.size as usize, options, 0) }
+    /// Create a buffer whose GPU address is a multiple of `align` (a power of two). Small
+    /// buffers are placed into shared heaps; large ones get a placement heap of their own.
+    pub fn create_buffer_aligned(&self, desc: &BufferDesc, align: u64) -> RhiResult<GpuBuffer> {
+        if desc.export.is_some() {
+            return Err(RhiError::Unsupported(
+                "Metal does not support exportable memory".into(),
+            ));
+        }
+        let placed = self
+            .heap_allocator
+            .lock()
+            .expect("heap allocator lock poisoned")
+            .allocate(&self.device, desc.memory, desc.size, align)?;
+        let sub_allocated = placed.is_some();
+        let PlacedBuffer {
+            buffer,
+            heap,
+            heap_offset,
+        } = match placed {
+            Some(placed) => placed,
+            None => {
+                let options = resource_options(desc.memory);
+                let buffer_size = self
+                    .device
+                    .heapBufferSizeAndAlignWithLength_options(desc.size as usize, options);
+                let heap = new_placement_heap(&self.device, desc.memory, buffer_size.size as u64)?;
+                let buffer = unsafe {
+                    heap.newBufferWithLength_options_offset(desc.size as usize, options, 0)
+                }
                 .ok_or_else(|| {
                     RhiError::BufferCreation("Metal placed buffer allocation failed".into())
                 })?;
+                PlacedBuffer {
+                    buffer,
+                    heap,
+                    heap_offset: 0,
+                }
+            }
+        };
 
         // Register for residency (Metal 4 pointer model).
         let allocation = unsafe {
             &*(buffer.as_ref() as *const ProtocolObject<dyn MTLBuffer>
                 as *const ProtocolObject<dyn MTLAllocation>)
         };
-        self.residency_set.addAllocation(allocation);
-        self.residency_dirty.set(true);
+        self.residency.add(allocation);
 
         if let Some(label) = &desc.label {
             use objc2_metal::MTLResource;
@@ -775,12 +1156,14 @@ impl MetalDevice {
         let metal_buffer = MetalBuffer {
             buffer,
             heap: Some(heap),
+            heap_offset,
+            sub_allocated,
             size: desc.size,
             is_shared,
         };
 
         {
-            let mut allocations = self.allocations.borrow_mut();
+            let mut allocations = self.allocations.lock().expect("allocations lock poisoned");
             allocations.insert(
                 metal_buffer.gpu_address().0,
                 BufferAllocation {
@@ -788,33 +1171,106 @@ impl MetalDevice {
                     size: metal_buffer.size,
                     buffer: metal_buffer.buffer.clone(),
                     heap: metal_buffer.heap.clone(),
+                    heap_offset: metal_buffer.heap_offset,
+                    memory_type: desc.memory,
                     mapped_ptr: metal_buffer.mapped_ptr(),
                 },
             );
         }
+        if let Some(mapped) = metal_buffer.mapped_ptr() {
+            self.mapped_ranges
+                .lock()
+                .expect("mapped ranges lock poisoned")
+                .insert(
+                    mapped as usize,
+                    (metal_buffer.size, metal_buffer.gpu_address()),
+                );
+        }
 
         Ok(GpuBuffer {
             inner: GpuBufferInner::Metal(metal_buffer),
         })
     }
 
+    pub fn import_memory_fd(
+        &self,
+        _fd: std::os::fd::OwnedFd,
+        _handle: ExternalMemoryHandle,
+        _size: u64,
+        _memory: MemoryType,
+    ) -> RhiResult<GpuBuffer> {
+        Err(RhiError::Unsupported(
+            "Metal does not import memory from file descriptors".into(),
+        ))
+    }
+
+    pub fn memory_stats(&self) -> MemoryStats {
+        let mut stats = MemoryStats::default();
+        for alloc in self
+            .allocations
+            .lock()
+            .expect("allocations lock poisoned")
+            .values()
+        {
+            stats
+                .of_mut(alloc.memory_type)
+                .record_allocation(alloc.size);
+        }
+        {
+            let heaps = self
+                .heap_allocator
+                .lock()
+                .expect("heap allocator lock poisoned");
+            for memory in [
+                MemoryType::Default,
+                MemoryType::GpuOnly,
+                MemoryType::Readback,
+            ] {
+                let (count, bytes) = heaps.block_usage(memory);
+                let entry = stats.of_mut(memory);
+                entry.block_count = count;
+                entry.block_bytes = bytes;
+            }
+        }
+        // Metal exposes one pool per device: the recommended working set is the budget.
+        let budget = self.device.recommendedMaxWorkingSetSize();
+        stats.heaps = vec![MemoryHeapBudget {
+            size: budget,
+            budget,
+            usage: self.device.currentAllocatedSize() as u64,
+            device_local: true,
+        }];
+        stats
+    }
+
     pub fn host_to_device_pointer(&self, cpu_ptr: *const u8) -> Option<GpuAddress> {
         if cpu_ptr.is_null() {
             return None;
         }
         let ptr = cpu_ptr as usize;
-        let allocations = self.allocations.borrow();
-        for alloc in allocations.values() {
-            if let Some(mapped) = alloc.mapped_ptr {
-                let base = mapped as usize;
-                let end = base + alloc.size as usize;
-                if ptr >= base && ptr < end {
-                    let offset = (ptr - base) as u64;
-                    return Some(GpuAddress(alloc.base.0 + offset));
-                }
-            }
+        let ranges = self
+            .mapped_ranges
+            .lock()
+            .expect("mapped ranges lock poisoned");
+        let (&base, &(size, gpu)) = ranges.range(..=ptr).next_back()?;
+        let offset = (ptr - base) as u64;
+        (offset < size).then(|| GpuAddress(gpu.0 + offset))
+    }
+
+    pub fn device_to_host_pointer(&self, addr: GpuAddress) -> Option<*mut u8> {
+        if addr.is_null() {
+            return None;
+        }
+        let allocations = self.allocations.lock().expect("allocations lock poisoned");
+        let (&base, alloc) = allocations.range(..=addr.0).next_back()?;
+        let offset = addr.0 - base;
+        if offset >= alloc.size {
+            return None;
         }
-        None
+        // SAFETY: `offset` is within the mapped range of the allocation.
+        alloc
+            .mapped_ptr
+            .map(|mapped| unsafe { mapped.add(offset as usize) })
     }
 
     /// Build the native `MTLTextureDescriptor` for a `TextureDesc`. Shared by
@@ -852,6 +1308,24 @@ impl MetalDevice {
         if desc.usage.contains(TextureUsage::DEPTH_STENCIL_ATTACHMENT) {
             usage |= MtlTextureUsage::RenderTarget;
         }
+        // `generate_mips` writes unfilterable formats from a compute kernel.
+        let compute_mips = matches!(
+            mip_generation(&self.device, desc.format),
+            Some(MipGeneration::Compute(_))
+        );
+        if desc.mip_levels > 1 && desc.usage.contains(TextureUsage::TRANSFER_DST) && compute_mips {
+            usage |= MtlTextureUsage::ShaderRead | MtlTextureUsage::ShaderWrite;
+        }
+        // `blit_texture` samples its source and draws into its destination.
+        let blittable = format_block(desc.format).is_some_and(|block| !block.is_compressed())
+            && desc.sample_count == SampleCount::S1
+            && !matches!(desc.dimension, TextureDimension::D1 | TextureDimension::D3);
+        if blittable && desc.usage.contains(TextureUsage::TRANSFER_SRC) {
+            usage |= MtlTextureUsage::ShaderRead;
+        }
+        if blittable && desc.usage.contains(TextureUsage::TRANSFER_DST) {
+            usage |= MtlTextureUsage::RenderTarget;
+        }
 
         unsafe {
             mtl_desc.setPixelFormat(format_to_mtl(desc.format));
@@ -888,11 +1362,16 @@ impl MetalDevice {
                 "create_texture requires a non-null texture allocation address".into(),
             ));
         }
+        if desc.export.is_some() {
+            return Err(RhiError::Unsupported(
+                "Metal does not support exportable memory".into(),
+            ));
+        }
 
         let mtl_desc = self.build_texture_descriptor(desc);
         let size_align = self.device.heapTextureSizeAndAlignWithDescriptor(&mtl_desc);
         let (heap, heap_offset) = {
-            let allocations = self.allocations.borrow();
+            let allocations = self.allocations.lock().expect("allocations lock poisoned");
             let alloc = allocations
                 .range(..=texture_gpu.0)
                 .next_back()
@@ -906,9 +1385,10 @@ impl MetalDevice {
                 })?;
 
             let offset = texture_gpu.0 - alloc.base.0;
-            if !offset.is_multiple_of(size_align.align as u64) {
+            let heap_offset = alloc.heap_offset + offset;
+            if !heap_offset.is_multiple_of(size_align.align as u64) {
                 return Err(RhiError::TextureCreation(format!(
-                    "texture allocation address 0x{:x} has heap offset {offset}, expected alignment {}",
+                    "texture allocation address 0x{:x} has heap offset {heap_offset}, expected alignment {}",
                     texture_gpu.0, size_align.align
                 )));
             }
@@ -926,7 +1406,7 @@ impl MetalDevice {
                 )
             })?;
 
-            (heap, offset)
+            (heap, heap_offset)
         };
 
         let texture =
@@ -940,8 +1420,7 @@ impl MetalDevice {
             &*(texture.as_ref() as *const ProtocolObject<dyn MTLTexture>
                 as *const ProtocolObject<dyn MTLAllocation>)
         };
-        self.residency_set.addAllocation(allocation);
-        self.residency_dirty.set(true);
+        self.residency.add(allocation);
 
         if let Some(label) = &desc.label {
             use objc2_metal::MTLResource;
@@ -949,11 +1428,7 @@ impl MetalDevice {
             texture.setLabel(Some(&ns_label));
         }
 
-        // Add to texture tracking
-        let mut textures = self.textures.borrow_mut();
-        let id = TextureId(textures.len() as u32);
-        textures.push(Some(texture.clone()));
-        drop(textures);
+        let id = self.insert_texture(texture)?;
 
         Ok(Texture {
             id,
@@ -982,18 +1457,94 @@ impl MetalDevice {
             mtl_desc.setCompareFunction(compare_op_to_mtl(cmp));
         }
         mtl_desc.setSupportArgumentBuffers(true);
+        if let Some(label) = &desc.label {
+            mtl_desc.setLabel(Some(&NSString::from_str(label)));
+        }
 
         let sampler = self
             .device
             .newSamplerStateWithDescriptor(&mtl_desc)
             .ok_or_else(|| RhiError::Backend("Failed to create Metal sampler".into()))?;
 
-        let mut samplers = self.samplers.borrow_mut();
-        let id = SamplerId(samplers.len() as u32);
-        samplers.push(sampler.clone());
-        drop(samplers);
+        let completed = self.rhi_queue.completed_serial();
+        let slot = self
+            .sampler_slots
+            .lock()
+            .expect("sampler slots lock poisoned")
+            .alloc(completed)
+            .ok_or_else(|| {
+                RhiError::Backend(format!(
+                    "Bindless sampler heap full ({METAL_BINDLESS_SAMPLER_CAPACITY} samplers)"
+                ))
+            })?;
+        let mut samplers = self.samplers.lock().expect("samplers lock poisoned");
+        if samplers.len() <= slot as usize {
+            samplers.resize(slot as usize + 1, None);
+        }
+        samplers[slot as usize] = Some(sampler);
 
-        Ok(Sampler { id })
+        Ok(Sampler {
+            id: SamplerId(slot),
+        })
+    }
+
+    /// Destroy a sampler. Its `SamplerId` is reused once the latest submission retires.
+    pub fn destroy_sampler(&self, sampler: Sampler) {
+        let slot = sampler.id.0;
+        let mut samplers = self.samplers.lock().expect("samplers lock poisoned");
+        if let Some(entry) = samplers.get_mut(slot as usize)
+            && entry.take().is_some()
+        {
+            self.sampler_slots
+                .lock()
+                .expect("sampler slots lock poisoned")
+                .free(slot, self.rhi_queue.submitted_serial());
+        }
+    }
+
+    pub fn descriptor_heap_stats(&self) -> DescriptorHeapStats {
+        DescriptorHeapStats {
+            textures: self
+                .texture_slots
+                .lock()
+                .expect("texture slots lock poisoned")
+                .occupancy(),
+            samplers: self
+                .sampler_slots
+                .lock()
+                .expect("sampler slots lock poisoned")
+                .occupancy(),
+        }
+    }
+
+    /// Track a resident texture or view in a free `TextureId` slot, reusing ones whose last
+    /// reader has retired. On failure the texture is removed from the residency set again.
+    fn insert_texture(
+        &self,
+        texture: Retained<ProtocolObject<dyn MTLTexture>>,
+    ) -> RhiResult<TextureId> {
+        let completed = self.rhi_queue.completed_serial();
+        let Some(slot) = self
+            .texture_slots
+            .lock()
+            .expect("texture slots lock poisoned")
+            .alloc(completed)
+        else {
+            let allocation = unsafe {
+                &*(texture.as_ref() as *const ProtocolObject<dyn MTLTexture>
+                    as *const ProtocolObject<dyn MTLAllocation>)
+            };
+            self.residency.remove(allocation);
+            return Err(RhiError::TextureCreation(format!(
+                "Bindless texture heap full ({METAL_BINDLESS_TEXTURE_CAPACITY} slots)"
+            )));
+        };
+        let mut textures = self.textures.lock().expect("textures lock poisoned");
+        if textures.len() <= slot as usize {
+            textures.resize(slot as usize + 1, None);
+        }
+        textures[slot as usize] = Some(texture);
+        Ok(TextureId(slot))
     }
 
     pub fn create_shader_module(&self, desc: &ShaderModuleDesc) -> RhiResult<ShaderModule> {
@@ -1020,6 +1571,18 @@ impl MetalDevice {
         })
     }
 
+    /// Views in `view_mask`, which vertex amplification renders in one pass. Metal caps
+    /// amplification at a device-dependent count, as low as 2.
+    fn vertex_amplification_count(&self, view_mask: u32) -> RhiResult<usize> {
+        let views = view_mask.count_ones() as usize;
+        if views > 1 && !self.device.supportsVertexAmplificationCount(views) {
+            return Err(RhiError::Unsupported(format!(
+                "view_mask {view_mask:#b}: the device cannot amplify vertices {views} ways"
+            )));
+        }
+        Ok(views.max(1))
+    }
+
     pub fn create_graphics_pso(
         &self,
         desc: &GraphicsPsoDesc,
@@ -1058,6 +1621,7 @@ impl MetalDevice {
             .map(format_to_mtl)
             .unwrap_or(MTLPixelFormat::Invalid);
 
+        let amplification_count = self.vertex_amplification_count(desc.view_mask)?;
         let pipeline_state = MetalGraphicsPso::compile_pipeline_state(
             compiler.as_ref(),
             vert_module.library.as_ref(),
@@ -1067,7 +1631,9 @@ impl MetalDevice {
             &color_formats,
             sample_count,
             desc.alpha_to_coverage,
+            amplification_count,
             &BlendState::default(),
+            desc.label.as_deref(),
         )?;
 
         let graphics_argument_buffer_slots = pipeline_state
@@ -1125,9 +1691,11 @@ impl MetalDevice {
                 stencil_format: stencil_format_mtl,
                 sample_count,
                 alpha_to_coverage: desc.alpha_to_coverage,
+                amplification_count,
                 root_constant_size: desc.root_constant_size,
                 graphics_argument_buffer_slots,
-                blend_pipelines: RefCell::new(blend_pipelines),
+                blend_pipelines: Mutex::new(blend_pipelines),
+                label: desc.label.clone(),
             })),
         })
     }
@@ -1152,6 +1720,9 @@ impl MetalDevice {
 
         let pipeline_desc = MTL4ComputePipelineDescriptor::new();
         pipeline_desc.setComputeFunctionDescriptor(Some(&func_desc));
+        // Compute bundles dispatch the pipeline from an indirect command buffer.
+        pipeline_desc
+            .setSupportIndirectCommandBuffers(MTL4IndirectCommandBufferSupportState::Enabled);
         let pipeline_options = MTL4PipelineOptions::new();
         pipeline_options.setShaderReflection(
             MTL4ShaderReflection::BindingInfo | MTL4ShaderReflection::BufferTypeInfo,
@@ -1168,6 +1739,10 @@ impl MetalDevice {
             depth: desc.threads_per_threadgroup[2] as usize,
         };
         pipeline_desc.setRequiredThreadsPerThreadgroup(tg);
+        if let Some(label) = &desc.label {
+            let base_desc: &MTL4PipelineDescriptor = pipeline_desc.as_ref();
+            base_desc.setLabel(Some(&NSString::from_str(label)));
+        }
 
         let pipeline_state = compiler
             .newComputePipelineStateWithDescriptor_compilerTaskOptions_error(&pipeline_desc, None)
@@ -1242,8 +1817,10 @@ impl MetalDevice {
             SampleCount::S8 => 8,
             SampleCount::S16 => 16,
         };
+        let amplification_count = self.vertex_amplification_count(desc.view_mask)?;
         unsafe {
             pipeline_desc.setRasterSampleCount(sample_count);
+            pipeline_desc.setMaxVertexAmplificationCount(amplification_count);
             if desc.alpha_to_coverage {
                 pipeline_desc
                     .setAlphaToCoverageState(objc2_metal::MTL4AlphaToCoverageState::Enabled);
@@ -1270,6 +1847,9 @@ impl MetalDevice {
         // Fragment function — needed by the argument encoder to build bindless heap layouts.
         // MTL4MeshRenderPipelineDescriptor inherits from MTL4PipelineDescriptor.
         let base_desc: &MTL4PipelineDescriptor = pipeline_desc.as_ref();
+        if let Some(label) = &desc.label {
+            base_desc.setLabel(Some(&NSString::from_str(label)));
+        }
         let default_pipeline = compiler
             .newRenderPipelineStateWithDescriptor_compilerTaskOptions_error(base_desc, None)
             .map_err(|e| RhiError::PipelineCreation(format!("Mesh PSO: {e}")))?;
@@ -1317,7 +1897,7 @@ impl MetalDevice {
                     .unwrap_or(MTLPixelFormat::Invalid),
                 root_constant_size: desc.root_constant_size,
                 argument_buffer_slots,
-                blend_pipelines: std::cell::RefCell::new(std::collections::HashMap::new()),
+                blend_pipelines: Mutex::new(std::collections::HashMap::new()),
                 default_pipeline,
             })),
         })
@@ -1444,21 +2024,15 @@ impl MetalDevice {
             let accel_alloc = &*(accel.as_ref()
                 as *const ProtocolObject<dyn objc2_metal::MTLAccelerationStructure>
                 as *const ProtocolObject<dyn MTLAllocation>);
-            self.residency_set.addAllocation(accel_alloc);
+            self.residency.add(accel_alloc);
             let scratch_alloc = &*(scratch.as_ref() as *const ProtocolObject<dyn MTLBuffer>
                 as *const ProtocolObject<dyn MTLAllocation>);
-            self.residency_set.addAllocation(scratch_alloc);
+            self.residency.add(scratch_alloc);
         }
-        self.residency_dirty.set(true);
 
         let gpu_resource_id = accel.gpuResourceID().to_raw();
 
-        let id = {
-            let mut counter = self.accel_counter.borrow_mut();
-            let next = *counter;
-            *counter += 1;
-            AccelerationStructureId(next)
-        };
+        let id = AccelerationStructureId(self.accel_counter.fetch_add(1, Ordering::Relaxed));
 
         Ok(AccelerationStructure {
             id,
@@ -1484,16 +2058,21 @@ impl MetalDevice {
             cmd,
             allocator,
             self.device.clone(),
-            self.residency_set.clone(),
-            self.residency_dirty.clone(),
+            self.residency.clone(),
             self.textures.clone(),
             self.samplers.clone(),
             self.allocations.clone(),
             self.mdi_icb_pipeline.clone(),
+            self.mip_downsample.clone(),
+            self.blit.clone(),
         )?;
 
         Ok(CommandBuffer {
             inner: crate::command::CommandBufferInner::Metal(Box::new(mtl_cmd)),
+            submit_slots: Vec::new(),
+            profile: None,
+            debug_groups: 0,
+            pass_view_mask: 0,
         })
     }
 
@@ -1515,6 +2094,26 @@ impl MetalDevice {
         Ok(cmd_buf)
     }
 
+    /// Bundles record on the CPU and are encoded into an indirect command buffer when first
+    /// executed.
+    pub(crate) fn create_render_bundle_encoder(
+        &self,
+        desc: &RenderBundleDesc,
+    ) -> RhiResult<BundleEncoderInner> {
+        Ok(BundleEncoderInner::Metal(Box::new(
+            MetalBundleEncoder::new(self.device.clone(), false, desc.label.clone()),
+        )))
+    }
+
+    pub(crate) fn create_compute_bundle_encoder(
+        &self,
+        desc: &ComputeBundleDesc,
+    ) -> RhiResult<BundleEncoderInner> {
+        Ok(BundleEncoderInner::Metal(Box::new(
+            MetalBundleEncoder::new(self.device.clone(), true, desc.label.clone()),
+        )))
+    }
+
     pub fn create_timeline_semaphore(&self, initial_value: u64) -> RhiResult<TimelineSemaphore> {
         let event = self
             .device
@@ -1529,20 +2128,98 @@ impl MetalDevice {
         })
     }
 
+    /// Timestamp pools are Metal 4 counter heaps; their entries resolve to `u64` ticks.
+    pub fn create_query_pool(&self, desc: &QueryPoolDesc) -> RhiResult<QueryPool> {
+        let inner = match desc.ty {
+            QueryType::Timestamp => {
+                let heap_desc = MTL4CounterHeapDescriptor::new();
+                heap_desc.setType(MTL4CounterHeapType::Timestamp);
+                unsafe { heap_desc.setCount(desc.count as usize) };
+                let heap = self
+                    .device
+                    .newCounterHeapWithDescriptor_error(&heap_desc)
+                    .map_err(|e| {
+                        RhiError::Backend(format!("Failed to create Metal counter heap: {e}"))
+                    })?;
+                if let Some(label) = &desc.label {
+                    heap.setLabel(Some(&NSString::from_str(label)));
+                }
+                MetalQueryPool::Counters(heap)
+            }
+            QueryType::Occlusion | QueryType::BinaryOcclusion => {
+                let size = desc.count as u64 * QUERY_RESULT_SIZE;
+                let buffer = self
+                    .device
+                    .newBufferWithLength_options(
+                        size as usize,
+                        MTLResourceOptions::StorageModeShared,
+                    )
+                    .ok_or_else(|| {
+                        RhiError::AllocationFailed(
+                            "Failed to allocate Metal visibility result buffer".into(),
+                        )
+                    })?;
+                if let Some(label) = &desc.label {
+                    use objc2_metal::MTLResource;
+                    buffer.setLabel(Some(&NSString::from_str(label)));
+                }
+                let allocation = unsafe {
+                    &*(buffer.as_ref() as *const ProtocolObject<dyn MTLBuffer>
+                        as *const ProtocolObject<dyn MTLAllocation>)
+                };
+                self.residency.add(allocation);
+                let pool = MetalQueryPool::Visibility(VisibilityBuffer {
+                    buffer,
+                    residency: self.residency.clone(),
+                });
+                pool.reset(0..desc.count);
+                pool
+            }
+            QueryType::PipelineStatistics(_) => {
+                return Err(RhiError::Unsupported(
+                    "Metal 4 has no pipeline-statistics counters".into(),
+                ));
+            }
+        };
+        Ok(QueryPool {
+            inner: QueryPoolInner::Metal(Box::new(inner)),
+            ty: desc.ty,
+            count: desc.count,
+        })
+    }
+
+    pub fn timestamp_period(&self) -> f64 {
+        1e9 / self.device.queryTimestampFrequency() as f64
+    }
+
     pub fn destroy_buffer(&self, buffer: GpuBuffer) {
         match buffer.inner {
             #[cfg(feature = "metal")]
             GpuBufferInner::Metal(mtl) => {
                 {
-                    let mut allocations = self.allocations.borrow_mut();
+                    let mut allocations =
+                        self.allocations.lock().expect("allocations lock poisoned");
                     allocations.remove(&mtl.gpu_address().0);
                 }
+                if let Some(mapped) = mtl.mapped_ptr() {
+                    self.mapped_ranges
+                        .lock()
+                        .expect("mapped ranges lock poisoned")
+                        .remove(&(mapped as usize));
+                }
                 let allocation = unsafe {
                     &*(mtl.buffer.as_ref() as *const ProtocolObject<dyn MTLBuffer>
                         as *const ProtocolObject<dyn MTLAllocation>)
                 };
-                self.residency_set.removeAllocation(allocation);
-                self.residency_dirty.set(true);
+                self.residency.remove(allocation);
+                if mtl.sub_allocated
+                    && let Some(heap) = mtl.heap.as_ref()
+                {
+                    self.heap_allocator
+                        .lock()
+                        .expect("heap allocator lock poisoned")
+                        .free(heap, mtl.heap_offset);
+                }
             }
             #[cfg(feature = "vulkan")]
             GpuBufferInner::Vulkan(_) => {}
@@ -1550,8 +2227,18 @@ impl MetalDevice {
     }
 
     pub fn destroy_texture(&self, texture: Texture) {
-        let mut textures = self.textures.borrow_mut();
-        let idx = texture.id.0 as usize;
+        self.release_texture_id(texture.id);
+    }
+
+    pub fn destroy_texture_view(&self, id: TextureId) {
+        self.release_texture_id(id);
+    }
+
+    /// Drop the texture or view in slot `id` from residency and recycle the slot once the
+    /// latest submission retires.
+    fn release_texture_id(&self, id: TextureId) {
+        let mut textures = self.textures.lock().expect("textures lock poisoned");
+        let idx = id.0 as usize;
         if idx < textures.len()
             && let Some(tex) = textures[idx].take()
         {
@@ -1559,8 +2246,11 @@ impl MetalDevice {
                 &*(tex.as_ref() as *const ProtocolObject<dyn MTLTexture>
                     as *const ProtocolObject<dyn MTLAllocation>)
             };
-            self.residency_set.removeAllocation(allocation);
-            self.residency_dirty.set(true);
+            self.residency.remove(allocation);
+            self.texture_slots
+                .lock()
+                .expect("texture slots lock poisoned")
+                .free(id.0, self.rhi_queue.submitted_serial());
         }
     }
 
@@ -1590,8 +2280,8 @@ impl MetalDevice {
         use crate::texture::{ALL_LAYERS, ALL_MIPS};
         use objc2_foundation::NSRange;
 
-        let textures_borrow = self.textures.borrow();
-        let src_texture = textures_borrow
+        let textures = self.textures.lock().expect("textures lock poisoned");
+        let src_texture = textures
             .get(source.id.0 as usize)
             .and_then(|t| t.as_ref())
             .ok_or_else(|| {
@@ -1600,7 +2290,7 @@ impl MetalDevice {
                 )
             })?
             .clone();
-        drop(textures_borrow);
+        drop(textures);
 
         let src_format = super::texture::format_to_mtl(view.format.unwrap_or(source.desc().format));
         let src_type = match source.desc().dimension {
@@ -1655,15 +2345,9 @@ impl MetalDevice {
             &*(view_texture.as_ref() as *const ProtocolObject<dyn MTLTexture>
                 as *const ProtocolObject<dyn MTLAllocation>)
         };
-        self.residency_set.addAllocation(allocation);
-        self.residency_dirty.set(true);
-
-        let mut textures = self.textures.borrow_mut();
-        let id = TextureId(textures.len() as u32);
-        textures.push(Some(view_texture));
-        drop(textures);
+        self.residency.add(allocation);
 
-        Ok(id)
+        self.insert_texture(view_texture)
     }
 }
 
@@ -2,15 +2,26 @@ use objc2::rc::Retained;
 use objc2::runtime::ProtocolObject;
 use objc2_metal::{MTLBuffer, MTLHeap};
 
+use crate::error::{RhiError, RhiResult};
 use crate::types::GpuAddress;
 
 pub struct MetalBuffer {
     pub(crate) buffer: Retained<ProtocolObject<dyn MTLBuffer>>,
     pub(crate) heap: Option<Retained<ProtocolObject<dyn MTLHeap>>>,
+    /// Offset of the buffer inside `heap`. Non-zero only for buffers sub-allocated from a
+    /// shared placement heap.
+    pub(crate) heap_offset: u64,
+    /// True when `heap` is a shared block owned by the device's heap allocator.
+    pub(crate) sub_allocated: bool,
     pub(crate) size: u64,
     pub(crate) is_shared: bool,
 }
 
+// SAFETY: Metal buffers and heaps are thread-safe; the mapped pointer is only used for
+// CPU-side uploads and readbacks, which the caller orders against GPU work.
+unsafe impl Send for MetalBuffer {}
+unsafe impl Sync for MetalBuffer {}
+
 impl MetalBuffer {
     pub fn mapped_ptr(&self) -> Option<*mut u8> {
         if self.is_shared {
@@ -27,4 +38,10 @@ impl MetalBuffer {
     pub fn size(&self) -> u64 {
         self.size
     }
+
+    pub fn export_fd(&self) -> RhiResult<std::os::fd::OwnedFd> {
+        Err(RhiError::Unsupported(
+            "Metal does not export memory as file descriptors".into(),
+        ))
+    }
 }
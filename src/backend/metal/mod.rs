@@ -1,9 +1,12 @@
 pub mod accel;
+pub(crate) mod allocator;
 pub mod barrier;
+pub mod bundle;
 pub mod command;
 pub mod device;
 pub mod memory;
 pub mod pipeline;
+pub mod query;
 pub mod shader;
 pub mod surface;
 pub mod swapchain;
@@ -1,5 +1,5 @@
-use std::cell::RefCell;
 use std::collections::HashMap;
+use std::sync::Mutex;
 
 use objc2::rc::Retained;
 use objc2::runtime::ProtocolObject;
@@ -34,10 +34,14 @@ pub struct MetalGraphicsPso {
     pub(crate) stencil_format: objc2_metal::MTLPixelFormat,
     pub(crate) sample_count: usize,
     pub(crate) alpha_to_coverage: bool,
+    /// Vertex amplification count for multiview; 1 without a view mask.
+    pub(crate) amplification_count: usize,
     pub(crate) root_constant_size: u32,
     pub(crate) graphics_argument_buffer_slots: Vec<usize>,
+    /// Debug label of every blend variant.
+    pub(crate) label: Option<String>,
     pub(crate) blend_pipelines:
-        RefCell<HashMap<BlendState, Retained<ProtocolObject<dyn MTLRenderPipelineState>>>>,
+        Mutex<HashMap<BlendState, Retained<ProtocolObject<dyn MTLRenderPipelineState>>>>,
 }
 
 pub struct MetalComputePso {
@@ -55,15 +59,18 @@ impl MetalGraphicsPso {
         &self,
         blend: &BlendState,
     ) -> Retained<ProtocolObject<dyn MTLRenderPipelineState>> {
-        if let Some(pso) = self.blend_pipelines.borrow().get(blend) {
+        // Held across creation so two threads missing the same variant build it once.
+        let mut pipelines = self
+            .blend_pipelines
+            .lock()
+            .expect("blend pipelines lock poisoned");
+        if let Some(pso) = pipelines.get(blend) {
             return pso.clone();
         }
         let pso = self
             .create_pipeline(blend)
             .expect("Metal 4 graphics PSO creation failed for dynamic blend variant");
-        self.blend_pipelines
-            .borrow_mut()
-            .insert(blend.clone(), pso.clone());
+        pipelines.insert(blend.clone(), pso.clone());
         pso
     }
 
@@ -80,7 +87,9 @@ impl MetalGraphicsPso {
             &self.color_formats,
             self.sample_count,
             self.alpha_to_coverage,
+            self.amplification_count,
             blend,
+            self.label.as_deref(),
         )
     }
 
@@ -94,7 +103,9 @@ impl MetalGraphicsPso {
         color_formats: &[objc2_metal::MTLPixelFormat],
         sample_count: usize,
         alpha_to_coverage: bool,
+        amplification_count: usize,
         blend: &BlendState,
+        label: Option<&str>,
     ) -> RhiResult<Retained<ProtocolObject<dyn MTLRenderPipelineState>>> {
         let vertex_name = NSString::from_str(vertex_entry_point);
         let fragment_name = NSString::from_str(fragment_entry_point);
@@ -125,6 +136,7 @@ impl MetalGraphicsPso {
 
         unsafe {
             pso_desc.setRasterSampleCount(sample_count);
+            pso_desc.setMaxVertexAmplificationCount(amplification_count);
         }
         pso_desc.setAlphaToCoverageState(if alpha_to_coverage {
             MTL4AlphaToCoverageState::Enabled
@@ -140,6 +152,9 @@ impl MetalGraphicsPso {
         pso_desc.setOptions(Some(&options));
 
         let base_desc: &MTL4PipelineDescriptor = pso_desc.as_ref();
+        if let Some(label) = label {
+            base_desc.setLabel(Some(&NSString::from_str(label)));
+        }
         compiler
             .newRenderPipelineStateWithDescriptor_compilerTaskOptions_error(base_desc, None)
             .map_err(|e| {
@@ -238,7 +253,7 @@ pub struct MetalMeshletPso {
     /// Blend pipeline variants (same flyweight mechanism as graphics PSOs).
     #[allow(dead_code)]
     pub(crate) blend_pipelines:
-        RefCell<HashMap<BlendState, Retained<ProtocolObject<dyn MTLRenderPipelineState>>>>,
+        Mutex<HashMap<BlendState, Retained<ProtocolObject<dyn MTLRenderPipelineState>>>>,
     // Hold the compiled pipeline state (default blend variant).
     pub(crate) default_pipeline: Retained<ProtocolObject<dyn MTLRenderPipelineState>>,
 }
@@ -0,0 +1,59 @@
+use std::ops::Range;
+
+use objc2::rc::Retained;
+use objc2::runtime::ProtocolObject;
+use objc2_foundation::NSRange;
+use objc2_metal::{MTL4CounterHeap, MTLAllocation, MTLBuffer};
+
+use crate::query::QUERY_RESULT_SIZE;
+
+use super::device::SharedResidency;
+
+/// Storage backing a Metal query pool.
+pub enum MetalQueryPool {
+    /// Timestamps, in a Metal 4 counter heap.
+    Counters(Retained<ProtocolObject<dyn MTL4CounterHeap>>),
+    /// Occlusion queries, in a shared visibility result buffer of one `u64` per query.
+    Visibility(VisibilityBuffer),
+}
+
+/// Visibility result buffer, resident while the pool lives.
+pub struct VisibilityBuffer {
+    pub(crate) buffer: Retained<ProtocolObject<dyn MTLBuffer>>,
+    pub(crate) residency: SharedResidency,
+}
+
+impl Drop for VisibilityBuffer {
+    fn drop(&mut self) {
+        let allocation = unsafe {
+            &*(self.buffer.as_ref() as *const ProtocolObject<dyn MTLBuffer>
+                as *const ProtocolObject<dyn MTLAllocation>)
+        };
+        self.residency.remove(allocation);
+    }
+}
+
+// SAFETY: counter heaps and buffers are Metal resources, which any thread may reference;
+// results are read and reset through the buffer contents, as for any other shared buffer.
+unsafe impl Send for MetalQueryPool {}
+unsafe impl Sync for MetalQueryPool {}
+
+impl MetalQueryPool {
+    /// Invalidated counters resolve to zero until written again. Visibility results
+    /// accumulate across render passes, so they are zeroed.
+    pub fn reset(&self, range: Range<u32>) {
+        match self {
+            MetalQueryPool::Counters(heap) => unsafe {
+                heap.invalidateCounterRange(NSRange::new(range.start as usize, range.len()))
+            },
+            MetalQueryPool::Visibility(visibility) => unsafe {
+                let results = visibility.buffer.contents().as_ptr() as *mut u8;
+                std::ptr::write_bytes(
+                    results.add((range.start as u64 * QUERY_RESULT_SIZE) as usize),
+                    0,
+                    (range.len() as u64 * QUERY_RESULT_SIZE) as usize,
+                );
+            },
+        }
+    }
+}
@@ -1,5 +1,6 @@
 use objc2_metal::MTLPixelFormat;
 
+use crate::command::ClearValue;
 use crate::types::Format;
 
 /// Convert RHI Format to MTLPixelFormat.
@@ -28,6 +29,59 @@ pub fn format_to_mtl(format: Format) -> MTLPixelFormat {
         // Index (not used as pixel format, but map for completeness)
         Format::R16Uint => MTLPixelFormat::R16Uint,
         Format::R32Uint => MTLPixelFormat::R32Uint,
+        // Block-compressed
+        Format::Bc1RgbaUnorm => MTLPixelFormat::BC1_RGBA,
+        Format::Bc1RgbaSrgb => MTLPixelFormat::BC1_RGBA_sRGB,
+        Format::Bc2RgbaUnorm => MTLPixelFormat::BC2_RGBA,
+        Format::Bc2RgbaSrgb => MTLPixelFormat::BC2_RGBA_sRGB,
+        Format::Bc3RgbaUnorm => MTLPixelFormat::BC3_RGBA,
+        Format::Bc3RgbaSrgb => MTLPixelFormat::BC3_RGBA_sRGB,
+        Format::Bc4RUnorm => MTLPixelFormat::BC4_RUnorm,
+        Format::Bc4RSnorm => MTLPixelFormat::BC4_RSnorm,
+        Format::Bc5RgUnorm => MTLPixelFormat::BC5_RGUnorm,
+        Format::Bc5RgSnorm => MTLPixelFormat::BC5_RGSnorm,
+        Format::Bc6hRgbUfloat => MTLPixelFormat::BC6H_RGBUfloat,
+        Format::Bc6hRgbSfloat => MTLPixelFormat::BC6H_RGBFloat,
+        Format::Bc7RgbaUnorm => MTLPixelFormat::BC7_RGBAUnorm,
+        Format::Bc7RgbaSrgb => MTLPixelFormat::BC7_RGBAUnorm_sRGB,
+        Format::Etc2R8G8B8Unorm => MTLPixelFormat::ETC2_RGB8,
+        Format::Etc2R8G8B8Srgb => MTLPixelFormat::ETC2_RGB8_sRGB,
+        Format::Etc2R8G8B8A1Unorm => MTLPixelFormat::ETC2_RGB8A1,
+        Format::Etc2R8G8B8A1Srgb => MTLPixelFormat::ETC2_RGB8A1_sRGB,
+        Format::Etc2R8G8B8A8Unorm => MTLPixelFormat::EAC_RGBA8,
+        Format::Etc2R8G8B8A8Srgb => MTLPixelFormat::EAC_RGBA8_sRGB,
+        Format::EacR11Unorm => MTLPixelFormat::EAC_R11Unorm,
+        Format::EacR11Snorm => MTLPixelFormat::EAC_R11Snorm,
+        Format::EacR11G11Unorm => MTLPixelFormat::EAC_RG11Unorm,
+        Format::EacR11G11Snorm => MTLPixelFormat::EAC_RG11Snorm,
+        Format::Astc4x4Unorm => MTLPixelFormat::ASTC_4x4_LDR,
+        Format::Astc4x4Srgb => MTLPixelFormat::ASTC_4x4_sRGB,
+        Format::Astc5x4Unorm => MTLPixelFormat::ASTC_5x4_LDR,
+        Format::Astc5x4Srgb => MTLPixelFormat::ASTC_5x4_sRGB,
+        Format::Astc5x5Unorm => MTLPixelFormat::ASTC_5x5_LDR,
+        Format::Astc5x5Srgb => MTLPixelFormat::ASTC_5x5_sRGB,
+        Format::Astc6x5Unorm => MTLPixelFormat::ASTC_6x5_LDR,
+        Format::Astc6x5Srgb => MTLPixelFormat::ASTC_6x5_sRGB,
+        Format::Astc6x6Unorm => MTLPixelFormat::ASTC_6x6_LDR,
+        Format::Astc6x6Srgb => MTLPixelFormat::ASTC_6x6_sRGB,
+        Format::Astc8x5Unorm => MTLPixelFormat::ASTC_8x5_LDR,
+        Format::Astc8x5Srgb => MTLPixelFormat::ASTC_8x5_sRGB,
+        Format::Astc8x6Unorm => MTLPixelFormat::ASTC_8x6_LDR,
+        Format::Astc8x6Srgb => MTLPixelFormat::ASTC_8x6_sRGB,
+        Format::Astc8x8Unorm => MTLPixelFormat::ASTC_8x8_LDR,
+        Format::Astc8x8Srgb => MTLPixelFormat::ASTC_8x8_sRGB,
+        Format::Astc10x5Unorm => MTLPixelFormat::ASTC_10x5_LDR,
+        Format::Astc10x5Srgb => MTLPixelFormat::ASTC_10x5_sRGB,
+        Format::Astc10x6Unorm => MTLPixelFormat::ASTC_10x6_LDR,
+        Format::Astc10x6Srgb => MTLPixelFormat::ASTC_10x6_sRGB,
+        Format::Astc10x8Unorm => MTLPixelFormat::ASTC_10x8_LDR,
+        Format::Astc10x8Srgb => MTLPixelFormat::ASTC_10x8_sRGB,
+        Format::Astc10x10Unorm => MTLPixelFormat::ASTC_10x10_LDR,
+        Format::Astc10x10Srgb => MTLPixelFormat::ASTC_10x10_sRGB,
+        Format::Astc12x10Unorm => MTLPixelFormat::ASTC_12x10_LDR,
+        Format::Astc12x10Srgb => MTLPixelFormat::ASTC_12x10_sRGB,
+        Format::Astc12x12Unorm => MTLPixelFormat::ASTC_12x12_LDR,
+        Format::Astc12x12Srgb => MTLPixelFormat::ASTC_12x12_sRGB,
     }
 }
 
@@ -57,6 +111,170 @@ pub fn mtl_to_format(mtl: MTLPixelFormat) -> Format {
         MTLPixelFormat::Depth32Float_Stencil8 => Format::D32FloatS8Uint,
         MTLPixelFormat::R16Uint => Format::R16Uint,
         MTLPixelFormat::R32Uint => Format::R32Uint,
+        MTLPixelFormat::BC1_RGBA => Format::Bc1RgbaUnorm,
+        MTLPixelFormat::BC1_RGBA_sRGB => Format::Bc1RgbaSrgb,
+        MTLPixelFormat::BC2_RGBA => Format::Bc2RgbaUnorm,
+        MTLPixelFormat::BC2_RGBA_sRGB => Format::Bc2RgbaSrgb,
+        MTLPixelFormat::BC3_RGBA => Format::Bc3RgbaUnorm,
+        MTLPixelFormat::BC3_RGBA_sRGB => Format::Bc3RgbaSrgb,
+        MTLPixelFormat::BC4_RUnorm => Format::Bc4RUnorm,
+        MTLPixelFormat::BC4_RSnorm => Format::Bc4RSnorm,
+        MTLPixelFormat::BC5_RGUnorm => Format::Bc5RgUnorm,
+        MTLPixelFormat::BC5_RGSnorm => Format::Bc5RgSnorm,
+        MTLPixelFormat::BC6H_RGBUfloat => Format::Bc6hRgbUfloat,
+        MTLPixelFormat::BC6H_RGBFloat => Format::Bc6hRgbSfloat,
+        MTLPixelFormat::BC7_RGBAUnorm => Format::Bc7RgbaUnorm,
+        MTLPixelFormat::BC7_RGBAUnorm_sRGB => Format::Bc7RgbaSrgb,
+        MTLPixelFormat::ETC2_RGB8 => Format::Etc2R8G8B8Unorm,
+        MTLPixelFormat::ETC2_RGB8_sRGB => Format::Etc2R8G8B8Srgb,
+        MTLPixelFormat::ETC2_RGB8A1 => Format::Etc2R8G8B8A1Unorm,
+        MTLPixelFormat::ETC2_RGB8A1_sRGB => Format::Etc2R8G8B8A1Srgb,
+        MTLPixelFormat::EAC_RGBA8 => Format::Etc2R8G8B8A8Unorm,
+        MTLPixelFormat::EAC_RGBA8_sRGB => Format::Etc2R8G8B8A8Srgb,
+        MTLPixelFormat::EAC_R11Unorm => Format::EacR11Unorm,
+        MTLPixelFormat::EAC_R11Snorm => Format::EacR11Snorm,
+        MTLPixelFormat::EAC_RG11Unorm => Format::EacR11G11Unorm,
+        MTLPixelFormat::EAC_RG11Snorm => Format::EacR11G11Snorm,
+        MTLPixelFormat::ASTC_4x4_LDR => Format::Astc4x4Unorm,
+        MTLPixelFormat::ASTC_4x4_sRGB => Format::Astc4x4Srgb,
+        MTLPixelFormat::ASTC_5x4_LDR => Format::Astc5x4Unorm,
+        MTLPixelFormat::ASTC_5x4_sRGB => Format::Astc5x4Srgb,
+        MTLPixelFormat::ASTC_5x5_LDR => Format::Astc5x5Unorm,
+        MTLPixelFormat::ASTC_5x5_sRGB => Format::Astc5x5Srgb,
+        MTLPixelFormat::ASTC_6x5_LDR => Format::Astc6x5Unorm,
+        MTLPixelFormat::ASTC_6x5_sRGB => Format::Astc6x5Srgb,
+        MTLPixelFormat::ASTC_6x6_LDR => Format::Astc6x6Unorm,
+        MTLPixelFormat::ASTC_6x6_sRGB => Format::Astc6x6Srgb,
+        MTLPixelFormat::ASTC_8x5_LDR => Format::Astc8x5Unorm,
+        MTLPixelFormat::ASTC_8x5_sRGB => Format::Astc8x5Srgb,
+        MTLPixelFormat::ASTC_8x6_LDR => Format::Astc8x6Unorm,
+        MTLPixelFormat::ASTC_8x6_sRGB => Format::Astc8x6Srgb,
+        MTLPixelFormat::ASTC_8x8_LDR => Format::Astc8x8Unorm,
+        MTLPixelFormat::ASTC_8x8_sRGB => Format::Astc8x8Srgb,
+        MTLPixelFormat::ASTC_10x5_LDR => Format::Astc10x5Unorm,
+        MTLPixelFormat::ASTC_10x5_sRGB => Format::Astc10x5Srgb,
+        MTLPixelFormat::ASTC_10x6_LDR => Format::Astc10x6Unorm,
+        MTLPixelFormat::ASTC_10x6_sRGB => Format::Astc10x6Srgb,
+        MTLPixelFormat::ASTC_10x8_LDR => Format::Astc10x8Unorm,
+        MTLPixelFormat::ASTC_10x8_sRGB => Format::Astc10x8Srgb,
+        MTLPixelFormat::ASTC_10x10_LDR => Format::Astc10x10Unorm,
+        MTLPixelFormat::ASTC_10x10_sRGB => Format::Astc10x10Srgb,
+        MTLPixelFormat::ASTC_12x10_LDR => Format::Astc12x10Unorm,
+        MTLPixelFormat::ASTC_12x10_sRGB => Format::Astc12x10Srgb,
+        MTLPixelFormat::ASTC_12x12_LDR => Format::Astc12x12Unorm,
+        MTLPixelFormat::ASTC_12x12_sRGB => Format::Astc12x12Srgb,
         other => panic!("MTLPixelFormat {other:?} has no kiln-rhi Format mapping"),
     }
 }
+
+/// Texel bytes `clear_texture` copies into each plane of a `format` texture: one entry for
+/// color and single-aspect depth formats, the depth then the stencil plane for combined
+/// depth-stencil formats (copied with `DepthFromDepthStencil` / `StencilFromDepthStencil`).
+pub(crate) fn clear_texel_planes(format: Format, value: ClearValue) -> Vec<Vec<u8>> {
+    if let ClearValue::DepthStencil { depth, stencil } = value {
+        let depth = depth.clamp(0.0, 1.0);
+        return match format {
+            Format::D16Unorm => vec![(unorm(depth, 16) as u16).to_le_bytes().to_vec()],
+            Format::D32Float => vec![depth.to_le_bytes().to_vec()],
+            // Depth travels as a 32-bit word with the 24 depth bits at the bottom.
+            Format::D24UnormS8Uint => vec![unorm(depth, 24).to_le_bytes().to_vec(), vec![stencil]],
+            Format::D32FloatS8Uint => vec![depth.to_le_bytes().to_vec(), vec![stencil]],
+            _ => unreachable!("depth clear value for {format:?}"),
+        };
+    }
+    let texel = match (format, value) {
+        (Format::R16Uint, ClearValue::Uint([r, ..])) => (r as u16).to_le_bytes().to_vec(),
+        (Format::R32Uint, ClearValue::Uint([r, ..])) => r.to_le_bytes().to_vec(),
+        (_, ClearValue::Color(c)) => color_texel(format, c),
+        _ => unreachable!("clear value {value:?} for {format:?}"),
+    };
+    vec![texel]
+}
+
+fn color_texel(format: Format, [r, g, b, a]: [f32; 4]) -> Vec<u8> {
+    let unorm8 = |c: f32| unorm(c, 8) as u8;
+    let srgb8 = |c: f32| unorm(linear_to_srgb(c), 8) as u8;
+    let half = |c: f32| (small_float(c, 10, true) as u16).to_le_bytes();
+    let floats = |cs: &[f32]| cs.iter().flat_map(|c| c.to_le_bytes()).collect();
+    match format {
+        Format::R8Unorm => vec![unorm8(r)],
+        Format::R8G8Unorm => vec![unorm8(r), unorm8(g)],
+        Format::R8G8B8A8Unorm => vec![unorm8(r), unorm8(g), unorm8(b), unorm8(a)],
+        Format::R8G8B8A8Srgb => vec![srgb8(r), srgb8(g), srgb8(b), unorm8(a)],
+        Format::B8G8R8A8Unorm => vec![unorm8(b), unorm8(g), unorm8(r), unorm8(a)],
+        Format::B8G8R8A8Srgb => vec![srgb8(b), srgb8(g), srgb8(r), unorm8(a)],
+        Format::R16Float => half(r).to_vec(),
+        Format::R16G16Float => [half(r), half(g)].concat(),
+        Format::R16G16B16A16Float => [half(r), half(g), half(b), half(a)].concat(),
+        Format::R32Float => floats(&[r]),
+        Format::R32G32Float => floats(&[r, g]),
+        Format::R32G32B32A32Float => floats(&[r, g, b, a]),
+        Format::R10G10B10A2Unorm => {
+            let packed = unorm(r, 10) | unorm(g, 10) << 10 | unorm(b, 10) << 20 | unorm(a, 2) << 30;
+            packed.to_le_bytes().to_vec()
+        }
+        Format::R11G11B10Float => {
+            let packed = small_float(r, 6, false)
+                | small_float(g, 6, false) << 11
+                | small_float(b, 5, false) << 22;
+            packed.to_le_bytes().to_vec()
+        }
+        _ => unreachable!("color clear value for {format:?}"),
+    }
+}
+
+/// `c` clamped to [0, 1] and scaled to a `bits`-wide unsigned normalized integer.
+fn unorm(c: f32, bits: u32) -> u32 {
+    let max = ((1u64 << bits) - 1) as f32;
+    (c.clamp(0.0, 1.0) * max).round() as u32
+}
+
+fn linear_to_srgb(c: f32) -> f32 {
+    let c = c.clamp(0.0, 1.0);
+    if c <= 0.003_130_8 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// `v` as a float with a 5-bit exponent and `mantissa_bits` of mantissa, rounded to nearest
+/// even: half precision when `signed`, the 11- and 10-bit floats of R11G11B10 otherwise
+/// (which clamp negatives to zero).
+fn small_float(v: f32, mantissa_bits: u32, signed: bool) -> u32 {
+    let exponent_max: u32 = 0x1f;
+    let sign = if signed && v.is_sign_negative() {
+        1 << (5 + mantissa_bits)
+    } else {
+        0
+    };
+    if v.is_nan() {
+        return sign | exponent_max << mantissa_bits | 1 << (mantissa_bits - 1);
+    }
+    if !signed && v <= 0.0 {
+        return 0;
+    }
+    let bits = v.abs().to_bits();
+    let exponent = (bits >> 23) as i32 - 127 + 15;
+    if exponent >= exponent_max as i32 {
+        return sign | exponent_max << mantissa_bits;
+    }
+    // Subnormals keep the implicit leading one and shift further right.
+    let (mantissa, shift, biased) = if exponent > 0 {
+        (bits & 0x7f_ffff, 23 - mantissa_bits, exponent as u32)
+    } else {
+        let shift = 23 - mantissa_bits + (1 - exponent) as u32;
+        if shift > 24 {
+            return sign;
+        }
+        ((bits & 0x7f_ffff) | 0x80_0000, shift, 0)
+    };
+    let halfway = 1 << (shift - 1);
+    let remainder = mantissa & ((1 << shift) - 1);
+    let mut rounded = mantissa >> shift;
+    if remainder > halfway || (remainder == halfway && rounded & 1 == 1) {
+        rounded += 1;
+    }
+    // A mantissa carry rolls into the exponent, up to infinity at worst.
+    sign | ((biased << mantissa_bits) + rounded)
+}
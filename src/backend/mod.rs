@@ -4,6 +4,8 @@ pub mod vulkan;
 #[cfg(feature = "metal")]
 pub mod metal;
 
+pub(crate) mod tlsf;
+
 /// Active backend kind.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum BackendKind {
@@ -0,0 +1,247 @@
+//! Two-level segregated fit (TLSF) range allocator.
+//!
+//! Manages byte offsets inside a fixed-size range without touching the memory itself, so the
+//! same placement logic backs Vulkan `VkDeviceMemory` blocks and Metal placement heaps. Free
+//! ranges are binned by a first level (power of two) and a second level (linear subdivision of
+//! that power of two); two bitmaps make finding a fitting bin O(1), and neighbouring free ranges
+//! are coalesced on free so fragmentation stays bounded.
+
+use std::collections::HashMap;
+
+/// Allocation granularity: every offset and size handed out is a multiple of this.
+pub(crate) const TLSF_GRANULE: u64 = 16;
+
+const SL_LOG2: u32 = 4;
+const SL_COUNT: usize = 1 << SL_LOG2;
+const FL_COUNT: usize = 64 - SL_LOG2 as usize + 1;
+const NIL: u32 = u32::MAX;
+
+struct Node {
+    offset: u64,
+    size: u64,
+    free: bool,
+    prev_phys: u32,
+    next_phys: u32,
+    prev_free: u32,
+    next_free: u32,
+}
+
+pub(crate) struct Tlsf {
+    nodes: Vec<Node>,
+    unused_nodes: Vec<u32>,
+    fl_bitmap: u64,
+    sl_bitmaps: [u32; FL_COUNT],
+    heads: [[u32; SL_COUNT]; FL_COUNT],
+    /// Live allocations keyed by offset, so `free` needs only the offset it handed out.
+    used: HashMap<u64, u32>,
+    allocated: u64,
+}
+
+/// Bin for a size expressed in granules: first level is the power of two, second level the
+/// `SL_COUNT`-way linear split below it. Sizes smaller than `SL_COUNT` granules share level 0.
+fn mapping(granules: u64) -> (usize, usize) {
+    if granules < SL_COUNT as u64 {
+        (0, granules as usize)
+    } else {
+        let fl = 63 - granules.leading_zeros();
+        let sl = ((granules >> (fl - SL_LOG2)) as usize) ^ SL_COUNT;
+        ((fl - SL_LOG2 + 1) as usize, sl)
+    }
+}
+
+impl Tlsf {
+    /// An allocator over `[0, size)`; `size` is rounded down to the granule.
+    pub(crate) fn new(size: u64) -> Self {
+        let size = size & !(TLSF_GRANULE - 1);
+        let mut tlsf = Self {
+            nodes: Vec::new(),
+            unused_nodes: Vec::new(),
+            fl_bitmap: 0,
+            sl_bitmaps: [0; FL_COUNT],
+            heads: [[NIL; SL_COUNT]; FL_COUNT],
+            used: HashMap::new(),
+            allocated: 0,
+        };
+        if size > 0 {
+            let node = tlsf.new_node(0, size, NIL, NIL);
+            tlsf.insert_free(node);
+        }
+        tlsf
+    }
+
+    /// Bytes currently handed out (granule-rounded).
+    pub(crate) fn allocated(&self) -> u64 {
+        self.allocated
+    }
+
+    /// Place `size` bytes at an offset that is a multiple of `align` (a power of two).
+    /// Returns `None` when no free range can hold the request.
+    pub(crate) fn allocate(&mut self, size: u64, align: u64) -> Option<u64> {
+        debug_assert!(
+            align.is_power_of_two(),
+            "TLSF alignment must be a power of two"
+        );
+        let size = size.max(1).checked_next_multiple_of(TLSF_GRANULE)?;
+        let align = align.max(TLSF_GRANULE);
+        // Over-ask by the worst-case alignment padding so any range found can be aligned.
+        let search = size.checked_add(align - TLSF_GRANULE)?;
+        let node = self.find_free(search)?;
+        self.remove_free(node);
+
+        let offset = self.nodes[node as usize].offset;
+        let aligned = offset.next_multiple_of(align);
+        let pad = aligned - offset;
+        if pad > 0 {
+            // The physical predecessor is never free (free neighbours are always coalesced),
+            // so the padding becomes a standalone free range.
+            let prev = self.nodes[node as usize].prev_phys;
+            let front = self.new_node(offset, pad, prev, node);
+            if prev != NIL {
+                self.nodes[prev as usize].next_phys = front;
+            }
+            let n = &mut self.nodes[node as usize];
+            n.prev_phys = front;
+            n.offset = aligned;
+            n.size -= pad;
+            self.insert_free(front);
+        }
+
+        let remaining = self.nodes[node as usize].size - size;
+        if remaining > 0 {
+            let next = self.nodes[node as usize].next_phys;
+            let back = self.new_node(aligned + size, remaining, node, next);
+            if next != NIL {
+                self.nodes[next as usize].prev_phys = back;
+            }
+            let n = &mut self.nodes[node as usize];
+            n.next_phys = back;
+            n.size = size;
+            self.insert_free(back);
+        }
+
+        self.nodes[node as usize].free = false;
+        self.used.insert(aligned, node);
+        self.allocated += size;
+        Some(aligned)
+    }
+
+    /// Release the allocation that `allocate` placed at `offset`, returning its size, or
+    /// `None` if no live allocation starts there.
+    pub(crate) fn free(&mut self, offset: u64) -> Option<u64> {
+        let mut node = self.used.remove(&offset)?;
+        let size = self.nodes[node as usize].size;
+        self.allocated -= size;
+        self.nodes[node as usize].free = true;
+
+        let prev = self.nodes[node as usize].prev_phys;
+        if prev != NIL && self.nodes[prev as usize].free {
+            self.remove_free(prev);
+            self.absorb_next(prev);
+            node = prev;
+        }
+        let next = self.nodes[node as usize].next_phys;
+        if next != NIL && self.nodes[next as usize].free {
+            self.remove_free(next);
+            self.absorb_next(node);
+        }
+        self.insert_free(node);
+        Some(size)
+    }
+
+    /// Merge `node`'s physical successor into `node` and recycle the successor's slot.
+    fn absorb_next(&mut self, node: u32) {
+        let next = self.nodes[node as usize].next_phys;
+        let (next_size, next_next) = {
+            let n = &self.nodes[next as usize];
+            (n.size, n.next_phys)
+        };
+        let n = &mut self.nodes[node as usize];
+        n.size += next_size;
+        n.next_phys = next_next;
+        if next_next != NIL {
+            self.nodes[next_next as usize].prev_phys = node;
+        }
+        self.unused_nodes.push(next);
+    }
+
+    fn new_node(&mut self, offset: u64, size: u64, prev_phys: u32, next_phys: u32) -> u32 {
+        let node = Node {
+            offset,
+            size,
+            free: true,
+            prev_phys,
+            next_phys,
+            prev_free: NIL,
+            next_free: NIL,
+        };
+        if let Some(index) = self.unused_nodes.pop() {
+            self.nodes[index as usize] = node;
+            index
+        } else {
+            self.nodes.push(node);
+            (self.nodes.len() - 1) as u32
+        }
+    }
+
+    /// First free range of at least `size` bytes. The request is rounded up to the next bin
+    /// boundary so that every range in the chosen bin is guaranteed to fit.
+    fn find_free(&self, size: u64) -> Option<u32> {
+        let mut granules = size / TLSF_GRANULE;
+        if granules >= SL_COUNT as u64 {
+            let fl = 63 - granules.leading_zeros();
+            granules = granules.checked_add((1 << (fl - SL_LOG2)) - 1)?;
+        }
+        let (mut fl, sl) = mapping(granules);
+        let mut sl_map = self.sl_bitmaps[fl] & (!0u32 << sl);
+        if sl_map == 0 {
+            let fl_map = self.fl_bitmap & (!0u64).checked_shl(fl as u32 + 1).unwrap_or(0);
+            if fl_map == 0 {
+                return None;
+            }
+            fl = fl_map.trailing_zeros() as usize;
+            sl_map = self.sl_bitmaps[fl];
+        }
+        let sl = sl_map.trailing_zeros() as usize;
+        Some(self.heads[fl][sl])
+    }
+
+    fn insert_free(&mut self, node: u32) {
+        let (fl, sl) = mapping(self.nodes[node as usize].size / TLSF_GRANULE);
+        let head = self.heads[fl][sl];
+        {
+            let n = &mut self.nodes[node as usize];
+            n.free = true;
+            n.prev_free = NIL;
+            n.next_free = head;
+        }
+        if head != NIL {
+            self.nodes[head as usize].prev_free = node;
+        }
+        self.heads[fl][sl] = node;
+        self.sl_bitmaps[fl] |= 1 << sl;
+        self.fl_bitmap |= 1 << fl;
+    }
+
+    fn remove_free(&mut self, node: u32) {
+        let (fl, sl) = mapping(self.nodes[node as usize].size / TLSF_GRANULE);
+        let (prev, next) = {
+            let n = &self.nodes[node as usize];
+            (n.prev_free, n.next_free)
+        };
+        if prev != NIL {
+            self.nodes[prev as usize].next_free = next;
+        }
+        if next != NIL {
+            self.nodes[next as usize].prev_free = prev;
+        }
+        if self.heads[fl][sl] == node {
+            self.heads[fl][sl] = next;
+            if next == NIL {
+                self.sl_bitmaps[fl] &= !(1 << sl);
+                if self.sl_bitmaps[fl] == 0 {
+                    self.fl_bitmap &= !(1 << fl);
+                }
+            }
+        }
+    }
+}
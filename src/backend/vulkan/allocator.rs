@@ -0,0 +1,449 @@
+//! Sub-allocation of `VkDeviceMemory` blocks.
+//!
+//! Every buffer used to cost one `vkAllocateMemory`, which runs into
+//! `maxMemoryAllocationCount` with many small allocations. Instead, each `MemoryType` owns a
+//! list of large blocks. A block is one memory allocation with a single `VkBuffer` bound over
+//! all of it, so a sub-allocation is just `(block buffer, offset)` and its GPU address is the
+//! block's device address plus that offset. Placement inside a block is TLSF.
+//! Requests above `DEDICATED_THRESHOLD` still get their own allocation.
+
+use std::collections::HashMap;
+
+use ash::{Device, vk};
+
+use crate::backend::tlsf::Tlsf;
+use crate::error::{RhiError, RhiResult};
+use crate::memory::MemoryType;
+use crate::types::GpuAddress;
+
+use super::device::find_memorytype_index;
+
+/// Size of each sub-allocated memory block.
+pub(crate) const BLOCK_SIZE: u64 = 64 << 20;
+/// Requests larger than this bypass the blocks and get a dedicated allocation.
+pub(crate) const DEDICATED_THRESHOLD: u64 = BLOCK_SIZE / 2;
+/// Largest alignment served from a block; anything coarser gets a dedicated allocation.
+pub(crate) const MAX_BLOCK_ALIGNMENT: u64 = 64 << 10;
+/// Alignment used by `create_buffer` when the caller does not ask for one.
+pub(crate) const MIN_BUFFER_ALIGNMENT: u64 = 256;
+
+/// A `VkBuffer` bound at offset 0 of its own `VkDeviceMemory`, addressed and (if
+/// host-visible) persistently mapped.
+pub(crate) struct RawBuffer {
+    pub buffer: vk::Buffer,
+    pub memory: vk::DeviceMemory,
+    pub memory_type_index: u32,
+    pub gpu_address: GpuAddress,
+    pub mapped_ptr: Option<*mut u8>,
+}
+
+/// Placement of one sub-allocation inside a block.
+pub(crate) struct SubAllocation {
+    pub buffer: vk::Buffer,
+    pub memory: vk::DeviceMemory,
+    pub memory_type_index: u32,
+    pub offset: u64,
+    pub gpu_address: GpuAddress,
+    pub mapped_ptr: Option<*mut u8>,
+}
+
+struct MemoryBlock {
+    raw: RawBuffer,
+    /// Largest power of two dividing the block's GPU base; offsets aligned to anything up to
+    /// this are aligned in GPU address space too.
+    address_align: u64,
+    tlsf: Tlsf,
+}
+
+/// Per-`MemoryType` block lists. Lives behind the device's mutex.
+pub(crate) struct BlockAllocator {
+    blocks: HashMap<MemoryType, Vec<MemoryBlock>>,
+}
+
+// Blocks hold mapped host pointers; they are only dereferenced through the owning
+// allocations and the allocator itself is accessed behind a mutex.
+unsafe impl Send for BlockAllocator {}
+
+pub(crate) fn buffer_usage_flags() -> vk::BufferUsageFlags {
+    vk::BufferUsageFlags::STORAGE_BUFFER
+        | vk::BufferUsageFlags::INDEX_BUFFER
+        | vk::BufferUsageFlags::VERTEX_BUFFER
+        | vk::BufferUsageFlags::INDIRECT_BUFFER
+        | vk::BufferUsageFlags::TRANSFER_DST
+        | vk::BufferUsageFlags::TRANSFER_SRC
+        | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS
+}
+
+pub(crate) fn memory_property_flags(memory: MemoryType) -> vk::MemoryPropertyFlags {
+    match memory {
+        MemoryType::GpuOnly => vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        MemoryType::Default => {
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT
+        }
+        MemoryType::Readback => {
+            vk::MemoryPropertyFlags::HOST_VISIBLE
+                | vk::MemoryPropertyFlags::HOST_COHERENT
+                | vk::MemoryPropertyFlags::HOST_CACHED
+        }
+    }
+}
+
+/// Memory type index for `memory`. Host-visible memory is always coherent, since nothing
+/// flushes or invalidates mapped ranges: a device without cached coherent memory gets
+/// uncached coherent memory for `Readback`.
+pub(crate) fn find_memory_type(
+    mem_requirements: &vk::MemoryRequirements,
+    memory_properties: &vk::PhysicalDeviceMemoryProperties,
+    memory: MemoryType,
+) -> Option<u32> {
+    find_memorytype_index(
+        mem_requirements,
+        memory_properties,
+        memory_property_flags(memory),
+    )
+    .or_else(|| {
+        (memory == MemoryType::Readback)
+            .then(|| {
+                find_memorytype_index(
+                    mem_requirements,
+                    memory_properties,
+                    memory_property_flags(MemoryType::Default),
+                )
+            })
+            .flatten()
+    })
+}
+
+/// Create a buffer with its own memory allocation: used for dedicated allocations and as the
+/// backing of each block. With `export`, the memory is exportable as that handle type.
+pub(crate) fn allocate_raw_buffer(
+    device: &Device,
+    memory_properties: &vk::PhysicalDeviceMemoryProperties,
+    memory: MemoryType,
+    size: u64,
+    export: Option<vk::ExternalMemoryHandleTypeFlags>,
+) -> RhiResult<RawBuffer> {
+    let buffer = create_external_buffer(device, size, export)?;
+
+    let mem_requirements = unsafe { device.get_buffer_memory_requirements(buffer) };
+    let Some(memory_type_index) = find_memory_type(&mem_requirements, memory_properties, memory)
+    else {
+        unsafe { device.destroy_buffer(buffer, None) };
+        return Err(RhiError::AllocationFailed("No suitable memory type".into()));
+    };
+
+    let mut alloc_flags_info =
+        vk::MemoryAllocateFlagsInfo::default().flags(vk::MemoryAllocateFlags::DEVICE_ADDRESS);
+    let mut alloc_info = vk::MemoryAllocateInfo::default()
+        .allocation_size(mem_requirements.size)
+        .memory_type_index(memory_type_index)
+        .push_next(&mut alloc_flags_info);
+    let mut export_info = vk::ExportMemoryAllocateInfo::default();
+    if let Some(handle_types) = export {
+        export_info = export_info.handle_types(handle_types);
+        alloc_info = alloc_info.push_next(&mut export_info);
+    }
+
+    let device_memory = match unsafe { device.allocate_memory(&alloc_info, None) } {
+        Ok(m) => m,
+        Err(e) => {
+            unsafe { device.destroy_buffer(buffer, None) };
+            return Err(RhiError::AllocationFailed(e.to_string()));
+        }
+    };
+
+    bind_raw_buffer(
+        device,
+        buffer,
+        device_memory,
+        memory_type_index,
+        memory,
+        size,
+    )
+}
+
+/// Create a buffer over memory imported from `fd`. On success the driver owns `fd`.
+#[cfg(unix)]
+pub(crate) fn import_raw_buffer(
+    device: &Device,
+    memory_properties: &vk::PhysicalDeviceMemoryProperties,
+    external_memory_fd: &ash::khr::external_memory_fd::Device,
+    fd: std::os::fd::OwnedFd,
+    handle_type: vk::ExternalMemoryHandleTypeFlags,
+    memory: MemoryType,
+    size: u64,
+) -> RhiResult<RawBuffer> {
+    use std::os::fd::{AsRawFd, IntoRawFd};
+
+    let buffer = create_external_buffer(device, size, Some(handle_type))?;
+    let mut mem_requirements = unsafe { device.get_buffer_memory_requirements(buffer) };
+    // Opaque fds must be imported with the exporter's memory type, which the buffer
+    // requirements already restrict to; other handle types report their own.
+    if handle_type != vk::ExternalMemoryHandleTypeFlags::OPAQUE_FD {
+        let mut fd_properties = vk::MemoryFdPropertiesKHR::default();
+        let queried = unsafe {
+            external_memory_fd.get_memory_fd_properties(
+                handle_type,
+                fd.as_raw_fd(),
+                &mut fd_properties,
+            )
+        };
+        if let Err(e) = queried {
+            unsafe { device.destroy_buffer(buffer, None) };
+            return Err(RhiError::AllocationFailed(format!(
+                "external memory fd properties: {e}"
+            )));
+        }
+        mem_requirements.memory_type_bits &= fd_properties.memory_type_bits;
+    }
+    let Some(memory_type_index) = find_memory_type(&mem_requirements, memory_properties, memory)
+    else {
+        unsafe { device.destroy_buffer(buffer, None) };
+        return Err(RhiError::AllocationFailed(
+            "No memory type compatible with the imported memory".into(),
+        ));
+    };
+
+    let mut alloc_flags_info =
+        vk::MemoryAllocateFlagsInfo::default().flags(vk::MemoryAllocateFlags::DEVICE_ADDRESS);
+    let mut import_info = vk::ImportMemoryFdInfoKHR::default()
+        .handle_type(handle_type)
+        .fd(fd.as_raw_fd());
+    let alloc_info = vk::MemoryAllocateInfo::default()
+        .allocation_size(mem_requirements.size)
+        .memory_type_index(memory_type_index)
+        .push_next(&mut alloc_flags_info)
+        .push_next(&mut import_info);
+
+    let device_memory = match unsafe { device.allocate_memory(&alloc_info, None) } {
+        Ok(m) => m,
+        Err(e) => {
+            unsafe { device.destroy_buffer(buffer, None) };
+            return Err(RhiError::AllocationFailed(format!(
+                "import external memory: {e}"
+            )));
+        }
+    };
+    // A successful import transfers ownership of the fd to the driver.
+    let _ = fd.into_raw_fd();
+
+    bind_raw_buffer(
+        device,
+        buffer,
+        device_memory,
+        memory_type_index,
+        memory,
+        size,
+    )
+}
+
+fn create_external_buffer(
+    device: &Device,
+    size: u64,
+    external: Option<vk::ExternalMemoryHandleTypeFlags>,
+) -> RhiResult<vk::Buffer> {
+    let mut external_info = vk::ExternalMemoryBufferCreateInfo::default();
+    let mut buffer_info = vk::BufferCreateInfo::default()
+        .size(size)
+        .usage(buffer_usage_flags())
+        .sharing_mode(vk::SharingMode::EXCLUSIVE);
+    if let Some(handle_types) = external {
+        external_info = external_info.handle_types(handle_types);
+        buffer_info = buffer_info.push_next(&mut external_info);
+    }
+
+    unsafe {
+        device
+            .create_buffer(&buffer_info, None)
+            .map_err(|e| RhiError::BufferCreation(e.to_string()))
+    }
+}
+
+/// Bind `buffer` at offset 0 of `device_memory`, query its address and map it if `memory` is
+/// CPU-visible. Releases both on failure.
+fn bind_raw_buffer(
+    device: &Device,
+    buffer: vk::Buffer,
+    device_memory: vk::DeviceMemory,
+    memory_type_index: u32,
+    memory: MemoryType,
+    size: u64,
+) -> RhiResult<RawBuffer> {
+    let release = |err: RhiError| {
+        unsafe {
+            device.destroy_buffer(buffer, None);
+            device.free_memory(device_memory, None);
+        }
+        err
+    };
+
+    unsafe { device.bind_buffer_memory(buffer, device_memory, 0) }
+        .map_err(|e| release(RhiError::BufferCreation(e.to_string())))?;
+
+    let addr_info = vk::BufferDeviceAddressInfo::default().buffer(buffer);
+    let gpu_address = GpuAddress(unsafe { device.get_buffer_device_address(&addr_info) });
+
+    let mapped_ptr = match memory {
+        MemoryType::Default | MemoryType::Readback => {
+            let ptr =
+                unsafe { device.map_memory(device_memory, 0, size, vk::MemoryMapFlags::empty()) }
+                    .map_err(|e| release(RhiError::AllocationFailed(e.to_string())))?;
+            Some(ptr as *mut u8)
+        }
+        MemoryType::GpuOnly => None,
+    };
+
+    Ok(RawBuffer {
+        buffer,
+        memory: device_memory,
+        memory_type_index,
+        gpu_address,
+        mapped_ptr,
+    })
+}
+
+/// Unmap (if mapped), destroy and free a buffer created by `allocate_raw_buffer`.
+pub(crate) unsafe fn release_raw_buffer(
+    device: &Device,
+    buffer: vk::Buffer,
+    memory: vk::DeviceMemory,
+    mapped: bool,
+) {
+    unsafe {
+        if mapped {
+            device.unmap_memory(memory);
+        }
+        device.destroy_buffer(buffer, None);
+        device.free_memory(memory, None);
+    }
+}
+
+impl BlockAllocator {
+    pub(crate) fn new() -> Self {
+        Self {
+            blocks: HashMap::new(),
+        }
+    }
+
+    /// Sub-allocate `size` bytes aligned to `align` in both memory offset and GPU address.
+    /// Returns `Ok(None)` when the request cannot live in a block (too large, or an
+    /// alignment the block's base address cannot honour); the caller falls back to a
+    /// dedicated allocation.
+    pub(crate) fn allocate(
+        &mut self,
+        device: &Device,
+        memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        memory: MemoryType,
+        size: u64,
+        align: u64,
+    ) -> RhiResult<Option<SubAllocation>> {
+        if size > DEDICATED_THRESHOLD || align > MAX_BLOCK_ALIGNMENT {
+            return Ok(None);
+        }
+        let blocks = self.blocks.entry(memory).or_default();
+        let mut misaligned = false;
+        for block in blocks.iter_mut() {
+            if block.address_align < align {
+                misaligned = true;
+                continue;
+            }
+            if let Some(offset) = block.tlsf.allocate(size, align) {
+                return Ok(Some(block.sub_allocation(offset)));
+            }
+        }
+        // A driver that hands out block addresses too coarsely aligned for this request
+        // would do so again; don't grow a block per call, go dedicated instead.
+        if misaligned {
+            return Ok(None);
+        }
+
+        let raw = allocate_raw_buffer(device, memory_properties, memory, BLOCK_SIZE, None)?;
+        let address_align = 1u64 << raw.gpu_address.0.trailing_zeros().min(63);
+        let mut block = MemoryBlock {
+            raw,
+            address_align,
+            tlsf: Tlsf::new(BLOCK_SIZE),
+        };
+        log::debug!(
+            "allocated {} MiB {memory:?} memory block at {:#x}",
+            BLOCK_SIZE >> 20,
+            block.raw.gpu_address
+        );
+        let placed = if address_align >= align {
+            block
+                .tlsf
+                .allocate(size, align)
+                .map(|offset| block.sub_allocation(offset))
+        } else {
+            None
+        };
+        blocks.push(block);
+        Ok(placed)
+    }
+
+    /// Number of blocks held for `memory` and their total size.
+    pub(crate) fn block_usage(&self, memory: MemoryType) -> (u64, u64) {
+        let count = self.blocks.get(&memory).map_or(0, Vec::len) as u64;
+        (count, count * BLOCK_SIZE)
+    }
+
+    /// Return a sub-allocation to its block. Returns `false` if `memory` is not one of the
+    /// allocator's blocks (i.e. the buffer was a dedicated allocation).
+    pub(crate) fn free(&mut self, device: &Device, memory: vk::DeviceMemory, offset: u64) -> bool {
+        for blocks in self.blocks.values_mut() {
+            let Some(index) = blocks.iter().position(|b| b.raw.memory == memory) else {
+                continue;
+            };
+            blocks[index].tlsf.free(offset);
+            // Keep one empty block per memory type around for reuse; release the rest.
+            if blocks[index].tlsf.allocated() == 0
+                && blocks
+                    .iter()
+                    .enumerate()
+                    .any(|(i, b)| i != index && b.tlsf.allocated() == 0)
+            {
+                let block = blocks.swap_remove(index);
+                unsafe {
+                    release_raw_buffer(
+                        device,
+                        block.raw.buffer,
+                        block.raw.memory,
+                        block.raw.mapped_ptr.is_some(),
+                    );
+                }
+            }
+            return true;
+        }
+        false
+    }
+
+    /// Release every block. Outstanding sub-allocations become dangling.
+    pub(crate) fn destroy(&mut self, device: &Device) {
+        for block in self.blocks.drain().flat_map(|(_, blocks)| blocks) {
+            unsafe {
+                release_raw_buffer(
+                    device,
+                    block.raw.buffer,
+                    block.raw.memory,
+                    block.raw.mapped_ptr.is_some(),
+                );
+            }
+        }
+    }
+}
+
+impl MemoryBlock {
+    fn sub_allocation(&self, offset: u64) -> SubAllocation {
+        SubAllocation {
+            buffer: self.raw.buffer,
+            memory: self.raw.memory,
+            memory_type_index: self.raw.memory_type_index,
+            offset,
+            gpu_address: self.raw.gpu_address.offset(offset),
+            mapped_ptr: self
+                .raw
+                .mapped_ptr
+                .map(|ptr| unsafe { ptr.add(offset as usize) }),
+        }
+    }
+}
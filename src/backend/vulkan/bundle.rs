@@ -0,0 +1,115 @@
+use ash::vk;
+
+use super::command::VulkanCommandBuffer;
+use crate::error::{RhiError, RhiResult};
+use crate::pipeline::{BlendState, ComputePso, DepthStencilState, GraphicsPso};
+use crate::types::GpuAddress;
+
+/// A bundle being recorded: a secondary command buffer and the pool it was allocated from,
+/// which nothing else records into.
+pub struct VulkanBundleEncoder {
+    pub(crate) cmd: VulkanCommandBuffer,
+    pub(crate) pool: vk::CommandPool,
+}
+
+impl VulkanBundleEncoder {
+    pub fn set_graphics_pipeline(&mut self, pso: &GraphicsPso) {
+        self.cmd.set_graphics_pipeline(pso);
+    }
+
+    pub fn set_compute_pipeline(&mut self, pso: &ComputePso) {
+        self.cmd.set_compute_pipeline(pso);
+    }
+
+    pub fn set_depth_stencil_state(&mut self, state: &DepthStencilState) {
+        self.cmd.set_depth_stencil_state(state);
+    }
+
+    pub fn set_blend_state(&mut self, state: &BlendState) {
+        self.cmd.set_blend_state(state);
+    }
+
+    pub fn set_viewport(
+        &mut self,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        min_depth: f32,
+        max_depth: f32,
+    ) {
+        self.cmd
+            .set_viewport(x, y, width, height, min_depth, max_depth);
+    }
+
+    pub fn set_scissor(&mut self, x: i32, y: i32, width: u32, height: u32) {
+        self.cmd.set_scissor(x, y, width, height);
+    }
+
+    pub fn draw(
+        &mut self,
+        vertex_root: GpuAddress,
+        pixel_root: GpuAddress,
+        vertex_count: u32,
+        instance_count: u32,
+        first_vertex: u32,
+        first_instance: u32,
+    ) {
+        self.cmd.set_root_data(vertex_root, pixel_root);
+        self.cmd
+            .draw(vertex_count, instance_count, first_vertex, first_instance);
+    }
+
+    pub fn draw_indexed(
+        &mut self,
+        vertex_root: GpuAddress,
+        pixel_root: GpuAddress,
+        indices: GpuAddress,
+        index_count: u32,
+        instance_count: u32,
+    ) {
+        self.cmd.set_root_data(vertex_root, pixel_root);
+        self.cmd.draw_indexed(indices, index_count, instance_count);
+    }
+
+    pub fn dispatch(&mut self, root: GpuAddress, x: u32, y: u32, z: u32) {
+        self.cmd.set_compute_root(root);
+        self.cmd.dispatch(x, y, z);
+    }
+
+    pub fn finish(mut self) -> RhiResult<VulkanBundle> {
+        unsafe {
+            self.cmd
+                .device
+                .end_command_buffer(self.cmd.command_buffer)
+                .map_err(|e| RhiError::CommandBuffer(e.to_string()))?;
+        }
+        let pool = std::mem::replace(&mut self.pool, vk::CommandPool::null());
+        Ok(VulkanBundle {
+            command_buffer: self.cmd.command_buffer,
+            pool,
+            device: self.cmd.device.clone(),
+        })
+    }
+}
+
+impl Drop for VulkanBundleEncoder {
+    fn drop(&mut self) {
+        if self.pool != vk::CommandPool::null() {
+            unsafe { self.cmd.device.destroy_command_pool(self.pool, None) };
+        }
+    }
+}
+
+/// A recorded secondary command buffer, freed with its pool on drop.
+pub struct VulkanBundle {
+    pub(crate) command_buffer: vk::CommandBuffer,
+    pub(crate) pool: vk::CommandPool,
+    pub(crate) device: ash::Device,
+}
+
+impl Drop for VulkanBundle {
+    fn drop(&mut self) {
+        unsafe { self.device.destroy_command_pool(self.pool, None) };
+    }
+}
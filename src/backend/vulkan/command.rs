@@ -1,18 +1,24 @@
+use std::ops::Range;
+
 use super::barrier::{to_vk_access_flags, to_vk_stage_flags};
-use super::device::{SharedAllocations, SharedTextures};
+use super::command_pool::CommandPoolLease;
+use super::device::{SharedAllocations, SharedTextures, debug_label_name};
 use crate::barrier::{HazardFlags, StageFlags};
+use crate::bundle::{BundleInner, ComputeBundle, RenderBundle};
 use crate::command::{
-    DispatchIndirectArgs, DrawIndexedIndirectArgs, DrawIndirectMultiArgs, LoadOp, RenderPassDesc,
-    RenderTarget, SignalValueDesc, StoreOp, WaitValueDesc,
+    BlitTarget, ClearValue, DepthResolveMode, DispatchIndirectArgs, DrawIndexedIndirectArgs,
+    DrawIndirectMultiArgs, LoadOp, RenderPassDesc, RenderTarget, SignalValueDesc, StoreOp,
+    WaitValueDesc,
 };
 use crate::pipeline::{
     BlendState, ComputePso, ComputePsoInner, DepthStencilState, GraphicsPso, GraphicsPsoInner,
     MeshletPso,
 };
-use crate::texture::{Texture, bytes_per_pixel};
+use crate::query::{QueryPool, QueryPoolInner, QueryType};
+use crate::texture::{ResolvedCopyRegion, Texture, TextureCopyRegion, TextureRegion};
 use crate::types::*;
 use ash::{
-    ext::{descriptor_buffer, mesh_shader as vk_mesh_shader},
+    ext::{debug_utils, descriptor_buffer, mesh_shader as vk_mesh_shader},
     khr::acceleration_structure as vk_accel_structure,
     vk,
 };
@@ -20,10 +26,14 @@ use ash::{
 /// Vulkan command buffer wrapper.
 pub struct VulkanCommandBuffer {
     pub(crate) command_buffer: vk::CommandBuffer,
+    /// The transient pool `command_buffer` was allocated from; `None` for bundles, whose
+    /// encoder owns the pool.
+    pub(crate) pool: Option<CommandPoolLease>,
     pub(crate) device: ash::Device,
     /// Swapchain image views for resolving RenderTarget::SwapchainImage
     pub(crate) swapchain_image_views: Vec<vk::ImageView>,
     pub(crate) swapchain_images: Vec<vk::Image>,
+    pub(crate) swapchain_extent: vk::Extent2D,
     pub(crate) depth_image_view: vk::ImageView,
     /// Current pipeline layout for push constants
     pub(crate) pipeline_layout: vk::PipelineLayout,
@@ -50,14 +60,26 @@ pub struct VulkanCommandBuffer {
     pub(crate) mesh_shader: Option<vk_mesh_shader::Device>,
     /// Acceleration structure extension loader (VK_KHR_acceleration_structure).
     pub(crate) acceleration_structure: Option<vk_accel_structure::Device>,
+    /// Debug label loader (VK_EXT_debug_utils); labels are dropped without it.
+    pub(crate) debug_utils: Option<debug_utils::Device>,
     /// Device limit used as the native indirect-count upper bound.
     pub(crate) max_draw_indirect_count: u32,
+    /// Depth resolve modes the device supports.
+    pub(crate) depth_resolve_modes: vk::ResolveModeFlags,
 }
 
-// SAFETY: VulkanCommandBuffer is only used from one thread at a time.
+// SAFETY: `&mut self` recording is confined to one thread at a time, and no other command
+// buffer is allocated from the pool behind `command_buffer`.
 unsafe impl Send for VulkanCommandBuffer {}
 
 impl VulkanCommandBuffer {
+    /// Hand the pool back for reuse once the submission with `serial` retires.
+    pub(crate) fn retire_after(&mut self, serial: u64) {
+        if let Some(pool) = &mut self.pool {
+            pool.retire_after(serial);
+        }
+    }
+
     fn bind_descriptor_buffer(
         &self,
         bind_point: vk::PipelineBindPoint,
@@ -91,7 +113,7 @@ impl VulkanCommandBuffer {
             && addr_u64 < base + alloc.size
         {
             let offset = addr_u64 - base;
-            return (alloc.buffer, offset, alloc.size - offset);
+            return (alloc.buffer, alloc.offset + offset, alloc.size - offset);
         }
         panic!("GPU address {addr_u64:#x} not found in allocation registry");
     }
@@ -131,41 +153,85 @@ impl VulkanCommandBuffer {
         (tex.image, tex.image_view)
     }
 
+    /// A view of `layer_count` layers of one mip level of `id` (2D for a single layer, else
+    /// 2D array), created on first use and cached on the texture until it is destroyed.
+    fn resolve_attachment_view(
+        &self,
+        id: TextureId,
+        mip: u32,
+        base_layer: u32,
+        layer_count: u32,
+    ) -> vk::ImageView {
+        let mut textures = self.textures.lock().expect("textures lock poisoned");
+        let tex = textures
+            .get_mut(id.0 as usize)
+            .and_then(|t| t.as_mut())
+            .expect("Invalid texture ID");
+        assert!(
+            !tex.is_view,
+            "subresource render targets need the source texture, not a view"
+        );
+        assert!(
+            mip < tex.mip_levels
+                && layer_count > 0
+                && base_layer
+                    .checked_add(layer_count)
+                    .is_some_and(|end| end <= tex.attachment_layers(mip)),
+            "render target mip {mip} layers {base_layer}..+{layer_count} outside texture {id:?} \
+             ({} mips, {} layers at that mip)",
+            tex.mip_levels,
+            tex.attachment_layers(mip)
+        );
+        let key = (mip, base_layer, layer_count);
+        if let Some(&view) = tex.attachment_views.get(&key) {
+            return view;
+        }
+        let view_type = if layer_count == 1 {
+            vk::ImageViewType::TYPE_2D
+        } else {
+            vk::ImageViewType::TYPE_2D_ARRAY
+        };
+        let view_info = vk::ImageViewCreateInfo::default()
+            .image(tex.image)
+            .view_type(view_type)
+            .format(tex.format)
+            .subresource_range(vk::ImageSubresourceRange {
+                aspect_mask: tex.aspect,
+                base_mip_level: mip,
+                level_count: 1,
+                base_array_layer: base_layer,
+                layer_count,
+            });
+        let view = unsafe {
+            self.device
+                .create_image_view(&view_info, None)
+                .expect("Failed to create attachment view")
+        };
+        tex.attachment_views.insert(key, view);
+        view
+    }
+
     pub fn begin_render_pass(&mut self, desc: &RenderPassDesc) {
+        self.begin_rendering(desc, vk::RenderingFlags::empty());
+    }
+
+    /// Begin dynamic rendering of `desc`. With `CONTENTS_SECONDARY_COMMAND_BUFFERS` in
+    /// `flags`, the pass may only execute secondary command buffers.
+    fn begin_rendering(&mut self, desc: &RenderPassDesc, flags: vk::RenderingFlags) {
         let cmd = self.command_buffer;
 
-        // Transition swapchain images to COLOR_ATTACHMENT_OPTIMAL before rendering.
+        // Transition swapchain images to COLOR_ATTACHMENT_OPTIMAL before rendering. A
+        // resolve overwrites its target, so the previous contents need not be kept.
         for ca in &desc.color_attachments {
             if let RenderTarget::SwapchainImage(idx) = ca.target {
-                let image = self.swapchain_images[idx as usize];
                 let old_layout = match ca.load_op {
                     LoadOp::Load => vk::ImageLayout::PRESENT_SRC_KHR,
                     LoadOp::Clear | LoadOp::DontCare => vk::ImageLayout::UNDEFINED,
                 };
-                let barrier = vk::ImageMemoryBarrier::default()
-                    .old_layout(old_layout)
-                    .new_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
-                    .src_access_mask(vk::AccessFlags::empty())
-                    .dst_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
-                    .image(image)
-                    .subresource_range(vk::ImageSubresourceRange {
-                        aspect_mask: vk::ImageAspectFlags::COLOR,
-                        base_mip_level: 0,
-                        level_count: 1,
-                        base_array_layer: 0,
-                        layer_count: 1,
-                    });
-                unsafe {
-                    self.device.cmd_pipeline_barrier(
-                        cmd,
-                        vk::PipelineStageFlags::TOP_OF_PIPE,
-                        vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
-                        vk::DependencyFlags::empty(),
-                        &[],
-                        &[],
-                        &[barrier],
-                    );
-                }
+                self.transition_swapchain_to_attachment(idx, old_layout);
+            }
+            if let Some(RenderTarget::SwapchainImage(idx)) = ca.resolve_target {
+                self.transition_swapchain_to_attachment(idx, vk::ImageLayout::UNDEFINED);
             }
         }
 
@@ -174,72 +240,76 @@ impl VulkanCommandBuffer {
             .color_attachments
             .iter()
             .map(|ca| {
-                let image_view = match ca.target {
-                    RenderTarget::SwapchainImage(idx) => self.swapchain_image_views[idx as usize],
-                    RenderTarget::Texture(id) => self.resolve_texture(id).1,
-                };
-                let image_layout = match ca.target {
-                    RenderTarget::SwapchainImage(_) => vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
-                    RenderTarget::Texture(_) => vk::ImageLayout::GENERAL,
-                };
+                let (image_view, image_layout) = self.color_target_view(&ca.target);
 
                 let load_op = match ca.load_op {
                     LoadOp::Load => vk::AttachmentLoadOp::LOAD,
                     LoadOp::Clear => vk::AttachmentLoadOp::CLEAR,
                     LoadOp::DontCare => vk::AttachmentLoadOp::DONT_CARE,
                 };
-                let store_op = match ca.store_op {
-                    StoreOp::Store => vk::AttachmentStoreOp::STORE,
-                    StoreOp::DontCare => vk::AttachmentStoreOp::DONT_CARE,
-                };
 
-                vk::RenderingAttachmentInfo::default()
+                let info = vk::RenderingAttachmentInfo::default()
                     .image_view(image_view)
                     .image_layout(image_layout)
                     .load_op(load_op)
-                    .store_op(store_op)
+                    .store_op(to_vk_store_op(ca.store_op))
                     .clear_value(vk::ClearValue {
                         color: vk::ClearColorValue {
                             float32: ca.clear_color,
                         },
-                    })
+                    });
+                match &ca.resolve_target {
+                    Some(target) => {
+                        let (resolve_view, resolve_layout) = self.color_target_view(target);
+                        info.resolve_mode(vk::ResolveModeFlags::AVERAGE)
+                            .resolve_image_view(resolve_view)
+                            .resolve_image_layout(resolve_layout)
+                    }
+                    None => info,
+                }
             })
             .collect();
 
         // Build depth attachment
         let depth_attachment = desc.depth_attachment.as_ref().map(|da| {
-            let image_view = match da.target {
-                RenderTarget::SwapchainImage(_) => self.depth_image_view,
-                RenderTarget::Texture(id) => self.resolve_texture(id).1,
-            };
-            let image_layout = match da.target {
-                RenderTarget::SwapchainImage(_) => {
-                    vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL
-                }
-                RenderTarget::Texture(_) => vk::ImageLayout::GENERAL,
-            };
+            let (image_view, image_layout) = self.depth_target_view(&da.target);
 
             let load_op = match da.load_op {
                 LoadOp::Load => vk::AttachmentLoadOp::LOAD,
                 LoadOp::Clear => vk::AttachmentLoadOp::CLEAR,
                 LoadOp::DontCare => vk::AttachmentLoadOp::DONT_CARE,
             };
-            let store_op = match da.store_op {
-                StoreOp::Store => vk::AttachmentStoreOp::STORE,
-                StoreOp::DontCare => vk::AttachmentStoreOp::DONT_CARE,
-            };
 
-            vk::RenderingAttachmentInfo::default()
+            let info = vk::RenderingAttachmentInfo::default()
                 .image_view(image_view)
                 .image_layout(image_layout)
                 .load_op(load_op)
-                .store_op(store_op)
+                .store_op(to_vk_store_op(da.store_op))
                 .clear_value(vk::ClearValue {
                     depth_stencil: vk::ClearDepthStencilValue {
                         depth: da.clear_depth,
                         stencil: da.clear_stencil as u32,
                     },
-                })
+                });
+            match &da.resolve_target {
+                Some(target) => {
+                    let mode = match da.resolve_mode {
+                        DepthResolveMode::SampleZero => vk::ResolveModeFlags::SAMPLE_ZERO,
+                        DepthResolveMode::Min => vk::ResolveModeFlags::MIN,
+                        DepthResolveMode::Max => vk::ResolveModeFlags::MAX,
+                    };
+                    assert!(
+                        self.depth_resolve_modes.contains(mode),
+                        "depth resolve mode {:?} is not supported by this device",
+                        da.resolve_mode
+                    );
+                    let (resolve_view, resolve_layout) = self.depth_target_view(target);
+                    info.resolve_mode(mode)
+                        .resolve_image_view(resolve_view)
+                        .resolve_image_layout(resolve_layout)
+                }
+                None => info,
+            }
         });
 
         let render_area = vk::Rect2D {
@@ -254,8 +324,10 @@ impl VulkanCommandBuffer {
         };
 
         let mut rendering_info = vk::RenderingInfo::default()
+            .flags(flags)
             .render_area(render_area)
-            .layer_count(1)
+            .layer_count(desc.layer_count())
+            .view_mask(desc.view_mask)
             .color_attachments(&color_attachments);
 
         if let Some(ref da) = depth_attachment {
@@ -267,6 +339,88 @@ impl VulkanCommandBuffer {
         }
     }
 
+    /// Move swapchain image `idx` into COLOR_ATTACHMENT_OPTIMAL for rendering or resolving.
+    fn transition_swapchain_to_attachment(&self, idx: u32, old_layout: vk::ImageLayout) {
+        let barrier = vk::ImageMemoryBarrier::default()
+            .old_layout(old_layout)
+            .new_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+            .src_access_mask(vk::AccessFlags::empty())
+            .dst_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+            .image(self.swapchain_images[idx as usize])
+            .subresource_range(vk::ImageSubresourceRange {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                base_mip_level: 0,
+                level_count: 1,
+                base_array_layer: 0,
+                layer_count: 1,
+            });
+        unsafe {
+            self.device.cmd_pipeline_barrier(
+                self.command_buffer,
+                vk::PipelineStageFlags::TOP_OF_PIPE,
+                vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[barrier],
+            );
+        }
+    }
+
+    fn color_target_view(&self, target: &RenderTarget) -> (vk::ImageView, vk::ImageLayout) {
+        match *target {
+            RenderTarget::SwapchainImage(idx) => (
+                self.swapchain_image_views[idx as usize],
+                vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+            ),
+            RenderTarget::Texture(id) => (self.resolve_texture(id).1, vk::ImageLayout::GENERAL),
+            RenderTarget::TextureSubresource {
+                texture,
+                mip,
+                layer,
+            } => (
+                self.resolve_attachment_view(texture, mip, layer, 1),
+                vk::ImageLayout::GENERAL,
+            ),
+            RenderTarget::TextureLayers {
+                texture,
+                mip,
+                base_layer,
+                layer_count,
+            } => (
+                self.resolve_attachment_view(texture, mip, base_layer, layer_count),
+                vk::ImageLayout::GENERAL,
+            ),
+        }
+    }
+
+    fn depth_target_view(&self, target: &RenderTarget) -> (vk::ImageView, vk::ImageLayout) {
+        match *target {
+            RenderTarget::SwapchainImage(_) => (
+                self.depth_image_view,
+                vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+            ),
+            RenderTarget::Texture(id) => (self.resolve_texture(id).1, vk::ImageLayout::GENERAL),
+            RenderTarget::TextureSubresource {
+                texture,
+                mip,
+                layer,
+            } => (
+                self.resolve_attachment_view(texture, mip, layer, 1),
+                vk::ImageLayout::GENERAL,
+            ),
+            RenderTarget::TextureLayers {
+                texture,
+                mip,
+                base_layer,
+                layer_count,
+            } => (
+                self.resolve_attachment_view(texture, mip, base_layer, layer_count),
+                vk::ImageLayout::GENERAL,
+            ),
+        }
+    }
+
     pub fn end_render_pass(&mut self) {
         unsafe {
             self.device.cmd_end_rendering(self.command_buffer);
@@ -277,6 +431,33 @@ impl VulkanCommandBuffer {
         // but for the common case of rendering to swapchain, we handle it here.
     }
 
+    /// Secondary command buffers leave the bound pipeline and all dynamic state undefined,
+    /// so root data needs a fresh pipeline bind afterwards.
+    pub fn execute_render_bundles(&mut self, desc: &RenderPassDesc, bundles: &[&RenderBundle]) {
+        self.begin_rendering(desc, vk::RenderingFlags::CONTENTS_SECONDARY_COMMAND_BUFFERS);
+        self.execute_bundles(bundles.iter().map(|bundle| bundle.inner()));
+        self.end_render_pass();
+    }
+
+    pub fn execute_compute_bundles(&mut self, bundles: &[&ComputeBundle]) {
+        self.execute_bundles(bundles.iter().map(|bundle| bundle.inner()));
+    }
+
+    fn execute_bundles<'a>(&mut self, bundles: impl Iterator<Item = &'a BundleInner>) {
+        let command_buffers: Vec<vk::CommandBuffer> = bundles
+            .map(|bundle| match bundle {
+                BundleInner::Vulkan(b) => b.command_buffer,
+                #[allow(unreachable_patterns)]
+                _ => unreachable!(),
+            })
+            .collect();
+        unsafe {
+            self.device
+                .cmd_execute_commands(self.command_buffer, &command_buffers);
+        }
+        self.push_constant_stages = vk::ShaderStageFlags::empty();
+    }
+
     pub fn set_graphics_pipeline(&mut self, pso: &GraphicsPso) {
         let vk_pso = match &pso.inner {
             GraphicsPsoInner::Vulkan(p) => p,
@@ -603,9 +784,82 @@ impl VulkanCommandBuffer {
         }
     }
 
-    pub fn copy_to_texture(&mut self, texture_gpu: GpuAddress, src: GpuAddress, texture: &Texture) {
-        let (image, aspect, width, height, src_buffer, src_offset) =
-            self.prepare_texture_copy(texture_gpu, src, texture, "copy_to_texture");
+    pub fn fill(&mut self, dst: GpuAddress, size: u64, pattern: u32) {
+        let (buffer, offset) = self.resolve_buffer(dst, size);
+        unsafe {
+            self.device
+                .cmd_fill_buffer(self.command_buffer, buffer, offset, size, pattern);
+        }
+    }
+
+    pub fn update(&mut self, dst: GpuAddress, data: &[u8]) {
+        let (buffer, offset) = self.resolve_buffer(dst, data.len() as u64);
+        unsafe {
+            self.device
+                .cmd_update_buffer(self.command_buffer, buffer, offset, data);
+        }
+    }
+
+    /// Clears in GENERAL, the layout textures live in, so no transition is needed.
+    pub fn clear_texture(
+        &mut self,
+        texture: &Texture,
+        mips: Range<u32>,
+        layers: Range<u32>,
+        value: ClearValue,
+    ) {
+        let image = self.resolve_texture(texture.id()).0;
+        let range = vk::ImageSubresourceRange {
+            aspect_mask: transfer_aspect(texture.desc().format),
+            base_mip_level: mips.start,
+            level_count: mips.len() as u32,
+            base_array_layer: layers.start,
+            layer_count: layers.len() as u32,
+        };
+        unsafe {
+            match value {
+                ClearValue::DepthStencil { depth, stencil } => {
+                    self.device.cmd_clear_depth_stencil_image(
+                        self.command_buffer,
+                        image,
+                        vk::ImageLayout::GENERAL,
+                        &vk::ClearDepthStencilValue {
+                            depth,
+                            stencil: stencil as u32,
+                        },
+                        std::slice::from_ref(&range),
+                    );
+                }
+                ClearValue::Color(float32) => self.device.cmd_clear_color_image(
+                    self.command_buffer,
+                    image,
+                    vk::ImageLayout::GENERAL,
+                    &vk::ClearColorValue { float32 },
+                    std::slice::from_ref(&range),
+                ),
+                ClearValue::Uint(uint32) => self.device.cmd_clear_color_image(
+                    self.command_buffer,
+                    image,
+                    vk::ImageLayout::GENERAL,
+                    &vk::ClearColorValue { uint32 },
+                    std::slice::from_ref(&range),
+                ),
+            }
+        }
+    }
+
+    pub fn copy_to_texture(
+        &mut self,
+        texture_gpu: GpuAddress,
+        src: GpuAddress,
+        texture: &Texture,
+        regions: &[TextureCopyRegion],
+    ) {
+        if regions.is_empty() {
+            return;
+        }
+        let (image, aspect, src_buffer, vk_regions) =
+            self.prepare_texture_copy(texture_gpu, src, texture, regions, "copy_to_texture");
         self.transition_texture(
             image,
             aspect,
@@ -615,14 +869,13 @@ impl VulkanCommandBuffer {
             vk::PipelineStageFlags::TRANSFER,
             false,
         );
-        let region = build_buffer_image_region(src_offset, aspect, width, height);
         unsafe {
             self.device.cmd_copy_buffer_to_image(
                 self.command_buffer,
                 src_buffer,
                 image,
                 vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-                std::slice::from_ref(&region),
+                &vk_regions,
             );
         }
         self.transition_texture(
@@ -641,9 +894,13 @@ impl VulkanCommandBuffer {
         dst: GpuAddress,
         texture_gpu: GpuAddress,
         texture: &Texture,
+        regions: &[TextureCopyRegion],
     ) {
-        let (image, aspect, width, height, dst_buffer, dst_offset) =
-            self.prepare_texture_copy(texture_gpu, dst, texture, "copy_from_texture");
+        if regions.is_empty() {
+            return;
+        }
+        let (image, aspect, dst_buffer, vk_regions) =
+            self.prepare_texture_copy(texture_gpu, dst, texture, regions, "copy_from_texture");
         self.transition_texture(
             image,
             aspect,
@@ -653,14 +910,13 @@ impl VulkanCommandBuffer {
             vk::PipelineStageFlags::TRANSFER,
             false,
         );
-        let region = build_buffer_image_region(dst_offset, aspect, width, height);
         unsafe {
             self.device.cmd_copy_image_to_buffer(
                 self.command_buffer,
                 image,
                 vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
                 dst_buffer,
-                std::slice::from_ref(&region),
+                &vk_regions,
             );
         }
         self.transition_texture(
@@ -674,36 +930,292 @@ impl VulkanCommandBuffer {
         );
     }
 
-    /// Validate the texture address, resolve the linear buffer, and return
-    /// `(image, aspect, w, h, buffer, offset)` for a copy command.
+    /// Copies in GENERAL, fenced by barriers on both images as the buffer copies are.
+    pub fn copy_texture(
+        &mut self,
+        src: &Texture,
+        src_region: &TextureRegion,
+        dst: &Texture,
+        dst_region: &TextureRegion,
+    ) {
+        let src_image = self.resolve_texture(src.id()).0;
+        let dst_image = self.resolve_texture(dst.id()).0;
+        let src_aspect = transfer_aspect(src.desc().format);
+        let dst_aspect = transfer_aspect(dst.desc().format);
+        let transition = |cmd: &Self, reverse| {
+            for (image, aspect, access) in [
+                (src_image, src_aspect, vk::AccessFlags::TRANSFER_READ),
+                (dst_image, dst_aspect, vk::AccessFlags::TRANSFER_WRITE),
+            ] {
+                cmd.transition_texture(
+                    image,
+                    aspect,
+                    vk::ImageLayout::GENERAL,
+                    vk::ImageLayout::GENERAL,
+                    access,
+                    vk::PipelineStageFlags::TRANSFER,
+                    reverse,
+                );
+            }
+        };
+        transition(self, false);
+        let region = vk::ImageCopy::default()
+            .src_subresource(subresource_layers(src_aspect, src_region))
+            .src_offset(offset_3d(src_region.offset))
+            .dst_subresource(subresource_layers(dst_aspect, dst_region))
+            .dst_offset(offset_3d(dst_region.offset))
+            .extent(vk::Extent3D {
+                width: src_region.extent[0],
+                height: src_region.extent[1],
+                depth: src_region.extent[2],
+            });
+        unsafe {
+            self.device.cmd_copy_image(
+                self.command_buffer,
+                src_image,
+                vk::ImageLayout::GENERAL,
+                dst_image,
+                vk::ImageLayout::GENERAL,
+                &[region],
+            );
+        }
+        transition(self, true);
+    }
+
+    /// Blits from GENERAL. A swapchain image goes from UNDEFINED to TRANSFER_DST_OPTIMAL and
+    /// ends in COLOR_ATTACHMENT_OPTIMAL, as a render pass would leave it for
+    /// `transition_to_present`.
+    pub fn blit_texture(
+        &mut self,
+        src: &Texture,
+        src_region: &TextureRegion,
+        dst: BlitTarget,
+        dst_region: &TextureRegion,
+        filter: FilterMode,
+    ) {
+        let (src_image, src_filter) = self.resolve_blit_texture(src, "blit_texture");
+        let filter = match filter {
+            FilterMode::Linear => src_filter,
+            FilterMode::Nearest => vk::Filter::NEAREST,
+        };
+        let general = vk::ImageLayout::GENERAL;
+        // The destination's layouts before, during and after the blit.
+        let (dst_image, [dst_before, dst_layout, dst_after], dst_region) = match dst {
+            BlitTarget::Texture(texture) => (
+                self.resolve_blit_texture(texture, "blit_texture").0,
+                [general; 3],
+                *dst_region,
+            ),
+            BlitTarget::SwapchainImage(idx) => {
+                let extent = [self.swapchain_extent.width, self.swapchain_extent.height, 1];
+                (
+                    self.swapchain_images[idx as usize],
+                    [
+                        vk::ImageLayout::UNDEFINED,
+                        vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                        vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+                    ],
+                    dst_region.resolve_in(extent, "blit_texture destination"),
+                )
+            }
+        };
+        let transition = |cmd: &Self, reverse| {
+            let (old_layout, new_layout) = if reverse {
+                (dst_layout, dst_after)
+            } else {
+                (dst_before, dst_layout)
+            };
+            cmd.transition_texture(
+                src_image,
+                vk::ImageAspectFlags::COLOR,
+                general,
+                general,
+                vk::AccessFlags::TRANSFER_READ,
+                vk::PipelineStageFlags::TRANSFER,
+                reverse,
+            );
+            cmd.transition_texture(
+                dst_image,
+                vk::ImageAspectFlags::COLOR,
+                old_layout,
+                new_layout,
+                vk::AccessFlags::TRANSFER_WRITE,
+                vk::PipelineStageFlags::TRANSFER,
+                reverse,
+            );
+        };
+        transition(self, false);
+        let corners = |region: &TextureRegion| {
+            let [x, y, z] = region.offset;
+            let [w, h, d] = region.extent;
+            [offset_3d([x, y, z]), offset_3d([x + w, y + h, z + d])]
+        };
+        let color = vk::ImageAspectFlags::COLOR;
+        let region = vk::ImageBlit::default()
+            .src_subresource(subresource_layers(color, src_region))
+            .src_offsets(corners(src_region))
+            .dst_subresource(subresource_layers(color, &dst_region))
+            .dst_offsets(corners(&dst_region));
+        unsafe {
+            self.device.cmd_blit_image(
+                self.command_buffer,
+                src_image,
+                general,
+                dst_image,
+                dst_layout,
+                &[region],
+                filter,
+            );
+        }
+        transition(self, true);
+    }
+
+    /// Fill mips 1.. of every layer by blitting each level from the one above it. The image
+    /// stays in GENERAL throughout; a barrier on each finished level orders the next blit.
+    pub fn generate_mips(&mut self, texture: &Texture) {
+        let desc = texture.desc();
+        let (image, filter) = self.resolve_blit_texture(texture, "generate_mips");
+        let layers = desc.layer_count();
+
+        self.transition_texture(
+            image,
+            vk::ImageAspectFlags::COLOR,
+            vk::ImageLayout::GENERAL,
+            vk::ImageLayout::GENERAL,
+            vk::AccessFlags::TRANSFER_READ | vk::AccessFlags::TRANSFER_WRITE,
+            vk::PipelineStageFlags::TRANSFER,
+            false,
+        );
+        for mip in 1..desc.mip_levels {
+            let subresource = |mip| vk::ImageSubresourceLayers {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                mip_level: mip,
+                base_array_layer: 0,
+                layer_count: layers,
+            };
+            let corner = |[w, h, d]: [u32; 3]| vk::Offset3D {
+                x: w as i32,
+                y: h as i32,
+                z: d as i32,
+            };
+            let region = vk::ImageBlit::default()
+                .src_subresource(subresource(mip - 1))
+                .src_offsets([vk::Offset3D::default(), corner(desc.mip_extent(mip - 1))])
+                .dst_subresource(subresource(mip))
+                .dst_offsets([vk::Offset3D::default(), corner(desc.mip_extent(mip))]);
+            unsafe {
+                self.device.cmd_blit_image(
+                    self.command_buffer,
+                    image,
+                    vk::ImageLayout::GENERAL,
+                    image,
+                    vk::ImageLayout::GENERAL,
+                    &[region],
+                    filter,
+                );
+            }
+            self.mip_barrier(image, mip);
+        }
+        self.transition_texture(
+            image,
+            vk::ImageAspectFlags::COLOR,
+            vk::ImageLayout::GENERAL,
+            vk::ImageLayout::GENERAL,
+            vk::AccessFlags::TRANSFER_WRITE,
+            vk::PipelineStageFlags::TRANSFER,
+            true,
+        );
+    }
+
+    /// The image of `texture` and the filter blits from it use; panics if the device cannot
+    /// blit its format.
+    fn resolve_blit_texture(&self, texture: &Texture, op: &str) -> (vk::Image, vk::Filter) {
+        let textures = self.textures.lock().expect("textures lock poisoned");
+        let tex = textures
+            .get(texture.id().0 as usize)
+            .and_then(|t| t.as_ref())
+            .expect("Invalid texture ID");
+        let filter = tex.mip_filter.unwrap_or_else(|| {
+            panic!(
+                "{op}: {:?} textures cannot be blitted on this device",
+                texture.desc().format
+            )
+        });
+        (tex.image, filter)
+    }
+
+    /// Make the blit into one mip level (every layer) visible to the blit reading it.
+    fn mip_barrier(&self, image: vk::Image, mip: u32) {
+        let barrier = vk::ImageMemoryBarrier::default()
+            .old_layout(vk::ImageLayout::GENERAL)
+            .new_layout(vk::ImageLayout::GENERAL)
+            .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+            .dst_access_mask(vk::AccessFlags::TRANSFER_READ)
+            .image(image)
+            .subresource_range(vk::ImageSubresourceRange {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                base_mip_level: mip,
+                level_count: 1,
+                base_array_layer: 0,
+                layer_count: vk::REMAINING_ARRAY_LAYERS,
+            });
+        unsafe {
+            self.device.cmd_pipeline_barrier(
+                self.command_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[barrier],
+            );
+        }
+    }
+
+    /// Validate the texture address and regions, resolve the linear buffer (which must hold
+    /// every region), and return `(image, aspect, buffer, regions)` for a copy command.
     fn prepare_texture_copy(
         &self,
         texture_gpu: GpuAddress,
         buffer_gpu: GpuAddress,
         texture: &Texture,
+        regions: &[TextureCopyRegion],
         op: &'static str,
-    ) -> (vk::Image, vk::ImageAspectFlags, u32, u32, vk::Buffer, u64) {
+    ) -> (
+        vk::Image,
+        vk::ImageAspectFlags,
+        vk::Buffer,
+        Vec<vk::BufferImageCopy>,
+    ) {
         assert_eq!(
             texture_gpu,
             texture.gpu(),
             "{op} texture_gpu must match the address used to create the texture"
         );
+        let resolved: Vec<_> = regions
+            .iter()
+            .map(|r| r.resolve(texture.desc(), op))
+            .collect();
+        let size = resolved
+            .iter()
+            .map(|r| r.buffer_offset + r.byte_size)
+            .max()
+            .unwrap_or(0);
         let (image, _view) = self.resolve_texture(texture.id());
-        let width = texture.desc().width;
-        let height = texture.desc().height;
-        let bpp = bytes_per_pixel(texture.desc().format)
-            .unwrap_or_else(|| panic!("Unsupported texture format for {op}"));
-        let size = (width as u64) * (height as u64) * (bpp as u64);
         let (buffer, offset) = self.resolve_buffer(buffer_gpu, size);
         let aspect = if is_depth_format(texture.desc().format) {
             vk::ImageAspectFlags::DEPTH
         } else {
             vk::ImageAspectFlags::COLOR
         };
-        (image, aspect, width, height, buffer, offset)
+        let vk_regions = resolved
+            .iter()
+            .map(|r| build_buffer_image_region(offset, aspect, r))
+            .collect();
+        (image, aspect, buffer, vk_regions)
     }
 
-    /// Emit a single-mip, single-layer image layout transition. `reverse=true` swaps
+    /// Emit a layout transition of every subresource of `image`. `reverse=true` swaps
     /// pipeline stages and access masks so the same call can wrap a copy on both sides.
     #[allow(clippy::too_many_arguments)]
     fn transition_texture(
@@ -740,9 +1252,9 @@ impl VulkanCommandBuffer {
             .subresource_range(vk::ImageSubresourceRange {
                 aspect_mask: aspect,
                 base_mip_level: 0,
-                level_count: 1,
+                level_count: vk::REMAINING_MIP_LEVELS,
                 base_array_layer: 0,
-                layer_count: 1,
+                layer_count: vk::REMAINING_ARRAY_LAYERS,
             });
         unsafe {
             self.device.cmd_pipeline_barrier(
@@ -773,6 +1285,52 @@ impl VulkanCommandBuffer {
         }
     }
 
+    /// Global barrier that also re-initialises `textures` (UNDEFINED → GENERAL), discarding
+    /// their contents: their memory was last written through another, aliased resource.
+    pub fn alias_barrier(
+        &mut self,
+        src: StageFlags,
+        dst: StageFlags,
+        textures: &[&crate::texture::Texture],
+    ) {
+        let memory_barrier = vk::MemoryBarrier2::default()
+            .src_stage_mask(to_vk_stage_flags(src))
+            .src_access_mask(vk::AccessFlags2::MEMORY_WRITE)
+            .dst_stage_mask(to_vk_stage_flags(dst))
+            .dst_access_mask(vk::AccessFlags2::MEMORY_READ | vk::AccessFlags2::MEMORY_WRITE);
+        let image_barriers: Vec<vk::ImageMemoryBarrier2> = textures
+            .iter()
+            .map(|texture| {
+                let (image, _) = self.resolve_texture(texture.id());
+                let aspect = transfer_aspect(texture.desc().format);
+                vk::ImageMemoryBarrier2::default()
+                    .src_stage_mask(to_vk_stage_flags(src))
+                    .src_access_mask(vk::AccessFlags2::MEMORY_WRITE)
+                    .dst_stage_mask(to_vk_stage_flags(dst))
+                    .dst_access_mask(vk::AccessFlags2::MEMORY_READ | vk::AccessFlags2::MEMORY_WRITE)
+                    .old_layout(vk::ImageLayout::UNDEFINED)
+                    .new_layout(vk::ImageLayout::GENERAL)
+                    .image(image)
+                    .subresource_range(vk::ImageSubresourceRange {
+                        aspect_mask: aspect,
+                        base_mip_level: 0,
+                        level_count: vk::REMAINING_MIP_LEVELS,
+                        base_array_layer: 0,
+                        layer_count: vk::REMAINING_ARRAY_LAYERS,
+                    })
+            })
+            .collect();
+
+        let dep_info = vk::DependencyInfo::default()
+            .memory_barriers(std::slice::from_ref(&memory_barrier))
+            .image_memory_barriers(&image_barriers);
+
+        unsafe {
+            self.device
+                .cmd_pipeline_barrier2(self.command_buffer, &dep_info);
+        }
+    }
+
     pub fn barrier_with_hazard(&mut self, src: StageFlags, dst: StageFlags, hazard: HazardFlags) {
         let use_descriptor_buffer_hazard = hazard.contains(HazardFlags::DESCRIPTORS);
         let hazard_for_access = if use_descriptor_buffer_hazard {
@@ -918,6 +1476,81 @@ impl VulkanCommandBuffer {
 
     // -- Mesh shader pipeline + draws --
 
+    /// `vkCmdWriteTimestamp2` takes a single stage, so a mask of several waits for them all.
+    pub fn write_timestamp(&mut self, stage: StageFlags, pool: &QueryPool, index: u32) {
+        let stage = match to_vk_stage_flags(stage) {
+            single if single.as_raw().is_power_of_two() => single,
+            _ => vk::PipelineStageFlags2::ALL_COMMANDS,
+        };
+        unsafe {
+            self.device
+                .cmd_write_timestamp2(self.command_buffer, stage, query_pool(pool), index);
+        }
+    }
+
+    pub fn begin_query(&mut self, pool: &QueryPool, index: u32) {
+        let flags = if pool.ty() == QueryType::Occlusion {
+            vk::QueryControlFlags::PRECISE
+        } else {
+            vk::QueryControlFlags::empty()
+        };
+        unsafe {
+            self.device
+                .cmd_begin_query(self.command_buffer, query_pool(pool), index, flags);
+        }
+    }
+
+    pub fn end_query(&mut self, pool: &QueryPool, index: u32) {
+        unsafe {
+            self.device
+                .cmd_end_query(self.command_buffer, query_pool(pool), index);
+        }
+    }
+
+    pub fn resolve_queries(&mut self, pool: &QueryPool, range: Range<u32>, dst: GpuAddress) {
+        let count = range.len() as u32;
+        let stride = pool.result_size();
+        let (buffer, offset) = self.resolve_buffer(dst, count as u64 * stride);
+        unsafe {
+            self.device.cmd_copy_query_pool_results(
+                self.command_buffer,
+                query_pool(pool),
+                range.start,
+                count,
+                buffer,
+                offset,
+                stride,
+                vk::QueryResultFlags::TYPE_64 | vk::QueryResultFlags::WAIT,
+            );
+        }
+    }
+
+    pub fn push_debug_group(&mut self, name: &str, color: [f32; 4]) {
+        let Some(debug_utils) = &self.debug_utils else {
+            return;
+        };
+        let name = debug_label_name(name);
+        let label = vk::DebugUtilsLabelEXT::default()
+            .label_name(&name)
+            .color(color);
+        unsafe { debug_utils.cmd_begin_debug_utils_label(self.command_buffer, &label) };
+    }
+
+    pub fn pop_debug_group(&mut self) {
+        if let Some(debug_utils) = &self.debug_utils {
+            unsafe { debug_utils.cmd_end_debug_utils_label(self.command_buffer) };
+        }
+    }
+
+    pub fn insert_debug_marker(&mut self, name: &str) {
+        let Some(debug_utils) = &self.debug_utils else {
+            return;
+        };
+        let name = debug_label_name(name);
+        let label = vk::DebugUtilsLabelEXT::default().label_name(&name);
+        unsafe { debug_utils.cmd_insert_debug_utils_label(self.command_buffer, &label) };
+    }
+
     pub fn set_meshlet_pipeline(&mut self, pso: &MeshletPso) {
         let vk_pso = match &pso.inner {
             crate::pipeline::MeshletPsoInner::Vulkan(p) => p,
@@ -1145,27 +1778,80 @@ impl VulkanCommandBuffer {
     }
 }
 
+fn query_pool(pool: &QueryPool) -> vk::QueryPool {
+    match &pool.inner {
+        QueryPoolInner::Vulkan(p) => p.pool,
+        #[allow(unreachable_patterns)]
+        _ => unreachable!("query pool backend does not match command buffer backend"),
+    }
+}
+
+/// The multisampled contents are kept for `Store`-ing ops; resolves are set separately.
+fn to_vk_store_op(op: StoreOp) -> vk::AttachmentStoreOp {
+    match op {
+        StoreOp::Store | StoreOp::StoreAndResolve => vk::AttachmentStoreOp::STORE,
+        StoreOp::DontCare | StoreOp::Resolve => vk::AttachmentStoreOp::DONT_CARE,
+    }
+}
+
 fn build_buffer_image_region(
     buffer_offset: u64,
     aspect: vk::ImageAspectFlags,
-    width: u32,
-    height: u32,
+    region: &ResolvedCopyRegion,
 ) -> vk::BufferImageCopy {
     vk::BufferImageCopy::default()
-        .buffer_offset(buffer_offset)
+        .buffer_offset(buffer_offset + region.buffer_offset)
+        .buffer_row_length(region.row_texels)
+        .buffer_image_height(region.image_height)
         .image_subresource(vk::ImageSubresourceLayers {
             aspect_mask: aspect,
-            mip_level: 0,
-            base_array_layer: 0,
-            layer_count: 1,
+            mip_level: region.mip,
+            base_array_layer: region.base_layer,
+            layer_count: region.layer_count,
+        })
+        .image_offset(vk::Offset3D {
+            x: region.offset[0] as i32,
+            y: region.offset[1] as i32,
+            z: region.offset[2] as i32,
         })
         .image_extent(vk::Extent3D {
-            width,
-            height,
-            depth: 1,
+            width: region.extent[0],
+            height: region.extent[1],
+            depth: region.extent[2],
         })
 }
 
+/// Every aspect of `format`, for transfers that write whole texels: clears and copies.
+fn transfer_aspect(format: Format) -> vk::ImageAspectFlags {
+    match format {
+        Format::D24UnormS8Uint | Format::D32FloatS8Uint => {
+            vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL
+        }
+        _ if is_depth_format(format) => vk::ImageAspectFlags::DEPTH,
+        _ => vk::ImageAspectFlags::COLOR,
+    }
+}
+
+fn subresource_layers(
+    aspect: vk::ImageAspectFlags,
+    region: &TextureRegion,
+) -> vk::ImageSubresourceLayers {
+    vk::ImageSubresourceLayers {
+        aspect_mask: aspect,
+        mip_level: region.mip,
+        base_array_layer: region.base_layer,
+        layer_count: region.layer_count,
+    }
+}
+
+fn offset_3d([x, y, z]: [u32; 3]) -> vk::Offset3D {
+    vk::Offset3D {
+        x: x as i32,
+        y: y as i32,
+        z: z as i32,
+    }
+}
+
 fn is_depth_format(format: Format) -> bool {
     matches!(
         format,
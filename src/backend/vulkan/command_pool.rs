@@ -0,0 +1,126 @@
+//! Transient command pools, one per primary command buffer being recorded.
+//!
+//! A `VkCommandPool` and everything allocated from it must only be used by one thread at a
+//! time, so a single device-wide pool would serialize recording. Instead every command buffer
+//! leases a pool of its own, holding just that one command buffer. Once the submission that
+//! used it retires, the pool is reset with `vkResetCommandPool` and leased again, so steady
+//! state recording allocates nothing: a frame recorded on N threads cycles through about
+//! N pools per frame in flight.
+
+use std::sync::{Arc, Mutex};
+
+use ash::{Device, vk};
+
+use crate::error::{RhiError, RhiResult};
+
+/// A pool and the one primary command buffer allocated from it.
+#[derive(Clone, Copy)]
+struct TransientPool {
+    pool: vk::CommandPool,
+    cmd: vk::CommandBuffer,
+}
+
+/// Pools not leased out, each tagged with the submission serial that last used it.
+pub(crate) struct CommandPoolRecycler {
+    device: Device,
+    queue_family_index: u32,
+    retired: Mutex<Vec<(u64, TransientPool)>>,
+}
+
+impl CommandPoolRecycler {
+    pub(crate) fn new(device: Device, queue_family_index: u32) -> Self {
+        Self {
+            device,
+            queue_family_index,
+            retired: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Lease a pool whose last submission is at or before `completed_serial`, resetting it,
+    /// or create a new one.
+    pub(crate) fn lease(self: &Arc<Self>, completed_serial: u64) -> RhiResult<CommandPoolLease> {
+        let reusable = {
+            let mut retired = self.retired.lock().expect("command pools lock poisoned");
+            retired
+                .iter()
+                .position(|&(serial, _)| serial <= completed_serial)
+                .map(|i| retired.swap_remove(i).1)
+        };
+        let transient = match reusable {
+            Some(transient) => {
+                unsafe {
+                    self.device
+                        .reset_command_pool(transient.pool, vk::CommandPoolResetFlags::empty())
+                        .map_err(|e| RhiError::CommandBuffer(e.to_string()))?;
+                }
+                transient
+            }
+            None => self.create_pool()?,
+        };
+        Ok(CommandPoolLease {
+            transient,
+            serial: 0,
+            recycler: self.clone(),
+        })
+    }
+
+    fn create_pool(&self) -> RhiResult<TransientPool> {
+        let pool_info = vk::CommandPoolCreateInfo::default()
+            .flags(vk::CommandPoolCreateFlags::TRANSIENT)
+            .queue_family_index(self.queue_family_index);
+        let pool = unsafe {
+            self.device
+                .create_command_pool(&pool_info, None)
+                .map_err(|e| RhiError::CommandBuffer(e.to_string()))?
+        };
+        let alloc_info = vk::CommandBufferAllocateInfo::default()
+            .command_buffer_count(1)
+            .command_pool(pool)
+            .level(vk::CommandBufferLevel::PRIMARY);
+        match unsafe { self.device.allocate_command_buffers(&alloc_info) } {
+            Ok(cmds) => Ok(TransientPool { pool, cmd: cmds[0] }),
+            Err(e) => {
+                unsafe { self.device.destroy_command_pool(pool, None) };
+                Err(RhiError::CommandBuffer(e.to_string()))
+            }
+        }
+    }
+
+    /// Destroy every pool not leased out. Called by the device once the GPU is idle.
+    pub(crate) fn destroy(&self) {
+        let mut retired = self.retired.lock().expect("command pools lock poisoned");
+        for (_, transient) in retired.drain(..) {
+            unsafe { self.device.destroy_command_pool(transient.pool, None) };
+        }
+    }
+}
+
+/// A pool leased to one command buffer. Dropping it hands the pool back, to be reused once
+/// the queue has completed `serial`.
+pub(crate) struct CommandPoolLease {
+    transient: TransientPool,
+    /// Serial of the submission that executes the command buffer; 0 if never submitted.
+    serial: u64,
+    recycler: Arc<CommandPoolRecycler>,
+}
+
+impl CommandPoolLease {
+    pub(crate) fn command_buffer(&self) -> vk::CommandBuffer {
+        self.transient.cmd
+    }
+
+    /// Record that the command buffer was submitted as `serial`.
+    pub(crate) fn retire_after(&mut self, serial: u64) {
+        self.serial = serial;
+    }
+}
+
+impl Drop for CommandPoolLease {
+    fn drop(&mut self) {
+        self.recycler
+            .retired
+            .lock()
+            .expect("command pools lock poisoned")
+            .push((self.serial, self.transient));
+    }
+}
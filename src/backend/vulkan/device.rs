@@ -1,6 +1,6 @@
-use std::cell::RefCell;
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::ffi::{CStr, CString, c_char};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
 
 use ash::{
@@ -10,29 +10,46 @@ use ash::{
     vk,
 };
 
+use crate::bindless::{DescriptorHeapStats, SlotAllocator};
+use crate::bundle::{BundleEncoderInner, ComputeBundleDesc, RenderBundleDesc};
 use crate::command::{
     CommandBuffer, CommandBufferInner, SignalOp, SignalValueDesc, WaitOp, WaitValueDesc,
 };
 use crate::device::{BindlessMode, DeviceDesc};
 use crate::error::{RhiError, RhiResult};
-use crate::memory::{BufferDesc, GpuBuffer, GpuBufferInner, MemoryType};
+use crate::memory::{
+    BufferDesc, ExternalMemoryHandle, GpuBuffer, GpuBufferInner, MemoryHeapBudget, MemoryStats,
+    MemoryType,
+};
 use crate::pipeline::*;
+use crate::query::{PipelineStatistics, QueryPool, QueryPoolDesc, QueryPoolInner, QueryType};
 use crate::queue::{Queue, QueueInner, SubmitDesc};
 use crate::sampler::{Sampler, SamplerDesc};
 use crate::shader::{ShaderModule, ShaderModuleDesc, ShaderModuleInner};
 use crate::surface::{Surface, SurfaceDesc, SurfaceInner};
 use crate::swapchain::{AcquiredImage, Swapchain, SwapchainDesc, SwapchainInner};
 use crate::sync::{TimelineSemaphore, TimelineSemaphoreInner};
-use crate::texture::{Texture, TextureDesc, TextureSizeAlign};
+use crate::texture::{
+    FormatCaps, Texture, TextureCompression, TextureDesc, TextureSizeAlign, format_block,
+    format_compression,
+};
 use crate::types::*;
 
 use super::accel::VulkanAccelerationStructure;
+#[cfg(unix)]
+use super::allocator::import_raw_buffer;
+use super::allocator::{
+    BlockAllocator, MIN_BUFFER_ALIGNMENT, allocate_raw_buffer, release_raw_buffer,
+};
+use super::bundle::VulkanBundleEncoder;
 use super::command::VulkanCommandBuffer;
+use super::command_pool::CommandPoolRecycler;
 use super::memory::VulkanBuffer;
 use super::pipeline::{
     VulkanComputePso, VulkanGraphicsPso, VulkanGraphicsPsoDesc, VulkanMeshletPso,
     VulkanMeshletPsoDesc,
 };
+use super::query::VulkanQueryPool;
 use super::shader::VulkanShaderModule;
 use super::surface::VulkanSurface;
 use super::swapchain::VulkanSwapchain;
@@ -56,7 +73,10 @@ pub(crate) struct BufferAllocation {
     pub size: u64,
     pub buffer: vk::Buffer,
     pub memory: vk::DeviceMemory,
+    /// Offset of `base` inside `buffer` (and `memory`, which the buffer spans from 0).
+    pub offset: u64,
     pub memory_type_index: u32,
+    pub memory_type: MemoryType,
     pub mapped_ptr: Option<*mut u8>,
 }
 
@@ -79,11 +99,26 @@ pub(crate) struct DescriptorBufferHeap {
     pub storage_image_stride: u64,
 }
 
+// The heap is written through `mapped_ptr` only at the descriptors of slots handed out by the
+// device's slot allocators, so threads never write the same bytes.
+unsafe impl Send for DescriptorBufferHeap {}
+unsafe impl Sync for DescriptorBufferHeap {}
+
 /// Buffer allocations keyed by GPU base address, enabling O(log n) address->buffer
 /// resolution instead of a linear scan on every indirect/copy/index command.
 pub(crate) type SharedAllocations = Arc<Mutex<BTreeMap<u64, BufferAllocation>>>;
+/// CPU-mapped buffers keyed by mapped base address: `(size, GPU base)`. The reverse index of
+/// `SharedAllocations`, so host→device pointer translation is a range lookup too.
+type MappedRanges = Mutex<BTreeMap<usize, (u64, GpuAddress)>>;
 pub(crate) type SharedTextures = Arc<Mutex<Vec<Option<VulkanTexture>>>>;
 
+/// A texture, view or sampler whose bindless slot was freed while submitted work may still
+/// read it. Destroyed once that work retires, when its slot becomes reusable.
+enum Parked {
+    Texture(VulkanTexture),
+    Sampler(vk::Sampler),
+}
+
 /// Components produced by `build_swapchain_contents` (shared between create and recreate).
 struct SwapchainContents {
     swapchain: vk::SwapchainKHR,
@@ -96,7 +131,6 @@ struct SwapchainContents {
     present_complete_semaphores: Vec<vk::Semaphore>,
     rendering_complete_semaphores: Vec<vk::Semaphore>,
     in_flight_fences: Vec<vk::Fence>,
-    in_flight_cmd_buffers: Vec<vk::CommandBuffer>,
 }
 
 /// Vulkan backend device.
@@ -108,10 +142,30 @@ pub struct VulkanDevice {
     pub(crate) queue_family_index: u32,
     pub(crate) queue: Queue,
     pub(crate) present_queue: vk::Queue,
+    /// Not recorded into by the RHI; handed out through `VulkanHandles`.
     pub(crate) command_pool: vk::CommandPool,
+    /// Transient pools leased by command buffers, so threads record in parallel.
+    pub(crate) command_pools: Arc<CommandPoolRecycler>,
     pub(crate) device_memory_properties: vk::PhysicalDeviceMemoryProperties,
     pub(crate) bindless_mode: BindlessMode,
     pub(crate) max_draw_indirect_count: u32,
+    /// Depth resolve modes the device supports for multisampled depth attachments.
+    pub(crate) depth_resolve_modes: vk::ResolveModeFlags,
+    /// Most views a multiview pipeline may render.
+    pub(crate) max_multiview_views: u32,
+    /// True when mesh pipelines may use multiview (`multiviewMeshShader`).
+    pub(crate) mesh_multiview_supported: bool,
+    /// Nanoseconds per timestamp tick.
+    pub(crate) timestamp_period: f32,
+    /// Meaningful bits of the queue's timestamps; zero if it has none.
+    pub(crate) timestamp_valid_bits: u32,
+    /// True when occlusion queries may count samples (`occlusionQueryPrecise`).
+    pub(crate) occlusion_query_precise: bool,
+    /// True when pipeline-statistics queries are available (`pipelineStatisticsQuery`).
+    pub(crate) pipeline_statistics_query: bool,
+    /// True when query pools can be reset from the CPU (`hostQueryReset`), which every query
+    /// pool relies on.
+    pub(crate) host_query_reset: bool,
 
     // Extension loaders
     pub(crate) surface_loader: surface::Instance,
@@ -121,38 +175,58 @@ pub struct VulkanDevice {
     // Debug
     pub(crate) debug_utils_loader: Option<debug_utils::Instance>,
     pub(crate) debug_callback: vk::DebugUtilsMessengerEXT,
+    /// Object naming and command labels; `None` without VK_EXT_debug_utils.
+    pub(crate) debug_utils: Option<debug_utils::Device>,
 
     // Bindless texture heap
     pub(crate) texture_descriptor_set_layout: vk::DescriptorSetLayout,
     pub(crate) descriptor_buffer_heap: Option<DescriptorBufferHeap>,
     pub(crate) textures: SharedTextures,
-    pub(crate) next_texture_id: RefCell<u32>,
+    /// `TextureId` slots, indexing both the sampled and the storage image tables.
+    pub(crate) texture_slots: Mutex<SlotAllocator>,
     pub(crate) allocations: SharedAllocations,
+    mapped_ranges: MappedRanges,
+    /// Sub-allocator carving buffers out of large per-`MemoryType` memory blocks.
+    pub(crate) block_allocator: Mutex<BlockAllocator>,
 
     // Sampler storage
-    pub(crate) samplers: RefCell<Vec<vk::Sampler>>,
-    pub(crate) next_sampler_id: RefCell<u32>,
-
-    // Setup command buffer
-    pub(crate) setup_command_buffer: vk::CommandBuffer,
+    /// Indexed by `SamplerId`; null for freed slots.
+    pub(crate) samplers: Mutex<Vec<vk::Sampler>>,
+    pub(crate) sampler_slots: Mutex<SlotAllocator>,
+    /// Objects of freed bindless slots and the serial that must complete first, in free order.
+    parked: Mutex<VecDeque<(u64, Parked)>>,
 
     // Mesh shader support
     /// True when `VK_EXT_mesh_shader` was enabled at device creation.
     pub(crate) mesh_shader_supported: bool,
+    /// True when `VK_EXT_memory_budget` was enabled; `memory_stats` then reports real
+    /// per-heap budgets instead of heap sizes.
+    pub(crate) memory_budget_supported: bool,
+    /// Present when `VK_KHR_external_memory_fd` was enabled (memory export/import).
+    pub(crate) external_memory_fd: Option<ash::khr::external_memory_fd::Device>,
+    /// True when `VK_EXT_external_memory_dma_buf` was enabled.
+    pub(crate) dma_buf_supported: bool,
+    /// Compressed format families whose device features were enabled.
+    pub(crate) texture_compression: TextureCompression,
 
     /// Present when VK_KHR_acceleration_structure was enabled (for BLAS/TLAS builds).
     pub(crate) acceleration_structure: Option<vk_accel_structure::Device>,
     /// Monotonic counter for AccelerationStructureId assignment.
-    pub(crate) accel_counter: RefCell<u32>,
+    pub(crate) accel_counter: AtomicU32,
 }
 
 /// Vulkan queue wrapper.
 pub struct VulkanQueue {
-    pub(crate) queue: vk::Queue,
+    /// Vulkan requires submits, presents and idle waits on a queue to be externally
+    /// synchronized; holding this lock also keeps serials in submission order.
+    queue: Mutex<vk::Queue>,
     pub(crate) device: Device,
     pub(crate) swapchain_loader: swapchain::Device,
-    pub(crate) command_pool: vk::CommandPool,
     value_sync: Mutex<HashMap<u64, VulkanValueSyncState>>,
+    /// Timeline signalled with a fresh serial by every submit, so deferred destruction can
+    /// tell which submissions have retired.
+    submission_timeline: vk::Semaphore,
+    submitted_serial: AtomicU64,
 }
 
 #[derive(Clone, Copy)]
@@ -277,54 +351,107 @@ impl VulkanQueue {
         cmd: VulkanCommandBuffer,
         desc: &SubmitDesc<'_>,
     ) -> RhiResult<()> {
+        self.submit_batch(vec![cmd], desc)
+    }
+
+    /// Submit `cmds` in one `vkQueueSubmit`, in order. Their value waits all happen before
+    /// the first one starts and their value signals after the last one finishes.
+    pub fn submit_batch(
+        &self,
+        mut cmds: Vec<VulkanCommandBuffer>,
+        desc: &SubmitDesc<'_>,
+    ) -> RhiResult<()> {
+        let queue = self.queue.lock().expect("queue lock poisoned");
+        let value_waits: Vec<WaitValueDesc> = cmds
+            .iter()
+            .flat_map(|cmd| cmd.pending_value_waits.iter().copied())
+            .collect();
+        let value_signals: Vec<SignalValueDesc> = cmds
+            .iter()
+            .flat_map(|cmd| cmd.pending_value_signals.iter().copied())
+            .collect();
         let mut waits = timeline_pairs(desc.wait_semaphores, "wait")?;
-        waits.extend(self.collect_value_waits(&cmd.pending_value_waits)?);
+        waits.extend(self.collect_value_waits(&value_waits)?);
         let mut signals = timeline_pairs(desc.signal_semaphores, "signal")?;
-        signals.extend(self.collect_value_signals(&cmd.pending_value_signals)?);
+        signals.extend(self.collect_value_signals(&value_signals)?);
         let wait_stages = vec![vk::PipelineStageFlags::ALL_COMMANDS; waits.len()];
-        self.submit_timeline(
-            cmd.command_buffer,
+        let raw_cmds: Vec<vk::CommandBuffer> = cmds.iter().map(|cmd| cmd.command_buffer).collect();
+        let serial = self.submit_timeline(
+            *queue,
+            &raw_cmds,
             &waits,
             &wait_stages,
             &signals,
             vk::Fence::null(),
-        )
+        )?;
+        for cmd in &mut cmds {
+            cmd.retire_after(serial);
+        }
+        Ok(())
+    }
+
+    pub fn submitted_serial(&self) -> u64 {
+        self.submitted_serial.load(Ordering::Acquire)
     }
 
-    /// Encode a `vkQueueSubmit` with timeline-semaphore wait/signal pairs.
+    pub fn completed_serial(&self) -> u64 {
+        unsafe {
+            self.device
+                .get_semaphore_counter_value(self.submission_timeline)
+                .unwrap_or(0)
+        }
+    }
+
+    pub fn wait_for_serial(&self, serial: u64) {
+        let semaphores = [self.submission_timeline];
+        let values = [serial];
+        let wait_info = vk::SemaphoreWaitInfo::default()
+            .semaphores(&semaphores)
+            .values(&values);
+        unsafe {
+            let _ = self.device.wait_semaphores(&wait_info, u64::MAX);
+        }
+    }
+
+    /// Encode a `vkQueueSubmit` with timeline-semaphore wait/signal pairs, plus the next
+    /// submission serial on `submission_timeline`, and return that serial.
     ///
-    /// `wait_stages` must have the same length as `waits`. Pass `vk::Fence::null()` when
-    /// no completion fence is needed.
+    /// `queue` comes from the caller's lock on `self.queue`. `wait_stages` must have the
+    /// same length as `waits`. Pass `vk::Fence::null()` when no completion fence is needed.
     fn submit_timeline(
         &self,
-        cmd: vk::CommandBuffer,
+        queue: vk::Queue,
+        command_buffers: &[vk::CommandBuffer],
         waits: &[(vk::Semaphore, u64)],
         wait_stages: &[vk::PipelineStageFlags],
         signals: &[(vk::Semaphore, u64)],
         fence: vk::Fence,
-    ) -> RhiResult<()> {
-        let command_buffers = [cmd];
+    ) -> RhiResult<u64> {
+        let serial = self.submitted_serial() + 1;
         let wait_semaphores: Vec<vk::Semaphore> = waits.iter().map(|(s, _)| *s).collect();
         let wait_values: Vec<u64> = waits.iter().map(|(_, v)| *v).collect();
-        let signal_semaphores: Vec<vk::Semaphore> = signals.iter().map(|(s, _)| *s).collect();
-        let signal_values: Vec<u64> = signals.iter().map(|(_, v)| *v).collect();
-        let mut submit_info = vk::SubmitInfo::default()
-            .wait_semaphores(&wait_semaphores)
-            .wait_dst_stage_mask(wait_stages)
-            .command_buffers(&command_buffers)
-            .signal_semaphores(&signal_semaphores);
+        let signal_semaphores: Vec<vk::Semaphore> = signals
+            .iter()
+            .map(|(s, _)| *s)
+            .chain([self.submission_timeline])
+            .collect();
+        let signal_values: Vec<u64> = signals.iter().map(|(_, v)| *v).chain([serial]).collect();
         let mut timeline_info = vk::TimelineSemaphoreSubmitInfo::default()
             .wait_semaphore_values(&wait_values)
             .signal_semaphore_values(&signal_values);
-        if !wait_values.is_empty() || !signal_values.is_empty() {
-            submit_info = submit_info.push_next(&mut timeline_info);
-        }
+        let submit_info = vk::SubmitInfo::default()
+            .wait_semaphores(&wait_semaphores)
+            .wait_dst_stage_mask(wait_stages)
+            .command_buffers(command_buffers)
+            .signal_semaphores(&signal_semaphores)
+            .push_next(&mut timeline_info);
         unsafe {
             self.device
-                .queue_submit(self.queue, &[submit_info], fence)
+                .queue_submit(queue, &[submit_info], fence)
                 .map_err(|e| RhiError::QueueSubmit(e.to_string()))?;
         }
-        Ok(())
+        self.submitted_serial.store(serial, Ordering::Release);
+        Ok(serial)
     }
 
     pub fn acquire_image(
@@ -342,18 +469,6 @@ impl VulkanQueue {
                 .reset_fences(&[fence])
                 .map_err(|e| RhiError::SyncError(e.to_string()))?;
 
-            // Reclaim the previous command buffer used for this frame.
-            {
-                let mut cmd_buffers = sc.in_flight_cmd_buffers.borrow_mut();
-                if let Some(prev) = cmd_buffers.get_mut(frame_index)
-                    && *prev != vk::CommandBuffer::null()
-                {
-                    self.device
-                        .free_command_buffers(self.command_pool, std::slice::from_ref(prev));
-                    *prev = vk::CommandBuffer::null();
-                }
-            }
-
             let semaphore = sc.present_complete_semaphores[frame_index];
             let (image_index, _suboptimal) = self
                 .swapchain_loader
@@ -386,9 +501,10 @@ impl VulkanQueue {
             .swapchains(&swapchains)
             .image_indices(&image_indices);
 
+        let queue = self.queue.lock().expect("queue lock poisoned");
         unsafe {
             self.swapchain_loader
-                .queue_present(self.queue, &present_info)
+                .queue_present(*queue, &present_info)
                 .map_err(|e| match e {
                     vk::Result::ERROR_OUT_OF_DATE_KHR => RhiError::SwapchainOutOfDate,
                     _ => RhiError::PresentFailed(e.to_string()),
@@ -399,11 +515,12 @@ impl VulkanQueue {
 
     pub fn submit_frame(
         &self,
-        cmd: super::command::VulkanCommandBuffer,
+        mut cmd: super::command::VulkanCommandBuffer,
         sc: &super::swapchain::VulkanSwapchain,
         frame_index: usize,
         image_index: u32,
     ) -> RhiResult<()> {
+        let queue = self.queue.lock().expect("queue lock poisoned");
         // Seed with the swapchain's acquire→render→present semaphores, then append the
         // command buffer's pending value sync. Value waits use ALL_COMMANDS; the acquire
         // wait only needs to gate the color attachment write.
@@ -416,19 +533,22 @@ impl VulkanQueue {
         let mut signals = vec![(sc.rendering_complete_semaphores[image_index as usize], 0u64)];
         signals.extend(self.collect_value_signals(&cmd.pending_value_signals)?);
         let fence = sc.in_flight_fences[frame_index];
-        let raw_cmd = cmd.command_buffer;
-        self.submit_timeline(raw_cmd, &waits, &wait_stages, &signals, fence)?;
-
-        // Track the command buffer so it can be freed after the fence signals.
-        if let Some(slot) = sc.in_flight_cmd_buffers.borrow_mut().get_mut(frame_index) {
-            *slot = raw_cmd;
-        }
+        let serial = self.submit_timeline(
+            *queue,
+            &[cmd.command_buffer],
+            &waits,
+            &wait_stages,
+            &signals,
+            fence,
+        )?;
+        cmd.retire_after(serial);
         Ok(())
     }
 
     pub fn wait_idle(&self) {
+        let queue = self.queue.lock().expect("queue lock poisoned");
         unsafe {
-            let _ = self.device.queue_wait_idle(self.queue);
+            let _ = self.device.queue_wait_idle(*queue);
         }
     }
 }
@@ -481,7 +601,7 @@ unsafe extern "system" fn vulkan_debug_callback(
 }
 
 /// Helper: find memory type index.
-fn find_memorytype_index(
+pub(crate) fn find_memorytype_index(
     memory_req: &vk::MemoryRequirements,
     memory_prop: &vk::PhysicalDeviceMemoryProperties,
     flags: vk::MemoryPropertyFlags,
@@ -520,9 +640,85 @@ pub fn format_to_vk(format: Format) -> vk::Format {
         Format::D32FloatS8Uint => vk::Format::D32_SFLOAT_S8_UINT,
         Format::R16Uint => vk::Format::R16_UINT,
         Format::R32Uint => vk::Format::R32_UINT,
+        Format::Bc1RgbaUnorm => vk::Format::BC1_RGBA_UNORM_BLOCK,
+        Format::Bc1RgbaSrgb => vk::Format::BC1_RGBA_SRGB_BLOCK,
+        Format::Bc2RgbaUnorm => vk::Format::BC2_UNORM_BLOCK,
+        Format::Bc2RgbaSrgb => vk::Format::BC2_SRGB_BLOCK,
+        Format::Bc3RgbaUnorm => vk::Format::BC3_UNORM_BLOCK,
+        Format::Bc3RgbaSrgb => vk::Format::BC3_SRGB_BLOCK,
+        Format::Bc4RUnorm => vk::Format::BC4_UNORM_BLOCK,
+        Format::Bc4RSnorm => vk::Format::BC4_SNORM_BLOCK,
+        Format::Bc5RgUnorm => vk::Format::BC5_UNORM_BLOCK,
+        Format::Bc5RgSnorm => vk::Format::BC5_SNORM_BLOCK,
+        Format::Bc6hRgbUfloat => vk::Format::BC6H_UFLOAT_BLOCK,
+        Format::Bc6hRgbSfloat => vk::Format::BC6H_SFLOAT_BLOCK,
+        Format::Bc7RgbaUnorm => vk::Format::BC7_UNORM_BLOCK,
+        Format::Bc7RgbaSrgb => vk::Format::BC7_SRGB_BLOCK,
+        Format::Etc2R8G8B8Unorm => vk::Format::ETC2_R8G8B8_UNORM_BLOCK,
+        Format::Etc2R8G8B8Srgb => vk::Format::ETC2_R8G8B8_SRGB_BLOCK,
+        Format::Etc2R8G8B8A1Unorm => vk::Format::ETC2_R8G8B8A1_UNORM_BLOCK,
+        Format::Etc2R8G8B8A1Srgb => vk::Format::ETC2_R8G8B8A1_SRGB_BLOCK,
+        Format::Etc2R8G8B8A8Unorm => vk::Format::ETC2_R8G8B8A8_UNORM_BLOCK,
+        Format::Etc2R8G8B8A8Srgb => vk::Format::ETC2_R8G8B8A8_SRGB_BLOCK,
+        Format::EacR11Unorm => vk::Format::EAC_R11_UNORM_BLOCK,
+        Format::EacR11Snorm => vk::Format::EAC_R11_SNORM_BLOCK,
+        Format::EacR11G11Unorm => vk::Format::EAC_R11G11_UNORM_BLOCK,
+        Format::EacR11G11Snorm => vk::Format::EAC_R11G11_SNORM_BLOCK,
+        Format::Astc4x4Unorm => vk::Format::ASTC_4X4_UNORM_BLOCK,
+        Format::Astc4x4Srgb => vk::Format::ASTC_4X4_SRGB_BLOCK,
+        Format::Astc5x4Unorm => vk::Format::ASTC_5X4_UNORM_BLOCK,
+        Format::Astc5x4Srgb => vk::Format::ASTC_5X4_SRGB_BLOCK,
+        Format::Astc5x5Unorm => vk::Format::ASTC_5X5_UNORM_BLOCK,
+        Format::Astc5x5Srgb => vk::Format::ASTC_5X5_SRGB_BLOCK,
+        Format::Astc6x5Unorm => vk::Format::ASTC_6X5_UNORM_BLOCK,
+        Format::Astc6x5Srgb => vk::Format::ASTC_6X5_SRGB_BLOCK,
+        Format::Astc6x6Unorm => vk::Format::ASTC_6X6_UNORM_BLOCK,
+        Format::Astc6x6Srgb => vk::Format::ASTC_6X6_SRGB_BLOCK,
+        Format::Astc8x5Unorm => vk::Format::ASTC_8X5_UNORM_BLOCK,
+        Format::Astc8x5Srgb => vk::Format::ASTC_8X5_SRGB_BLOCK,
+        Format::Astc8x6Unorm => vk::Format::ASTC_8X6_UNORM_BLOCK,
+        Format::Astc8x6Srgb => vk::Format::ASTC_8X6_SRGB_BLOCK,
+        Format::Astc8x8Unorm => vk::Format::ASTC_8X8_UNORM_BLOCK,
+        Format::Astc8x8Srgb => vk::Format::ASTC_8X8_SRGB_BLOCK,
+        Format::Astc10x5Unorm => vk::Format::ASTC_10X5_UNORM_BLOCK,
+        Format::Astc10x5Srgb => vk::Format::ASTC_10X5_SRGB_BLOCK,
+        Format::Astc10x6Unorm => vk::Format::ASTC_10X6_UNORM_BLOCK,
+        Format::Astc10x6Srgb => vk::Format::ASTC_10X6_SRGB_BLOCK,
+        Format::Astc10x8Unorm => vk::Format::ASTC_10X8_UNORM_BLOCK,
+        Format::Astc10x8Srgb => vk::Format::ASTC_10X8_SRGB_BLOCK,
+        Format::Astc10x10Unorm => vk::Format::ASTC_10X10_UNORM_BLOCK,
+        Format::Astc10x10Srgb => vk::Format::ASTC_10X10_SRGB_BLOCK,
+        Format::Astc12x10Unorm => vk::Format::ASTC_12X10_UNORM_BLOCK,
+        Format::Astc12x10Srgb => vk::Format::ASTC_12X10_SRGB_BLOCK,
+        Format::Astc12x12Unorm => vk::Format::ASTC_12X12_UNORM_BLOCK,
+        Format::Astc12x12Srgb => vk::Format::ASTC_12X12_SRGB_BLOCK,
     }
 }
 
+/// Name `handle` after `label` (cut at any interior NUL) in validation messages and capture
+/// tools. A no-op without VK_EXT_debug_utils or a label.
+pub(crate) fn set_object_name(
+    debug_utils: Option<&debug_utils::Device>,
+    handle: impl vk::Handle,
+    label: Option<&str>,
+) {
+    let (Some(debug_utils), Some(label)) = (debug_utils, label) else {
+        return;
+    };
+    let name = debug_label_name(label);
+    let info = vk::DebugUtilsObjectNameInfoEXT::default()
+        .object_handle(handle)
+        .object_name(&name);
+    // Naming is best-effort: a failure only loses the name.
+    let _ = unsafe { debug_utils.set_debug_utils_object_name(&info) };
+}
+
+/// `name` as a C string, cut at its first interior NUL.
+pub(crate) fn debug_label_name(name: &str) -> CString {
+    let name = name.split('\0').next().unwrap_or_default();
+    CString::new(name).expect("NUL-free label")
+}
+
 /// Convert Vulkan format to RHI Format.
 pub(crate) fn vk_to_format(format: vk::Format) -> Format {
     match format {
@@ -557,7 +753,14 @@ impl VulkanDevice {
         // Note: We don't have a window handle here, so surface extensions
         // will be added when creating the surface. For now, add debug + portability.
         let mut extension_names: Vec<*const c_char> = Vec::new();
-        if desc.validation {
+        // Debug utils also names objects and labels command ranges for capture tools, so
+        // enable it whenever the loader offers it, not only with validation.
+        let debug_utils_available = unsafe { entry.enumerate_instance_extension_properties(None) }
+            .unwrap_or_default()
+            .iter()
+            .any(|ext| ext.extension_name_as_c_str() == Ok(debug_utils::NAME));
+        let debug_utils_enabled = desc.validation || debug_utils_available;
+        if debug_utils_enabled {
             extension_names.push(debug_utils::NAME.as_ptr());
         }
 
@@ -613,29 +816,30 @@ impl VulkanDevice {
         };
 
         // Debug callback
-        let (debug_utils_loader, debug_callback) = if desc.validation {
-            let debug_info = vk::DebugUtilsMessengerCreateInfoEXT::default()
-                .message_severity(
-                    vk::DebugUtilsMessageSeverityFlagsEXT::ERROR
-                        | vk::DebugUtilsMessageSeverityFlagsEXT::WARNING,
-                )
-                .message_type(
-                    vk::DebugUtilsMessageTypeFlagsEXT::GENERAL
-                        | vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION
-                        | vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE,
-                )
-                .pfn_user_callback(Some(vulkan_debug_callback));
+        let debug_utils_loader =
+            debug_utils_enabled.then(|| debug_utils::Instance::new(&entry, &instance));
+        let debug_callback =
+            if let Some(loader) = debug_utils_loader.as_ref().filter(|_| desc.validation) {
+                let debug_info = vk::DebugUtilsMessengerCreateInfoEXT::default()
+                    .message_severity(
+                        vk::DebugUtilsMessageSeverityFlagsEXT::ERROR
+                            | vk::DebugUtilsMessageSeverityFlagsEXT::WARNING,
+                    )
+                    .message_type(
+                        vk::DebugUtilsMessageTypeFlagsEXT::GENERAL
+                            | vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION
+                            | vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE,
+                    )
+                    .pfn_user_callback(Some(vulkan_debug_callback));
 
-            let loader = debug_utils::Instance::new(&entry, &instance);
-            let callback = unsafe {
-                loader
-                    .create_debug_utils_messenger(&debug_info, None)
-                    .map_err(|e| RhiError::DeviceCreation(format!("Debug callback: {e}")))?
+                unsafe {
+                    loader
+                        .create_debug_utils_messenger(&debug_info, None)
+                        .map_err(|e| RhiError::DeviceCreation(format!("Debug callback: {e}")))?
+                }
+            } else {
+                vk::DebugUtilsMessengerEXT::null()
             };
-            (Some(loader), callback)
-        } else {
-            (None, vk::DebugUtilsMessengerEXT::null())
-        };
 
         // Physical device selection
         let physical_devices = unsafe {
@@ -681,6 +885,21 @@ impl VulkanDevice {
         };
         log::info!("RHI: Selected GPU: {}", device_name);
 
+        let mut resolve_props = vk::PhysicalDeviceDepthStencilResolveProperties::default();
+        let mut multiview_props = vk::PhysicalDeviceMultiviewProperties::default();
+        let mut props2 = vk::PhysicalDeviceProperties2::default()
+            .push_next(&mut resolve_props)
+            .push_next(&mut multiview_props);
+        unsafe {
+            instance.get_physical_device_properties2(physical_device, &mut props2);
+        }
+        let depth_resolve_modes = resolve_props.supported_depth_resolve_modes;
+        let max_multiview_views = multiview_props.max_multiview_view_count;
+        let timestamp_valid_bits =
+            unsafe { instance.get_physical_device_queue_family_properties(physical_device) }
+                [queue_family_index as usize]
+                .timestamp_valid_bits;
+
         // Device extension support
         let device_extension_props = unsafe {
             instance
@@ -701,8 +920,12 @@ impl VulkanDevice {
         // VK_KHR_deferred_host_operations. Ray tracing is inline ray query, not RT pipelines.
         let supports_accel = has_ext(b"VK_KHR_acceleration_structure")
             && has_ext(b"VK_KHR_deferred_host_operations");
+        let supports_memory_budget = has_ext(b"VK_EXT_memory_budget");
+        let supports_external_memory_fd = has_ext(b"VK_KHR_external_memory_fd");
+        let supports_dma_buf =
+            supports_external_memory_fd && has_ext(b"VK_EXT_external_memory_dma_buf");
         log::info!(
-            "RHI: Optional extensions — mesh_shader={supports_mesh_shader} acceleration_structure={supports_accel}"
+            "RHI: Optional extensions — mesh_shader={supports_mesh_shader} acceleration_structure={supports_accel} memory_budget={supports_memory_budget} external_memory_fd={supports_external_memory_fd} dma_buf={supports_dma_buf}"
         );
 
         if desc.bindless_mode == Some(BindlessMode::ArgumentTable) {
@@ -728,19 +951,46 @@ impl VulkanDevice {
             device_extension_names.push(vk_accel_structure::NAME.as_ptr());
             device_extension_names.push(ash::khr::deferred_host_operations::NAME.as_ptr());
         }
+        if supports_memory_budget {
+            device_extension_names.push(ash::ext::memory_budget::NAME.as_ptr());
+        }
+        if supports_external_memory_fd {
+            device_extension_names.push(ash::khr::external_memory_fd::NAME.as_ptr());
+        }
+        if supports_dma_buf {
+            device_extension_names.push(ash::ext::external_memory_dma_buf::NAME.as_ptr());
+        }
 
         #[cfg(any(target_os = "macos", target_os = "ios"))]
         {
             device_extension_names.push(ash::khr::portability_subset::NAME.as_ptr());
         }
 
-        // All required features that were promoted to Vulkan 1.2/1.3 core go through the
-        // consolidated PhysicalDeviceVulkan1{2,3}Features structs — no separate per-feature
+        // Layered rendering without multiview (SV_RenderTargetArrayIndex from the vertex
+        // stage), host query reset and multiview mesh pipelines are optional; query them
+        // before enabling.
+        let mut available_vulkan12 = vk::PhysicalDeviceVulkan12Features::default();
+        let mut available_mesh = vk::PhysicalDeviceMeshShaderFeaturesEXT::default();
+        let mut available2 =
+            vk::PhysicalDeviceFeatures2::default().push_next(&mut available_vulkan12);
+        if supports_mesh_shader {
+            available2 = available2.push_next(&mut available_mesh);
+        }
+        unsafe { instance.get_physical_device_features2(physical_device, &mut available2) };
+        let mesh_multiview_supported =
+            supports_mesh_shader && available_mesh.multiview_mesh_shader != 0;
+        let host_query_reset = available_vulkan12.host_query_reset != 0;
+
+        // All required features that were promoted to Vulkan 1.1/1.2/1.3 core go through the
+        // consolidated PhysicalDeviceVulkan1{1,2,3}Features structs — no separate per-feature
         // structs needed since we require Vulkan 1.3.
+        let mut vulkan11_features = vk::PhysicalDeviceVulkan11Features::default().multiview(true);
         let mut vulkan12_features = vk::PhysicalDeviceVulkan12Features::default()
             .buffer_device_address(true)
             .timeline_semaphore(true)
-            .draw_indirect_count(true);
+            .draw_indirect_count(true)
+            .host_query_reset(host_query_reset)
+            .shader_output_layer(available_vulkan12.shader_output_layer != 0);
         let mut vulkan13_features = vk::PhysicalDeviceVulkan13Features::default()
             .dynamic_rendering(true)
             .synchronization2(true);
@@ -752,20 +1002,44 @@ impl VulkanDevice {
         // so they outlive `features2`, then conditionally chained below).
         let mut mesh_shader_features = vk::PhysicalDeviceMeshShaderFeaturesEXT::default()
             .mesh_shader(true)
-            .task_shader(true);
+            .task_shader(true)
+            .multiview_mesh_shader(mesh_multiview_supported);
         let mut accel_structure_features =
             vk::PhysicalDeviceAccelerationStructureFeaturesKHR::default()
                 .acceleration_structure(true);
 
+        // Compressed texture families are optional; enable whichever the GPU has.
+        let available_features = unsafe { instance.get_physical_device_features(physical_device) };
+        let mut texture_compression = TextureCompression::empty();
+        texture_compression.set(
+            TextureCompression::BC,
+            available_features.texture_compression_bc != 0,
+        );
+        texture_compression.set(
+            TextureCompression::ETC2,
+            available_features.texture_compression_etc2 != 0,
+        );
+        texture_compression.set(
+            TextureCompression::ASTC,
+            available_features.texture_compression_astc_ldr != 0,
+        );
+        log::info!("RHI: Texture compression — {texture_compression:?}");
+
         let features = vk::PhysicalDeviceFeatures {
             shader_clip_distance: 1,
             fill_mode_non_solid: 1,
             multi_draw_indirect: 1,
+            texture_compression_bc: available_features.texture_compression_bc,
+            texture_compression_etc2: available_features.texture_compression_etc2,
+            texture_compression_astc_ldr: available_features.texture_compression_astc_ldr,
+            occlusion_query_precise: available_features.occlusion_query_precise,
+            pipeline_statistics_query: available_features.pipeline_statistics_query,
             ..Default::default()
         };
 
         let mut features2 = vk::PhysicalDeviceFeatures2::default()
             .features(features)
+            .push_next(&mut vulkan11_features)
             .push_next(&mut vulkan12_features)
             .push_next(&mut vulkan13_features)
             .push_next(&mut descriptor_buffer_features);
@@ -798,6 +1072,9 @@ impl VulkanDevice {
         // Extension loaders
         let surface_loader = surface::Instance::new(&entry, &instance);
         let swapchain_loader = swapchain::Device::new(&instance, &device);
+        let debug_utils = debug_utils_loader
+            .as_ref()
+            .map(|_| debug_utils::Device::new(&instance, &device));
         // Note: dynamic_rendering, buffer_device_address, and synchronization2 are Vulkan 1.3 core
         // — their functionality is available directly on `ash::Device` without a loader.
 
@@ -814,19 +1091,13 @@ impl VulkanDevice {
                 .map_err(|e| RhiError::DeviceCreation(format!("Command pool: {e}")))?
         };
 
-        // Setup command buffer
-        let cmd_alloc_info = vk::CommandBufferAllocateInfo::default()
-            .command_buffer_count(1)
-            .command_pool(command_pool)
-            .level(vk::CommandBufferLevel::PRIMARY);
-        let setup_command_buffer = unsafe {
-            device
-                .allocate_command_buffers(&cmd_alloc_info)
-                .map_err(|e| RhiError::DeviceCreation(format!("Setup cmd buffer: {e}")))?
-        }[0];
+        let command_pools = Arc::new(CommandPoolRecycler::new(device.clone(), queue_family_index));
 
         let descriptor_buffer_loader = Some(descriptor_buffer::Device::new(&instance, &device));
 
+        let external_memory_fd = supports_external_memory_fd
+            .then(|| ash::khr::external_memory_fd::Device::new(&instance, &device));
+
         // Acceleration structure loader (for BLAS/TLAS builds).
         let acceleration_structure_opt = if supports_accel {
             Some(vk_accel_structure::Device::new(&instance, &device))
@@ -848,14 +1119,28 @@ impl VulkanDevice {
             (heap.layout, Some(heap))
         };
 
+        let submission_timeline = {
+            let mut type_info = vk::SemaphoreTypeCreateInfo::default()
+                .semaphore_type(vk::SemaphoreType::TIMELINE)
+                .initial_value(0);
+            let semaphore_info = vk::SemaphoreCreateInfo::default().push_next(&mut type_info);
+            unsafe {
+                device
+                    .create_semaphore(&semaphore_info, None)
+                    .map_err(|e| RhiError::DeviceCreation(format!("Submission timeline: {e}")))?
+            }
+        };
+
         let queue = Queue {
             inner: QueueInner::Vulkan(Box::new(VulkanQueue {
-                queue: present_queue,
+                queue: Mutex::new(present_queue),
                 device: device.clone(),
                 swapchain_loader: swapchain::Device::new(&instance, &device),
-                command_pool,
                 value_sync: Mutex::new(HashMap::new()),
+                submission_timeline,
+                submitted_serial: AtomicU64::new(0),
             })),
+            graveyard: Default::default(),
         };
 
         Ok(Self {
@@ -867,25 +1152,43 @@ impl VulkanDevice {
             queue,
             present_queue,
             command_pool,
+            command_pools,
             device_memory_properties,
             bindless_mode,
             max_draw_indirect_count: device_props.limits.max_draw_indirect_count,
+            depth_resolve_modes,
+            max_multiview_views,
+            mesh_multiview_supported,
+            timestamp_period: device_props.limits.timestamp_period,
+            timestamp_valid_bits,
+            occlusion_query_precise: available_features.occlusion_query_precise != 0,
+            pipeline_statistics_query: available_features.pipeline_statistics_query != 0,
+            host_query_reset,
             surface_loader,
             swapchain_loader,
             descriptor_buffer_loader,
             debug_utils_loader,
             debug_callback,
+            debug_utils,
             texture_descriptor_set_layout,
             descriptor_buffer_heap,
             textures: Arc::new(Mutex::new(Vec::new())),
-            next_texture_id: RefCell::new(0),
+            texture_slots: Mutex::new(SlotAllocator::new(
+                MAX_BINDLESS_TEXTURES.min(MAX_BINDLESS_STORAGE_IMAGES),
+            )),
             allocations: Arc::new(Mutex::new(BTreeMap::new())),
-            samplers: RefCell::new(Vec::new()),
-            next_sampler_id: RefCell::new(0),
-            setup_command_buffer,
+            mapped_ranges: Mutex::new(BTreeMap::new()),
+            block_allocator: Mutex::new(BlockAllocator::new()),
+            samplers: Mutex::new(Vec::new()),
+            sampler_slots: Mutex::new(SlotAllocator::new(MAX_BINDLESS_SAMPLERS)),
+            parked: Mutex::new(VecDeque::new()),
             mesh_shader_supported: supports_mesh_shader,
+            memory_budget_supported: supports_memory_budget,
+            external_memory_fd,
+            dma_buf_supported: supports_dma_buf,
+            texture_compression,
             acceleration_structure: acceleration_structure_opt,
-            accel_counter: RefCell::new(0),
+            accel_counter: AtomicU32::new(0),
         })
     }
 
@@ -897,10 +1200,87 @@ impl VulkanDevice {
         self.bindless_mode
     }
 
+    pub fn texture_compression(&self) -> TextureCompression {
+        self.texture_compression
+    }
+
+    /// Optimal-tiling format features, plus the sample counts of a 2D attachment image.
+    /// Compressed formats whose family feature is not enabled report nothing.
+    pub fn format_caps(&self, format: Format) -> FormatCaps {
+        if !self
+            .texture_compression
+            .contains(format_compression(format))
+        {
+            return FormatCaps::empty();
+        }
+        let vk_format = format_to_vk(format);
+        let mut properties = vk::FormatProperties2::default();
+        unsafe {
+            self.instance.get_physical_device_format_properties2(
+                self.physical_device,
+                vk_format,
+                &mut properties,
+            );
+        }
+        let features = properties.format_properties.optimal_tiling_features;
+        let mut caps = FormatCaps::empty();
+        for (feature, cap) in [
+            (vk::FormatFeatureFlags::SAMPLED_IMAGE, FormatCaps::SAMPLED),
+            (
+                vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR,
+                FormatCaps::FILTER_LINEAR,
+            ),
+            (vk::FormatFeatureFlags::STORAGE_IMAGE, FormatCaps::STORAGE),
+            (
+                vk::FormatFeatureFlags::COLOR_ATTACHMENT,
+                FormatCaps::COLOR_ATTACHMENT,
+            ),
+            (
+                vk::FormatFeatureFlags::COLOR_ATTACHMENT_BLEND,
+                FormatCaps::BLEND,
+            ),
+            (
+                vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT,
+                FormatCaps::DEPTH_STENCIL_ATTACHMENT,
+            ),
+        ] {
+            caps.set(cap, features.contains(feature));
+        }
+
+        let usage = if caps.contains(FormatCaps::COLOR_ATTACHMENT) {
+            vk::ImageUsageFlags::COLOR_ATTACHMENT
+        } else if caps.contains(FormatCaps::DEPTH_STENCIL_ATTACHMENT) {
+            vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT
+        } else {
+            return caps;
+        };
+        let sample_counts = unsafe {
+            self.instance.get_physical_device_image_format_properties(
+                self.physical_device,
+                vk_format,
+                vk::ImageType::TYPE_2D,
+                vk::ImageTiling::OPTIMAL,
+                usage,
+                vk::ImageCreateFlags::empty(),
+            )
+        }
+        .map_or(vk::SampleCountFlags::TYPE_1, |p| p.sample_counts);
+        for (count, cap) in [
+            (vk::SampleCountFlags::TYPE_2, FormatCaps::MSAA_2),
+            (vk::SampleCountFlags::TYPE_4, FormatCaps::MSAA_4),
+            (vk::SampleCountFlags::TYPE_8, FormatCaps::MSAA_8),
+            (vk::SampleCountFlags::TYPE_16, FormatCaps::MSAA_16),
+        ] {
+            caps.set(cap, sample_counts.contains(count));
+        }
+        caps
+    }
+
     pub fn wait_idle(&self) {
         unsafe {
             let _ = self.device.device_wait_idle();
         }
+        self.destroy_parked(self.queue.completed_serial());
     }
 
     pub fn wait_for_frame(&self, _frame_index: usize) {
@@ -974,7 +1354,6 @@ impl VulkanDevice {
             present_complete_semaphores,
             rendering_complete_semaphores,
             in_flight_fences,
-            in_flight_cmd_buffers,
         } = self.build_swapchain_contents(
             vk_surface,
             surface_format,
@@ -997,7 +1376,6 @@ impl VulkanDevice {
                 present_complete_semaphores,
                 rendering_complete_semaphores,
                 in_flight_fences,
-                in_flight_cmd_buffers: RefCell::new(in_flight_cmd_buffers),
             }),
         })
     }
@@ -1023,7 +1401,7 @@ impl VulkanDevice {
         let surface = sc.surface;
         let surface_format = sc.surface_format;
 
-        // Tear down old image views, depth buffer, in-flight command buffers, and sync objects.
+        // Tear down old image views, depth buffer, and sync objects.
         unsafe {
             self.device.free_memory(sc.depth_image_memory, None);
             self.device.destroy_image_view(sc.depth_image_view, None);
@@ -1032,21 +1410,6 @@ impl VulkanDevice {
                 self.device.destroy_image_view(view, None);
             }
         }
-        {
-            let mut cmd_buffers = sc.in_flight_cmd_buffers.borrow_mut();
-            let to_free: Vec<_> = cmd_buffers
-                .iter()
-                .copied()
-                .filter(|c| *c != vk::CommandBuffer::null())
-                .collect();
-            if !to_free.is_empty() {
-                unsafe {
-                    self.device
-                        .free_command_buffers(self.command_pool, &to_free);
-                }
-            }
-            cmd_buffers.clear();
-        }
         unsafe {
             for &sem in &sc.present_complete_semaphores {
                 self.device.destroy_semaphore(sem, None);
@@ -1076,7 +1439,6 @@ impl VulkanDevice {
         sc.present_complete_semaphores = contents.present_complete_semaphores;
         sc.rendering_complete_semaphores = contents.rendering_complete_semaphores;
         sc.in_flight_fences = contents.in_flight_fences;
-        sc.in_flight_cmd_buffers = RefCell::new(contents.in_flight_cmd_buffers);
 
         Ok(())
     }
@@ -1130,13 +1492,17 @@ impl VulkanDevice {
             .unwrap_or(vk::PresentModeKHR::FIFO)
         };
 
+        // TRANSFER_DST lets `blit_texture` write the images directly.
+        let image_usage = vk::ImageUsageFlags::COLOR_ATTACHMENT
+            | (caps.supported_usage_flags & vk::ImageUsageFlags::TRANSFER_DST);
+
         let create_info = vk::SwapchainCreateInfoKHR::default()
             .surface(surface)
             .min_image_count(image_count)
             .image_color_space(surface_format.color_space)
             .image_format(surface_format.format)
             .image_extent(extent)
-            .image_usage(vk::ImageUsageFlags::COLOR_ATTACHMENT)
+            .image_usage(image_usage)
             .image_sharing_mode(vk::SharingMode::EXCLUSIVE)
             .pre_transform(pre_transform)
             .composite_alpha(vk::CompositeAlphaFlagsKHR::OPAQUE)
@@ -1194,93 +1560,147 @@ impl VulkanDevice {
             present_complete_semaphores,
             rendering_complete_semaphores,
             in_flight_fences,
-            in_flight_cmd_buffers: vec![vk::CommandBuffer::null(); MAX_FRAMES_IN_FLIGHT],
         })
     }
 
     // -- Buffer --
 
     pub fn create_buffer(&self, desc: &BufferDesc) -> RhiResult<GpuBuffer> {
-        let usage_flags = vk::BufferUsageFlags::STORAGE_BUFFER
-            | vk::BufferUsageFlags::INDEX_BUFFER
-            | vk::BufferUsageFlags::VERTEX_BUFFER
-            | vk::BufferUsageFlags::INDIRECT_BUFFER
-            | vk::BufferUsageFlags::TRANSFER_DST
-            | vk::BufferUsageFlags::TRANSFER_SRC
-            | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS;
-
-        let buffer_info = vk::BufferCreateInfo::default()
-            .size(desc.size)
-            .usage(usage_flags)
-            .sharing_mode(vk::SharingMode::EXCLUSIVE);
-
-        let buffer = unsafe {
-            self.device
-                .create_buffer(&buffer_info, None)
-                .map_err(|e| RhiError::BufferCreation(e.to_string()))?
-        };
-
-        let mem_requirements = unsafe { self.device.get_buffer_memory_requirements(buffer) };
+        self.create_buffer_aligned(desc, MIN_BUFFER_ALIGNMENT)
+    }
 
-        let mem_flags = match desc.memory {
-            MemoryType::GpuOnly => vk::MemoryPropertyFlags::DEVICE_LOCAL,
-            MemoryType::Default => {
-                vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT
-            }
-            MemoryType::Readback => {
-                vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_CACHED
+    /// Create a buffer whose GPU address is a multiple of `align` (a power of two). Small
+    /// buffers are sub-allocated from shared memory blocks; large and exportable ones get
+    /// their own memory.
+    pub fn create_buffer_aligned(&self, desc: &BufferDesc, align: u64) -> RhiResult<GpuBuffer> {
+        let export = desc
+            .export
+            .map(|handle| self.external_memory(handle))
+            .transpose()?;
+        let sub = if export.is_some() {
+            None
+        } else {
+            self.block_allocator
+                .lock()
+                .expect("block allocator lock poisoned")
+                .allocate(
+                    &self.device,
+                    &self.device_memory_properties,
+                    desc.memory,
+                    desc.size,
+                    align,
+                )?
+        };
+
+        let (vk_buffer, memory_type_index) = match sub {
+            Some(sub) => (
+                VulkanBuffer {
+                    buffer: sub.buffer,
+                    memory: sub.memory,
+                    offset: sub.offset,
+                    sub_allocated: true,
+                    size: desc.size,
+                    mapped_ptr: sub.mapped_ptr,
+                    gpu_address: sub.gpu_address,
+                    export: None,
+                },
+                sub.memory_type_index,
+            ),
+            None => {
+                let raw = allocate_raw_buffer(
+                    &self.device,
+                    &self.device_memory_properties,
+                    desc.memory,
+                    desc.size,
+                    export.as_ref().map(|(_, handle_type)| *handle_type),
+                )?;
+                (
+                    VulkanBuffer {
+                        buffer: raw.buffer,
+                        memory: raw.memory,
+                        offset: 0,
+                        sub_allocated: false,
+                        size: desc.size,
+                        mapped_ptr: raw.mapped_ptr,
+                        gpu_address: raw.gpu_address,
+                        export,
+                    },
+                    raw.memory_type_index,
+                )
             }
         };
-
-        let mem_type_index =
-            find_memorytype_index(&mem_requirements, &self.device_memory_properties, mem_flags)
-                .ok_or_else(|| RhiError::AllocationFailed("No suitable memory type".into()))?;
-
-        let mut alloc_flags_info =
-            vk::MemoryAllocateFlagsInfo::default().flags(vk::MemoryAllocateFlags::DEVICE_ADDRESS);
-
-        let alloc_info = vk::MemoryAllocateInfo::default()
-            .allocation_size(mem_requirements.size)
-            .memory_type_index(mem_type_index)
-            .push_next(&mut alloc_flags_info);
-
-        let memory = unsafe {
-            self.device
-                .allocate_memory(&alloc_info, None)
-                .map_err(|e| RhiError::AllocationFailed(e.to_string()))?
-        };
-
-        unsafe {
-            self.device
-                .bind_buffer_memory(buffer, memory, 0)
-                .map_err(|e| RhiError::BufferCreation(e.to_string()))?;
+        // Sub-allocated buffers share their block's VkBuffer, so only dedicated ones are named.
+        if !vk_buffer.sub_allocated {
+            self.name_object(vk_buffer.buffer, desc.label.as_deref());
+            self.name_object(vk_buffer.memory, desc.label.as_deref());
         }
 
-        // Get GPU address
-        let addr_info = vk::BufferDeviceAddressInfo::default().buffer(buffer);
-        let gpu_addr = unsafe { self.device.get_buffer_device_address(&addr_info) };
-
-        // Map if host-visible
-        let mapped_ptr = match desc.memory {
-            MemoryType::Default | MemoryType::Readback => {
-                let ptr = unsafe {
-                    self.device
-                        .map_memory(memory, 0, desc.size, vk::MemoryMapFlags::empty())
-                        .map_err(|e| RhiError::AllocationFailed(e.to_string()))?
-                };
-                Some(ptr as *mut u8)
-            }
-            MemoryType::GpuOnly => None,
-        };
+        Ok(self.register_buffer(vk_buffer, memory_type_index, desc.memory))
+    }
 
-        let vk_buffer = VulkanBuffer {
-            buffer,
+    #[cfg(unix)]
+    pub fn import_memory_fd(
+        &self,
+        fd: std::os::fd::OwnedFd,
+        handle: ExternalMemoryHandle,
+        size: u64,
+        memory: MemoryType,
+    ) -> RhiResult<GpuBuffer> {
+        let (loader, handle_type) = self.external_memory(handle)?;
+        let raw = import_raw_buffer(
+            &self.device,
+            &self.device_memory_properties,
+            &loader,
+            fd,
+            handle_type,
             memory,
-            size: desc.size,
-            mapped_ptr,
-            gpu_address: GpuAddress(gpu_addr),
+            size,
+        )?;
+        let vk_buffer = VulkanBuffer {
+            buffer: raw.buffer,
+            memory: raw.memory,
+            offset: 0,
+            sub_allocated: false,
+            size,
+            mapped_ptr: raw.mapped_ptr,
+            gpu_address: raw.gpu_address,
+            export: None,
+        };
+        Ok(self.register_buffer(vk_buffer, raw.memory_type_index, memory))
+    }
+
+    /// The fd loader and Vulkan handle type for `handle`, if the device supports it.
+    fn external_memory(
+        &self,
+        handle: ExternalMemoryHandle,
+    ) -> RhiResult<(
+        ash::khr::external_memory_fd::Device,
+        vk::ExternalMemoryHandleTypeFlags,
+    )> {
+        let loader = self.external_memory_fd.clone().ok_or_else(|| {
+            RhiError::Unsupported("VK_KHR_external_memory_fd is not supported".into())
+        })?;
+        let handle_type = match handle {
+            ExternalMemoryHandle::OpaqueFd => vk::ExternalMemoryHandleTypeFlags::OPAQUE_FD,
+            ExternalMemoryHandle::DmaBuf if self.dma_buf_supported => {
+                vk::ExternalMemoryHandleTypeFlags::DMA_BUF_EXT
+            }
+            ExternalMemoryHandle::DmaBuf => {
+                return Err(RhiError::Unsupported(
+                    "VK_EXT_external_memory_dma_buf is not supported".into(),
+                ));
+            }
         };
+        Ok((loader, handle_type))
+    }
 
+    /// Record a new buffer in the allocation registry and mapped-range index.
+    fn register_buffer(
+        &self,
+        vk_buffer: VulkanBuffer,
+        memory_type_index: u32,
+        memory_type: MemoryType,
+    ) -> GpuBuffer {
         {
             let mut allocations = self.allocations.lock().expect("allocations lock poisoned");
             allocations.insert(
@@ -1290,15 +1710,84 @@ impl VulkanDevice {
                     size: vk_buffer.size,
                     buffer: vk_buffer.buffer,
                     memory: vk_buffer.memory,
-                    memory_type_index: mem_type_index,
+                    offset: vk_buffer.offset,
+                    memory_type_index,
+                    memory_type,
                     mapped_ptr: vk_buffer.mapped_ptr,
                 },
             );
         }
+        if let Some(mapped) = vk_buffer.mapped_ptr {
+            self.mapped_ranges
+                .lock()
+                .expect("mapped ranges lock poisoned")
+                .insert(mapped as usize, (vk_buffer.size, vk_buffer.gpu_address));
+        }
 
-        Ok(GpuBuffer {
+        GpuBuffer {
             inner: GpuBufferInner::Vulkan(vk_buffer),
-        })
+        }
+    }
+
+    pub fn memory_stats(&self) -> MemoryStats {
+        let mut stats = MemoryStats::default();
+        {
+            let allocations = self.allocations.lock().expect("allocations lock poisoned");
+            for alloc in allocations.values() {
+                stats
+                    .of_mut(alloc.memory_type)
+                    .record_allocation(alloc.size);
+            }
+        }
+        {
+            let blocks = self
+                .block_allocator
+                .lock()
+                .expect("block allocator lock poisoned");
+            for memory in [
+                MemoryType::Default,
+                MemoryType::GpuOnly,
+                MemoryType::Readback,
+            ] {
+                let (count, bytes) = blocks.block_usage(memory);
+                let entry = stats.of_mut(memory);
+                entry.block_count = count;
+                entry.block_bytes = bytes;
+            }
+        }
+
+        let mut budget = vk::PhysicalDeviceMemoryBudgetPropertiesEXT::default();
+        let memory_properties = {
+            let mut props2 = vk::PhysicalDeviceMemoryProperties2::default();
+            if self.memory_budget_supported {
+                props2 = props2.push_next(&mut budget);
+            }
+            unsafe {
+                self.instance
+                    .get_physical_device_memory_properties2(self.physical_device, &mut props2)
+            };
+            props2.memory_properties
+        };
+        stats.heaps = memory_properties.memory_heaps
+            [..memory_properties.memory_heap_count as usize]
+            .iter()
+            .enumerate()
+            .map(|(i, heap)| MemoryHeapBudget {
+                size: heap.size,
+                budget: if self.memory_budget_supported {
+                    budget.heap_budget[i]
+                } else {
+                    heap.size
+                },
+                usage: if self.memory_budget_supported {
+                    budget.heap_usage[i]
+                } else {
+                    0
+                },
+                device_local: heap.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL),
+            })
+            .collect();
+        stats
     }
 
     pub fn host_to_device_pointer(&self, cpu_ptr: *const u8) -> Option<GpuAddress> {
@@ -1306,18 +1795,29 @@ impl VulkanDevice {
             return None;
         }
         let ptr = cpu_ptr as usize;
+        let ranges = self
+            .mapped_ranges
+            .lock()
+            .expect("mapped ranges lock poisoned");
+        let (&base, &(size, gpu)) = ranges.range(..=ptr).next_back()?;
+        let offset = (ptr - base) as u64;
+        (offset < size).then(|| GpuAddress(gpu.0 + offset))
+    }
+
+    pub fn device_to_host_pointer(&self, addr: GpuAddress) -> Option<*mut u8> {
+        if addr.is_null() {
+            return None;
+        }
         let allocations = self.allocations.lock().expect("allocations lock poisoned");
-        for alloc in allocations.values() {
-            if let Some(mapped) = alloc.mapped_ptr {
-                let base = mapped as usize;
-                let end = base + alloc.size as usize;
-                if ptr >= base && ptr < end {
-                    let offset = (ptr - base) as u64;
-                    return Some(GpuAddress(alloc.base.0 + offset));
-                }
-            }
+        let (&base, alloc) = allocations.range(..=addr.0).next_back()?;
+        let offset = addr.0 - base;
+        if offset >= alloc.size {
+            return None;
         }
-        None
+        // SAFETY: `offset` is within the mapped range of the allocation.
+        alloc
+            .mapped_ptr
+            .map(|mapped| unsafe { mapped.add(offset as usize) })
     }
 
     // -- Texture --
@@ -1335,6 +1835,29 @@ impl VulkanDevice {
         })
     }
 
+    /// The filter for blitting one mip level of `format` into the next: linear where the
+    /// format supports it, nearest otherwise (integer and some 32-bit float formats).
+    fn mip_blit_filter(&self, format: Format) -> Option<vk::Filter> {
+        if is_depth_format(format) || format_block(format).is_some_and(|b| b.is_compressed()) {
+            return None;
+        }
+        let properties = unsafe {
+            self.instance
+                .get_physical_device_format_properties(self.physical_device, format_to_vk(format))
+        };
+        let features = properties.optimal_tiling_features;
+        if !features.contains(vk::FormatFeatureFlags::BLIT_SRC | vk::FormatFeatureFlags::BLIT_DST) {
+            return None;
+        }
+        Some(
+            if features.contains(vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR) {
+                vk::Filter::LINEAR
+            } else {
+                vk::Filter::NEAREST
+            },
+        )
+    }
+
     /// Build the `vk::ImageCreateInfo` for `desc` and create the image.
     /// Returns the image plus the effective `array_layers` (cube → ×6) and the format.
     fn create_image_for_desc(&self, desc: &TextureDesc) -> RhiResult<(vk::Image, u32, vk::Format)> {
@@ -1392,10 +1915,14 @@ impl VulkanDevice {
             TextureDimension::Cube | TextureDimension::CubeArray => {
                 vk::ImageCreateFlags::CUBE_COMPATIBLE
             }
+            // Lets a single depth slice be bound as a 2D attachment view.
+            TextureDimension::D3 if usage.contains(vk::ImageUsageFlags::COLOR_ATTACHMENT) => {
+                vk::ImageCreateFlags::TYPE_2D_ARRAY_COMPATIBLE
+            }
             _ => vk::ImageCreateFlags::empty(),
         };
 
-        let image_info = vk::ImageCreateInfo::default()
+        let mut image_info = vk::ImageCreateInfo::default()
             .flags(image_flags)
             .image_type(image_type)
             .format(vk_format)
@@ -1411,6 +1938,12 @@ impl VulkanDevice {
             .usage(usage)
             .sharing_mode(vk::SharingMode::EXCLUSIVE)
             .initial_layout(vk::ImageLayout::UNDEFINED);
+        let mut external_info = vk::ExternalMemoryImageCreateInfo::default();
+        if let Some(handle) = desc.export {
+            let (_, handle_type) = self.external_memory(handle)?;
+            external_info = external_info.handle_types(handle_type);
+            image_info = image_info.push_next(&mut external_info);
+        }
 
         let image = unsafe {
             self.device
@@ -1450,9 +1983,10 @@ impl VulkanDevice {
                     ))
                 })?;
             let offset = texture_gpu.0 - alloc.base.0;
-            if !offset.is_multiple_of(mem_reqs.alignment) {
+            let memory_offset = alloc.offset + offset;
+            if !memory_offset.is_multiple_of(mem_reqs.alignment) {
                 return Err(RhiError::TextureCreation(format!(
-                    "texture allocation address 0x{:x} has memory offset {offset}, expected alignment {}",
+                    "texture allocation address 0x{:x} has memory offset {memory_offset}, expected alignment {}",
                     texture_gpu.0, mem_reqs.alignment
                 )));
             }
@@ -1469,7 +2003,7 @@ impl VulkanDevice {
                     "texture allocation memory type is not compatible with this image".into(),
                 ));
             }
-            Ok((alloc.memory, offset))
+            Ok((alloc.memory, memory_offset))
         };
         let (memory, memory_offset) = match resolve() {
             Ok(v) => v,
@@ -1519,14 +2053,20 @@ impl VulkanDevice {
                 .map_err(|e| RhiError::TextureCreation(e.to_string()))?
         };
 
-        // Allocate texture ID from RefCell
-        let texture_id = {
-            let mut id = self.next_texture_id.borrow_mut();
-            let tid = TextureId(*id);
-            *id += 1;
-            tid
+        let texture_id = match self.alloc_texture_id() {
+            Ok(id) => id,
+            Err(e) => {
+                unsafe {
+                    self.device.destroy_image_view(image_view, None);
+                    self.device.destroy_image(image, None);
+                }
+                return Err(e);
+            }
         };
 
+        self.name_object(image, desc.label.as_deref());
+        self.name_object(image_view, desc.label.as_deref());
+
         // Transition to unified GENERAL layout before first use.
         self.transition_image_to_general(image, aspect, desc.mip_levels, array_layers)?;
 
@@ -1542,6 +2082,16 @@ impl VulkanDevice {
             image,
             image_view,
             is_view: false,
+            mip_filter: self.mip_blit_filter(desc.format),
+            format: vk_format,
+            aspect,
+            attachment_views: HashMap::new(),
+            mip_levels: desc.mip_levels,
+            layers: match desc.dimension {
+                TextureDimension::D3 => desc.depth,
+                _ => array_layers,
+            },
+            depth_slices: desc.dimension == TextureDimension::D3,
         };
 
         {
@@ -1606,21 +2156,134 @@ impl VulkanDevice {
                 .create_sampler(&sampler_info, None)
                 .map_err(|e| RhiError::Backend(format!("Sampler creation: {e}")))?
         };
-
-        let id = {
-            let mut sid = self.next_sampler_id.borrow_mut();
-            let id = SamplerId(*sid);
-            *sid += 1;
-            id
+        self.name_object(sampler, desc.label.as_deref());
+
+        let completed = self.queue.completed_serial();
+        self.destroy_parked(completed);
+        let Some(slot) = self
+            .sampler_slots
+            .lock()
+            .expect("sampler slots lock poisoned")
+            .alloc(completed)
+        else {
+            unsafe { self.device.destroy_sampler(sampler, None) };
+            return Err(RhiError::Backend(format!(
+                "Bindless sampler heap full ({MAX_BINDLESS_SAMPLERS} samplers)"
+            )));
         };
+        let id = SamplerId(slot);
 
-        self.write_sampler_descriptor(id, sampler)?;
+        if let Err(e) = self.write_sampler_descriptor(id, sampler) {
+            unsafe { self.device.destroy_sampler(sampler, None) };
+            self.sampler_slots
+                .lock()
+                .expect("sampler slots lock poisoned")
+                .free(slot, self.queue.submitted_serial());
+            return Err(e);
+        }
 
-        self.samplers.borrow_mut().push(sampler);
+        let mut samplers = self.samplers.lock().expect("samplers lock poisoned");
+        if samplers.len() <= slot as usize {
+            samplers.resize(slot as usize + 1, vk::Sampler::null());
+        }
+        samplers[slot as usize] = sampler;
 
         Ok(Sampler { id })
     }
 
+    /// Destroy a sampler once the latest submission retires; its `SamplerId` is reused then.
+    pub fn destroy_sampler(&self, sampler: Sampler) {
+        let slot = sampler.id.0;
+        let mut samplers = self.samplers.lock().expect("samplers lock poisoned");
+        if let Some(vk_sampler) = samplers.get_mut(slot as usize)
+            && *vk_sampler != vk::Sampler::null()
+        {
+            let serial = self.queue.submitted_serial();
+            self.park(serial, Parked::Sampler(std::mem::take(vk_sampler)));
+            self.sampler_slots
+                .lock()
+                .expect("sampler slots lock poisoned")
+                .free(slot, serial);
+        }
+    }
+
+    /// Keep `object` alive until the submission with `serial` retires.
+    fn park(&self, serial: u64, object: Parked) {
+        self.parked
+            .lock()
+            .expect("parked objects lock poisoned")
+            .push_back((serial, object));
+    }
+
+    /// Destroy the parked objects whose submission has completed.
+    fn destroy_parked(&self, completed: u64) {
+        let retired: Vec<Parked> = {
+            let mut parked = self.parked.lock().expect("parked objects lock poisoned");
+            let count = parked
+                .iter()
+                .take_while(|(serial, _)| *serial <= completed)
+                .count();
+            parked.drain(..count).map(|(_, object)| object).collect()
+        };
+        for object in retired {
+            self.destroy_parked_object(object);
+        }
+    }
+
+    fn destroy_parked_object(&self, object: Parked) {
+        unsafe {
+            match object {
+                Parked::Texture(texture) => {
+                    self.device.destroy_image_view(texture.image_view, None);
+                    for view in texture.attachment_views.into_values() {
+                        self.device.destroy_image_view(view, None);
+                    }
+                    // View-only entries don't own the underlying image.
+                    if !texture.is_view {
+                        self.device.destroy_image(texture.image, None);
+                    }
+                }
+                Parked::Sampler(sampler) => self.device.destroy_sampler(sampler, None),
+            }
+        }
+    }
+
+    pub fn descriptor_heap_stats(&self) -> DescriptorHeapStats {
+        DescriptorHeapStats {
+            textures: self
+                .texture_slots
+                .lock()
+                .expect("texture slots lock poisoned")
+                .occupancy(),
+            samplers: self
+                .sampler_slots
+                .lock()
+                .expect("sampler slots lock poisoned")
+                .occupancy(),
+        }
+    }
+
+    /// A free `TextureId` slot, reusing ones whose last reader has retired.
+    fn alloc_texture_id(&self) -> RhiResult<TextureId> {
+        let completed = self.queue.completed_serial();
+        self.destroy_parked(completed);
+        let slot = self
+            .texture_slots
+            .lock()
+            .expect("texture slots lock poisoned")
+            .alloc(completed);
+        slot.map(TextureId).ok_or_else(|| {
+            RhiError::TextureCreation(format!(
+                "Bindless texture heap full ({} slots)",
+                self.texture_slots
+                    .lock()
+                    .expect("texture slots lock poisoned")
+                    .occupancy()
+                    .capacity
+            ))
+        })
+    }
+
     // -- Shader --
 
     pub fn create_shader_module(&self, desc: &ShaderModuleDesc) -> RhiResult<ShaderModule> {
@@ -1653,12 +2316,24 @@ impl VulkanDevice {
 
     // -- Pipeline --
 
+    fn check_view_mask(&self, view_mask: u32) -> RhiResult<()> {
+        let views = u32::BITS - view_mask.leading_zeros();
+        if views > self.max_multiview_views {
+            return Err(RhiError::Unsupported(format!(
+                "view_mask {view_mask:#b} needs {views} views; the device renders at most {}",
+                self.max_multiview_views
+            )));
+        }
+        Ok(())
+    }
+
     pub fn create_graphics_pso(
         &self,
         desc: &GraphicsPsoDesc,
         vert_module: &VulkanShaderModule,
         frag_module: &VulkanShaderModule,
     ) -> RhiResult<GraphicsPso> {
+        self.check_view_mask(desc.view_mask)?;
         let pso_desc = VulkanGraphicsPsoDesc {
             vert_module: vert_module.module,
             frag_module: frag_module.module,
@@ -1674,6 +2349,8 @@ impl VulkanDevice {
                 .stencil_format
                 .map(format_to_vk)
                 .unwrap_or(vk::Format::UNDEFINED),
+            view_mask: desc.view_mask,
+            label: desc.label.clone(),
         };
 
         // Push constants for root data
@@ -1698,8 +2375,9 @@ impl VulkanDevice {
             pipeline_layout,
             root_constant_size: desc.root_constant_size,
             device: self.device.clone(),
+            debug_utils: self.debug_utils.clone(),
             desc: pso_desc,
-            blend_pipelines: RefCell::new(std::collections::HashMap::new()),
+            blend_pipelines: Mutex::new(std::collections::HashMap::new()),
         };
         // Pre-bake the embedded blend state if provided, otherwise bake the default. Unlike the
         // draw-time `pipeline_for_blend`, creation has a `Result` channel, so propagate failures.
@@ -1708,7 +2386,8 @@ impl VulkanDevice {
         vk_pso.pipeline = pipeline;
         vk_pso
             .blend_pipelines
-            .borrow_mut()
+            .get_mut()
+            .expect("blend pipelines lock poisoned")
             .insert(initial_blend, pipeline);
 
         Ok(GraphicsPso {
@@ -1751,6 +2430,7 @@ impl VulkanDevice {
                 .create_compute_pipelines(vk::PipelineCache::null(), &[pipeline_info], None)
                 .map_err(|e| RhiError::PipelineCreation(format!("{e:?}")))?
         };
+        self.name_object(pipelines[0], desc.label.as_deref());
 
         Ok(ComputePso {
             inner: ComputePsoInner::Vulkan(VulkanComputePso {
@@ -1777,6 +2457,13 @@ impl VulkanDevice {
             ));
         }
 
+        if desc.view_mask != 0 && !self.mesh_multiview_supported {
+            return Err(RhiError::Unsupported(
+                "multiview mesh pipelines need multiviewMeshShader".into(),
+            ));
+        }
+        self.check_view_mask(desc.view_mask)?;
+
         let pso_desc = VulkanMeshletPsoDesc {
             mesh_module: mesh_module.module,
             frag_module: frag_module.module,
@@ -1791,6 +2478,8 @@ impl VulkanDevice {
             sample_count: desc.sample_count,
             alpha_to_coverage: desc.alpha_to_coverage,
             cull: desc.cull,
+            view_mask: desc.view_mask,
+            label: desc.label.clone(),
         };
 
         let push_constant_range = vk::PushConstantRange::default()
@@ -1814,15 +2503,17 @@ impl VulkanDevice {
             pipeline_layout,
             root_constant_size: desc.root_constant_size,
             device: self.device.clone(),
+            debug_utils: self.debug_utils.clone(),
             desc: pso_desc,
-            blend_pipelines: RefCell::new(std::collections::HashMap::new()),
+            blend_pipelines: Mutex::new(std::collections::HashMap::new()),
         };
         let initial_blend = desc.blendstate.as_ref().cloned().unwrap_or_default();
         let pipeline = vk_pso.create_pipeline(&initial_blend)?;
         vk_pso.pipeline = pipeline;
         vk_pso
             .blend_pipelines
-            .borrow_mut()
+            .get_mut()
+            .expect("blend pipelines lock poisoned")
             .insert(initial_blend, pipeline);
 
         Ok(MeshletPso {
@@ -2031,12 +2722,7 @@ impl VulkanDevice {
             )
         };
 
-        let id = {
-            let mut next = self.accel_counter.borrow_mut();
-            let id = *next;
-            *next += 1;
-            AccelerationStructureId(id)
-        };
+        let id = AccelerationStructureId(self.accel_counter.fetch_add(1, Ordering::Relaxed));
 
         Ok(AccelerationStructure {
             id,
@@ -2094,17 +2780,48 @@ impl VulkanDevice {
     // -- Command Buffer --
 
     pub fn create_command_buffer(&self) -> RhiResult<CommandBuffer> {
-        let alloc_info = vk::CommandBufferAllocateInfo::default()
-            .command_buffer_count(1)
-            .command_pool(self.command_pool)
-            .level(vk::CommandBufferLevel::PRIMARY);
+        let cmd = self.begin_primary_command_buffer()?;
+        Ok(CommandBuffer {
+            inner: CommandBufferInner::Vulkan(Box::new(cmd)),
+            submit_slots: Vec::new(),
+            profile: None,
+            debug_groups: 0,
+            pass_view_mask: 0,
+        })
+    }
 
-        let cmd = unsafe {
-            self.device
-                .allocate_command_buffers(&alloc_info)
-                .map_err(|e| RhiError::CommandBuffer(e.to_string()))?
-        }[0];
+    /// Create a command buffer pre-configured with swapchain image views for rendering.
+    pub fn create_command_buffer_for_swapchain(
+        &self,
+        swapchain: &Swapchain,
+    ) -> RhiResult<CommandBuffer> {
+        let sc = match &swapchain.inner {
+            SwapchainInner::Vulkan(s) => s,
+            #[allow(unreachable_patterns)]
+            _ => unreachable!(),
+        };
+
+        let cmd = self.begin_primary_command_buffer()?;
+        Ok(CommandBuffer {
+            inner: CommandBufferInner::Vulkan(Box::new(VulkanCommandBuffer {
+                swapchain_image_views: sc.image_views.clone(),
+                swapchain_images: sc.images.clone(),
+                swapchain_extent: sc.extent,
+                depth_image_view: sc.depth_image_view,
+                ..cmd
+            })),
+            submit_slots: Vec::new(),
+            profile: None,
+            debug_groups: 0,
+            pass_view_mask: 0,
+        })
+    }
 
+    /// Begin recording a one-time-submit primary command buffer in a transient pool of its
+    /// own, leased until the submission executing it retires.
+    fn begin_primary_command_buffer(&self) -> RhiResult<VulkanCommandBuffer> {
+        let lease = self.command_pools.lease(self.queue.completed_serial())?;
+        let cmd = lease.command_buffer();
         let begin_info = vk::CommandBufferBeginInfo::default()
             .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
 
@@ -2113,7 +2830,14 @@ impl VulkanDevice {
                 .begin_command_buffer(cmd, &begin_info)
                 .map_err(|e| RhiError::CommandBuffer(e.to_string()))?;
         }
+        Ok(VulkanCommandBuffer {
+            pool: Some(lease),
+            ..self.command_buffer_state(cmd)
+        })
+    }
 
+    /// Recording state for `cmd`, with no swapchain attached.
+    fn command_buffer_state(&self, cmd: vk::CommandBuffer) -> VulkanCommandBuffer {
         let heap = self
             .descriptor_buffer_heap
             .as_ref()
@@ -2134,107 +2858,131 @@ impl VulkanDevice {
         };
         let accel_loader_cmd = self.acceleration_structure.clone();
 
-        Ok(CommandBuffer {
-            inner: CommandBufferInner::Vulkan(Box::new(VulkanCommandBuffer {
-                command_buffer: cmd,
-                device: self.device.clone(),
-                swapchain_image_views: Vec::new(),
-                swapchain_images: Vec::new(),
-                depth_image_view: vk::ImageView::null(),
-                pipeline_layout: vk::PipelineLayout::null(),
-                descriptor_buffer_loader,
-                descriptor_buffer_binding,
-                active_descriptor_buffer_offset: 0,
-                root_constant_size: (std::mem::size_of::<GpuAddress>() * 4) as u32,
-                push_constant_stages: vk::ShaderStageFlags::empty(),
-                current_blend_state: BlendState::default(),
-                pending_split_barrier: None,
-                pending_value_waits: Vec::new(),
-                pending_value_signals: Vec::new(),
-                allocations: self.allocations.clone(),
-                textures: self.textures.clone(),
-                mesh_shader,
-                acceleration_structure: accel_loader_cmd,
-                max_draw_indirect_count: self.max_draw_indirect_count,
-            })),
-        })
+        VulkanCommandBuffer {
+            command_buffer: cmd,
+            pool: None,
+            device: self.device.clone(),
+            swapchain_image_views: Vec::new(),
+            swapchain_images: Vec::new(),
+            swapchain_extent: vk::Extent2D::default(),
+            depth_image_view: vk::ImageView::null(),
+            pipeline_layout: vk::PipelineLayout::null(),
+            descriptor_buffer_loader,
+            descriptor_buffer_binding,
+            active_descriptor_buffer_offset: 0,
+            root_constant_size: (std::mem::size_of::<GpuAddress>() * 4) as u32,
+            push_constant_stages: vk::ShaderStageFlags::empty(),
+            current_blend_state: BlendState::default(),
+            pending_split_barrier: None,
+            pending_value_waits: Vec::new(),
+            pending_value_signals: Vec::new(),
+            allocations: self.allocations.clone(),
+            textures: self.textures.clone(),
+            mesh_shader,
+            acceleration_structure: accel_loader_cmd,
+            debug_utils: self.debug_utils.clone(),
+            max_draw_indirect_count: self.max_draw_indirect_count,
+            depth_resolve_modes: self.depth_resolve_modes,
+        }
     }
 
-    /// Create a command buffer pre-configured with swapchain image views for rendering.
-    pub fn create_command_buffer_for_swapchain(
+    // -- Bundles --
+
+    /// A render bundle is a secondary command buffer continuing a dynamic-rendering pass
+    /// with the attachment formats of `desc`.
+    pub(crate) fn create_render_bundle_encoder(
         &self,
-        swapchain: &Swapchain,
-    ) -> RhiResult<CommandBuffer> {
-        let sc = match &swapchain.inner {
-            SwapchainInner::Vulkan(s) => s,
-            #[allow(unreachable_patterns)]
-            _ => unreachable!(),
+        desc: &RenderBundleDesc,
+    ) -> RhiResult<BundleEncoderInner> {
+        let color_formats: Vec<vk::Format> = desc
+            .color_formats
+            .iter()
+            .map(|&f| format_to_vk(f))
+            .collect();
+        let samples = match desc.sample_count {
+            SampleCount::S1 => vk::SampleCountFlags::TYPE_1,
+            SampleCount::S2 => vk::SampleCountFlags::TYPE_2,
+            SampleCount::S4 => vk::SampleCountFlags::TYPE_4,
+            SampleCount::S8 => vk::SampleCountFlags::TYPE_8,
+            SampleCount::S16 => vk::SampleCountFlags::TYPE_16,
         };
+        let mut rendering_info = vk::CommandBufferInheritanceRenderingInfo::default()
+            .color_attachment_formats(&color_formats)
+            .depth_attachment_format(
+                desc.depth_format
+                    .map_or(vk::Format::UNDEFINED, format_to_vk),
+            )
+            .stencil_attachment_format(
+                desc.stencil_format
+                    .map_or(vk::Format::UNDEFINED, format_to_vk),
+            )
+            .rasterization_samples(samples)
+            .view_mask(desc.view_mask);
+        let inheritance =
+            vk::CommandBufferInheritanceInfo::default().push_next(&mut rendering_info);
+        let mut encoder = self.begin_bundle(
+            &inheritance,
+            vk::CommandBufferUsageFlags::RENDER_PASS_CONTINUE,
+            desc.label.as_deref(),
+        )?;
+        // Dynamic state is not inherited from the executing command buffer.
+        encoder.set_depth_stencil_state(&DepthStencilState::default());
+        Ok(BundleEncoderInner::Vulkan(Box::new(encoder)))
+    }
 
+    pub(crate) fn create_compute_bundle_encoder(
+        &self,
+        desc: &ComputeBundleDesc,
+    ) -> RhiResult<BundleEncoderInner> {
+        let inheritance = vk::CommandBufferInheritanceInfo::default();
+        let encoder = self.begin_bundle(
+            &inheritance,
+            vk::CommandBufferUsageFlags::empty(),
+            desc.label.as_deref(),
+        )?;
+        Ok(BundleEncoderInner::Vulkan(Box::new(encoder)))
+    }
+
+    /// Begin a secondary command buffer in a pool of its own, so the bundle can be recorded
+    /// on any thread and executed by many command buffers at once.
+    fn begin_bundle(
+        &self,
+        inheritance: &vk::CommandBufferInheritanceInfo,
+        flags: vk::CommandBufferUsageFlags,
+        label: Option<&str>,
+    ) -> RhiResult<VulkanBundleEncoder> {
+        let pool_info =
+            vk::CommandPoolCreateInfo::default().queue_family_index(self.queue_family_index);
+        let pool = unsafe {
+            self.device
+                .create_command_pool(&pool_info, None)
+                .map_err(|e| RhiError::CommandBuffer(e.to_string()))?
+        };
+        // The encoder owns the pool from here, destroying it if recording fails.
+        let mut encoder = VulkanBundleEncoder {
+            cmd: self.command_buffer_state(vk::CommandBuffer::null()),
+            pool,
+        };
         let alloc_info = vk::CommandBufferAllocateInfo::default()
             .command_buffer_count(1)
-            .command_pool(self.command_pool)
-            .level(vk::CommandBufferLevel::PRIMARY);
-
-        let cmd = unsafe {
+            .command_pool(pool)
+            .level(vk::CommandBufferLevel::SECONDARY);
+        encoder.cmd.command_buffer = unsafe {
             self.device
                 .allocate_command_buffers(&alloc_info)
                 .map_err(|e| RhiError::CommandBuffer(e.to_string()))?
         }[0];
+        self.name_object(encoder.cmd.command_buffer, label);
 
         let begin_info = vk::CommandBufferBeginInfo::default()
-            .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
-
+            .flags(flags | vk::CommandBufferUsageFlags::SIMULTANEOUS_USE)
+            .inheritance_info(inheritance);
         unsafe {
             self.device
-                .begin_command_buffer(cmd, &begin_info)
+                .begin_command_buffer(encoder.cmd.command_buffer, &begin_info)
                 .map_err(|e| RhiError::CommandBuffer(e.to_string()))?;
         }
-
-        let heap = self
-            .descriptor_buffer_heap
-            .as_ref()
-            .expect("descriptor buffer heap missing");
-        let descriptor_buffer_binding = vk::DescriptorBufferBindingInfoEXT::default()
-            .address(heap.gpu_address.0)
-            .usage(
-                vk::BufferUsageFlags::RESOURCE_DESCRIPTOR_BUFFER_EXT
-                    | vk::BufferUsageFlags::SAMPLER_DESCRIPTOR_BUFFER_EXT,
-            );
-        let descriptor_buffer_loader =
-            Some(descriptor_buffer::Device::new(&self.instance, &self.device));
-        let descriptor_buffer_binding = Some(descriptor_buffer_binding);
-        let mesh_shader = if self.mesh_shader_supported {
-            Some(vk_mesh_shader::Device::new(&self.instance, &self.device))
-        } else {
-            None
-        };
-        let accel_loader_cmd = self.acceleration_structure.clone();
-
-        Ok(CommandBuffer {
-            inner: CommandBufferInner::Vulkan(Box::new(VulkanCommandBuffer {
-                command_buffer: cmd,
-                device: self.device.clone(),
-                swapchain_image_views: sc.image_views.clone(),
-                swapchain_images: sc.images.clone(),
-                depth_image_view: sc.depth_image_view,
-                pipeline_layout: vk::PipelineLayout::null(),
-                descriptor_buffer_loader,
-                descriptor_buffer_binding,
-                active_descriptor_buffer_offset: 0,
-                root_constant_size: (std::mem::size_of::<GpuAddress>() * 4) as u32,
-                push_constant_stages: vk::ShaderStageFlags::empty(),
-                current_blend_state: BlendState::default(),
-                pending_split_barrier: None,
-                pending_value_waits: Vec::new(),
-                pending_value_signals: Vec::new(),
-                allocations: self.allocations.clone(),
-                textures: self.textures.clone(),
-                mesh_shader,
-                acceleration_structure: accel_loader_cmd,
-                max_draw_indirect_count: self.max_draw_indirect_count,
-            })),
-        })
+        Ok(encoder)
     }
 
     // -- Timeline Semaphore --
@@ -2260,6 +3008,68 @@ impl VulkanDevice {
         })
     }
 
+    // -- Queries --
+
+    pub fn create_query_pool(&self, desc: &QueryPoolDesc) -> RhiResult<QueryPool> {
+        if !self.host_query_reset {
+            return Err(RhiError::Unsupported(
+                "the Vulkan device cannot reset queries from the host".into(),
+            ));
+        }
+        let query_type = match desc.ty {
+            QueryType::Timestamp => {
+                if self.timestamp_valid_bits == 0 {
+                    return Err(RhiError::Unsupported(
+                        "the Vulkan queue does not support timestamps".into(),
+                    ));
+                }
+                vk::QueryType::TIMESTAMP
+            }
+            QueryType::Occlusion if !self.occlusion_query_precise => {
+                return Err(RhiError::Unsupported(
+                    "the Vulkan device only has binary occlusion queries".into(),
+                ));
+            }
+            QueryType::Occlusion | QueryType::BinaryOcclusion => vk::QueryType::OCCLUSION,
+            QueryType::PipelineStatistics(_) if !self.pipeline_statistics_query => {
+                return Err(RhiError::Unsupported(
+                    "the Vulkan device has no pipeline-statistics queries".into(),
+                ));
+            }
+            QueryType::PipelineStatistics(_) => vk::QueryType::PIPELINE_STATISTICS,
+        };
+        let mut info = vk::QueryPoolCreateInfo::default()
+            .query_type(query_type)
+            .query_count(desc.count);
+        if let QueryType::PipelineStatistics(stats) = desc.ty {
+            info = info.pipeline_statistics(to_vk_pipeline_statistics(stats));
+        }
+        let pool = unsafe {
+            self.device
+                .create_query_pool(&info, None)
+                .map_err(|e| RhiError::Backend(format!("Failed to create query pool: {e}")))?
+        };
+        self.name_object(pool, desc.label.as_deref());
+        // Queries start out unavailable; reset them so the first use needs no reset.
+        unsafe { self.device.reset_query_pool(pool, 0, desc.count) };
+        Ok(QueryPool {
+            inner: QueryPoolInner::Vulkan(Box::new(VulkanQueryPool {
+                pool,
+                device: self.device.clone(),
+            })),
+            ty: desc.ty,
+            count: desc.count,
+        })
+    }
+
+    pub fn timestamp_period(&self) -> f64 {
+        self.timestamp_period as f64
+    }
+
+    fn name_object(&self, handle: impl vk::Handle, label: Option<&str>) {
+        set_object_name(self.debug_utils.as_ref(), handle, label);
+    }
+
     // -- Destroy --
 
     pub fn destroy_buffer(&self, buffer: GpuBuffer) {
@@ -2270,11 +3080,21 @@ impl VulkanDevice {
                         self.allocations.lock().expect("allocations lock poisoned");
                     allocations.remove(&b.gpu_address.0);
                 }
-                if b.mapped_ptr.is_some() {
-                    self.device.unmap_memory(b.memory);
+                if let Some(mapped) = b.mapped_ptr {
+                    self.mapped_ranges
+                        .lock()
+                        .expect("mapped ranges lock poisoned")
+                        .remove(&(mapped as usize));
+                }
+                let returned = b.sub_allocated
+                    && self
+                        .block_allocator
+                        .lock()
+                        .expect("block allocator lock poisoned")
+                        .free(&self.device, b.memory, b.offset);
+                if !returned {
+                    release_raw_buffer(&self.device, b.buffer, b.memory, b.mapped_ptr.is_some());
                 }
-                self.device.destroy_buffer(b.buffer, None);
-                self.device.free_memory(b.memory, None);
             },
             #[allow(unreachable_patterns)]
             _ => unreachable!(),
@@ -2282,19 +3102,27 @@ impl VulkanDevice {
     }
 
     pub fn destroy_texture(&self, texture: Texture) {
-        let idx = texture.id.0 as usize;
+        self.release_texture_id(texture.id);
+    }
+
+    pub fn destroy_texture_view(&self, id: TextureId) {
+        self.release_texture_id(id);
+    }
+
+    /// Destroy the texture or view in slot `id` and recycle the slot once the latest
+    /// submission retires, which may still read it.
+    fn release_texture_id(&self, id: TextureId) {
+        let idx = id.0 as usize;
         let mut textures = self.textures.lock().expect("textures lock poisoned");
-        if let Some(Some(vk_tex)) = textures.get(idx) {
-            unsafe {
-                self.device.destroy_image_view(vk_tex.image_view, None);
-                // View-only entries don't own the underlying image.
-                if !vk_tex.is_view {
-                    self.device.destroy_image(vk_tex.image, None);
-                }
-            }
-        }
-        if idx < textures.len() {
-            textures[idx] = None;
+        if let Some(slot) = textures.get_mut(idx)
+            && let Some(vk_tex) = slot.take()
+        {
+            let serial = self.queue.submitted_serial();
+            self.park(serial, Parked::Texture(vk_tex));
+            self.texture_slots
+                .lock()
+                .expect("texture slots lock poisoned")
+                .free(id.0, serial);
         }
     }
 
@@ -2385,11 +3213,12 @@ impl VulkanDevice {
                 .map_err(|e| RhiError::TextureCreation(format!("texture_view_descriptor: {e}")))?
         };
 
-        let texture_id = {
-            let mut id = self.next_texture_id.borrow_mut();
-            let tid = TextureId(*id);
-            *id += 1;
-            tid
+        let texture_id = match self.alloc_texture_id() {
+            Ok(id) => id,
+            Err(e) => {
+                unsafe { self.device.destroy_image_view(image_view, None) };
+                return Err(e);
+            }
         };
 
         self.write_image_descriptor(texture_id, image_view, vk::ImageLayout::GENERAL, storage)?;
@@ -2398,6 +3227,13 @@ impl VulkanDevice {
             image: vk::Image::null(),
             image_view,
             is_view: true,
+            mip_filter: None,
+            format: vk_format,
+            aspect: src_aspect,
+            attachment_views: HashMap::new(),
+            mip_levels: level_count,
+            layers: layer_count,
+            depth_slices: false,
         };
 
         {
@@ -2666,29 +3502,26 @@ impl VulkanDevice {
         )
     }
 
-    /// Record a single image barrier on the setup command buffer, submit it, and
-    /// block on the queue. Used for one-shot UNDEFINED → initial-layout transitions
-    /// that happen outside the normal command-buffer flow.
+    /// Record a single image barrier into a leased command buffer, submit it, and block
+    /// until it retires. Used for one-shot UNDEFINED → initial-layout transitions that
+    /// happen outside the normal command-buffer flow.
     fn submit_setup_barrier(
         &self,
         barrier: vk::ImageMemoryBarrier<'_>,
         src_stage: vk::PipelineStageFlags,
         dst_stage: vk::PipelineStageFlags,
     ) -> RhiResult<()> {
+        let queue = self.vulkan_queue();
+        let mut lease = self.command_pools.lease(queue.completed_serial())?;
+        let cmd = lease.command_buffer();
         let begin_info = vk::CommandBufferBeginInfo::default()
             .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
         unsafe {
             self.device
-                .reset_command_buffer(
-                    self.setup_command_buffer,
-                    vk::CommandBufferResetFlags::empty(),
-                )
-                .map_err(|e| RhiError::CommandBuffer(e.to_string()))?;
-            self.device
-                .begin_command_buffer(self.setup_command_buffer, &begin_info)
+                .begin_command_buffer(cmd, &begin_info)
                 .map_err(|e| RhiError::CommandBuffer(e.to_string()))?;
             self.device.cmd_pipeline_barrier(
-                self.setup_command_buffer,
+                cmd,
                 src_stage,
                 dst_stage,
                 vk::DependencyFlags::empty(),
@@ -2697,19 +3530,25 @@ impl VulkanDevice {
                 &[barrier],
             );
             self.device
-                .end_command_buffer(self.setup_command_buffer)
+                .end_command_buffer(cmd)
                 .map_err(|e| RhiError::CommandBuffer(e.to_string()))?;
-            let submit_info = vk::SubmitInfo::default()
-                .command_buffers(std::slice::from_ref(&self.setup_command_buffer));
-            self.device
-                .queue_submit(self.present_queue, &[submit_info], vk::Fence::null())
-                .map_err(|e| RhiError::QueueSubmit(e.to_string()))?;
-            self.device
-                .queue_wait_idle(self.present_queue)
-                .map_err(|e| RhiError::QueueSubmit(e.to_string()))?;
         }
+        let serial = {
+            let vk_queue = queue.queue.lock().expect("queue lock poisoned");
+            queue.submit_timeline(*vk_queue, &[cmd], &[], &[], &[], vk::Fence::null())?
+        };
+        lease.retire_after(serial);
+        queue.wait_for_serial(serial);
         Ok(())
     }
+
+    fn vulkan_queue(&self) -> &VulkanQueue {
+        match &self.queue.inner {
+            QueueInner::Vulkan(q) => q,
+            #[allow(unreachable_patterns)]
+            _ => unreachable!(),
+        }
+    }
 }
 
 impl Drop for VulkanDevice {
@@ -2717,17 +3556,14 @@ impl Drop for VulkanDevice {
         unsafe {
             let _ = self.device.device_wait_idle();
 
-            let q = match &self.queue.inner {
-                QueueInner::Vulkan(q) => q,
-                #[allow(unreachable_patterns)]
-                _ => unreachable!(),
-            };
+            let q = self.vulkan_queue();
             let mut value_sync = q.value_sync.lock().expect("value sync lock poisoned");
             for (_ptr, state) in value_sync.drain() {
                 self.device.destroy_semaphore(state.semaphore, None);
             }
+            self.device.destroy_semaphore(q.submission_timeline, None);
 
-            // Destroy textures
+            // Destroy textures, and the objects of freed slots
             for t in self
                 .textures
                 .lock()
@@ -2735,19 +3571,29 @@ impl Drop for VulkanDevice {
                 .drain(..)
                 .flatten()
             {
-                self.device.destroy_image_view(t.image_view, None);
-                if !t.is_view {
-                    self.device.destroy_image(t.image, None);
-                }
+                self.destroy_parked_object(Parked::Texture(t));
             }
+            self.destroy_parked(u64::MAX);
 
             // Destroy samplers
-            for sampler in self.samplers.borrow_mut().drain(..) {
-                self.device.destroy_sampler(sampler, None);
+            for sampler in self
+                .samplers
+                .lock()
+                .expect("samplers lock poisoned")
+                .drain(..)
+            {
+                if sampler != vk::Sampler::null() {
+                    self.device.destroy_sampler(sampler, None);
+                }
             }
 
             // Shader modules are owned by the frontend `ShaderModule` (RAII) and freed there.
 
+            self.block_allocator
+                .lock()
+                .expect("block allocator lock poisoned")
+                .destroy(&self.device);
+
             if let Some(heap) = self.descriptor_buffer_heap.as_ref() {
                 self.device.unmap_memory(heap.memory);
                 self.device.destroy_buffer(heap.buffer, None);
@@ -2755,10 +3601,13 @@ impl Drop for VulkanDevice {
                 self.device.destroy_descriptor_set_layout(heap.layout, None);
             }
 
+            self.command_pools.destroy();
             self.device.destroy_command_pool(self.command_pool, None);
             self.device.destroy_device(None);
 
-            if let Some(ref debug_loader) = self.debug_utils_loader {
+            if let Some(ref debug_loader) = self.debug_utils_loader
+                && self.debug_callback != vk::DebugUtilsMessengerEXT::null()
+            {
                 debug_loader.destroy_debug_utils_messenger(self.debug_callback, None);
             }
 
@@ -2930,6 +3779,24 @@ fn compare_op_to_vk(op: CompareOp) -> vk::CompareOp {
     }
 }
 
+/// Vulkan writes the counters in flag-bit order, which `PipelineStatistics` follows.
+fn to_vk_pipeline_statistics(stats: PipelineStatistics) -> vk::QueryPipelineStatisticFlags {
+    let mut flags = vk::QueryPipelineStatisticFlags::empty();
+    if stats.contains(PipelineStatistics::PRIMITIVES) {
+        flags |= vk::QueryPipelineStatisticFlags::INPUT_ASSEMBLY_PRIMITIVES;
+    }
+    if stats.contains(PipelineStatistics::VERTEX_INVOCATIONS) {
+        flags |= vk::QueryPipelineStatisticFlags::VERTEX_SHADER_INVOCATIONS;
+    }
+    if stats.contains(PipelineStatistics::FRAGMENT_INVOCATIONS) {
+        flags |= vk::QueryPipelineStatisticFlags::FRAGMENT_SHADER_INVOCATIONS;
+    }
+    if stats.contains(PipelineStatistics::COMPUTE_INVOCATIONS) {
+        flags |= vk::QueryPipelineStatisticFlags::COMPUTE_SHADER_INVOCATIONS;
+    }
+    flags
+}
+
 fn is_depth_format(format: Format) -> bool {
     matches!(
         format,
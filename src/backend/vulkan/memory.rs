@@ -1,3 +1,4 @@
+use crate::error::{RhiError, RhiResult};
 use crate::types::GpuAddress;
 use ash::vk;
 
@@ -5,9 +6,20 @@ use ash::vk;
 pub struct VulkanBuffer {
     pub(crate) buffer: vk::Buffer,
     pub(crate) memory: vk::DeviceMemory,
+    /// Byte offset of this buffer's range inside `buffer` / `memory`. Non-zero only for
+    /// sub-allocations, which share their block's `VkBuffer`.
+    pub(crate) offset: u64,
+    /// True when the range was carved out of a shared memory block rather than owning
+    /// `buffer` and `memory` outright.
+    pub(crate) sub_allocated: bool,
     pub(crate) size: u64,
     pub(crate) mapped_ptr: Option<*mut u8>,
     pub(crate) gpu_address: GpuAddress,
+    /// Loader and handle type for exportable memory (`BufferDesc::export`).
+    pub(crate) export: Option<(
+        ash::khr::external_memory_fd::Device,
+        vk::ExternalMemoryHandleTypeFlags,
+    )>,
 }
 
 // SAFETY: VulkanBuffer's raw pointer is only used for CPU-side uploads
@@ -27,4 +39,20 @@ impl VulkanBuffer {
     pub fn size(&self) -> u64 {
         self.size
     }
+
+    #[cfg(unix)]
+    pub fn export_fd(&self) -> RhiResult<std::os::fd::OwnedFd> {
+        use std::os::fd::FromRawFd;
+
+        let (loader, handle_type) = self.export.as_ref().ok_or_else(|| {
+            RhiError::Unsupported("buffer was not created with BufferDesc::export".into())
+        })?;
+        let info = vk::MemoryGetFdInfoKHR::default()
+            .memory(self.memory)
+            .handle_type(*handle_type);
+        let fd = unsafe { loader.get_memory_fd(&info) }
+            .map_err(|e| RhiError::AllocationFailed(format!("export memory fd: {e}")))?;
+        // SAFETY: `vkGetMemoryFdKHR` returns a new fd owned by the caller.
+        Ok(unsafe { std::os::fd::OwnedFd::from_raw_fd(fd) })
+    }
 }
@@ -1,9 +1,13 @@
 pub mod accel;
+pub(crate) mod allocator;
 pub mod barrier;
+pub mod bundle;
 pub mod command;
+pub(crate) mod command_pool;
 pub mod device;
 pub mod memory;
 pub mod pipeline;
+pub mod query;
 pub mod shader;
 pub mod surface;
 pub mod swapchain;
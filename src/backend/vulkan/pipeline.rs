@@ -1,9 +1,10 @@
-use std::cell::RefCell;
 use std::collections::HashMap;
+use std::sync::Mutex;
 
+use ash::ext::debug_utils;
 use ash::vk;
 
-use super::device::format_to_vk;
+use super::device::{format_to_vk, set_object_name};
 use crate::error::{RhiError, RhiResult};
 use crate::pipeline::{BlendAttachment, BlendState, ColorTarget};
 use crate::types::{BlendFactor, BlendOp, ColorWriteMask, Cull, SampleCount, Topology};
@@ -14,8 +15,9 @@ pub struct VulkanGraphicsPso {
     pub(crate) pipeline_layout: vk::PipelineLayout,
     pub(crate) root_constant_size: u32,
     pub(crate) device: ash::Device,
+    pub(crate) debug_utils: Option<debug_utils::Device>,
     pub(crate) desc: VulkanGraphicsPsoDesc,
-    pub(crate) blend_pipelines: RefCell<HashMap<BlendState, vk::Pipeline>>,
+    pub(crate) blend_pipelines: Mutex<HashMap<BlendState, vk::Pipeline>>,
 }
 
 /// Vulkan compute pipeline state.
@@ -39,21 +41,27 @@ pub struct VulkanGraphicsPsoDesc {
     pub(crate) alpha_to_coverage: bool,
     pub(crate) cull: Cull,
     pub(crate) stencil_format: vk::Format,
+    pub(crate) view_mask: u32,
+    /// Debug name of every blend variant.
+    pub(crate) label: Option<String>,
 }
 
 impl VulkanGraphicsPso {
     /// Draw-time variant fetch: no `Result` channel here, so a compile failure (an
     /// unsupported blend combo) is a programmer error and panics, like other draw-time guards.
     pub(crate) fn pipeline_for_blend(&self, blend: &BlendState) -> vk::Pipeline {
-        if let Some(p) = self.blend_pipelines.borrow().get(blend) {
+        // Held across creation so two threads missing the same variant build it once.
+        let mut pipelines = self
+            .blend_pipelines
+            .lock()
+            .expect("blend pipelines lock poisoned");
+        if let Some(p) = pipelines.get(blend) {
             return *p;
         }
         let pipeline = self
             .create_pipeline(blend)
             .expect("Vulkan graphics PSO creation failed for dynamic blend variant");
-        self.blend_pipelines
-            .borrow_mut()
-            .insert(blend.clone(), pipeline);
+        pipelines.insert(blend.clone(), pipeline);
         pipeline
     }
 
@@ -152,7 +160,8 @@ impl VulkanGraphicsPso {
         let mut rendering_info = vk::PipelineRenderingCreateInfo::default()
             .color_attachment_formats(&color_attachment_formats)
             .depth_attachment_format(depth_format)
-            .stencil_attachment_format(self.desc.stencil_format);
+            .stencil_attachment_format(self.desc.stencil_format)
+            .view_mask(self.desc.view_mask);
 
         let pipeline_info = vk::GraphicsPipelineCreateInfo::default()
             .stages(&shader_stages)
@@ -174,7 +183,11 @@ impl VulkanGraphicsPso {
                     RhiError::PipelineCreation(format!("Vulkan graphics pipeline creation: {e:?}"))
                 })?
         };
-
+        set_object_name(
+            self.debug_utils.as_ref(),
+            pipelines[0],
+            self.desc.label.as_deref(),
+        );
         Ok(pipelines[0])
     }
 }
@@ -183,7 +196,12 @@ impl Drop for VulkanGraphicsPso {
     fn drop(&mut self) {
         unsafe {
             self.device.destroy_pipeline(self.pipeline, None);
-            for (_, pipe) in self.blend_pipelines.borrow_mut().drain() {
+            for (_, pipe) in self
+                .blend_pipelines
+                .get_mut()
+                .expect("blend pipelines lock poisoned")
+                .drain()
+            {
                 if pipe != self.pipeline {
                     self.device.destroy_pipeline(pipe, None);
                 }
@@ -279,9 +297,10 @@ pub struct VulkanMeshletPso {
     pub(crate) pipeline_layout: vk::PipelineLayout,
     pub(crate) root_constant_size: u32,
     pub(crate) device: ash::Device,
+    pub(crate) debug_utils: Option<debug_utils::Device>,
     /// Blend variants (same per-draw flyweight mechanism as graphics PSOs).
     pub(crate) desc: VulkanMeshletPsoDesc,
-    pub(crate) blend_pipelines: RefCell<HashMap<BlendState, vk::Pipeline>>,
+    pub(crate) blend_pipelines: Mutex<HashMap<BlendState, vk::Pipeline>>,
 }
 
 pub struct VulkanMeshletPsoDesc {
@@ -295,21 +314,27 @@ pub struct VulkanMeshletPsoDesc {
     pub(crate) sample_count: SampleCount,
     pub(crate) alpha_to_coverage: bool,
     pub(crate) cull: Cull,
+    pub(crate) view_mask: u32,
+    /// Debug name of every blend variant.
+    pub(crate) label: Option<String>,
 }
 
 impl VulkanMeshletPso {
     /// Draw-time variant fetch — panics on compile failure (no `Result` channel), see the
     /// graphics PSO equivalent.
     pub(crate) fn pipeline_for_blend(&self, blend: &BlendState) -> vk::Pipeline {
-        if let Some(p) = self.blend_pipelines.borrow().get(blend) {
+        // Held across creation so two threads missing the same variant build it once.
+        let mut pipelines = self
+            .blend_pipelines
+            .lock()
+            .expect("blend pipelines lock poisoned");
+        if let Some(p) = pipelines.get(blend) {
             return *p;
         }
         let pipeline = self
             .create_pipeline(blend)
             .expect("Vulkan meshlet PSO creation failed for dynamic blend variant");
-        self.blend_pipelines
-            .borrow_mut()
-            .insert(blend.clone(), pipeline);
+        pipelines.insert(blend.clone(), pipeline);
         pipeline
     }
 
@@ -402,7 +427,8 @@ impl VulkanMeshletPso {
         let mut rendering_info = vk::PipelineRenderingCreateInfo::default()
             .color_attachment_formats(&color_attachment_formats)
             .depth_attachment_format(depth_format)
-            .stencil_attachment_format(self.desc.stencil_format);
+            .stencil_attachment_format(self.desc.stencil_format)
+            .view_mask(self.desc.view_mask);
 
         let pipeline_info = vk::GraphicsPipelineCreateInfo::default()
             .stages(&stages)
@@ -422,6 +448,11 @@ impl VulkanMeshletPso {
                     RhiError::PipelineCreation(format!("Vulkan meshlet pipeline creation: {e:?}"))
                 })?
         };
+        set_object_name(
+            self.debug_utils.as_ref(),
+            pipelines[0],
+            self.desc.label.as_deref(),
+        );
         Ok(pipelines[0])
     }
 }
@@ -430,7 +461,12 @@ impl Drop for VulkanMeshletPso {
     fn drop(&mut self) {
         unsafe {
             self.device.destroy_pipeline(self.pipeline, None);
-            for (_, pipe) in self.blend_pipelines.borrow_mut().drain() {
+            for (_, pipe) in self
+                .blend_pipelines
+                .get_mut()
+                .expect("blend pipelines lock poisoned")
+                .drain()
+            {
                 if pipe != self.pipeline {
                     self.device.destroy_pipeline(pipe, None);
                 }
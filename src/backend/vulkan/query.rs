@@ -0,0 +1,24 @@
+use std::ops::Range;
+
+use ash::vk;
+
+/// Vulkan query pool, destroyed on drop.
+pub struct VulkanQueryPool {
+    pub(crate) pool: vk::QueryPool,
+    pub(crate) device: ash::Device,
+}
+
+impl Drop for VulkanQueryPool {
+    fn drop(&mut self) {
+        unsafe { self.device.destroy_query_pool(self.pool, None) };
+    }
+}
+
+impl VulkanQueryPool {
+    pub fn reset(&self, range: Range<u32>) {
+        unsafe {
+            self.device
+                .reset_query_pool(self.pool, range.start, range.len() as u32)
+        };
+    }
+}
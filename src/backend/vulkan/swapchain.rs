@@ -1,5 +1,3 @@
-use std::cell::RefCell;
-
 use crate::types::Format;
 use ash::vk;
 
@@ -18,5 +16,4 @@ pub struct VulkanSwapchain {
     pub(crate) present_complete_semaphores: Vec<vk::Semaphore>,
     pub(crate) rendering_complete_semaphores: Vec<vk::Semaphore>,
     pub(crate) in_flight_fences: Vec<vk::Fence>,
-    pub(crate) in_flight_cmd_buffers: RefCell<Vec<vk::CommandBuffer>>,
 }
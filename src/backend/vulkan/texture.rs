@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use ash::vk;
 
 /// Vulkan texture stored in the bindless heap.
@@ -7,4 +9,28 @@ pub struct VulkanTexture {
     /// True when this entry is a view into another texture's image.
     /// On destruction, only `image_view` is freed; `image` belongs to the source.
     pub(crate) is_view: bool,
+    /// Filter `generate_mips` and `blit_texture` read with; `None` if the format cannot be
+    /// blitted.
+    pub(crate) mip_filter: Option<vk::Filter>,
+    pub(crate) format: vk::Format,
+    pub(crate) aspect: vk::ImageAspectFlags,
+    /// Single-mip views for `RenderTarget::TextureSubresource` and `TextureLayers`, keyed by
+    /// `(mip, base_layer, layer_count)`. Created on first use and freed with the texture.
+    pub(crate) attachment_views: HashMap<(u32, u32, u32), vk::ImageView>,
+    pub(crate) mip_levels: u32,
+    /// Array layers (cube faces counted), or the depth of mip 0 for a 3D texture.
+    pub(crate) layers: u32,
+    /// True for 3D textures, whose layers are depth slices that halve with each mip.
+    pub(crate) depth_slices: bool,
+}
+
+impl VulkanTexture {
+    /// Layers an attachment view of mip `mip` can select.
+    pub(crate) fn attachment_layers(&self, mip: u32) -> u32 {
+        if self.depth_slices {
+            self.layers.checked_shr(mip).unwrap_or(0).max(1)
+        } else {
+            self.layers
+        }
+    }
 }
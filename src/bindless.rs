@@ -0,0 +1,92 @@
+//! Slot allocation for the bindless descriptor heaps.
+//!
+//! A `TextureId` or `SamplerId` is an index into a global heap that shaders read directly, so
+//! a slot cannot be handed out again while submitted work may still read the descriptor it
+//! held. Freed slots are tagged with the queue's latest submission serial and become reusable
+//! once the GPU has retired it; [`Device::descriptor_heap_stats`](crate::Device) reports how
+//! full each heap is.
+
+use std::collections::VecDeque;
+
+/// Occupancy of one bindless heap.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct HeapOccupancy {
+    /// Slots holding a live texture, view or sampler.
+    pub live: u32,
+    /// Freed slots waiting for the submissions that may still read them to retire.
+    pub retiring: u32,
+    /// Slots ever handed out; the heap's used prefix.
+    pub high_water: u32,
+    /// Slots the heap has room for.
+    pub capacity: u32,
+}
+
+/// Snapshot of the bindless heaps, from `Device::descriptor_heap_stats`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DescriptorHeapStats {
+    /// `TextureId` slots, shared by sampled and storage descriptors.
+    pub textures: HeapOccupancy,
+    /// `SamplerId` slots.
+    pub samplers: HeapOccupancy,
+}
+
+/// Free-list allocator over the slots of one heap.
+pub(crate) struct SlotAllocator {
+    capacity: u32,
+    /// Slots below this have been handed out at least once.
+    high_water: u32,
+    /// Retired slots, reusable now.
+    free: Vec<u32>,
+    /// Freed slots and the serial that must complete first, in free order (so the serials
+    /// never decrease).
+    retiring: VecDeque<(u64, u32)>,
+}
+
+impl SlotAllocator {
+    pub(crate) fn new(capacity: u32) -> Self {
+        Self {
+            capacity,
+            high_water: 0,
+            free: Vec::new(),
+            retiring: VecDeque::new(),
+        }
+    }
+
+    /// Hand out a slot, preferring retired ones. `completed` is the queue's completed serial.
+    /// `None` if every slot is live or still retiring.
+    pub(crate) fn alloc(&mut self, completed: u64) -> Option<u32> {
+        while let Some(&(serial, slot)) = self.retiring.front() {
+            if serial > completed {
+                break;
+            }
+            self.retiring.pop_front();
+            self.free.push(slot);
+        }
+        if let Some(slot) = self.free.pop() {
+            return Some(slot);
+        }
+        (self.high_water < self.capacity).then(|| {
+            self.high_water += 1;
+            self.high_water - 1
+        })
+    }
+
+    /// Release `slot` once the submission with `serial` (the latest at free time) retires.
+    pub(crate) fn free(&mut self, slot: u32, serial: u64) {
+        debug_assert!(
+            slot < self.high_water,
+            "freeing slot {slot} never allocated"
+        );
+        self.retiring.push_back((serial, slot));
+    }
+
+    pub(crate) fn occupancy(&self) -> HeapOccupancy {
+        let retiring = self.retiring.len() as u32;
+        HeapOccupancy {
+            live: self.high_water - self.free.len() as u32 - retiring,
+            retiring,
+            high_water: self.high_water,
+            capacity: self.capacity,
+        }
+    }
+}
@@ -0,0 +1,338 @@
+//! Reusable command bundles: draws or dispatches recorded once and executed from any number
+//! of command buffers.
+//!
+//! A [`RenderBundleEncoder`] or [`ComputeBundleEncoder`] records a restricted set of commands
+//! and seals them with `finish`. Encoders are `Send`, so static work can be recorded on a
+//! worker thread. Root pointers are recorded as they are: the bundle replays the same
+//! pointers every time, while the memory behind them may change between executions. On
+//! Vulkan a bundle is a secondary command buffer; on Metal, an indirect command buffer.
+//!
+//! ```ignore
+//! let mut encoder = device.create_render_bundle_encoder(&RenderBundleDesc {
+//!     color_formats: vec![Format::R8G8B8A8Unorm],
+//!     depth_format: Some(Format::D32Float),
+//!     ..Default::default()
+//! })?;
+//! encoder.set_graphics_pipeline(&pso);
+//! encoder.set_viewport(0.0, 0.0, width as f32, height as f32, 0.0, 1.0);
+//! encoder.set_scissor(0, 0, width, height);
+//! for mesh in &static_meshes {
+//!     encoder.draw_indexed(mesh.root, mesh.root, mesh.indices, mesh.index_count, 1);
+//! }
+//! let statics = encoder.finish()?;
+//!
+//! // Every frame:
+//! cmd.execute_render_bundles(&gbuffer_pass, &[&statics]);
+//! ```
+
+use crate::command::{CommandBuffer, CommandBufferInner, RenderPassDesc, root_or_null};
+use crate::deferred::{Garbage, SharedGraveyard};
+use crate::error::RhiResult;
+use crate::pipeline::{BlendState, ComputePso, DepthStencilState, GraphicsPso};
+use crate::types::*;
+
+/// Attachments of the render passes a render bundle executes in. Its pipelines must be
+/// built for the same formats, sample count and view mask.
+#[derive(Clone, Debug)]
+pub struct RenderBundleDesc {
+    pub color_formats: Vec<Format>,
+    /// Depth attachment format (None = no depth).
+    pub depth_format: Option<Format>,
+    /// Separate stencil attachment format (None = no stencil).
+    pub stencil_format: Option<Format>,
+    pub sample_count: SampleCount,
+    /// Multiview mask; must equal the executing pass's `view_mask`.
+    pub view_mask: u32,
+    pub label: Option<String>,
+}
+
+impl Default for RenderBundleDesc {
+    fn default() -> Self {
+        Self {
+            color_formats: vec![Format::B8G8R8A8Srgb],
+            depth_format: Some(Format::D32Float),
+            stencil_format: None,
+            sample_count: SampleCount::S1,
+            view_mask: 0,
+            label: None,
+        }
+    }
+}
+
+/// Description for creating a compute bundle encoder.
+#[derive(Clone, Debug, Default)]
+pub struct ComputeBundleDesc {
+    pub label: Option<String>,
+}
+
+/// Records draws into a [`RenderBundle`]. A bundle inherits no state from the command
+/// buffer executing it: bind a pipeline and set the viewport and scissor before the first
+/// draw. Depth-stencil state starts out as `DepthStencilState::default()`.
+pub struct RenderBundleEncoder {
+    pub(crate) inner: BundleEncoderInner,
+    view_mask: u32,
+    graveyard: SharedGraveyard,
+    has_pipeline: bool,
+    has_viewport: bool,
+    has_scissor: bool,
+}
+
+impl RenderBundleEncoder {
+    pub(crate) fn new(
+        inner: BundleEncoderInner,
+        view_mask: u32,
+        graveyard: SharedGraveyard,
+    ) -> Self {
+        Self {
+            inner,
+            view_mask,
+            graveyard,
+            has_pipeline: false,
+            has_viewport: false,
+            has_scissor: false,
+        }
+    }
+
+    pub fn set_graphics_pipeline(&mut self, pso: &GraphicsPso) {
+        self.has_pipeline = true;
+        backend_dispatch!(&mut self.inner, BundleEncoderInner, e => e.set_graphics_pipeline(pso))
+    }
+
+    pub fn set_depth_stencil_state(&mut self, state: &DepthStencilState) {
+        backend_dispatch!(&mut self.inner, BundleEncoderInner, e => e.set_depth_stencil_state(state))
+    }
+
+    /// Blend state for the pipelines bound after it.
+    pub fn set_blend_state(&mut self, state: &BlendState) {
+        backend_dispatch!(&mut self.inner, BundleEncoderInner, e => e.set_blend_state(state))
+    }
+
+    pub fn set_viewport(
+        &mut self,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        min_depth: f32,
+        max_depth: f32,
+    ) {
+        self.has_viewport = true;
+        backend_dispatch!(&mut self.inner, BundleEncoderInner, e =>
+            e.set_viewport(x, y, width, height, min_depth, max_depth))
+    }
+
+    pub fn set_scissor(&mut self, x: i32, y: i32, width: u32, height: u32) {
+        self.has_scissor = true;
+        backend_dispatch!(&mut self.inner, BundleEncoderInner, e => e.set_scissor(x, y, width, height))
+    }
+
+    /// Non-indexed draw, as [`CommandBuffer::draw`].
+    pub fn draw(
+        &mut self,
+        vertex_root: impl Into<Option<GpuAddress>>,
+        pixel_root: impl Into<Option<GpuAddress>>,
+        vertex_count: u32,
+        instance_count: u32,
+        first_vertex: u32,
+        first_instance: u32,
+    ) {
+        self.check_draw_state("draw");
+        let (vertex_root, pixel_root) = (root_or_null(vertex_root), root_or_null(pixel_root));
+        backend_dispatch!(&mut self.inner, BundleEncoderInner, e => e.draw(
+            vertex_root,
+            pixel_root,
+            vertex_count,
+            instance_count,
+            first_vertex,
+            first_instance,
+        ))
+    }
+
+    /// Indexed draw, as [`CommandBuffer::draw_indexed`].
+    pub fn draw_indexed(
+        &mut self,
+        vertex_root: impl Into<Option<GpuAddress>>,
+        pixel_root: impl Into<Option<GpuAddress>>,
+        indices: GpuAddress,
+        index_count: u32,
+        instance_count: u32,
+    ) {
+        self.check_draw_state("draw_indexed");
+        let (vertex_root, pixel_root) = (root_or_null(vertex_root), root_or_null(pixel_root));
+        backend_dispatch!(&mut self.inner, BundleEncoderInner, e =>
+            e.draw_indexed(vertex_root, pixel_root, indices, index_count, instance_count))
+    }
+
+    /// Seal the recorded draws into a bundle.
+    pub fn finish(self) -> RhiResult<RenderBundle> {
+        let (view_mask, graveyard) = (self.view_mask, self.graveyard);
+        let inner = backend_dispatch!(self.inner, BundleEncoderInner, e => e.finish()
+            .map(BundleInner::from))?;
+        Ok(RenderBundle {
+            inner: Some(inner),
+            view_mask,
+            graveyard,
+        })
+    }
+
+    fn check_draw_state(&self, op: &str) {
+        assert!(
+            self.has_pipeline,
+            "{op} in a render bundle without a pipeline"
+        );
+        assert!(
+            self.has_viewport && self.has_scissor,
+            "{op} in a render bundle before set_viewport and set_scissor"
+        );
+    }
+}
+
+/// Records dispatches into a [`ComputeBundle`]. Bind a pipeline before the first dispatch.
+/// Dispatches of one bundle may run concurrently: split dependent work into bundles
+/// executed with a barrier between them.
+pub struct ComputeBundleEncoder {
+    pub(crate) inner: BundleEncoderInner,
+    graveyard: SharedGraveyard,
+    has_pipeline: bool,
+}
+
+impl ComputeBundleEncoder {
+    pub(crate) fn new(inner: BundleEncoderInner, graveyard: SharedGraveyard) -> Self {
+        Self {
+            inner,
+            graveyard,
+            has_pipeline: false,
+        }
+    }
+
+    pub fn set_compute_pipeline(&mut self, pso: &ComputePso) {
+        self.has_pipeline = true;
+        backend_dispatch!(&mut self.inner, BundleEncoderInner, e => e.set_compute_pipeline(pso))
+    }
+
+    /// Dispatch `x * y * z` threadgroups, as [`CommandBuffer::dispatch`].
+    pub fn dispatch(&mut self, root: impl Into<Option<GpuAddress>>, x: u32, y: u32, z: u32) {
+        assert!(
+            self.has_pipeline,
+            "dispatch in a compute bundle without a pipeline"
+        );
+        let root = root_or_null(root);
+        backend_dispatch!(&mut self.inner, BundleEncoderInner, e => e.dispatch(root, x, y, z))
+    }
+
+    /// Seal the recorded dispatches into a bundle.
+    pub fn finish(self) -> RhiResult<ComputeBundle> {
+        let graveyard = self.graveyard;
+        let inner = backend_dispatch!(self.inner, BundleEncoderInner, e => e.finish()
+            .map(BundleInner::from))?;
+        Ok(ComputeBundle {
+            inner: Some(inner),
+            graveyard,
+        })
+    }
+}
+
+/// Recorded draws, executed with [`CommandBuffer::execute_render_bundles`]. Dropping a
+/// bundle parks it like an [`Owned`](crate::Owned) handle, until the GPU has retired every
+/// submission made before the drop: drop it after submitting the work that executes it. The
+/// pipelines it binds must outlive it.
+pub struct RenderBundle {
+    inner: Option<BundleInner>,
+    view_mask: u32,
+    graveyard: SharedGraveyard,
+}
+
+/// Recorded dispatches, executed with [`CommandBuffer::execute_compute_bundles`]. Dropped
+/// like a [`RenderBundle`].
+pub struct ComputeBundle {
+    inner: Option<BundleInner>,
+    graveyard: SharedGraveyard,
+}
+
+impl RenderBundle {
+    pub(crate) fn inner(&self) -> &BundleInner {
+        self.inner.as_ref().expect("bundle already dropped")
+    }
+}
+
+impl ComputeBundle {
+    pub(crate) fn inner(&self) -> &BundleInner {
+        self.inner.as_ref().expect("bundle already dropped")
+    }
+}
+
+impl Drop for RenderBundle {
+    fn drop(&mut self) {
+        bury(&self.graveyard, self.inner.take());
+    }
+}
+
+impl Drop for ComputeBundle {
+    fn drop(&mut self) {
+        bury(&self.graveyard, self.inner.take());
+    }
+}
+
+fn bury(graveyard: &SharedGraveyard, inner: Option<BundleInner>) {
+    if let Some(inner) = inner {
+        graveyard
+            .lock()
+            .expect("graveyard lock poisoned")
+            .bury(Garbage::Dropped(Box::new(inner)));
+    }
+}
+
+pub(crate) enum BundleEncoderInner {
+    #[cfg(feature = "vulkan")]
+    Vulkan(Box<crate::backend::vulkan::bundle::VulkanBundleEncoder>),
+    #[cfg(feature = "metal")]
+    Metal(Box<crate::backend::metal::bundle::MetalBundleEncoder>),
+}
+
+pub(crate) enum BundleInner {
+    #[cfg(feature = "vulkan")]
+    Vulkan(Box<crate::backend::vulkan::bundle::VulkanBundle>),
+    #[cfg(feature = "metal")]
+    Metal(Box<crate::backend::metal::bundle::MetalBundle>),
+}
+
+#[cfg(feature = "vulkan")]
+impl From<crate::backend::vulkan::bundle::VulkanBundle> for BundleInner {
+    fn from(bundle: crate::backend::vulkan::bundle::VulkanBundle) -> Self {
+        BundleInner::Vulkan(Box::new(bundle))
+    }
+}
+
+#[cfg(feature = "metal")]
+impl From<crate::backend::metal::bundle::MetalBundle> for BundleInner {
+    fn from(bundle: crate::backend::metal::bundle::MetalBundle) -> Self {
+        BundleInner::Metal(Box::new(bundle))
+    }
+}
+
+impl CommandBuffer {
+    /// Run `bundles`, in order, as one render pass described by `pass`; the pass holds
+    /// nothing else. Call it outside render passes. Each bundle's `RenderBundleDesc` must
+    /// match the pass's attachments, and no query may be active. The pipeline,
+    /// depth-stencil state, viewport and scissor are undefined afterwards.
+    pub fn execute_render_bundles(&mut self, pass: &RenderPassDesc, bundles: &[&RenderBundle]) {
+        pass.validate();
+        for bundle in bundles {
+            assert_eq!(
+                bundle.view_mask, pass.view_mask,
+                "render bundle recorded for view_mask {:#b} executed in a pass with {:#b}",
+                bundle.view_mask, pass.view_mask
+            );
+        }
+        backend_dispatch!(&mut self.inner, CommandBufferInner, cmd =>
+            cmd.execute_render_bundles(pass, bundles))
+    }
+
+    /// Run the dispatches of `bundles`, in order, outside render passes. Earlier commands
+    /// need a barrier before the bundles depend on them, as for `dispatch`. The compute
+    /// pipeline is undefined afterwards.
+    pub fn execute_compute_bundles(&mut self, bundles: &[&ComputeBundle]) {
+        backend_dispatch!(&mut self.inner, CommandBufferInner, cmd =>
+            cmd.execute_compute_bundles(bundles))
+    }
+}
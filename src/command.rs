@@ -1,6 +1,15 @@
+use std::ops::Range;
+use std::sync::Arc;
+use std::sync::atomic::AtomicU64;
+
 use crate::accel::AccelerationStructure;
 use crate::barrier::{HazardFlags, StageFlags};
 use crate::pipeline::{BlendState, ComputePso, DepthStencilState, GraphicsPso, MeshletPso};
+use crate::profiler::ProfileRecording;
+use crate::query::{QUERY_RESULT_SIZE, QueryPool, QueryType};
+use crate::texture::{
+    Texture, TextureCopyRegion, TextureRegion, TextureSubresourceRange, TextureUsage, format_block,
+};
 use crate::types::*;
 use crate::types::{BlasDesc, TlasDesc};
 
@@ -12,6 +21,9 @@ pub struct ColorAttachment {
     pub load_op: LoadOp,
     pub store_op: StoreOp,
     pub clear_color: [f32; 4],
+    /// Single-sampled target the multisampled `target` is averaged into at the end of the
+    /// pass. Set exactly when `store_op` is `Resolve` or `StoreAndResolve`.
+    pub resolve_target: Option<RenderTarget>,
 }
 
 /// Depth attachment for dynamic rendering.
@@ -22,6 +34,10 @@ pub struct DepthAttachment {
     pub store_op: StoreOp,
     pub clear_depth: f32,
     pub clear_stencil: u8,
+    /// Single-sampled target the multisampled `target` is resolved into with `resolve_mode`.
+    /// Set exactly when `store_op` is `Resolve` or `StoreAndResolve`.
+    pub resolve_target: Option<RenderTarget>,
+    pub resolve_mode: DepthResolveMode,
 }
 
 /// Render target reference.
@@ -31,6 +47,33 @@ pub enum RenderTarget {
     SwapchainImage(u32),
     /// Off-screen texture by TextureId.
     Texture(TextureId),
+    /// A single mip level and layer of an off-screen texture. `layer` is the array layer,
+    /// the cube face (`array_layer * 6 + face` for cube arrays) or the depth slice of a 3D
+    /// texture. The texture must not be a view.
+    TextureSubresource {
+        texture: TextureId,
+        mip: u32,
+        layer: u32,
+    },
+    /// `layer_count` consecutive layers of one mip level, starting at `base_layer`, bound as
+    /// a layered attachment for multiview passes or shaders writing `SV_RenderTargetArrayIndex`.
+    /// Layers count as in `TextureSubresource`. The texture must not be a view.
+    TextureLayers {
+        texture: TextureId,
+        mip: u32,
+        base_layer: u32,
+        layer_count: u32,
+    },
+}
+
+impl RenderTarget {
+    /// Number of layers the target spans; 1 unless it is `TextureLayers`.
+    pub fn layer_count(&self) -> u32 {
+        match *self {
+            RenderTarget::TextureLayers { layer_count, .. } => layer_count,
+            _ => 1,
+        }
+    }
 }
 
 /// Load operation for attachments.
@@ -41,11 +84,63 @@ pub enum LoadOp {
     DontCare,
 }
 
+/// Value `clear_texture` writes to every texel.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ClearValue {
+    /// Float, unorm and sRGB color formats; sRGB values are linear.
+    Color([f32; 4]),
+    /// Integer color formats.
+    Uint([u32; 4]),
+    /// Depth formats; `stencil` is ignored by formats without stencil.
+    DepthStencil { depth: f32, stencil: u8 },
+}
+
+/// Destination of `blit_texture`.
+#[derive(Clone, Copy)]
+pub enum BlitTarget<'a> {
+    Texture(&'a Texture),
+    /// Swapchain image by index, as in `RenderTarget::SwapchainImage`. The blit stands in for
+    /// the render pass that would draw the image: texels outside the destination region are
+    /// undefined afterwards, and `transition_to_present` follows as usual.
+    SwapchainImage(u32),
+}
+
+impl<'a> From<&'a Texture> for BlitTarget<'a> {
+    fn from(texture: &'a Texture) -> Self {
+        BlitTarget::Texture(texture)
+    }
+}
+
 /// Store operation for attachments.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum StoreOp {
     Store,
     DontCare,
+    /// Write the resolved samples to the attachment's `resolve_target` and discard the
+    /// multisampled contents.
+    Resolve,
+    /// Keep the multisampled contents as well as resolving them.
+    StoreAndResolve,
+}
+
+impl StoreOp {
+    /// True for the ops that write the attachment's `resolve_target`.
+    pub fn resolves(self) -> bool {
+        matches!(self, StoreOp::Resolve | StoreOp::StoreAndResolve)
+    }
+}
+
+/// How the samples of a multisampled depth attachment combine into the resolved value.
+/// Color attachments always average.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DepthResolveMode {
+    /// The value of sample 0.
+    #[default]
+    SampleZero,
+    /// The smallest sample value.
+    Min,
+    /// The largest sample value.
+    Max,
 }
 
 /// Description for beginning dynamic rendering.
@@ -54,6 +149,67 @@ pub struct RenderPassDesc {
     pub color_attachments: Vec<ColorAttachment>,
     pub depth_attachment: Option<DepthAttachment>,
     pub render_area: [u32; 4], // x, y, width, height
+    /// Multiview: bit `i` set renders view `i` into layer `i` of every attachment, which must
+    /// then be `RenderTarget::TextureLayers` spanning the highest view. Shaders read the view
+    /// with `SV_ViewID`, and the pipeline's `view_mask` must match. 0 = a single view, layered
+    /// across the attachments' common layer count.
+    pub view_mask: u32,
+}
+
+impl RenderPassDesc {
+    fn targets(&self) -> impl Iterator<Item = &RenderTarget> {
+        let color = self
+            .color_attachments
+            .iter()
+            .flat_map(|ca| std::iter::once(&ca.target).chain(&ca.resolve_target));
+        let depth = self
+            .depth_attachment
+            .iter()
+            .flat_map(|da| std::iter::once(&da.target).chain(&da.resolve_target));
+        color.chain(depth)
+    }
+
+    /// Layers each attachment renders: one per view up to the highest view in `view_mask`,
+    /// or the smallest attachment layer count when there is no view mask.
+    pub(crate) fn layer_count(&self) -> u32 {
+        if self.view_mask != 0 {
+            u32::BITS - self.view_mask.leading_zeros()
+        } else {
+            self.targets()
+                .map(RenderTarget::layer_count)
+                .min()
+                .unwrap_or(1)
+        }
+    }
+
+    /// Check the invariants every backend relies on; violations are programmer errors.
+    pub(crate) fn validate(&self) {
+        for (i, ca) in self.color_attachments.iter().enumerate() {
+            assert_eq!(
+                ca.store_op.resolves(),
+                ca.resolve_target.is_some(),
+                "color attachment {i}: a resolve_target goes with a resolving store_op"
+            );
+        }
+        if let Some(da) = &self.depth_attachment {
+            assert_eq!(
+                da.store_op.resolves(),
+                da.resolve_target.is_some(),
+                "depth attachment: a resolve_target goes with a resolving store_op"
+            );
+        }
+        if self.view_mask != 0 {
+            let views = self.layer_count();
+            for target in self.targets() {
+                assert!(
+                    matches!(target, RenderTarget::TextureLayers { .. })
+                        && target.layer_count() >= views,
+                    "view_mask {:#b} needs TextureLayers attachments spanning {views} layers, got {target:?}",
+                    self.view_mask
+                );
+            }
+        }
+    }
 }
 
 /// Arguments for non-indexed indirect draws.
@@ -141,15 +297,41 @@ pub struct WaitValueDesc {
 /// Transient command buffer. Created, recorded, submitted, auto-reclaimed.
 pub struct CommandBuffer {
     pub(crate) inner: CommandBufferInner,
+    /// Filled with the submission's queue serial when this command buffer is submitted, so
+    /// readbacks recorded into it learn when they complete.
+    pub(crate) submit_slots: Vec<Arc<AtomicU64>>,
+    /// The `GpuProfiler` frame being recorded, between its `begin_frame` and `end_frame`.
+    pub(crate) profile: Option<ProfileRecording>,
+    /// Debug groups pushed and not yet popped.
+    pub(crate) debug_groups: u32,
+    /// `view_mask` of the render pass being recorded; 0 outside passes.
+    pub(crate) pass_view_mask: u32,
 }
 
 /// Resolve an optional root pointer: `None` (a draw that carries no root data) maps to
 /// the null GPU address, which the backends bind as a never-dereferenced `buffer(0)`.
 #[inline]
-fn root_or_null(root: impl Into<Option<GpuAddress>>) -> GpuAddress {
+pub(crate) fn root_or_null(root: impl Into<Option<GpuAddress>>) -> GpuAddress {
     root.into().unwrap_or(GpuAddress::NULL)
 }
 
+/// True if two resolved regions of the same texture share a texel.
+fn regions_overlap(a: &TextureRegion, b: &TextureRegion) -> bool {
+    let overlaps = |start_a: u32, len_a: u32, start_b: u32, len_b: u32| {
+        start_a < start_b + len_b && start_b < start_a + len_a
+    };
+    a.mip == b.mip
+        && overlaps(a.base_layer, a.layer_count, b.base_layer, b.layer_count)
+        && (0..3).all(|axis| {
+            overlaps(
+                a.offset[axis],
+                a.extent[axis],
+                b.offset[axis],
+                b.extent[axis],
+            )
+        })
+}
+
 pub(crate) enum CommandBufferInner {
     #[cfg(feature = "vulkan")]
     Vulkan(Box<crate::backend::vulkan::command::VulkanCommandBuffer>),
@@ -162,11 +344,14 @@ impl CommandBuffer {
 
     /// Begin dynamic rendering (no VkRenderPass objects).
     pub fn begin_render_pass(&mut self, desc: &RenderPassDesc) {
+        desc.validate();
+        self.pass_view_mask = desc.view_mask;
         backend_dispatch!(&mut self.inner, CommandBufferInner, cmd => cmd.begin_render_pass(desc))
     }
 
     /// End dynamic rendering.
     pub fn end_render_pass(&mut self) {
+        self.pass_view_mask = 0;
         backend_dispatch!(&mut self.inner, CommandBufferInner, cmd => cmd.end_render_pass())
     }
 
@@ -302,6 +487,85 @@ impl CommandBuffer {
         backend_dispatch!(&mut self.inner, CommandBufferInner, cmd => cmd.memcpy(dst, src, size))
     }
 
+    /// Fill `size` bytes at `dst` with the repeated 32-bit `pattern`. `dst` and `size` must be
+    /// multiples of 4.
+    pub fn fill(&mut self, dst: GpuAddress, size: u64, pattern: u32) {
+        assert!(
+            dst.0.is_multiple_of(4) && size.is_multiple_of(4),
+            "fill needs a 4-byte aligned address and size, got {:#x} + {size}",
+            dst.0
+        );
+        if size == 0 {
+            return;
+        }
+        backend_dispatch!(&mut self.inner, CommandBufferInner, cmd => cmd.fill(dst, size, pattern))
+    }
+
+    /// Write `data` to `dst` in command order, without a staging buffer of the caller's.
+    /// Meant for small writes such as counters and constants: at most 65536 bytes, and `dst`
+    /// and the length must be multiples of 4.
+    pub fn update(&mut self, dst: GpuAddress, data: &[u8]) {
+        assert!(
+            dst.0.is_multiple_of(4) && data.len().is_multiple_of(4),
+            "update needs a 4-byte aligned address and length, got {:#x} + {}",
+            dst.0,
+            data.len()
+        );
+        assert!(
+            data.len() <= 65536,
+            "update of {} bytes; use a staging buffer and memcpy above 64 KiB",
+            data.len()
+        );
+        if data.is_empty() {
+            return;
+        }
+        backend_dispatch!(&mut self.inner, CommandBufferInner, cmd => cmd.update(dst, data))
+    }
+
+    /// Set every texel in `range` of `texture` to `value`, outside a render pass. Works on
+    /// any single-sampled, uncompressed texture with `TRANSFER_DST` usage, attachment or not.
+    /// `value` must suit the format: `DepthStencil` for depth formats, `Uint` for integer
+    /// formats and `Color` otherwise.
+    pub fn clear_texture(
+        &mut self,
+        texture: &crate::texture::Texture,
+        range: TextureSubresourceRange,
+        value: ClearValue,
+    ) {
+        let desc = texture.desc();
+        assert!(
+            desc.usage.contains(TextureUsage::TRANSFER_DST),
+            "clear_texture needs TRANSFER_DST usage"
+        );
+        assert_eq!(
+            desc.sample_count,
+            SampleCount::S1,
+            "clear_texture on a multisampled texture"
+        );
+        assert!(
+            !format_block(desc.format).is_some_and(|b| b.is_compressed()),
+            "clear_texture on compressed format {:?}",
+            desc.format
+        );
+        let expected = match desc.format {
+            Format::D16Unorm
+            | Format::D32Float
+            | Format::D24UnormS8Uint
+            | Format::D32FloatS8Uint => {
+                matches!(value, ClearValue::DepthStencil { .. })
+            }
+            Format::R16Uint | Format::R32Uint => matches!(value, ClearValue::Uint(_)),
+            _ => matches!(value, ClearValue::Color(_)),
+        };
+        assert!(
+            expected,
+            "clear value {value:?} does not suit format {:?}",
+            desc.format
+        );
+        let (mips, layers) = range.resolve(desc);
+        backend_dispatch!(&mut self.inner, CommandBufferInner, cmd => cmd.clear_texture(texture, mips, layers, value))
+    }
+
     /// Copy a staging buffer into a texture. `texture_gpu` is the texture's backing
     /// allocation address; `src` is the source buffer address.
     pub fn copy_to_texture(
@@ -310,7 +574,30 @@ impl CommandBuffer {
         src: GpuAddress,
         texture: &crate::texture::Texture,
     ) {
-        backend_dispatch!(&mut self.inner, CommandBufferInner, cmd => cmd.copy_to_texture(texture_gpu, src, texture))
+        let region = TextureCopyRegion::subresource(0, 0);
+        backend_dispatch!(&mut self.inner, CommandBufferInner, cmd => cmd.copy_to_texture(texture_gpu, src, texture, &[region]))
+    }
+
+    /// Copy tightly packed texels at `src` into one mip level of one array layer of `texture`.
+    pub(crate) fn copy_to_texture_subresource(
+        &mut self,
+        src: GpuAddress,
+        texture: &crate::texture::Texture,
+        mip: u32,
+        layer: u32,
+    ) {
+        self.copy_buffer_to_texture(src, texture, &[TextureCopyRegion::subresource(mip, layer)]);
+    }
+
+    /// Copy texels from the buffer at `src` into `regions` of `texture`. Each region's
+    /// `buffer_offset` is relative to `src`.
+    pub fn copy_buffer_to_texture(
+        &mut self,
+        src: GpuAddress,
+        texture: &crate::texture::Texture,
+        regions: &[TextureCopyRegion],
+    ) {
+        backend_dispatch!(&mut self.inner, CommandBufferInner, cmd => cmd.copy_to_texture(texture.gpu(), src, texture, regions))
     }
 
     /// Copy a texture into a buffer. `dst` is the destination buffer address; `texture_gpu`
@@ -321,7 +608,190 @@ impl CommandBuffer {
         texture_gpu: GpuAddress,
         texture: &crate::texture::Texture,
     ) {
-        backend_dispatch!(&mut self.inner, CommandBufferInner, cmd => cmd.copy_from_texture(dst, texture_gpu, texture))
+        let region = TextureCopyRegion::subresource(0, 0);
+        backend_dispatch!(&mut self.inner, CommandBufferInner, cmd => cmd.copy_from_texture(dst, texture_gpu, texture, &[region]))
+    }
+
+    /// Copy one mip level of one array layer of `texture` into `dst`, tightly packed.
+    pub(crate) fn copy_from_texture_subresource(
+        &mut self,
+        dst: GpuAddress,
+        texture: &crate::texture::Texture,
+        mip: u32,
+        layer: u32,
+    ) {
+        self.copy_texture_to_buffer(texture, dst, &[TextureCopyRegion::subresource(mip, layer)]);
+    }
+
+    /// Copy `regions` of `texture` into the buffer at `dst`. Each region's `buffer_offset` is
+    /// relative to `dst`.
+    pub fn copy_texture_to_buffer(
+        &mut self,
+        texture: &crate::texture::Texture,
+        dst: GpuAddress,
+        regions: &[TextureCopyRegion],
+    ) {
+        backend_dispatch!(&mut self.inner, CommandBufferInner, cmd => cmd.copy_from_texture(dst, texture.gpu(), texture, regions))
+    }
+
+    /// Copy `src_region` of `src` into `dst` at `dst_region`, texel for texel. The copy is the
+    /// size of `src_region`; `dst_region.extent` may be left zero, and must match otherwise.
+    /// The formats must be identical, or share a `FormatBlock` (as sRGB and UNORM variants,
+    /// or `R32Float` and `R8G8B8A8Unorm`, do), in which case the bits are copied unconverted.
+    ///
+    /// `src` needs `TRANSFER_SRC` usage, `dst` `TRANSFER_DST`, and both the same sample count.
+    /// Copying within one texture is allowed as long as the regions do not overlap.
+    pub fn copy_texture(
+        &mut self,
+        src: &Texture,
+        src_region: TextureRegion,
+        dst: &Texture,
+        dst_region: TextureRegion,
+    ) {
+        let (src_desc, dst_desc) = (src.desc(), dst.desc());
+        assert!(
+            src_desc.usage.contains(TextureUsage::TRANSFER_SRC),
+            "copy_texture source needs TRANSFER_SRC usage"
+        );
+        assert!(
+            dst_desc.usage.contains(TextureUsage::TRANSFER_DST),
+            "copy_texture destination needs TRANSFER_DST usage"
+        );
+        assert!(
+            src_desc.format == dst_desc.format
+                || format_block(src_desc.format)
+                    .is_some_and(|block| format_block(dst_desc.format) == Some(block)),
+            "copy_texture between incompatible formats {:?} and {:?}",
+            src_desc.format,
+            dst_desc.format
+        );
+        assert_eq!(
+            src_desc.sample_count, dst_desc.sample_count,
+            "copy_texture between different sample counts"
+        );
+        let src_region = src_region.resolve(src_desc, "copy_texture source");
+        assert!(
+            dst_region.extent == [0; 3] || dst_region.extent == src_region.extent,
+            "copy_texture destination extent {:?} differs from the source's {:?}",
+            dst_region.extent,
+            src_region.extent
+        );
+        assert_eq!(
+            dst_region.layer_count, src_region.layer_count,
+            "copy_texture between different layer counts"
+        );
+        let dst_region = TextureRegion {
+            extent: src_region.extent,
+            ..dst_region
+        }
+        .resolve(dst_desc, "copy_texture destination");
+        assert!(
+            src.id() != dst.id() || !regions_overlap(&src_region, &dst_region),
+            "copy_texture regions overlap"
+        );
+        backend_dispatch!(&mut self.inner, CommandBufferInner, cmd => cmd.copy_texture(src, &src_region, dst, &dst_region))
+    }
+
+    /// Scale `src_region` of `src` into `dst_region` of `dst`, reading with `filter`, and
+    /// converting between formats (sRGB included) on the way. Layer `i` of the source region
+    /// lands in layer `i` of the destination region, so their layer counts must match.
+    ///
+    /// Both sides need single-sampled, uncompressed, non-integer color formats (blits filter
+    /// and convert through floats, so `R16Uint` and `R32Uint` are rejected), and 3D textures
+    /// are not supported. `src` needs `TRANSFER_SRC` usage and a texture `dst` `TRANSFER_DST`. Formats
+    /// the device cannot filter are read `Nearest` whatever `filter` asks for.
+    pub fn blit_texture<'a>(
+        &mut self,
+        src: &Texture,
+        src_region: TextureRegion,
+        dst: impl Into<BlitTarget<'a>>,
+        dst_region: TextureRegion,
+        filter: FilterMode,
+    ) {
+        let blittable = |texture: &Texture, side: &str| {
+            let desc = texture.desc();
+            assert!(
+                format_block(desc.format).is_some_and(|block| !block.is_compressed()),
+                "blit_texture {side} format {:?} is not an uncompressed color format",
+                desc.format
+            );
+            assert!(
+                !matches!(desc.format, Format::R16Uint | Format::R32Uint),
+                "blit_texture {side} format {:?} is an integer format; use copy_texture",
+                desc.format
+            );
+            assert_eq!(
+                desc.sample_count,
+                SampleCount::S1,
+                "blit_texture {side} is multisampled"
+            );
+            assert_ne!(
+                desc.dimension,
+                TextureDimension::D3,
+                "blit_texture {side} is a 3D texture"
+            );
+        };
+        blittable(src, "source");
+        assert!(
+            src.desc().usage.contains(TextureUsage::TRANSFER_SRC),
+            "blit_texture source needs TRANSFER_SRC usage"
+        );
+        let src_region = src_region.resolve(src.desc(), "blit_texture source");
+        let dst = dst.into();
+        let dst_region = match dst {
+            BlitTarget::Texture(texture) => {
+                blittable(texture, "destination");
+                assert!(
+                    texture.desc().usage.contains(TextureUsage::TRANSFER_DST),
+                    "blit_texture destination needs TRANSFER_DST usage"
+                );
+                let dst_region = dst_region.resolve(texture.desc(), "blit_texture destination");
+                assert!(
+                    src.id() != texture.id() || !regions_overlap(&src_region, &dst_region),
+                    "blit_texture regions overlap"
+                );
+                dst_region
+            }
+            // Extents are filled in by the backend, which knows the swapchain size.
+            BlitTarget::SwapchainImage(_) => {
+                assert!(
+                    dst_region.mip == 0 && dst_region.base_layer == 0 && dst_region.offset[2] == 0,
+                    "blit_texture into a swapchain image has one mip level and layer"
+                );
+                dst_region
+            }
+        };
+        assert_eq!(
+            src_region.layer_count, dst_region.layer_count,
+            "blit_texture between different layer counts"
+        );
+        backend_dispatch!(&mut self.inner, CommandBufferInner, cmd => cmd.blit_texture(src, &src_region, dst, &dst_region, filter))
+    }
+
+    /// Fill mips 1.. of every array layer (and cube face) of `texture` by downsampling mip 0,
+    /// each level from the one above it. sRGB formats are filtered in linear space. Integer
+    /// formats take the nearest texel; so do float formats the device cannot filter on Vulkan,
+    /// while Metal box-filters those in a compute pass (2D, array and cube textures only).
+    ///
+    /// The texture needs `TRANSFER_SRC | TRANSFER_DST` usage and one sample per pixel;
+    /// depth and block-compressed formats are not supported. Order the call after the writes
+    /// to mip 0, and later reads after it, with barriers, as for any other transfer.
+    pub fn generate_mips(&mut self, texture: &crate::texture::Texture) {
+        let desc = texture.desc();
+        assert!(
+            desc.usage
+                .contains(TextureUsage::TRANSFER_SRC | TextureUsage::TRANSFER_DST),
+            "generate_mips needs TRANSFER_SRC | TRANSFER_DST usage"
+        );
+        assert_eq!(
+            desc.sample_count,
+            SampleCount::S1,
+            "generate_mips on a multisampled texture"
+        );
+        if desc.mip_levels <= 1 {
+            return;
+        }
+        backend_dispatch!(&mut self.inner, CommandBufferInner, cmd => cmd.generate_mips(texture))
     }
 
     // -- Barriers --
@@ -331,6 +801,18 @@ impl CommandBuffer {
         backend_dispatch!(&mut self.inner, CommandBufferInner, cmd => cmd.barrier(src, dst))
     }
 
+    /// Barrier for memory changing hands between aliased resources: orders the previous
+    /// owner's work in `src` before `dst`, and discards the contents of `textures`, which take
+    /// the memory over. Their first use must clear or fully overwrite them.
+    pub fn alias_barrier(
+        &mut self,
+        src: StageFlags,
+        dst: StageFlags,
+        textures: &[&crate::texture::Texture],
+    ) {
+        backend_dispatch!(&mut self.inner, CommandBufferInner, cmd => cmd.alias_barrier(src, dst, textures))
+    }
+
     /// Stage barrier with hazard flags.
     pub fn barrier_with_hazard(&mut self, src: StageFlags, dst: StageFlags, hazard: HazardFlags) {
         backend_dispatch!(&mut self.inner, CommandBufferInner, cmd => cmd.barrier_with_hazard(src, dst, hazard))
@@ -392,6 +874,10 @@ impl CommandBuffer {
     /// Finalize command buffer recording.
     /// On Vulkan, calls vkEndCommandBuffer. On Metal, this is a no-op.
     pub fn end(&mut self) {
+        assert_eq!(
+            self.debug_groups, 0,
+            "command buffer ended with unpopped debug groups"
+        );
         match &mut self.inner {
             #[cfg(feature = "vulkan")]
             CommandBufferInner::Vulkan(cmd) => unsafe {
@@ -406,6 +892,92 @@ impl CommandBuffer {
         }
     }
 
+    // -- Queries --
+
+    /// Write the GPU clock to timestamp query `index` of `pool` once earlier commands have
+    /// finished `stage`. Several stages, or `ALL_COMMANDS`, wait for all earlier work. Inside
+    /// a multiview render pass Vulkan writes one query per view, from `index` up.
+    pub fn write_timestamp(&mut self, stage: StageFlags, pool: &QueryPool, index: u32) {
+        assert_eq!(
+            pool.ty(),
+            QueryType::Timestamp,
+            "write_timestamp into a {:?} pool",
+            pool.ty()
+        );
+        pool.check_range(&(index..index + 1), "write_timestamp");
+        backend_dispatch!(&mut self.inner, CommandBufferInner, cmd => cmd.write_timestamp(stage, pool, index))
+    }
+
+    /// Start counting into query `index` of an occlusion or pipeline-statistics `pool`.
+    /// Occlusion queries are recorded inside a render pass; pipeline-statistics ones either
+    /// inside one or outside any, and end where they began. Only one query of each type may
+    /// be active at a time. Inside a multiview render pass Vulkan counts each view into its
+    /// own query, from `index` up, so the pool needs one query per view from `index`.
+    pub fn begin_query(&mut self, pool: &QueryPool, index: u32) {
+        assert_ne!(
+            pool.ty(),
+            QueryType::Timestamp,
+            "begin_query on a timestamp pool; use write_timestamp"
+        );
+        pool.check_range(&self.query_range(index), "begin_query");
+        backend_dispatch!(&mut self.inner, CommandBufferInner, cmd => cmd.begin_query(pool, index))
+    }
+
+    /// Stop counting into query `index` of `pool`, begun by `begin_query`.
+    pub fn end_query(&mut self, pool: &QueryPool, index: u32) {
+        assert_ne!(
+            pool.ty(),
+            QueryType::Timestamp,
+            "end_query on a timestamp pool; use write_timestamp"
+        );
+        pool.check_range(&self.query_range(index), "end_query");
+        backend_dispatch!(&mut self.inner, CommandBufferInner, cmd => cmd.end_query(pool, index))
+    }
+
+    /// Queries a query begun at `index` writes: one per view of the current render pass.
+    fn query_range(&self, index: u32) -> Range<u32> {
+        index..index + self.pass_view_mask.count_ones().max(1)
+    }
+
+    /// Copy the values of `range` of `pool`'s queries to `dst`, `pool.result_size()` bytes
+    /// each, once they have been written. Record it outside render passes. It is transfer
+    /// work: barrier from `StageFlags::TRANSFER` before the GPU reads `dst`, e.g. from a
+    /// culling compute shader.
+    pub fn resolve_queries(&mut self, pool: &QueryPool, range: Range<u32>, dst: GpuAddress) {
+        pool.check_range(&range, "resolve_queries");
+        assert!(
+            dst.0.is_multiple_of(QUERY_RESULT_SIZE),
+            "resolve_queries needs an 8-byte aligned destination, got {:#x}",
+            dst.0
+        );
+        backend_dispatch!(&mut self.inner, CommandBufferInner, cmd => cmd.resolve_queries(pool, range, dst))
+    }
+
+    // -- Debug labels --
+
+    /// Open a group of commands named `name` in validation messages and capture tools, with
+    /// an RGBA `color` where the tool shows one. Groups nest, may span render passes, and are
+    /// closed by `pop_debug_group` before `end`.
+    pub fn push_debug_group(&mut self, name: &str, color: [f32; 4]) {
+        self.debug_groups += 1;
+        backend_dispatch!(&mut self.inner, CommandBufferInner, cmd => cmd.push_debug_group(name, color))
+    }
+
+    /// Close the innermost group opened by `push_debug_group`.
+    pub fn pop_debug_group(&mut self) {
+        assert!(
+            self.debug_groups > 0,
+            "pop_debug_group without a pushed group"
+        );
+        self.debug_groups -= 1;
+        backend_dispatch!(&mut self.inner, CommandBufferInner, cmd => cmd.pop_debug_group())
+    }
+
+    /// Mark the current point of the command stream as `name`.
+    pub fn insert_debug_marker(&mut self, name: &str) {
+        backend_dispatch!(&mut self.inner, CommandBufferInner, cmd => cmd.insert_debug_marker(name))
+    }
+
     // -- Mesh shader (meshlet) draws --
 
     /// Draw using the bound mesh-shader pipeline.
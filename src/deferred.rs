@@ -0,0 +1,151 @@
+//! Deferred destruction of GPU resources.
+//!
+//! `Device::own` wraps a buffer, allocation or texture in an [`Owned`] handle. Dropping the
+//! handle frees nothing immediately: the resource is tagged with the serial of the queue's
+//! latest submission and parked until the GPU has retired that submission. The device then
+//! releases it from `collect_garbage`, `wait_for_frame` or `wait_idle`.
+//!
+//! The serial is read at drop time, so drop a handle after submitting the work that uses it.
+//! A command buffer recorded against the resource but submitted after the drop is not covered.
+//! Render and compute bundles are always parked this way when dropped.
+
+use std::collections::VecDeque;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex};
+
+use crate::memory::{GpuAllocation, GpuBuffer};
+use crate::texture::Texture;
+
+pub(crate) use sealed::Garbage;
+
+/// Resources dropped through `Owned` handles, waiting for their submission to retire. One per
+/// queue; the queue records each submission's serial here.
+#[derive(Default)]
+pub(crate) struct Graveyard {
+    /// Serial of the most recent submission on the queue.
+    submitted: u64,
+    /// Parked resources and the serial that must complete first, in drop order (so the serials
+    /// never decrease).
+    pending: VecDeque<(u64, Garbage)>,
+}
+
+pub(crate) type SharedGraveyard = Arc<Mutex<Graveyard>>;
+
+impl Graveyard {
+    /// Record that a submission with `serial` was made.
+    pub(crate) fn note_submission(&mut self, serial: u64) {
+        self.submitted = self.submitted.max(serial);
+    }
+
+    pub(crate) fn bury(&mut self, garbage: Garbage) {
+        self.pending.push_back((self.submitted, garbage));
+    }
+
+    /// Remove every parked resource whose submission has completed.
+    pub(crate) fn take_retired(&mut self, completed: u64) -> Vec<Garbage> {
+        let retired = self
+            .pending
+            .iter()
+            .take_while(|(serial, _)| *serial <= completed)
+            .count();
+        self.pending.drain(..retired).map(|(_, g)| g).collect()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
+
+mod sealed {
+    use crate::memory::GpuBuffer;
+    use crate::texture::Texture;
+
+    /// A resource parked for destruction.
+    pub enum Garbage {
+        Buffer(GpuBuffer),
+        Texture(Texture),
+        /// Released by its own `Drop`, such as a command bundle.
+        Dropped(Box<dyn Send>),
+    }
+
+    pub trait Sealed {
+        fn into_garbage(self) -> Garbage;
+    }
+}
+
+/// Resources `Device::own` can manage: [`GpuBuffer`], [`GpuAllocation`] and [`Texture`].
+pub trait DeferredDestroy: sealed::Sealed {}
+
+impl sealed::Sealed for GpuBuffer {
+    fn into_garbage(self) -> Garbage {
+        Garbage::Buffer(self)
+    }
+}
+impl DeferredDestroy for GpuBuffer {}
+
+impl sealed::Sealed for GpuAllocation {
+    fn into_garbage(self) -> Garbage {
+        Garbage::Buffer(self.into_buffer())
+    }
+}
+impl DeferredDestroy for GpuAllocation {}
+
+impl sealed::Sealed for Texture {
+    fn into_garbage(self) -> Garbage {
+        Garbage::Texture(self)
+    }
+}
+impl DeferredDestroy for Texture {}
+
+/// A resource destroyed by the device after the handle is dropped and the GPU has retired
+/// every submission made up to that point. Derefs to the wrapped resource.
+///
+/// A texture's backing allocation is a separate resource; own both (or neither) and drop the
+/// texture first.
+pub struct Owned<T: DeferredDestroy> {
+    resource: Option<T>,
+    graveyard: SharedGraveyard,
+}
+
+impl<T: DeferredDestroy> Owned<T> {
+    pub(crate) fn new(resource: T, graveyard: SharedGraveyard) -> Self {
+        Self {
+            resource: Some(resource),
+            graveyard,
+        }
+    }
+
+    /// Take the resource back out. The caller is again responsible for destroying it.
+    pub fn into_inner(mut self) -> T {
+        self.resource.take().expect("owned resource already taken")
+    }
+}
+
+impl<T: DeferredDestroy> Deref for Owned<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.resource
+            .as_ref()
+            .expect("owned resource already taken")
+    }
+}
+
+impl<T: DeferredDestroy> DerefMut for Owned<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.resource
+            .as_mut()
+            .expect("owned resource already taken")
+    }
+}
+
+impl<T: DeferredDestroy> Drop for Owned<T> {
+    fn drop(&mut self) {
+        if let Some(resource) = self.resource.take() {
+            self.graveyard
+                .lock()
+                .expect("graveyard lock poisoned")
+                .bury(resource.into_garbage());
+        }
+    }
+}
@@ -1,18 +1,29 @@
 use crate::accel::AccelerationStructure;
+use crate::bindless::DescriptorHeapStats;
+use crate::bundle::{
+    ComputeBundleDesc, ComputeBundleEncoder, RenderBundleDesc, RenderBundleEncoder,
+};
 use crate::command::CommandBuffer;
+use crate::deferred::{DeferredDestroy, Garbage, Owned};
 use crate::error::{RhiError, RhiResult};
-use crate::memory::{BufferDesc, GpuAllocation, GpuBuffer, MemoryType};
+use crate::memory::{
+    BufferDesc, ExternalMemoryHandle, GpuAllocation, GpuBuffer, MemoryStats, MemoryType,
+};
 use crate::pipeline::{
     ComputePso, ComputePsoDesc, GraphicsPso, GraphicsPsoDesc, MeshletPso, MeshletPsoDesc,
 };
+use crate::query::{QueryPool, QueryPoolDesc, QueryType};
 use crate::queue::Queue;
 use crate::sampler::{Sampler, SamplerDesc};
 use crate::shader::{ShaderModule, ShaderModuleDesc, ShaderModuleInner};
 use crate::surface::{Surface, SurfaceDesc};
 use crate::swapchain::{Swapchain, SwapchainDesc};
 use crate::sync::TimelineSemaphore;
-use crate::texture::{GpuViewDesc, Texture, TextureDesc, TextureSizeAlign};
-use crate::types::{BlasDesc, ClipSpaceY, GpuAddress, TlasDesc, TlasInstance};
+use crate::texture::{
+    FormatCaps, GpuViewDesc, Texture, TextureCompression, TextureDesc, TextureSizeAlign,
+    format_compression,
+};
+use crate::types::{BlasDesc, ClipSpaceY, Format, GpuAddress, TlasDesc, TlasInstance};
 
 /// Which GPU backend to use.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -65,7 +76,8 @@ impl Default for DeviceDesc {
 }
 
 /// The RHI device -- central object for resource creation.
-/// Uses enum dispatch for zero-cost backend selection.
+/// Uses enum dispatch for zero-cost backend selection. The device is `Sync`, so it can be
+/// shared by reference with recording threads.
 pub struct Device {
     pub(crate) inner: DeviceInner,
 }
@@ -171,7 +183,8 @@ impl Device {
         backend_dispatch!(&self.inner, DeviceInner, d => d.recreate_swapchain(swapchain, desc))
     }
 
-    /// Create a GPU buffer.
+    /// Create a GPU buffer. Buffers up to half a memory block are sub-allocated from shared
+    /// per-`MemoryType` blocks; larger ones get a dedicated allocation.
     pub fn create_buffer(&self, desc: &BufferDesc) -> RhiResult<GpuBuffer> {
         backend_dispatch!(&self.inner, DeviceInner, d => d.create_buffer(desc))
     }
@@ -188,14 +201,23 @@ impl Device {
         align: u64,
         memory: MemoryType,
     ) -> RhiResult<GpuAllocation> {
-        let align = align.max(1);
-        assert!(align.is_power_of_two(), "alignment must be a power of two");
-
-        let buffer = self.create_buffer(&BufferDesc {
+        let desc = BufferDesc {
             size,
             memory,
             label: None,
-        })?;
+            export: None,
+        };
+        self.malloc_with(&desc, align)
+    }
+
+    /// Allocate GPU memory described by `desc` (e.g. exportable memory) with explicit
+    /// alignment.
+    pub fn malloc_with(&self, desc: &BufferDesc, align: u64) -> RhiResult<GpuAllocation> {
+        let align = align.max(1);
+        assert!(align.is_power_of_two(), "alignment must be a power of two");
+
+        let buffer =
+            backend_dispatch!(&self.inner, DeviceInner, d => d.create_buffer_aligned(desc, align))?;
 
         debug_assert_eq!(
             buffer.gpu().0 & (align - 1),
@@ -203,6 +225,24 @@ impl Device {
             "backend returned a misaligned GPU address for malloc_aligned",
         );
 
+        Ok(GpuAllocation {
+            buffer,
+            size: desc.size,
+        })
+    }
+
+    /// Import memory exported by another device or process as `handle`, `size` bytes long.
+    /// The import is a normal allocation, mapped if `memory` is CPU-visible; free it with
+    /// `free`. On success the device takes ownership of `fd`.
+    #[cfg(unix)]
+    pub fn import_memory_fd(
+        &self,
+        fd: std::os::fd::OwnedFd,
+        handle: ExternalMemoryHandle,
+        size: u64,
+        memory: MemoryType,
+    ) -> RhiResult<GpuAllocation> {
+        let buffer = backend_dispatch!(&self.inner, DeviceInner, d => d.import_memory_fd(fd, handle, size, memory))?;
         Ok(GpuAllocation { buffer, size })
     }
 
@@ -211,13 +251,37 @@ impl Device {
         self.destroy_buffer(allocation.into_buffer());
     }
 
+    /// Snapshot of live buffer usage per `MemoryType`, shared block usage, and per-heap
+    /// budgets. Cheap enough to call once a frame for an overlay, not per allocation.
+    pub fn memory_stats(&self) -> MemoryStats {
+        backend_dispatch!(&self.inner, DeviceInner, d => d.memory_stats())
+    }
+
     /// Translate a CPU-mapped pointer to a GPU virtual address, if possible.
     pub fn host_to_device_pointer(&self, cpu_ptr: *const u8) -> Option<GpuAddress> {
         backend_dispatch!(&self.inner, DeviceInner, d => d.host_to_device_pointer(cpu_ptr))
     }
 
+    /// Translate a GPU virtual address inside a CPU-mapped allocation to its host pointer,
+    /// if possible. The inverse of `host_to_device_pointer`.
+    pub fn device_to_host_pointer(&self, addr: GpuAddress) -> Option<*mut u8> {
+        backend_dispatch!(&self.inner, DeviceInner, d => d.device_to_host_pointer(addr))
+    }
+
+    /// What textures of `format` can be used for on this device, for picking fallback formats
+    /// up front.
+    pub fn format_caps(&self, format: Format) -> FormatCaps {
+        backend_dispatch!(&self.inner, DeviceInner, d => d.format_caps(format))
+    }
+
+    /// Block-compressed format families textures can be created in.
+    pub fn texture_compression(&self) -> TextureCompression {
+        backend_dispatch!(&self.inner, DeviceInner, d => d.texture_compression())
+    }
+
     /// Query the size/alignment required for `create_texture`.
     pub fn texture_size_align(&self, desc: &TextureDesc) -> RhiResult<TextureSizeAlign> {
+        self.check_texture_format(desc)?;
         backend_dispatch!(&self.inner, DeviceInner, d => d.texture_size_align(desc))
     }
 
@@ -228,9 +292,24 @@ impl Device {
         desc: &TextureDesc,
         texture_gpu: GpuAddress,
     ) -> RhiResult<Texture> {
+        self.check_texture_format(desc)?;
         backend_dispatch!(&self.inner, DeviceInner, d => d.create_texture(desc, texture_gpu))
     }
 
+    fn check_texture_format(&self, desc: &TextureDesc) -> RhiResult<()> {
+        if self
+            .texture_compression()
+            .contains(format_compression(desc.format))
+        {
+            Ok(())
+        } else {
+            Err(RhiError::Unsupported(format!(
+                "{:?} textures are not supported on this device",
+                desc.format
+            )))
+        }
+    }
+
     /// Register a sampled (SRV) view of `source` in the bindless heap, returning its `TextureId`.
     /// The view shares `source`'s storage; using the id after `source` is destroyed is UB.
     pub fn texture_view_descriptor(
@@ -255,6 +334,17 @@ impl Device {
         backend_dispatch!(&self.inner, DeviceInner, d => d.create_sampler(desc))
     }
 
+    /// Destroy a sampler. Its `SamplerId` is handed out again once every submission made up
+    /// to now has retired.
+    pub fn destroy_sampler(&self, sampler: Sampler) {
+        backend_dispatch!(&self.inner, DeviceInner, d => d.destroy_sampler(sampler))
+    }
+
+    /// Slot usage of the bindless texture and sampler heaps.
+    pub fn descriptor_heap_stats(&self) -> DescriptorHeapStats {
+        backend_dispatch!(&self.inner, DeviceInner, d => d.descriptor_heap_stats())
+    }
+
     /// Create a shader module.
     pub fn create_shader_module(&self, desc: &ShaderModuleDesc) -> RhiResult<ShaderModule> {
         backend_dispatch!(&self.inner, DeviceInner, d => d.create_shader_module(desc))
@@ -376,7 +466,9 @@ impl Device {
         Ok(())
     }
 
-    /// Create a transient command buffer for recording.
+    /// Create a transient command buffer for recording. Command buffers are `Send`, and each
+    /// records into a command pool of its own, so several threads can record at once; see
+    /// [`Queue::submit_batch`].
     pub fn create_command_buffer(&self) -> RhiResult<CommandBuffer> {
         backend_dispatch!(&self.inner, DeviceInner, d => d.create_command_buffer())
     }
@@ -390,6 +482,33 @@ impl Device {
         backend_dispatch!(&self.inner, DeviceInner, d => d.create_command_buffer_for_swapchain(swapchain))
     }
 
+    /// Start recording a render bundle for passes with the attachments of `desc`. The
+    /// encoder is `Send`; record on any thread.
+    pub fn create_render_bundle_encoder(
+        &self,
+        desc: &RenderBundleDesc,
+    ) -> RhiResult<RenderBundleEncoder> {
+        let inner =
+            backend_dispatch!(&self.inner, DeviceInner, d => d.create_render_bundle_encoder(desc))?;
+        Ok(RenderBundleEncoder::new(
+            inner,
+            desc.view_mask,
+            self.queue().graveyard.clone(),
+        ))
+    }
+
+    /// Start recording a compute bundle.
+    pub fn create_compute_bundle_encoder(
+        &self,
+        desc: &ComputeBundleDesc,
+    ) -> RhiResult<ComputeBundleEncoder> {
+        let inner = backend_dispatch!(&self.inner, DeviceInner, d => d.create_compute_bundle_encoder(desc))?;
+        Ok(ComputeBundleEncoder::new(
+            inner,
+            self.queue().graveyard.clone(),
+        ))
+    }
+
     /// Get the primary queue.
     pub fn queue(&self) -> &Queue {
         backend_dispatch!(&self.inner, DeviceInner, d => d.queue())
@@ -400,9 +519,58 @@ impl Device {
         backend_dispatch!(&self.inner, DeviceInner, d => d.create_timeline_semaphore(initial_value))
     }
 
-    /// Wait for the device to be idle.
+    /// Create a pool of GPU queries. Returns `RhiError::Unsupported` for query types the
+    /// device lacks: timestamps on queues without a clock, precise occlusion, and pipeline
+    /// statistics (never available on Metal). On Vulkan every type needs `hostQueryReset`.
+    pub fn create_query_pool(&self, desc: &QueryPoolDesc) -> RhiResult<QueryPool> {
+        assert!(desc.count > 0, "query pool of zero queries");
+        if let QueryType::PipelineStatistics(stats) = desc.ty {
+            assert!(
+                !stats.is_empty(),
+                "pipeline-statistics pool counting nothing"
+            );
+        }
+        backend_dispatch!(&self.inner, DeviceInner, d => d.create_query_pool(desc))
+    }
+
+    /// Nanoseconds per tick of the timestamps `write_timestamp` records.
+    pub fn timestamp_period(&self) -> f64 {
+        backend_dispatch!(&self.inner, DeviceInner, d => d.timestamp_period())
+    }
+
+    /// Wait for the device to be idle. Releases every resource parked by a dropped `Owned`.
     pub fn wait_idle(&self) {
-        backend_dispatch!(&self.inner, DeviceInner, d => d.wait_idle())
+        backend_dispatch!(&self.inner, DeviceInner, d => d.wait_idle());
+        self.collect_garbage();
+    }
+
+    /// Hand `resource` to the device. Dropping the returned handle queues the resource for
+    /// destruction once the GPU has retired every submission made before the drop, instead of
+    /// requiring `free`/`destroy_buffer`/`destroy_texture`.
+    pub fn own<T: DeferredDestroy>(&self, resource: T) -> Owned<T> {
+        Owned::new(resource, self.queue().graveyard.clone())
+    }
+
+    /// Destroy resources dropped through `Owned` handles whose submissions have retired, and
+    /// return how many were released. Also runs from `wait_for_frame` and `wait_idle`; call it
+    /// directly in loops that do neither.
+    pub fn collect_garbage(&self) -> usize {
+        let queue = self.queue();
+        let completed = queue.completed_serial();
+        let retired = queue
+            .graveyard
+            .lock()
+            .expect("graveyard lock poisoned")
+            .take_retired(completed);
+        let count = retired.len();
+        for garbage in retired {
+            match garbage {
+                Garbage::Buffer(buffer) => self.destroy_buffer(buffer),
+                Garbage::Texture(texture) => self.destroy_texture(texture),
+                Garbage::Dropped(resource) => drop(resource),
+            }
+        }
+        count
     }
 
     /// Destroy a buffer.
@@ -410,14 +578,23 @@ impl Device {
         backend_dispatch!(&self.inner, DeviceInner, d => d.destroy_buffer(buffer))
     }
 
-    /// Destroy a texture.
+    /// Destroy a texture. Its `TextureId` is handed out again once every submission made up
+    /// to now has retired.
     pub fn destroy_texture(&self, texture: Texture) {
         backend_dispatch!(&self.inner, DeviceInner, d => d.destroy_texture(texture))
     }
 
-    /// Wait for a specific frame's fence before reusing resources.
+    /// Destroy a view registered with `texture_view_descriptor` or
+    /// `rw_texture_view_descriptor`, recycling its `TextureId` like `destroy_texture`.
+    pub fn destroy_texture_view(&self, id: crate::types::TextureId) {
+        backend_dispatch!(&self.inner, DeviceInner, d => d.destroy_texture_view(id))
+    }
+
+    /// Wait for a specific frame's fence before reusing resources, then release whatever
+    /// `Owned` resources have retired.
     pub fn wait_for_frame(&self, frame_index: usize) {
-        backend_dispatch!(&self.inner, DeviceInner, d => d.wait_for_frame(frame_index))
+        backend_dispatch!(&self.inner, DeviceInner, d => d.wait_for_frame(frame_index));
+        self.collect_garbage();
     }
 
     /// Get raw Vulkan handles for escape-hatch scenarios (e.g. ImGui).
@@ -432,6 +609,21 @@ impl Device {
     }
 }
 
+impl Drop for Device {
+    fn drop(&mut self) {
+        // Release parked `Owned` resources before the backend tears down.
+        let parked = !self
+            .queue()
+            .graveyard
+            .lock()
+            .expect("graveyard lock poisoned")
+            .is_empty();
+        if parked {
+            self.wait_idle();
+        }
+    }
+}
+
 impl CommandBuffer {
     /// Get the raw Vulkan command buffer handle for escape-hatch scenarios.
     #[cfg(feature = "vulkan")]
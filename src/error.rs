@@ -47,6 +47,9 @@ pub enum RhiError {
     #[error("Unsupported: {0}")]
     Unsupported(String),
 
+    #[error("Invalid argument: {0}")]
+    InvalidArgument(String),
+
     #[error("Backend error: {0}")]
     Backend(String),
 }
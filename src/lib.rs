@@ -20,19 +20,27 @@ mod macros;
 pub mod accel;
 pub mod backend;
 pub mod barrier;
+pub mod bindless;
+pub mod bundle;
 pub mod command;
+pub mod deferred;
 pub mod device;
 pub mod error;
 pub mod memory;
 pub mod pipeline;
+pub mod profiler;
+pub mod query;
 pub mod queue;
+pub mod readback;
 pub mod sampler;
 pub mod shader;
 pub mod surface;
 pub mod swapchain;
 pub mod sync;
 pub mod texture;
+pub mod transient;
 pub mod types;
+pub mod upload;
 
 // The RHI is built around zerocopy for its GPU data contract (`GpuPod`, `gpu_struct!`,
 // the indirect-args structs). Re-export it so the `gpu_struct!` macro and downstream
@@ -42,26 +50,42 @@ pub use zerocopy;
 // Re-export core types at crate root for convenience
 pub use accel::AccelerationStructure;
 pub use barrier::{HazardFlags, StageFlags};
+pub use bindless::{DescriptorHeapStats, HeapOccupancy};
+pub use bundle::{
+    ComputeBundle, ComputeBundleDesc, ComputeBundleEncoder, RenderBundle, RenderBundleDesc,
+    RenderBundleEncoder,
+};
 pub use command::{
-    ColorAttachment, CommandBuffer, DepthAttachment, DispatchIndirectArgs, DrawIndexedIndirectArgs,
-    DrawIndirectArgs, DrawIndirectMultiArgs, LoadOp, RenderPassDesc, RenderTarget, SignalOp,
-    SignalValueDesc, StoreOp, WaitOp, WaitValueDesc,
+    BlitTarget, ClearValue, ColorAttachment, CommandBuffer, DepthAttachment, DepthResolveMode,
+    DispatchIndirectArgs, DrawIndexedIndirectArgs, DrawIndirectArgs, DrawIndirectMultiArgs, LoadOp,
+    RenderPassDesc, RenderTarget, SignalOp, SignalValueDesc, StoreOp, WaitOp, WaitValueDesc,
 };
+pub use deferred::{DeferredDestroy, Owned};
 pub use device::{Backend, BindlessMode, Device, DeviceDesc};
 pub use error::{RhiError, RhiResult};
 pub use memory::{
-    BufferDesc, BumpAllocator, GpuAllocation, GpuBuffer, GpuPod, MemoryType, TransientAllocation,
+    BufferDesc, BumpAllocator, ExternalMemoryHandle, FrameRingAllocator, GpuAllocation, GpuBuffer,
+    GpuPod, MemoryHeapBudget, MemoryStats, MemoryType, MemoryTypeStats, TransientAllocation,
 };
 pub use pipeline::*;
+pub use profiler::{GpuProfiler, ProfileScope, ScopeTiming};
+pub use query::{PipelineStatistics, QUERY_RESULT_SIZE, QueryPool, QueryPoolDesc, QueryType};
 pub use queue::Queue;
+pub use readback::{DEFAULT_READBACK_CHUNK_SIZE, Readback, ReadbackData, ReadbackHandle};
 pub use sampler::{Sampler, SamplerDesc};
 pub use shader::{ShaderModule, ShaderModuleDesc, ShaderStage};
 pub use surface::{Surface, SurfaceDesc};
 pub use swapchain::{AcquiredImage, Swapchain, SwapchainDesc};
 pub use sync::TimelineSemaphore;
-pub use texture::{ALL_LAYERS, ALL_MIPS, GpuViewDesc, Texture, TextureDesc, TextureUsage};
+pub use texture::{
+    ALL_LAYERS, ALL_MIPS, FormatBlock, FormatCaps, GpuViewDesc, Texture, TextureCompression,
+    TextureCopyRegion, TextureDesc, TextureRegion, TextureSubresourceRange, TextureUsage,
+    format_block,
+};
+pub use transient::{TransientTexture, TransientTexturePool};
 pub use types::*;
 pub use types::{
     AccelerationStructureId, BlasDesc, BlasMeshDesc, BuildAccelFlags, GeometryFlags, GeometryType,
     InstanceFlags, TlasDesc, TlasInstance,
 };
+pub use upload::{DEFAULT_STAGING_CHUNK_SIZE, UploadToken, Uploader};
@@ -15,26 +15,64 @@ macro_rules! backend_dispatch {
 
 /// Define a GPU-facing struct once, generating the `#[repr(C)]` [`GpuPod`](crate::GpuPod) Rust
 /// type and a matching Slang declaration string `Name::SLANG` to prepend to a shader — keeping
-/// the host/device layout in lockstep. Each field gives its Rust and Slang type. Must be
-/// padding-free (add explicit tail padding where alignment would insert it).
+/// the host/device layout in lockstep. Each field gives its Rust and Slang type, except
+/// [`GpuPtr<T>`](crate::GpuPtr) fields, whose Slang type `T*` is derived: scalar pointees map
+/// to their Slang names, paths to their last segment and arrays to `T[N]`. Other pointees keep
+/// their Rust spelling; give those an explicit `as` Slang type. Must be padding-free (add
+/// explicit tail padding where alignment would insert it).
 ///
 /// ```ignore
 /// gpu_struct! {
 ///     pub struct Material {
 ///         albedo: u32 as "uint",            // bindless texture id
 ///         tint:   [f32; 4] as "float4",
-///         data:   GpuAddress as "Surface*", // 64-bit GPU pointer
+///         data:   GpuAddress as "Surface*", // untyped 64-bit GPU pointer
+///         verts:  GpuPtr<Vertex>,           // typed pointer, emits `Vertex* verts;`
+///         bounds: GpuPtr<[f32; 4]>,         // emits `float[4]* bounds;`
 ///     }
 /// }
 /// ```
 #[macro_export]
 macro_rules! gpu_struct {
-    (
-        $(#[$meta:meta])*
-        $vis:vis struct $name:ident {
-            $( $fname:ident : $fty:ty as $slang:literal ),* $(,)?
-        }
+    // Field muncher: accumulate Rust fields and Slang lines, one field at a time.
+    (@munch $meta:tt $vis:tt $name:ident [$($rust:tt)*] [$($slang:tt)*]
+        $fname:ident : GpuPtr<$($seg:ident)::+> $(, $($rest:tt)*)?
     ) => {
+        $crate::gpu_struct!(@munch $meta $vis $name
+            [$($rust)* $fname : $crate::GpuPtr<$($seg)::+>,]
+            [$($slang)* "    ", $crate::gpu_struct!(@pointee $($seg)::+), "* ", stringify!($fname), ";\n",]
+            $($($rest)*)?
+        );
+    };
+    (@munch $meta:tt $vis:tt $name:ident [$($rust:tt)*] [$($slang:tt)*]
+        $fname:ident : GpuPtr<[$($seg:ident)::+; $len:expr]> $(, $($rest:tt)*)?
+    ) => {
+        $crate::gpu_struct!(@munch $meta $vis $name
+            [$($rust)* $fname : $crate::GpuPtr<[$($seg)::+; $len]>,]
+            [$($slang)* "    ", $crate::gpu_struct!(@pointee $($seg)::+), "[", stringify!($len), "]* ",
+                stringify!($fname), ";\n",]
+            $($($rest)*)?
+        );
+    };
+    (@munch $meta:tt $vis:tt $name:ident [$($rust:tt)*] [$($slang:tt)*]
+        $fname:ident : GpuPtr<$pointee:ty> $(, $($rest:tt)*)?
+    ) => {
+        $crate::gpu_struct!(@munch $meta $vis $name
+            [$($rust)* $fname : $crate::GpuPtr<$pointee>,]
+            [$($slang)* "    ", stringify!($pointee), "* ", stringify!($fname), ";\n",]
+            $($($rest)*)?
+        );
+    };
+    (@munch $meta:tt $vis:tt $name:ident [$($rust:tt)*] [$($slang:tt)*]
+        $fname:ident : $fty:ty as $slang_ty:literal $(, $($rest:tt)*)?
+    ) => {
+        $crate::gpu_struct!(@munch $meta $vis $name
+            [$($rust)* $fname : $fty,]
+            [$($slang)* "    ", $slang_ty, " ", stringify!($fname), ";\n",]
+            $($($rest)*)?
+        );
+    };
+    (@munch [$(#[$meta:meta])*] [$vis:vis] $name:ident [$($fname:ident : $fty:ty,)*] [$($slang:tt)*]) => {
         $(#[$meta])*
         #[repr(C)]
         #[derive(
@@ -51,9 +89,30 @@ macro_rules! gpu_struct {
             /// Slang declaration matching this struct's layout; prepend to shader source.
             pub const SLANG: &'static str = concat!(
                 "struct ", stringify!($name), " {\n",
-                $( "    ", $slang, " ", stringify!($fname), ";\n", )*
+                $($slang)*
                 "};\n"
             );
         }
     };
+    // Slang spelling of a `GpuPtr` pointee.
+    (@pointee u8) => { "uint8_t" };
+    (@pointee u16) => { "uint16_t" };
+    (@pointee u32) => { "uint" };
+    (@pointee u64) => { "uint64_t" };
+    (@pointee i8) => { "int8_t" };
+    (@pointee i16) => { "int16_t" };
+    (@pointee i32) => { "int" };
+    (@pointee i64) => { "int64_t" };
+    (@pointee f32) => { "float" };
+    (@pointee f64) => { "double" };
+    (@pointee $pointee:ident) => { stringify!($pointee) };
+    (@pointee $module:ident :: $($rest:tt)+) => { $crate::gpu_struct!(@pointee $($rest)+) };
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $($fields:tt)*
+        }
+    ) => {
+        $crate::gpu_struct!(@munch [$(#[$meta])*] [$vis] $name [] [] $($fields)*);
+    };
 }
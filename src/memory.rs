@@ -1,4 +1,5 @@
-use crate::types::GpuAddress;
+use crate::device::Device;
+use crate::types::{GpuAddress, GpuPtr, GpuSlice};
 use crate::{RhiError, RhiResult};
 use zerocopy::{FromBytes, IntoBytes};
 
@@ -37,11 +38,19 @@ fn mapped_read<T: GpuPod>(ptr: Option<*mut u8>, capacity: u64) -> RhiResult<T> {
     T::read_from_bytes(bytes).map_err(|_| RhiError::AllocationFailed("read size mismatch".into()))
 }
 
+fn typed_slice<T: GpuPod>(gpu: GpuAddress, size: u64) -> GpuSlice<T> {
+    let len = size
+        .checked_div(std::mem::size_of::<T>() as u64)
+        .unwrap_or(0);
+    GpuSlice::new(GpuPtr::new(gpu), len)
+}
+
 /// Memory residency for GPU allocations.
 ///
 /// - `Default`: CPU-mapped, write-combined. Uniforms, staging, draw args, descriptors.
 /// - `GpuOnly`: device-local, not CPU-mapped. Textures and large persistent buffers.
-/// - `Readback`: GPU-writable, CPU-cached on read. Screenshots, feedback, GPGPU output.
+/// - `Readback`: GPU-writable, CPU-coherent and cached where the device allows. Screenshots,
+///   feedback, GPGPU output.
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
 pub enum MemoryType {
     #[default]
@@ -50,12 +59,96 @@ pub enum MemoryType {
     Readback,
 }
 
+/// Usage of one `MemoryType`, as reported by `Device::memory_stats`.
+///
+/// `allocation_count`/`allocated_bytes` count live buffers at their requested size;
+/// `block_count`/`block_bytes` count the shared blocks small buffers are carved from, so
+/// `block_bytes - (sub-allocated bytes)` is memory reserved but not handed out.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MemoryTypeStats {
+    pub allocation_count: u64,
+    pub allocated_bytes: u64,
+    pub largest_allocation: u64,
+    pub block_count: u64,
+    pub block_bytes: u64,
+}
+
+impl MemoryTypeStats {
+    pub(crate) fn record_allocation(&mut self, size: u64) {
+        self.allocation_count += 1;
+        self.allocated_bytes += size;
+        self.largest_allocation = self.largest_allocation.max(size);
+    }
+}
+
+/// Budget and usage of one physical memory heap.
+///
+/// `budget` is how much this process can allocate from the heap before the driver starts
+/// failing or evicting; `usage` is how much it currently holds. Where the platform has no
+/// budget query (Vulkan without `VK_EXT_memory_budget`) `budget` is the heap size and
+/// `usage` is 0.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MemoryHeapBudget {
+    pub size: u64,
+    pub budget: u64,
+    pub usage: u64,
+    pub device_local: bool,
+}
+
+/// Snapshot of the device's memory usage, from `Device::memory_stats`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MemoryStats {
+    pub default: MemoryTypeStats,
+    pub gpu_only: MemoryTypeStats,
+    pub readback: MemoryTypeStats,
+    /// One entry per physical heap. Metal reports a single heap.
+    pub heaps: Vec<MemoryHeapBudget>,
+}
+
+impl MemoryStats {
+    /// Stats for one `MemoryType`.
+    pub fn of(&self, memory: MemoryType) -> &MemoryTypeStats {
+        match memory {
+            MemoryType::Default => &self.default,
+            MemoryType::GpuOnly => &self.gpu_only,
+            MemoryType::Readback => &self.readback,
+        }
+    }
+
+    pub(crate) fn of_mut(&mut self, memory: MemoryType) -> &mut MemoryTypeStats {
+        match memory {
+            MemoryType::Default => &mut self.default,
+            MemoryType::GpuOnly => &mut self.gpu_only,
+            MemoryType::Readback => &mut self.readback,
+        }
+    }
+
+    /// Total bytes in live buffers across all memory types.
+    pub fn allocated_bytes(&self) -> u64 {
+        self.default.allocated_bytes + self.gpu_only.allocated_bytes + self.readback.allocated_bytes
+    }
+}
+
+/// OS handle type GPU memory is shared through.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ExternalMemoryHandle {
+    /// Driver-specific fd, only meaningful to another Vulkan instance on the same device
+    /// (`VK_KHR_external_memory_fd`).
+    OpaqueFd,
+    /// Linux dma-buf fd, understood by encoders, compositors and other APIs
+    /// (`VK_EXT_external_memory_dma_buf`).
+    DmaBuf,
+}
+
 /// Description for creating a GPU buffer.
 #[derive(Clone, Debug, Default)]
 pub struct BufferDesc {
     pub size: u64,
     pub memory: MemoryType,
     pub label: Option<String>,
+    /// Make the memory exportable through `export_fd` as this handle type. Exportable buffers
+    /// always get memory of their own rather than a slice of a shared block.
+    pub export: Option<ExternalMemoryHandle>,
 }
 
 /// A GPU allocation: a CPU-mapped pointer + GPU address over a backing buffer.
@@ -80,11 +173,22 @@ impl GpuAllocation {
         self.size
     }
 
+    /// The allocation as a typed GPU array of as many whole `T`s as fit.
+    pub fn typed<T: GpuPod>(&self) -> GpuSlice<T> {
+        typed_slice(self.gpu(), self.size)
+    }
+
     /// Consume the allocation and return the backing buffer.
     pub fn into_buffer(self) -> GpuBuffer {
         self.buffer
     }
 
+    /// Export the allocation's memory as a file descriptor. See [`GpuBuffer::export_fd`].
+    #[cfg(unix)]
+    pub fn export_fd(&self) -> RhiResult<std::os::fd::OwnedFd> {
+        self.buffer.export_fd()
+    }
+
     /// Upload a value into CPU-mapped memory (bounds-checked). Caller orders the write before
     /// the dependent submit.
     pub fn upload<T: GpuPod>(&self, value: &T) -> RhiResult<()> {
@@ -157,6 +261,14 @@ impl GpuBuffer {
     pub fn size(&self) -> u64 {
         backend_dispatch!(&self.inner, GpuBufferInner, b => b.size())
     }
+
+    /// Export the buffer's memory as a new file descriptor of the handle type it was created
+    /// with (`BufferDesc::export`). The memory stays alive until both the buffer is destroyed
+    /// and every importer has released it.
+    #[cfg(unix)]
+    pub fn export_fd(&self) -> RhiResult<std::os::fd::OwnedFd> {
+        backend_dispatch!(&self.inner, GpuBufferInner, b => b.export_fd())
+    }
 }
 
 /// Dual-pointer transient allocation from the bump allocator (the doc's `{ cpu, gpu }`).
@@ -168,6 +280,11 @@ pub struct TransientAllocation {
 }
 
 impl TransientAllocation {
+    /// The allocation as a typed GPU array of as many whole `T`s as fit.
+    pub fn typed<T: GpuPod>(&self) -> GpuSlice<T> {
+        typed_slice(self.gpu, self.size)
+    }
+
     /// Write a value into CPU-mapped memory (bounds-checked).
     pub fn upload<T: GpuPod>(&self, data: &T) -> RhiResult<()> {
         mapped_write(Some(self.cpu), self.size, data.as_bytes())
@@ -246,6 +363,138 @@ impl BumpAllocator {
     }
 }
 
+/// Alignment of every `FrameRingAllocator` arena's base address, so allocation alignments up
+/// to this hold in GPU address space and not just within the arena.
+const RING_ARENA_ALIGNMENT: u64 = 64 << 10;
+
+/// Per-frame transient allocator cycling through one region per frame in flight.
+///
+/// `begin_frame` moves to the next region and reclaims it: if the submissions made while that
+/// region was last current have not completed on the GPU yet, it waits for them, then resets
+/// the region. A full region grows by another arena instead of failing; the extra arena stays
+/// with the region for later frames.
+pub struct FrameRingAllocator {
+    regions: Vec<FrameRegion>,
+    current: usize,
+    region_size: u64,
+}
+
+struct FrameRegion {
+    arenas: Vec<BumpAllocator>,
+    /// Queue serial that must complete before the region may be reset.
+    retire_serial: u64,
+}
+
+fn new_ring_arena(device: &Device, size: u64) -> RhiResult<BumpAllocator> {
+    let allocation = device.malloc_aligned(size, RING_ARENA_ALIGNMENT, MemoryType::Default)?;
+    Ok(BumpAllocator::new(allocation.into_buffer()))
+}
+
+impl FrameRingAllocator {
+    /// `frames` regions of `region_size` bytes of `Default` memory each. Pass
+    /// `MAX_FRAMES_IN_FLIGHT` to match the swapchain.
+    pub fn new(device: &Device, frames: usize, region_size: u64) -> RhiResult<Self> {
+        assert!(frames > 0, "FrameRingAllocator needs at least one frame");
+        let regions = (0..frames)
+            .map(|_| {
+                Ok(FrameRegion {
+                    arenas: vec![new_ring_arena(device, region_size)?],
+                    retire_serial: 0,
+                })
+            })
+            .collect::<RhiResult<_>>()?;
+        Ok(Self {
+            regions,
+            current: 0,
+            region_size,
+        })
+    }
+
+    /// Start the next frame. Call once per frame, after submitting the previous frame's work;
+    /// blocks only while the GPU still uses the region being reclaimed.
+    pub fn begin_frame(&mut self, device: &Device) {
+        let queue = device.queue();
+        self.regions[self.current].retire_serial = queue.submitted_serial();
+        self.current = (self.current + 1) % self.regions.len();
+
+        let region = &mut self.regions[self.current];
+        if region.retire_serial > queue.completed_serial() {
+            queue.wait_for_serial(region.retire_serial);
+        }
+        for arena in &mut region.arenas {
+            arena.reset();
+        }
+    }
+
+    /// Allocate `size` bytes aligned to `align` (a power of two, at most 64 KiB) from the
+    /// current frame's region, growing the region when it is full.
+    pub fn alloc(
+        &mut self,
+        device: &Device,
+        size: u64,
+        align: u64,
+    ) -> RhiResult<TransientAllocation> {
+        let align = align.max(1);
+        if !align.is_power_of_two() || align > RING_ARENA_ALIGNMENT {
+            return Err(RhiError::AllocationFailed(format!(
+                "frame ring alignment {align} must be a power of two <= {RING_ARENA_ALIGNMENT}"
+            )));
+        }
+        let region = &mut self.regions[self.current];
+        if let Some(allocation) = region
+            .arenas
+            .iter_mut()
+            .find_map(|arena| arena.alloc(size, align))
+        {
+            return Ok(allocation);
+        }
+
+        let arena_size = self.region_size.max(size);
+        log::debug!(
+            "frame ring region {} overflowed; growing by {arena_size} bytes",
+            self.current
+        );
+        let mut arena = new_ring_arena(device, arena_size)?;
+        let allocation = arena.alloc(size, align).ok_or_else(|| {
+            RhiError::AllocationFailed(format!("{size} bytes do not fit a fresh ring arena"))
+        })?;
+        region.arenas.push(arena);
+        Ok(allocation)
+    }
+
+    /// Index of the current frame's region.
+    pub fn frame_index(&self) -> usize {
+        self.current
+    }
+
+    /// Bytes allocated from the current frame's region.
+    pub fn used(&self) -> u64 {
+        self.regions[self.current]
+            .arenas
+            .iter()
+            .map(BumpAllocator::used)
+            .sum()
+    }
+
+    /// Current frame's region capacity in bytes, including any growth.
+    pub fn capacity(&self) -> u64 {
+        self.regions[self.current]
+            .arenas
+            .iter()
+            .map(BumpAllocator::capacity)
+            .sum()
+    }
+
+    /// Wait for all submitted work, then free every region.
+    pub fn destroy(self, device: &Device) {
+        let queue = device.queue();
+        queue.wait_for_serial(queue.submitted_serial());
+        for arena in self.regions.into_iter().flat_map(|region| region.arenas) {
+            device.destroy_buffer(arena.into_buffer());
+        }
+    }
+}
+
 // Allocator behaviour is covered by the black-box headless tests in `tests/memory.rs`;
 // the bump allocator's end-to-end use as per-draw root data is exercised by
 // `graphics_root_from_bump_allocator` in `tests/graphics.rs`.
@@ -44,6 +44,9 @@ pub struct GraphicsPsoDesc {
     pub support_dual_source_blending: bool,
     /// Pre-baked default blend state. `None` = supply per-draw via `cmd.set_blend_state(...)`.
     pub blendstate: Option<BlendState>,
+    /// Multiview mask of the render passes this pipeline draws in; see
+    /// `RenderPassDesc::view_mask`. 0 = no multiview.
+    pub view_mask: u32,
     pub label: Option<String>,
 }
 
@@ -60,6 +63,7 @@ impl Default for GraphicsPsoDesc {
             stencil_format: None,
             support_dual_source_blending: false,
             blendstate: None,
+            view_mask: 0,
             label: None,
         }
     }
@@ -250,6 +254,8 @@ pub struct MeshletPsoDesc {
     pub blendstate: Option<BlendState>,
     /// Root constant size in bytes (passed via the mesh shader's root pointer).
     pub root_constant_size: u32,
+    /// Multiview mask, as in `GraphicsPsoDesc`.
+    pub view_mask: u32,
     pub label: Option<String>,
 }
 
@@ -266,6 +272,7 @@ impl Default for MeshletPsoDesc {
             support_dual_source_blending: false,
             blendstate: None,
             root_constant_size: (std::mem::size_of::<crate::types::GpuAddress>() * 2) as u32,
+            view_mask: 0,
             label: None,
         }
     }
@@ -0,0 +1,300 @@
+//! Per-frame GPU timings of named scopes.
+//!
+//! [`GpuProfiler`] attaches a timestamp query pool to a command buffer for one frame:
+//! [`begin_frame`](GpuProfiler::begin_frame) before recording, then any number of (nested)
+//! [`CommandBuffer::profile_scope`] guards, then [`end_frame`](GpuProfiler::end_frame), which
+//! resolves the frame's timestamps into `Readback` memory. Once the GPU has retired the
+//! submission, [`timings`](GpuProfiler::timings) reports how long each scope ran.
+//!
+//! ```ignore
+//! let mut profiler = GpuProfiler::new(&device, 64);
+//! profiler.begin_frame(&mut cmd)?;
+//! {
+//!     let mut shadow = cmd.profile_scope("shadow");
+//!     shadow.begin_render_pass(&shadow_pass);
+//!     // ...
+//! }
+//! profiler.end_frame(&mut cmd);
+//! queue.submit(cmd)?;
+//! for scope in profiler.timings() {
+//!     println!("{:indent$}{}: {:?}", "", scope.name, scope.gpu_time, indent = scope.depth * 2);
+//! }
+//! ```
+
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use crate::barrier::StageFlags;
+use crate::command::CommandBuffer;
+use crate::device::Device;
+use crate::error::RhiResult;
+use crate::memory::{GpuAllocation, MemoryType};
+use crate::query::{QUERY_RESULT_SIZE, QueryPool, QueryPoolDesc, QueryType};
+
+/// Serial of a frame whose command buffer has not been submitted yet.
+const UNSUBMITTED: u64 = 0;
+
+/// How long one scope of a profiled frame ran on the GPU.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScopeTiming {
+    pub name: String,
+    /// Number of scopes enclosing this one.
+    pub depth: usize,
+    pub gpu_time: Duration,
+}
+
+struct ScopeRecord {
+    name: String,
+    depth: usize,
+}
+
+/// A profiled frame being recorded into a command buffer.
+pub(crate) struct ProfileRecording {
+    frame: usize,
+    pool: Arc<QueryPool>,
+    slot: Arc<AtomicU64>,
+    scopes: Vec<ScopeRecord>,
+    depth: usize,
+}
+
+struct ProfilerFrame {
+    pool: Arc<QueryPool>,
+    /// Two timestamps per scope, begin and end, resolved by `end_frame`.
+    results: GpuAllocation,
+    /// Scopes of the frame in flight; empty once its timings were read or it was abandoned.
+    scopes: Vec<ScopeRecord>,
+    /// Submission slot, shared with the command buffer recording the frame until it is
+    /// submitted or dropped.
+    slot: Arc<AtomicU64>,
+}
+
+impl ProfilerFrame {
+    fn idle(&self) -> bool {
+        Arc::strong_count(&self.slot) == 1
+    }
+}
+
+/// A ring of timestamp query pools timing named scopes, one pool per frame in flight.
+///
+/// Dropping it waits for the frames in flight. Submit (or drop) command buffers recording a
+/// frame first.
+pub struct GpuProfiler<'d> {
+    device: &'d Device,
+    max_scopes: u32,
+    frames: Vec<ProfilerFrame>,
+    latest: Vec<ScopeTiming>,
+    latest_serial: u64,
+}
+
+impl<'d> GpuProfiler<'d> {
+    /// A profiler timing up to `max_scopes` scopes a frame; later scopes go untimed.
+    pub fn new(device: &'d Device, max_scopes: u32) -> Self {
+        assert!(max_scopes > 0, "GpuProfiler of zero scopes");
+        Self {
+            device,
+            max_scopes,
+            frames: Vec::new(),
+            latest: Vec::new(),
+            latest_serial: UNSUBMITTED,
+        }
+    }
+
+    /// Start timing a frame recorded into `cmd`. Returns `RhiError::Unsupported` if the
+    /// device cannot write timestamps.
+    pub fn begin_frame(&mut self, cmd: &mut CommandBuffer) -> RhiResult<()> {
+        assert!(
+            cmd.profile.is_none(),
+            "begin_frame on a command buffer already recording a profiled frame"
+        );
+        self.collect();
+        let completed = self.device.queue().completed_serial();
+        let reusable = self.frames.iter().position(|frame| {
+            frame.idle() && frame.scopes.is_empty() && {
+                let serial = frame.slot.load(Ordering::Acquire);
+                serial == UNSUBMITTED || serial <= completed
+            }
+        });
+        let index = match reusable {
+            Some(index) => {
+                let frame = &mut self.frames[index];
+                frame.pool.reset(0..self.max_scopes * 2);
+                frame.slot = Arc::new(AtomicU64::new(UNSUBMITTED));
+                index
+            }
+            None => {
+                let pool = self.device.create_query_pool(&QueryPoolDesc {
+                    ty: QueryType::Timestamp,
+                    count: self.max_scopes * 2,
+                    label: Some("GpuProfiler".into()),
+                })?;
+                let results = self.device.malloc(
+                    self.max_scopes as u64 * 2 * QUERY_RESULT_SIZE,
+                    MemoryType::Readback,
+                )?;
+                self.frames.push(ProfilerFrame {
+                    pool: Arc::new(pool),
+                    results,
+                    scopes: Vec::new(),
+                    slot: Arc::new(AtomicU64::new(UNSUBMITTED)),
+                });
+                self.frames.len() - 1
+            }
+        };
+        let frame = &self.frames[index];
+        cmd.profile = Some(ProfileRecording {
+            frame: index,
+            pool: frame.pool.clone(),
+            slot: frame.slot.clone(),
+            scopes: Vec::new(),
+            depth: 0,
+        });
+        Ok(())
+    }
+
+    /// Finish the frame begun on `cmd`, resolving its timestamps. Its timings are reported
+    /// once `cmd` has been submitted and has finished executing.
+    pub fn end_frame(&mut self, cmd: &mut CommandBuffer) {
+        let recording = cmd
+            .profile
+            .take()
+            .expect("end_frame on a command buffer without a profiled frame");
+        assert_eq!(recording.depth, 0, "end_frame inside an open profile_scope");
+        if !recording.scopes.is_empty() {
+            let frame = &mut self.frames[recording.frame];
+            let queries = recording.scopes.len() as u32 * 2;
+            cmd.resolve_queries(&frame.pool, 0..queries, frame.results.gpu());
+            frame.scopes = recording.scopes;
+        }
+        cmd.submit_slots.push(recording.slot);
+    }
+
+    /// GPU times of the scopes of the latest frame to finish executing, in the order they
+    /// began. Empty until a profiled frame has finished.
+    pub fn timings(&mut self) -> &[ScopeTiming] {
+        self.collect();
+        &self.latest
+    }
+
+    /// Read the timings of every finished frame, keeping the latest.
+    fn collect(&mut self) {
+        let completed = self.device.queue().completed_serial();
+        let period = self.device.timestamp_period();
+        for frame in &mut self.frames {
+            if frame.scopes.is_empty() || !frame.idle() {
+                continue;
+            }
+            let serial = frame.slot.load(Ordering::Acquire);
+            if serial == UNSUBMITTED {
+                // The command buffer was dropped unsubmitted.
+                frame.scopes.clear();
+                continue;
+            }
+            if serial > completed {
+                continue;
+            }
+            let scopes = std::mem::take(&mut frame.scopes);
+            if serial < self.latest_serial {
+                continue;
+            }
+            let ticks = frame
+                .results
+                .as_slice::<u64>()
+                .expect("profiler results are CPU-mapped");
+            self.latest = scopes
+                .into_iter()
+                .zip(ticks.chunks_exact(2))
+                .map(|(scope, ticks)| ScopeTiming {
+                    name: scope.name,
+                    depth: scope.depth,
+                    gpu_time: Duration::from_secs_f64(
+                        ticks[1].saturating_sub(ticks[0]) as f64 * period * 1e-9,
+                    ),
+                })
+                .collect();
+            self.latest_serial = serial;
+        }
+    }
+}
+
+impl Drop for GpuProfiler<'_> {
+    fn drop(&mut self) {
+        let last_frame = self
+            .frames
+            .iter()
+            .map(|frame| frame.slot.load(Ordering::Acquire))
+            .max();
+        if let Some(serial) = last_frame.filter(|&serial| serial != UNSUBMITTED) {
+            self.device.queue().wait_for_serial(serial);
+        }
+        for frame in self.frames.drain(..) {
+            self.device.free(frame.results);
+        }
+    }
+}
+
+impl CommandBuffer {
+    /// Time the commands recorded through the returned guard as the scope `name` of the
+    /// `GpuProfiler` frame this command buffer records. The scope ends when the guard drops.
+    /// Without a profiled frame, or past the profiler's `max_scopes`, nothing is timed.
+    pub fn profile_scope(&mut self, name: &str) -> ProfileScope<'_> {
+        let begin = self.profile.as_mut().and_then(|recording| {
+            let index = recording.scopes.len() as u32;
+            (index * 2 < recording.pool.count()).then(|| {
+                recording.scopes.push(ScopeRecord {
+                    name: name.to_owned(),
+                    depth: recording.depth,
+                });
+                recording.depth += 1;
+                (recording.pool.clone(), index * 2)
+            })
+        });
+        let end_query = begin.map(|(pool, query)| {
+            self.write_timestamp(StageFlags::ALL_COMMANDS, &pool, query);
+            query + 1
+        });
+        ProfileScope {
+            cmd: self,
+            end_query,
+        }
+    }
+}
+
+/// An open [`CommandBuffer::profile_scope`]. Derefs to the command buffer, so recording
+/// (and nesting scopes) goes through it; the scope's end timestamp is written on drop.
+pub struct ProfileScope<'a> {
+    cmd: &'a mut CommandBuffer,
+    end_query: Option<u32>,
+}
+
+impl Deref for ProfileScope<'_> {
+    type Target = CommandBuffer;
+
+    fn deref(&self) -> &CommandBuffer {
+        &*self.cmd
+    }
+}
+
+impl DerefMut for ProfileScope<'_> {
+    fn deref_mut(&mut self) -> &mut CommandBuffer {
+        &mut *self.cmd
+    }
+}
+
+impl Drop for ProfileScope<'_> {
+    fn drop(&mut self) {
+        let Some(query) = self.end_query else {
+            return;
+        };
+        let recording = self
+            .cmd
+            .profile
+            .as_mut()
+            .expect("profiled frame ended inside one of its scopes");
+        recording.depth -= 1;
+        let pool = recording.pool.clone();
+        self.cmd
+            .write_timestamp(StageFlags::ALL_COMMANDS, &pool, query);
+    }
+}
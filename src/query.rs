@@ -0,0 +1,103 @@
+//! GPU queries. A [`QueryPool`] holds `count` queries of one [`QueryType`]; commands write
+//! them and `resolve_queries` copies their values to a GPU pointer, where shaders can
+//! consume them directly, [`QueryPool::result_size`] bytes per query.
+
+use std::ops::Range;
+
+/// Size in bytes of one resolved query value.
+pub const QUERY_RESULT_SIZE: u64 = 8;
+
+bitflags::bitflags! {
+    /// Counters a pipeline-statistics query collects. Each query resolves to one `u64` per
+    /// set flag, in the order declared here.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    pub struct PipelineStatistics: u32 {
+        /// Primitives assembled from the vertex stream.
+        const PRIMITIVES            = 0x1;
+        const VERTEX_INVOCATIONS    = 0x2;
+        const FRAGMENT_INVOCATIONS  = 0x4;
+        const COMPUTE_INVOCATIONS   = 0x8;
+    }
+}
+
+/// What the queries of a pool measure.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum QueryType {
+    /// GPU clock ticks, written by `write_timestamp`. Multiply the difference of two by
+    /// `Device::timestamp_period` for nanoseconds.
+    Timestamp,
+    /// Number of samples passing the depth and stencil tests between `begin_query` and
+    /// `end_query`.
+    Occlusion,
+    /// Like `Occlusion`, but only zero versus non-zero is meaningful, which can be cheaper.
+    BinaryOcclusion,
+    /// The given counters, accumulated between `begin_query` and `end_query`.
+    PipelineStatistics(PipelineStatistics),
+}
+
+impl QueryType {
+    /// Size in bytes of one resolved query of this type.
+    pub fn result_size(self) -> u64 {
+        match self {
+            QueryType::PipelineStatistics(stats) => {
+                stats.bits().count_ones() as u64 * QUERY_RESULT_SIZE
+            }
+            _ => QUERY_RESULT_SIZE,
+        }
+    }
+}
+
+/// Description for creating a query pool.
+#[derive(Clone, Debug)]
+pub struct QueryPoolDesc {
+    pub ty: QueryType,
+    pub count: u32,
+    pub label: Option<String>,
+}
+
+/// A pool of GPU queries, destroyed on drop. Queries start out reset; a written query must
+/// be [`reset`](Self::reset) before it is written again.
+pub struct QueryPool {
+    pub(crate) inner: QueryPoolInner,
+    pub(crate) ty: QueryType,
+    pub(crate) count: u32,
+}
+
+impl QueryPool {
+    pub fn ty(&self) -> QueryType {
+        self.ty
+    }
+
+    /// Number of queries in the pool.
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    /// Size in bytes of one resolved query.
+    pub fn result_size(&self) -> u64 {
+        self.ty.result_size()
+    }
+
+    /// Reset `range` from the CPU so its queries can be written again. The GPU must be done
+    /// with them: wait for the submissions that wrote or resolved them first.
+    pub fn reset(&self, range: Range<u32>) {
+        self.check_range(&range, "reset");
+        backend_dispatch!(&self.inner, QueryPoolInner, p => p.reset(range))
+    }
+
+    /// Panic unless `range` is a non-empty range of this pool's queries.
+    pub(crate) fn check_range(&self, range: &Range<u32>, op: &str) {
+        assert!(
+            range.start < range.end && range.end <= self.count,
+            "{op}: queries {range:?} outside a pool of {}",
+            self.count
+        );
+    }
+}
+
+pub(crate) enum QueryPoolInner {
+    #[cfg(feature = "vulkan")]
+    Vulkan(Box<crate::backend::vulkan::query::VulkanQueryPool>),
+    #[cfg(feature = "metal")]
+    Metal(Box<crate::backend::metal::query::MetalQueryPool>),
+}
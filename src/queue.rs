@@ -1,11 +1,18 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
 use crate::command::CommandBuffer;
+use crate::deferred::SharedGraveyard;
 use crate::error::RhiResult;
 use crate::swapchain::{AcquiredImage, Swapchain};
 use crate::sync::TimelineSemaphore;
 
-/// GPU queue for submission and presentation.
+/// GPU queue for submission and presentation. The queue is `Sync`: any thread may submit,
+/// and submissions are serialized internally.
 pub struct Queue {
     pub(crate) inner: QueueInner,
+    /// Resources dropped through `Owned` handles, waiting for their submission to retire.
+    pub(crate) graveyard: SharedGraveyard,
 }
 
 pub(crate) enum QueueInner {
@@ -34,7 +41,7 @@ impl Queue {
     /// Submit a command buffer with explicit timeline wait/signal dependencies.
     /// The command buffer is consumed (transient, auto-reclaimed).
     pub fn submit_with_desc(&self, cmd: CommandBuffer, desc: &SubmitDesc<'_>) -> RhiResult<()> {
-        match (&self.inner, cmd.inner) {
+        let result = match (&self.inner, cmd.inner) {
             #[cfg(feature = "vulkan")]
             (QueueInner::Vulkan(q), crate::command::CommandBufferInner::Vulkan(cmd)) => {
                 q.submit_with_desc(*cmd, desc)
@@ -45,7 +52,51 @@ impl Queue {
             }
             #[allow(unreachable_patterns)]
             _ => unreachable!("mismatched backend types"),
-        }
+        };
+        self.note_submission(&cmd.submit_slots, result.is_ok());
+        result
+    }
+
+    /// Submit command buffers recorded in parallel with one queue submission. They execute
+    /// in order, as if submitted one by one, and are consumed (transient, auto-reclaimed).
+    /// Their value waits are all satisfied before the first starts, and their value signals
+    /// happen after the last finishes, so one buffer of the batch cannot wait on another.
+    pub fn submit_batch(&self, cmds: Vec<CommandBuffer>) -> RhiResult<()> {
+        let mut submit_slots = Vec::new();
+        let result = match &self.inner {
+            #[cfg(feature = "vulkan")]
+            QueueInner::Vulkan(q) => {
+                let cmds = cmds
+                    .into_iter()
+                    .map(|cmd| {
+                        submit_slots.extend(cmd.submit_slots);
+                        match cmd.inner {
+                            crate::command::CommandBufferInner::Vulkan(cmd) => *cmd,
+                            #[allow(unreachable_patterns)]
+                            _ => unreachable!("mismatched backend types"),
+                        }
+                    })
+                    .collect();
+                q.submit_batch(cmds, &SubmitDesc::default())
+            }
+            #[cfg(feature = "metal")]
+            QueueInner::Metal(q) => {
+                let cmds = cmds
+                    .into_iter()
+                    .map(|cmd| {
+                        submit_slots.extend(cmd.submit_slots);
+                        match cmd.inner {
+                            crate::command::CommandBufferInner::Metal(cmd) => *cmd,
+                            #[allow(unreachable_patterns)]
+                            _ => unreachable!("mismatched backend types"),
+                        }
+                    })
+                    .collect();
+                q.submit_batch(cmds, &SubmitDesc::default())
+            }
+        };
+        self.note_submission(&submit_slots, result.is_ok());
+        result
     }
 
     /// Acquire the next swapchain image for rendering.
@@ -98,7 +149,7 @@ impl Queue {
         frame_index: usize,
         image_index: u32,
     ) -> RhiResult<()> {
-        match (&self.inner, cmd.inner, &swapchain.inner) {
+        let result = match (&self.inner, cmd.inner, &swapchain.inner) {
             #[cfg(feature = "vulkan")]
             (
                 QueueInner::Vulkan(q),
@@ -113,7 +164,9 @@ impl Queue {
             ) => q.submit_frame(*cmd, sc, frame_index, image_index),
             #[allow(unreachable_patterns)]
             _ => unreachable!("mismatched backend types"),
-        }
+        };
+        self.note_submission(&cmd.submit_slots, result.is_ok());
+        result
     }
 
     /// Wait for the queue to be idle.
@@ -125,4 +178,34 @@ impl Queue {
             QueueInner::Metal(q) => q.wait_idle(),
         }
     }
+
+    /// Serial of the most recent submission. Serials only ever increase.
+    pub(crate) fn submitted_serial(&self) -> u64 {
+        backend_dispatch!(&self.inner, QueueInner, q => q.submitted_serial())
+    }
+
+    /// Highest serial whose submission has finished executing on the GPU.
+    pub(crate) fn completed_serial(&self) -> u64 {
+        backend_dispatch!(&self.inner, QueueInner, q => q.completed_serial())
+    }
+
+    /// Block until the submission with `serial` has finished executing.
+    pub(crate) fn wait_for_serial(&self, serial: u64) {
+        backend_dispatch!(&self.inner, QueueInner, q => q.wait_for_serial(serial))
+    }
+
+    /// Tag resources dropped from now on with the latest submission's serial, and hand that
+    /// serial to the readbacks recorded into the submitted command buffer.
+    fn note_submission(&self, slots: &[Arc<AtomicU64>], submitted: bool) {
+        let serial = self.submitted_serial();
+        if submitted {
+            for slot in slots {
+                slot.store(serial, Ordering::Release);
+            }
+        }
+        self.graveyard
+            .lock()
+            .expect("graveyard lock poisoned")
+            .note_submission(serial);
+    }
 }
@@ -0,0 +1,332 @@
+//! Asynchronous readback of GPU results into `Readback` memory.
+//!
+//! [`Readback`] records a copy from a buffer or texture into a ring of `Readback` chunks and
+//! hands back a [`ReadbackHandle`]. The handle learns its queue serial when the command buffer
+//! it was recorded into is submitted, and resolves once the GPU has retired that submission:
+//! poll it with [`ReadbackHandle::try_get`], block with [`ReadbackHandle::wait`], or `.await`
+//! it. A pending future is woken by [`Readback::collect`], which the application calls once per
+//! frame. Nothing stalls the rest of the device.
+//!
+//! Order the copy after the work that produces its source with a barrier, as for any other
+//! transfer. A chunk is reused only once every handle into it has been dropped and every copy
+//! into it has retired.
+
+use std::cell::RefCell;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Weak};
+use std::task::{Context, Poll, Waker};
+
+use crate::command::CommandBuffer;
+use crate::device::Device;
+use crate::error::RhiResult;
+use crate::memory::{BumpAllocator, GpuPod, MemoryType, TransientAllocation};
+use crate::texture::{Texture, TextureCopyRegion};
+use crate::types::GpuAddress;
+use zerocopy::FromBytes;
+
+/// Default size of each readback chunk.
+pub const DEFAULT_READBACK_CHUNK_SIZE: u64 = 4 << 20;
+
+/// Destination offset alignment. Covers every texel size and element alignment.
+const READBACK_ALIGNMENT: u64 = 256;
+
+/// Serial of a readback whose command buffer has not been submitted yet.
+const UNSUBMITTED: u64 = 0;
+
+struct ReadbackChunk {
+    arena: BumpAllocator,
+    /// Submission slot of every read placed since the last reset. Each slot is shared with
+    /// the read's handle and, until submission, its command buffer.
+    reads: Vec<Arc<AtomicU64>>,
+}
+
+impl ReadbackChunk {
+    /// No handle or pending command buffer still refers to the chunk, and every submitted
+    /// copy into it has retired.
+    fn reusable(&self, completed: u64) -> bool {
+        self.reads
+            .iter()
+            .all(|slot| Arc::strong_count(slot) == 1 && slot.load(Ordering::Acquire) <= completed)
+    }
+}
+
+/// A ring of `Readback` memory that GPU results are copied into.
+///
+/// Handles borrow the ring, so it outlives every read; dropping it waits for outstanding
+/// copies and frees the chunks. Submit (or drop) command buffers holding reads first.
+pub struct Readback<'d> {
+    device: &'d Device,
+    chunk_size: u64,
+    chunks: RefCell<Vec<ReadbackChunk>>,
+    /// Wakers of pending futures, by submission slot. Weak, so a dropped future does not keep
+    /// its chunk from being reused.
+    wakers: RefCell<Vec<(Weak<AtomicU64>, Waker)>>,
+}
+
+impl<'d> Readback<'d> {
+    /// A readback ring of `chunk_size`-byte chunks (larger reads get a chunk of their own).
+    /// See [`DEFAULT_READBACK_CHUNK_SIZE`].
+    pub fn new(device: &'d Device, chunk_size: u64) -> Self {
+        Self {
+            device,
+            chunk_size,
+            chunks: RefCell::new(Vec::new()),
+            wakers: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Record a copy of `count` elements of `T` at `src` into the ring.
+    pub fn read_buffer<T: GpuPod>(
+        &self,
+        cmd: &mut CommandBuffer,
+        src: GpuAddress,
+        count: usize,
+    ) -> RhiResult<ReadbackHandle<'_, T>> {
+        let size = (count * size_of::<T>()) as u64;
+        let (dst, slot) = self.alloc(size)?;
+        cmd.memcpy(dst.gpu, src, size);
+        cmd.submit_slots.push(slot.clone());
+        Ok(ReadbackHandle::new(self, dst, count, slot))
+    }
+
+    /// Record a copy of mip `mip` of array layer `layer` of `texture` into the ring, as
+    /// tightly packed texels.
+    pub fn read_texture(
+        &self,
+        cmd: &mut CommandBuffer,
+        texture: &Texture,
+        mip: u32,
+        layer: u32,
+    ) -> RhiResult<ReadbackHandle<'_, u8>> {
+        let len = TextureCopyRegion::subresource(mip, layer)
+            .resolve(texture.desc(), "readback")
+            .byte_size as usize;
+        let (dst, slot) = self.alloc(len as u64)?;
+        cmd.copy_from_texture_subresource(dst.gpu, texture, mip, layer);
+        cmd.submit_slots.push(slot.clone());
+        Ok(ReadbackHandle::new(self, dst, len, slot))
+    }
+
+    /// Wake every pending [`ReadbackHandle`] future whose copy has retired, and return how
+    /// many were woken. Call it once per frame, or whenever the executor should make progress.
+    pub fn collect(&self) -> usize {
+        let completed = self.device.queue().completed_serial();
+        let mut ready = Vec::new();
+        self.wakers.borrow_mut().retain(|(slot, waker)| {
+            let Some(slot) = slot.upgrade() else {
+                return false;
+            };
+            let serial = slot.load(Ordering::Acquire);
+            if serial == UNSUBMITTED || serial > completed {
+                return true;
+            }
+            ready.push(waker.clone());
+            false
+        });
+        // Woken outside the borrow: an executor may poll, and so re-register, inline.
+        let woken = ready.len();
+        ready.into_iter().for_each(Waker::wake);
+        woken
+    }
+
+    /// Keep `waker` to be woken by [`collect`](Self::collect) once the copy in `slot` retires.
+    fn register(&self, slot: &Arc<AtomicU64>, waker: &Waker) {
+        let mut wakers = self.wakers.borrow_mut();
+        match wakers
+            .iter_mut()
+            .find(|(registered, _)| std::ptr::eq(registered.as_ptr(), Arc::as_ptr(slot)))
+        {
+            Some((_, registered)) => registered.clone_from(waker),
+            None => wakers.push((Arc::downgrade(slot), waker.clone())),
+        }
+    }
+
+    /// Bytes of readback memory held, in use or not.
+    pub fn capacity(&self) -> u64 {
+        self.chunks
+            .borrow()
+            .iter()
+            .map(|c| c.arena.capacity())
+            .sum()
+    }
+
+    fn alloc(&self, size: u64) -> RhiResult<(TransientAllocation, Arc<AtomicU64>)> {
+        let mut chunks = self.chunks.borrow_mut();
+        let slot = Arc::new(AtomicU64::new(UNSUBMITTED));
+        if let Some((chunk, dst)) = chunks
+            .iter_mut()
+            .find_map(|c| c.arena.alloc(size, READBACK_ALIGNMENT).map(|dst| (c, dst)))
+        {
+            chunk.reads.push(slot.clone());
+            return Ok((dst, slot));
+        }
+
+        let completed = self.device.queue().completed_serial();
+        let index = match chunks
+            .iter()
+            .position(|c| c.arena.capacity() >= size && c.reusable(completed))
+        {
+            Some(index) => {
+                chunks[index].arena.reset();
+                chunks[index].reads.clear();
+                index
+            }
+            None => {
+                let allocation = self.device.malloc_aligned(
+                    self.chunk_size.max(size),
+                    READBACK_ALIGNMENT,
+                    MemoryType::Readback,
+                )?;
+                chunks.push(ReadbackChunk {
+                    arena: BumpAllocator::new(allocation.into_buffer()),
+                    reads: Vec::new(),
+                });
+                chunks.len() - 1
+            }
+        };
+        let chunk = &mut chunks[index];
+        let dst = chunk
+            .arena
+            .alloc(size, READBACK_ALIGNMENT)
+            .expect("fresh readback chunk fits the read");
+        chunk.reads.push(slot.clone());
+        Ok((dst, slot))
+    }
+}
+
+impl Drop for Readback<'_> {
+    fn drop(&mut self) {
+        let chunks = std::mem::take(self.chunks.get_mut());
+        let last_read = chunks
+            .iter()
+            .flat_map(|c| c.reads.iter())
+            .map(|slot| slot.load(Ordering::Acquire))
+            .max();
+        if let Some(serial) = last_read {
+            self.device.queue().wait_for_serial(serial);
+        }
+        for chunk in chunks {
+            self.device.destroy_buffer(chunk.arena.into_buffer());
+        }
+    }
+}
+
+/// A pending copy of `T`s into a [`Readback`] ring.
+///
+/// As a `Future` it does not wake itself: the GPU signals no event, so [`Readback::collect`]
+/// wakes it once the copy has retired.
+pub struct ReadbackHandle<'r, T> {
+    ring: &'r Readback<'r>,
+    dst: TransientAllocation,
+    len: usize,
+    slot: Arc<AtomicU64>,
+    _element: PhantomData<T>,
+}
+
+impl<'r, T: GpuPod> ReadbackHandle<'r, T> {
+    fn new(
+        ring: &'r Readback<'r>,
+        dst: TransientAllocation,
+        len: usize,
+        slot: Arc<AtomicU64>,
+    ) -> Self {
+        Self {
+            ring,
+            dst,
+            len,
+            slot,
+            _element: PhantomData,
+        }
+    }
+
+    /// Number of `T`s read back.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// True once the command buffer holding the copy has been submitted.
+    pub fn is_submitted(&self) -> bool {
+        self.slot.load(Ordering::Acquire) != UNSUBMITTED
+    }
+
+    /// True once the copy has finished executing on the GPU.
+    pub fn is_ready(&self) -> bool {
+        let serial = self.slot.load(Ordering::Acquire);
+        serial != UNSUBMITTED && self.ring.device.queue().completed_serial() >= serial
+    }
+
+    /// The results, if the copy has finished.
+    pub fn try_get(&self) -> Option<&[T]> {
+        self.is_ready().then(|| self.data())
+    }
+
+    /// Block until the copy has finished, then return the results. Panics if the command
+    /// buffer holding the copy has not been submitted.
+    pub fn wait(&self) -> &[T] {
+        let serial = self.slot.load(Ordering::Acquire);
+        assert_ne!(
+            serial, UNSUBMITTED,
+            "readback waited on before its command buffer was submitted"
+        );
+        self.ring.device.queue().wait_for_serial(serial);
+        self.data()
+    }
+
+    fn data(&self) -> &[T] {
+        // SAFETY: the chunk stays mapped and is not reset while the handle holds its slot.
+        unsafe { mapped_slice(&self.dst, self.len) }
+    }
+}
+
+impl<'r, T: GpuPod> Future for ReadbackHandle<'r, T> {
+    type Output = ReadbackData<'r, T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if self.is_ready() {
+            Poll::Ready(ReadbackData {
+                dst: self.dst,
+                len: self.len,
+                _slot: self.slot.clone(),
+                _ring: PhantomData,
+            })
+        } else {
+            self.ring.register(&self.slot, cx.waker());
+            Poll::Pending
+        }
+    }
+}
+
+/// Completed readback results, derefs to `[T]`. Keeps its ring chunk from being reused.
+pub struct ReadbackData<'r, T> {
+    dst: TransientAllocation,
+    len: usize,
+    _slot: Arc<AtomicU64>,
+    _ring: PhantomData<(&'r Readback<'r>, T)>,
+}
+
+impl<T: GpuPod> Deref for ReadbackData<'_, T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        // SAFETY: the chunk stays mapped and is not reset while `_slot` is held.
+        unsafe { mapped_slice(&self.dst, self.len) }
+    }
+}
+
+/// View `len` `T`s of a retired readback destination.
+///
+/// # Safety
+/// `dst` must stay mapped and unwritten for `'a`.
+unsafe fn mapped_slice<'a, T: GpuPod>(dst: &TransientAllocation, len: usize) -> &'a [T] {
+    // SAFETY: forwarded to the caller; `dst` covers `len` `T`s.
+    let bytes = unsafe { std::slice::from_raw_parts(dst.cpu as *const u8, len * size_of::<T>()) };
+    <[T]>::ref_from_bytes(bytes).expect("readback destination is aligned for T")
+}
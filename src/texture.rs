@@ -1,3 +1,6 @@
+use std::ops::Range;
+
+use crate::memory::ExternalMemoryHandle;
 use crate::types::{Format, GpuAddress, SampleCount, TextureDimension, TextureId};
 
 /// Sentinel for `GpuViewDesc::mip_count`: include all mip levels from `base_mip` to the last.
@@ -18,8 +21,62 @@ bitflags::bitflags! {
     }
 }
 
+bitflags::bitflags! {
+    /// Block-compressed format families, as reported by `Device::texture_compression`.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    pub struct TextureCompression: u32 {
+        /// BC1–BC7 (a.k.a. DXT / S3TC, RGTC, BPTC).
+        const BC   = 0x01;
+        /// ETC2 and EAC.
+        const ETC2 = 0x02;
+        /// ASTC LDR, every block size.
+        const ASTC = 0x04;
+    }
+}
+
+bitflags::bitflags! {
+    /// What a `Format` can be used for on a device, as reported by `Device::format_caps`.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    pub struct FormatCaps: u32 {
+        /// Sampled or loaded in shaders.
+        const SAMPLED                  = 0x001;
+        /// Sampled with linear (min/mag/mip) filtering.
+        const FILTER_LINEAR            = 0x002;
+        /// Bound as a storage (read-write) texture.
+        const STORAGE                  = 0x004;
+        const COLOR_ATTACHMENT         = 0x008;
+        /// Color attachment with blending enabled.
+        const BLEND                    = 0x010;
+        const DEPTH_STENCIL_ATTACHMENT = 0x020;
+        /// Rendered to with 2 samples per pixel; likewise for the other `MSAA_*` flags.
+        const MSAA_2                   = 0x100;
+        const MSAA_4                   = 0x200;
+        const MSAA_8                   = 0x400;
+        const MSAA_16                  = 0x800;
+    }
+}
+
+impl FormatCaps {
+    /// The flag for rendering with `count` samples; empty for `S1`.
+    pub fn msaa(count: SampleCount) -> Self {
+        match count {
+            SampleCount::S1 => Self::empty(),
+            SampleCount::S2 => Self::MSAA_2,
+            SampleCount::S4 => Self::MSAA_4,
+            SampleCount::S8 => Self::MSAA_8,
+            SampleCount::S16 => Self::MSAA_16,
+        }
+    }
+
+    /// True if the format can be a color or depth/stencil attachment with `count` samples.
+    pub fn supports_samples(&self, count: SampleCount) -> bool {
+        self.intersects(Self::COLOR_ATTACHMENT | Self::DEPTH_STENCIL_ATTACHMENT)
+            && self.contains(Self::msaa(count))
+    }
+}
+
 /// Description for creating a texture.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct TextureDesc {
     pub width: u32,
     pub height: u32,
@@ -31,6 +88,9 @@ pub struct TextureDesc {
     pub sample_count: SampleCount,
     pub usage: TextureUsage,
     pub label: Option<String>,
+    /// Create the texture so it can be placed in memory allocated with the same
+    /// `BufferDesc::export` handle type, and shared along with that memory.
+    pub export: Option<ExternalMemoryHandle>,
 }
 
 impl Default for TextureDesc {
@@ -46,6 +106,7 @@ impl Default for TextureDesc {
             sample_count: SampleCount::S1,
             usage: TextureUsage::SAMPLED | TextureUsage::TRANSFER_DST,
             label: None,
+            export: None,
         }
     }
 }
@@ -82,6 +143,232 @@ impl Texture {
     }
 }
 
+impl TextureDesc {
+    /// Width, height and depth of mip level `mip`.
+    pub fn mip_extent(&self, mip: u32) -> [u32; 3] {
+        let depth = match self.dimension {
+            TextureDimension::D3 => (self.depth >> mip).max(1),
+            _ => 1,
+        };
+        [
+            (self.width >> mip).max(1),
+            (self.height >> mip).max(1),
+            depth,
+        ]
+    }
+
+    /// Number of addressable array layers (each cube counts six faces).
+    pub fn layer_count(&self) -> u32 {
+        match self.dimension {
+            TextureDimension::Cube => self.array_layers * 6,
+            _ => self.array_layers,
+        }
+    }
+}
+
+/// One region of a buffer↔texture copy: a box of texels in one mip level of a range of array
+/// layers, and where its texels sit in the buffer.
+///
+/// In the buffer, rows are `buffer_row_pitch` bytes apart, 3D slices follow each other, and
+/// array layers follow each other after that.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TextureCopyRegion {
+    /// Byte offset of the region's first texel from the buffer address given to the copy.
+    pub buffer_offset: u64,
+    /// Bytes between the starts of consecutive rows in the buffer; 0 = tightly packed.
+    pub buffer_row_pitch: u32,
+    pub mip: u32,
+    pub base_layer: u32,
+    pub layer_count: u32,
+    /// First texel (x, y, z) of the box; z selects 3D slices.
+    pub offset: [u32; 3],
+    /// Size of the box in texels; 0 = to the end of the mip level along that axis.
+    pub extent: [u32; 3],
+}
+
+impl Default for TextureCopyRegion {
+    fn default() -> Self {
+        Self::subresource(0, 0)
+    }
+}
+
+impl TextureCopyRegion {
+    /// The whole of mip `mip` of array layer `layer`, tightly packed at the buffer address.
+    pub fn subresource(mip: u32, layer: u32) -> Self {
+        Self {
+            buffer_offset: 0,
+            buffer_row_pitch: 0,
+            mip,
+            base_layer: layer,
+            layer_count: 1,
+            offset: [0; 3],
+            extent: [0; 3],
+        }
+    }
+
+    /// Fill in defaults against `desc` and check the region lies inside the texture. For
+    /// block-compressed formats the box must start on a block boundary and end on one or at
+    /// the edge of the mip level, and buffer pitches count whole blocks.
+    pub(crate) fn resolve(&self, desc: &TextureDesc, op: &str) -> ResolvedCopyRegion {
+        let block = format_block(desc.format)
+            .unwrap_or_else(|| panic!("Unsupported texture format {:?} for {op}", desc.format));
+        let extent = TextureRegion {
+            mip: self.mip,
+            base_layer: self.base_layer,
+            layer_count: self.layer_count,
+            offset: self.offset,
+            extent: self.extent,
+        }
+        .resolve(desc, op)
+        .extent;
+        let (row_blocks, rows) = block.blocks(extent[0], extent[1]);
+        let block_bytes = block.bytes as u64;
+        let row_pitch = match self.buffer_row_pitch {
+            0 => row_blocks as u64 * block_bytes,
+            pitch => pitch as u64,
+        };
+        assert!(
+            row_pitch >= row_blocks as u64 * block_bytes && row_pitch.is_multiple_of(block_bytes),
+            "{op} row pitch {row_pitch} must cover {row_blocks} blocks and be a multiple of \
+             {block_bytes}"
+        );
+        let image_stride = row_pitch * rows as u64;
+        let layer_stride = image_stride * extent[2] as u64;
+        ResolvedCopyRegion {
+            buffer_offset: self.buffer_offset,
+            row_pitch,
+            row_texels: (row_pitch / block_bytes) as u32 * block.width,
+            image_height: rows * block.height,
+            image_stride,
+            layer_stride,
+            byte_size: layer_stride * self.layer_count as u64,
+            mip: self.mip,
+            base_layer: self.base_layer,
+            layer_count: self.layer_count,
+            offset: self.offset,
+            extent,
+        }
+    }
+}
+
+/// A box of texels in one mip level of a range of array layers, for texture-to-texture
+/// copies and blits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TextureRegion {
+    pub mip: u32,
+    pub base_layer: u32,
+    pub layer_count: u32,
+    /// First texel (x, y, z) of the box; z selects 3D slices.
+    pub offset: [u32; 3],
+    /// Size of the box in texels; 0 = to the end of the mip level along that axis.
+    pub extent: [u32; 3],
+}
+
+impl Default for TextureRegion {
+    fn default() -> Self {
+        Self::subresource(0, 0)
+    }
+}
+
+impl TextureRegion {
+    /// The whole of mip `mip` of array layer `layer`.
+    pub fn subresource(mip: u32, layer: u32) -> Self {
+        Self {
+            mip,
+            base_layer: layer,
+            layer_count: 1,
+            offset: [0; 3],
+            extent: [0; 3],
+        }
+    }
+
+    /// Fill in defaults against `desc` and check the region lies inside the texture. For
+    /// block-compressed formats the box must start on a block boundary and end on one or at
+    /// the edge of the mip level.
+    pub(crate) fn resolve(&self, desc: &TextureDesc, op: &str) -> TextureRegion {
+        assert!(
+            self.mip < desc.mip_levels,
+            "{op} mip {} out of range ({} levels)",
+            self.mip,
+            desc.mip_levels
+        );
+        assert!(
+            self.layer_count > 0 && self.base_layer + self.layer_count <= desc.layer_count(),
+            "{op} layers {}..{} out of range ({} layers)",
+            self.base_layer,
+            self.base_layer + self.layer_count,
+            desc.layer_count()
+        );
+        let resolved = self.resolve_in(desc.mip_extent(self.mip), op);
+        if let Some(block) = format_block(desc.format) {
+            let mip_extent = desc.mip_extent(self.mip);
+            let block_extent = [block.width, block.height, 1];
+            for axis in 0..3 {
+                let end = resolved.offset[axis] + resolved.extent[axis];
+                assert!(
+                    resolved.offset[axis].is_multiple_of(block_extent[axis])
+                        && (end.is_multiple_of(block_extent[axis]) || end == mip_extent[axis]),
+                    "{op} region {:?}+{:?} is not aligned to {}x{} blocks",
+                    resolved.offset,
+                    resolved.extent,
+                    block.width,
+                    block.height
+                );
+            }
+        }
+        resolved
+    }
+
+    /// Fill in zero extents to the end of a mip level of `mip_extent` texels and check the box
+    /// lies inside it.
+    pub(crate) fn resolve_in(&self, mip_extent: [u32; 3], op: &str) -> TextureRegion {
+        let mut extent = [0; 3];
+        for axis in 0..3 {
+            assert!(
+                self.offset[axis] < mip_extent[axis],
+                "{op} offset {:?} outside mip {} ({mip_extent:?})",
+                self.offset,
+                self.mip
+            );
+            extent[axis] = match self.extent[axis] {
+                0 => mip_extent[axis] - self.offset[axis],
+                e => e,
+            };
+            assert!(
+                self.offset[axis] + extent[axis] <= mip_extent[axis],
+                "{op} region {:?}+{extent:?} outside mip {} ({mip_extent:?})",
+                self.offset,
+                self.mip
+            );
+        }
+        TextureRegion { extent, ..*self }
+    }
+}
+
+/// A `TextureCopyRegion` with its defaults filled in and buffer strides computed.
+// The byte strides are only read by the Metal backend.
+#[allow(dead_code)]
+pub(crate) struct ResolvedCopyRegion {
+    pub buffer_offset: u64,
+    /// Bytes between rows of blocks.
+    pub row_pitch: u64,
+    /// `row_pitch` in texels.
+    pub row_texels: u32,
+    /// Texel rows between 3D slices (the extent's height rounded up to whole blocks).
+    pub image_height: u32,
+    /// Bytes between 3D slices.
+    pub image_stride: u64,
+    /// Bytes between array layers.
+    pub layer_stride: u64,
+    /// Bytes of buffer the region spans.
+    pub byte_size: u64,
+    pub mip: u32,
+    pub base_layer: u32,
+    pub layer_count: u32,
+    pub offset: [u32; 3],
+    pub extent: [u32; 3],
+}
+
 /// A non-default view of a texture, for `texture_view_descriptor` (SRV) /
 /// `rw_texture_view_descriptor` (UAV). `ALL_MIPS`/`ALL_LAYERS` cover the rest of the range;
 /// `format = None` keeps the source format.
@@ -111,21 +398,218 @@ impl Default for GpuViewDesc {
     }
 }
 
-/// Bytes per pixel for uncompressed formats.
-pub fn bytes_per_pixel(format: Format) -> Option<usize> {
+/// Mip levels and array layers of a texture, for `clear_texture`. `ALL_MIPS`/`ALL_LAYERS`
+/// cover the rest of the range; the default is the whole texture.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TextureSubresourceRange {
+    pub base_mip: u8,
+    pub mip_count: u8,
+    /// First array layer; cube faces count as layers.
+    pub base_layer: u16,
+    pub layer_count: u16,
+}
+
+impl Default for TextureSubresourceRange {
+    fn default() -> Self {
+        Self {
+            base_mip: 0,
+            mip_count: ALL_MIPS,
+            base_layer: 0,
+            layer_count: ALL_LAYERS,
+        }
+    }
+}
+
+impl TextureSubresourceRange {
+    /// The mip levels and layers of `desc` the range covers. Panics if it runs past the end.
+    pub(crate) fn resolve(&self, desc: &TextureDesc) -> (Range<u32>, Range<u32>) {
+        let resolve = |base: u32, count: u32, all: u32, total: u32, what: &str| {
+            let end = if count == all { total } else { base + count };
+            assert!(
+                base < end && end <= total,
+                "{what} {base}..{end} out of range for {total}"
+            );
+            base..end
+        };
+        let mips = resolve(
+            self.base_mip as u32,
+            self.mip_count as u32,
+            ALL_MIPS as u32,
+            desc.mip_levels,
+            "mips",
+        );
+        let layers = resolve(
+            self.base_layer as u32,
+            self.layer_count as u32,
+            ALL_LAYERS as u32,
+            desc.layer_count(),
+            "layers",
+        );
+        (mips, layers)
+    }
+}
+
+/// Texel block of a format: the smallest unit its data can be addressed in. 1×1 for
+/// uncompressed formats.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct FormatBlock {
+    /// Block width in texels.
+    pub width: u32,
+    /// Block height in texels.
+    pub height: u32,
+    /// Bytes per block.
+    pub bytes: u32,
+}
+
+impl FormatBlock {
+    const fn texel(bytes: u32) -> Option<Self> {
+        Some(Self {
+            width: 1,
+            height: 1,
+            bytes,
+        })
+    }
+
+    const fn compressed(width: u32, height: u32, bytes: u32) -> Option<Self> {
+        Some(Self {
+            width,
+            height,
+            bytes,
+        })
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        self.width > 1 || self.height > 1
+    }
+
+    /// Blocks needed to cover `width` × `height` texels.
+    pub fn blocks(&self, width: u32, height: u32) -> (u32, u32) {
+        (width.div_ceil(self.width), height.div_ceil(self.height))
+    }
+}
+
+/// Block size of the formats textures can be copied to and from buffers in; `None` for
+/// depth/stencil and index formats.
+pub fn format_block(format: Format) -> Option<FormatBlock> {
     match format {
-        Format::R8Unorm => Some(1),
-        Format::R8G8Unorm => Some(2),
-        Format::R8G8B8A8Unorm | Format::R8G8B8A8Srgb => Some(4),
-        Format::B8G8R8A8Unorm | Format::B8G8R8A8Srgb => Some(4),
-        Format::R16Float => Some(2),
-        Format::R16G16Float => Some(4),
-        Format::R16G16B16A16Float => Some(8),
-        Format::R32Float => Some(4),
-        Format::R32G32Float => Some(8),
-        Format::R32G32B32A32Float => Some(16),
-        Format::R10G10B10A2Unorm => Some(4),
-        Format::R11G11B10Float => Some(4),
+        Format::R8Unorm => FormatBlock::texel(1),
+        Format::R8G8Unorm => FormatBlock::texel(2),
+        Format::R8G8B8A8Unorm | Format::R8G8B8A8Srgb => FormatBlock::texel(4),
+        Format::B8G8R8A8Unorm | Format::B8G8R8A8Srgb => FormatBlock::texel(4),
+        Format::R16Float => FormatBlock::texel(2),
+        Format::R16G16Float => FormatBlock::texel(4),
+        Format::R16G16B16A16Float => FormatBlock::texel(8),
+        Format::R32Float => FormatBlock::texel(4),
+        Format::R32G32Float => FormatBlock::texel(8),
+        Format::R32G32B32A32Float => FormatBlock::texel(16),
+        Format::R10G10B10A2Unorm => FormatBlock::texel(4),
+        Format::R11G11B10Float => FormatBlock::texel(4),
+        Format::Bc1RgbaUnorm
+        | Format::Bc1RgbaSrgb
+        | Format::Bc4RUnorm
+        | Format::Bc4RSnorm
+        | Format::Etc2R8G8B8Unorm
+        | Format::Etc2R8G8B8Srgb
+        | Format::Etc2R8G8B8A1Unorm
+        | Format::Etc2R8G8B8A1Srgb
+        | Format::EacR11Unorm
+        | Format::EacR11Snorm => FormatBlock::compressed(4, 4, 8),
+        Format::Bc2RgbaUnorm
+        | Format::Bc2RgbaSrgb
+        | Format::Bc3RgbaUnorm
+        | Format::Bc3RgbaSrgb
+        | Format::Bc5RgUnorm
+        | Format::Bc5RgSnorm
+        | Format::Bc6hRgbUfloat
+        | Format::Bc6hRgbSfloat
+        | Format::Bc7RgbaUnorm
+        | Format::Bc7RgbaSrgb
+        | Format::Etc2R8G8B8A8Unorm
+        | Format::Etc2R8G8B8A8Srgb
+        | Format::EacR11G11Unorm
+        | Format::EacR11G11Snorm
+        | Format::Astc4x4Unorm
+        | Format::Astc4x4Srgb => FormatBlock::compressed(4, 4, 16),
+        Format::Astc5x4Unorm | Format::Astc5x4Srgb => FormatBlock::compressed(5, 4, 16),
+        Format::Astc5x5Unorm | Format::Astc5x5Srgb => FormatBlock::compressed(5, 5, 16),
+        Format::Astc6x5Unorm | Format::Astc6x5Srgb => FormatBlock::compressed(6, 5, 16),
+        Format::Astc6x6Unorm | Format::Astc6x6Srgb => FormatBlock::compressed(6, 6, 16),
+        Format::Astc8x5Unorm | Format::Astc8x5Srgb => FormatBlock::compressed(8, 5, 16),
+        Format::Astc8x6Unorm | Format::Astc8x6Srgb => FormatBlock::compressed(8, 6, 16),
+        Format::Astc8x8Unorm | Format::Astc8x8Srgb => FormatBlock::compressed(8, 8, 16),
+        Format::Astc10x5Unorm | Format::Astc10x5Srgb => FormatBlock::compressed(10, 5, 16),
+        Format::Astc10x6Unorm | Format::Astc10x6Srgb => FormatBlock::compressed(10, 6, 16),
+        Format::Astc10x8Unorm | Format::Astc10x8Srgb => FormatBlock::compressed(10, 8, 16),
+        Format::Astc10x10Unorm | Format::Astc10x10Srgb => FormatBlock::compressed(10, 10, 16),
+        Format::Astc12x10Unorm | Format::Astc12x10Srgb => FormatBlock::compressed(12, 10, 16),
+        Format::Astc12x12Unorm | Format::Astc12x12Srgb => FormatBlock::compressed(12, 12, 16),
         _ => None,
     }
 }
+
+/// Bytes per pixel for uncompressed formats; `None` for block-compressed ones (see
+/// [`format_block`]).
+pub fn bytes_per_pixel(format: Format) -> Option<usize> {
+    format_block(format)
+        .filter(|block| !block.is_compressed())
+        .map(|block| block.bytes as usize)
+}
+
+/// The compressed family `format` belongs to; empty for uncompressed formats.
+pub fn format_compression(format: Format) -> TextureCompression {
+    match format {
+        Format::Bc1RgbaUnorm
+        | Format::Bc1RgbaSrgb
+        | Format::Bc2RgbaUnorm
+        | Format::Bc2RgbaSrgb
+        | Format::Bc3RgbaUnorm
+        | Format::Bc3RgbaSrgb
+        | Format::Bc4RUnorm
+        | Format::Bc4RSnorm
+        | Format::Bc5RgUnorm
+        | Format::Bc5RgSnorm
+        | Format::Bc6hRgbUfloat
+        | Format::Bc6hRgbSfloat
+        | Format::Bc7RgbaUnorm
+        | Format::Bc7RgbaSrgb => TextureCompression::BC,
+        Format::Etc2R8G8B8Unorm
+        | Format::Etc2R8G8B8Srgb
+        | Format::Etc2R8G8B8A1Unorm
+        | Format::Etc2R8G8B8A1Srgb
+        | Format::Etc2R8G8B8A8Unorm
+        | Format::Etc2R8G8B8A8Srgb
+        | Format::EacR11Unorm
+        | Format::EacR11Snorm
+        | Format::EacR11G11Unorm
+        | Format::EacR11G11Snorm => TextureCompression::ETC2,
+        Format::Astc4x4Unorm
+        | Format::Astc4x4Srgb
+        | Format::Astc5x4Unorm
+        | Format::Astc5x4Srgb
+        | Format::Astc5x5Unorm
+        | Format::Astc5x5Srgb
+        | Format::Astc6x5Unorm
+        | Format::Astc6x5Srgb
+        | Format::Astc6x6Unorm
+        | Format::Astc6x6Srgb
+        | Format::Astc8x5Unorm
+        | Format::Astc8x5Srgb
+        | Format::Astc8x6Unorm
+        | Format::Astc8x6Srgb
+        | Format::Astc8x8Unorm
+        | Format::Astc8x8Srgb
+        | Format::Astc10x5Unorm
+        | Format::Astc10x5Srgb
+        | Format::Astc10x6Unorm
+        | Format::Astc10x6Srgb
+        | Format::Astc10x8Unorm
+        | Format::Astc10x8Srgb
+        | Format::Astc10x10Unorm
+        | Format::Astc10x10Srgb
+        | Format::Astc12x10Unorm
+        | Format::Astc12x10Srgb
+        | Format::Astc12x12Unorm
+        | Format::Astc12x12Srgb => TextureCompression::ASTC,
+        _ => TextureCompression::empty(),
+    }
+}
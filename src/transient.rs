@@ -0,0 +1,234 @@
+//! Transient textures aliased onto one `GpuOnly` block.
+//!
+//! Render targets that live for a few passes of a frame do not need memory of their own.
+//! [`TransientTexturePool`] takes each target's description and the (inclusive) range of pass
+//! indices it is live for, places targets with disjoint lifetimes at overlapping offsets of a
+//! single placement block, and creates them with `Device::create_texture`. `begin_pass`
+//! records the barrier each pass needs when a target takes over memory from another.
+//!
+//! A target's contents are undefined at the start of its first pass if it shares memory with
+//! any other target, so that pass must clear or fully overwrite it.
+
+use crate::barrier::StageFlags;
+use crate::command::CommandBuffer;
+use crate::deferred::Owned;
+use crate::device::Device;
+use crate::error::RhiResult;
+use crate::memory::{GpuAllocation, MemoryType};
+use crate::texture::{Texture, TextureDesc, TextureSizeAlign};
+
+/// Handle to a target declared on a [`TransientTexturePool`], valid until the next `build`
+/// with different declarations.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TransientTexture(u32);
+
+#[derive(Clone, Debug, PartialEq)]
+struct Declaration {
+    desc: TextureDesc,
+    first_pass: u32,
+    last_pass: u32,
+}
+
+impl Declaration {
+    fn lifetime_overlaps(&self, other: &Declaration) -> bool {
+        self.first_pass <= other.last_pass && other.first_pass <= self.last_pass
+    }
+}
+
+/// Where a target sits in the block.
+#[derive(Clone, Copy, Debug)]
+struct Placement {
+    offset: u64,
+    size: u64,
+    /// Shares memory with another target (or a previous build's), so its first pass starts
+    /// with an alias barrier.
+    aliased: bool,
+}
+
+impl Placement {
+    fn memory_overlaps(&self, other: &Placement) -> bool {
+        self.offset < other.offset + other.size && other.offset < self.offset + self.size
+    }
+}
+
+/// Pool of transient render targets sharing one `GpuOnly` allocation.
+///
+/// Declare the frame's targets, `build`, then call `begin_pass` at the start of every pass.
+/// Rebuilding with unchanged declarations keeps the existing textures, so the usual pattern is
+/// to declare and build every frame. Replaced textures and blocks are released through
+/// `Device::own` once the GPU is done with them.
+#[derive(Default)]
+pub struct TransientTexturePool {
+    pending: Vec<Declaration>,
+    declarations: Vec<Declaration>,
+    placements: Vec<Placement>,
+    // Field order matters: the textures must be dropped before the block they live in.
+    textures: Vec<Owned<Texture>>,
+    block: Option<Owned<GpuAllocation>>,
+    block_align: u64,
+}
+
+impl TransientTexturePool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Declare a target live from pass `first_pass` through `last_pass` (inclusive). Handles
+    /// are assigned in declaration order.
+    pub fn declare(
+        &mut self,
+        desc: TextureDesc,
+        first_pass: u32,
+        last_pass: u32,
+    ) -> TransientTexture {
+        assert!(
+            first_pass <= last_pass,
+            "transient texture lifetime {first_pass}..={last_pass} is empty"
+        );
+        self.pending.push(Declaration {
+            desc,
+            first_pass,
+            last_pass,
+        });
+        TransientTexture(self.pending.len() as u32 - 1)
+    }
+
+    /// Place and create the declared targets, then start collecting declarations afresh.
+    /// Does nothing if the declarations match the previous build.
+    pub fn build(&mut self, device: &Device) -> RhiResult<()> {
+        let declarations = std::mem::take(&mut self.pending);
+        if declarations == self.declarations && self.textures.len() == declarations.len() {
+            return Ok(());
+        }
+        self.textures.clear();
+        self.declarations.clear();
+        self.placements.clear();
+
+        let mut requirements = Vec::with_capacity(declarations.len());
+        for declaration in &declarations {
+            requirements.push(device.texture_size_align(&declaration.desc)?);
+        }
+        let mut placements = place(&declarations, &requirements);
+        let block_size = placements
+            .iter()
+            .map(|p| p.offset + p.size)
+            .max()
+            .unwrap_or(0);
+        let block_align = requirements.iter().map(|r| r.align).max().unwrap_or(1);
+
+        let block_fits = self
+            .block
+            .as_ref()
+            .is_some_and(|b| b.size() >= block_size && self.block_align >= block_align);
+        if block_fits {
+            // The previous targets' work may still be in flight in this memory.
+            for placement in &mut placements {
+                placement.aliased = true;
+            }
+        } else {
+            self.block = None;
+            if block_size > 0 {
+                let block = device.malloc_aligned(block_size, block_align, MemoryType::GpuOnly)?;
+                self.block = Some(device.own(block));
+                self.block_align = block_align;
+            }
+        }
+
+        if let Some(block) = &self.block {
+            for (declaration, placement) in declarations.iter().zip(&placements) {
+                let texture = device
+                    .create_texture(&declaration.desc, block.gpu().offset(placement.offset))?;
+                self.textures.push(device.own(texture));
+            }
+        }
+        self.declarations = declarations;
+        self.placements = placements;
+        Ok(())
+    }
+
+    /// The texture created for `handle` by the last `build`.
+    pub fn texture(&self, handle: TransientTexture) -> &Texture {
+        &self.textures[handle.0 as usize]
+    }
+
+    /// Record the barrier pass `pass` needs: targets whose lifetime starts here and that
+    /// share memory with other targets are handed the memory and have their contents
+    /// discarded. Records nothing if no such target starts at `pass`.
+    pub fn begin_pass(&self, cmd: &mut CommandBuffer, pass: u32) {
+        let starting: Vec<&Texture> = self
+            .declarations
+            .iter()
+            .zip(&self.placements)
+            .zip(&self.textures)
+            .filter(|((d, p), _)| d.first_pass == pass && p.aliased)
+            .map(|(_, texture)| &**texture)
+            .collect();
+        if !starting.is_empty() {
+            cmd.alias_barrier(
+                StageFlags::ALL_COMMANDS,
+                StageFlags::ALL_COMMANDS,
+                &starting,
+            );
+        }
+    }
+
+    /// Bytes of the shared block.
+    pub fn memory_size(&self) -> u64 {
+        self.block.as_ref().map_or(0, |b| b.size())
+    }
+
+    /// Bytes the built targets would take with an allocation each.
+    pub fn unaliased_size(&self) -> u64 {
+        self.placements.iter().map(|p| p.size).sum()
+    }
+}
+
+/// Assign block offsets, largest target first: each goes at the lowest aligned offset that
+/// does not collide with an already-placed target whose lifetime overlaps its own.
+fn place(declarations: &[Declaration], requirements: &[TextureSizeAlign]) -> Vec<Placement> {
+    let mut order: Vec<usize> = (0..declarations.len()).collect();
+    order.sort_by_key(|&i| std::cmp::Reverse(requirements[i].size));
+
+    let mut placements: Vec<Option<Placement>> = vec![None; declarations.len()];
+    for &i in &order {
+        let size = requirements[i].size;
+        let align = requirements[i].align.max(1);
+        let live: Vec<Placement> = order
+            .iter()
+            .filter_map(|&j| {
+                placements[j].filter(|_| declarations[i].lifetime_overlaps(&declarations[j]))
+            })
+            .collect();
+        let candidates = std::iter::once(0).chain(live.iter().map(|p| p.offset + p.size));
+        let offset = candidates
+            .map(|offset| offset.next_multiple_of(align))
+            .filter(|&offset| {
+                let candidate = Placement {
+                    offset,
+                    size,
+                    aliased: false,
+                };
+                live.iter().all(|p| !candidate.memory_overlaps(p))
+            })
+            .min()
+            .expect("the end of the highest live target is always free");
+        placements[i] = Some(Placement {
+            offset,
+            size,
+            aliased: false,
+        });
+    }
+
+    let placed: Vec<Placement> = placements.into_iter().map(Option::unwrap).collect();
+    placed
+        .iter()
+        .enumerate()
+        .map(|(i, p)| Placement {
+            aliased: placed
+                .iter()
+                .enumerate()
+                .any(|(j, q)| j != i && p.memory_overlaps(q)),
+            ..*p
+        })
+        .collect()
+}
@@ -1,11 +1,16 @@
+use std::marker::PhantomData;
+
 use zerocopy::{FromBytes, Immutable, IntoBytes};
 
+use crate::memory::GpuPod;
+
 /// GPU virtual address for buffer device address / Metal gpuAddress.
 ///
 /// Use this type directly as the field type for GPU-pointer fields in [`gpu_struct!`] (the
-/// Slang side stays `"T*"`). It is `#[repr(transparent)]` over `u64` and a [`GpuPod`], so a
-/// pointer field reads as a pointer and you can assign `alloc.gpu()` straight in — no `.0`
-/// unwrap. Reserve raw `u64` fields for genuine integers.
+/// Slang side stays `"T*"`), or [`GpuPtr<T>`] to have the pointee type checked. It is
+/// `#[repr(transparent)]` over `u64` and a [`GpuPod`], so a pointer field reads as a pointer
+/// and you can assign `alloc.gpu()` straight in — no `.0` unwrap. Reserve raw `u64` fields for
+/// genuine integers.
 ///
 /// [`gpu_struct!`]: crate::gpu_struct
 /// [`GpuPod`]: crate::GpuPod
@@ -42,6 +47,192 @@ impl std::fmt::LowerHex for GpuAddress {
     }
 }
 
+impl<T: GpuPod> From<GpuPtr<T>> for GpuAddress {
+    fn from(ptr: GpuPtr<T>) -> Self {
+        ptr.addr
+    }
+}
+
+/// Typed GPU pointer: a [`GpuAddress`] known to point at a `T`.
+///
+/// `#[repr(transparent)]` over `GpuAddress`, so it has the same wire format and can be a
+/// [`gpu_struct!`] field directly (`data: GpuPtr<Vertex>` emits `Vertex* data;`). Arithmetic
+/// is in elements, not bytes. `T` must be [`GpuPod`](crate::GpuPod), but the type is a
+/// compile-time label only: nothing checks that the memory really holds `T`s.
+///
+/// [`gpu_struct!`]: crate::gpu_struct
+#[repr(transparent)]
+#[derive(IntoBytes, FromBytes, Immutable)]
+pub struct GpuPtr<T: GpuPod> {
+    addr: GpuAddress,
+    _pointee: PhantomData<T>,
+}
+
+impl<T: GpuPod> GpuPtr<T> {
+    pub const NULL: Self = Self::new(GpuAddress::NULL);
+
+    /// Label `addr` as pointing at a `T`.
+    #[inline]
+    pub const fn new(addr: GpuAddress) -> Self {
+        Self {
+            addr,
+            _pointee: PhantomData,
+        }
+    }
+
+    /// The untyped address.
+    #[inline]
+    pub fn addr(self) -> GpuAddress {
+        self.addr
+    }
+
+    #[inline]
+    pub fn is_null(self) -> bool {
+        self.addr.is_null()
+    }
+
+    /// Pointer to the element `index` places further on (`ptr + index` in Slang).
+    #[inline]
+    pub fn offset(self, index: u64) -> Self {
+        Self::new(
+            self.addr
+                .offset(index.wrapping_mul(std::mem::size_of::<T>() as u64)),
+        )
+    }
+
+    /// Reinterpret as a pointer to `U`, keeping the address.
+    #[inline]
+    pub fn cast<U: GpuPod>(self) -> GpuPtr<U> {
+        GpuPtr::new(self.addr)
+    }
+}
+
+impl<T: GpuPod> Clone for GpuPtr<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<T: GpuPod> Copy for GpuPtr<T> {}
+impl<T: GpuPod> Default for GpuPtr<T> {
+    fn default() -> Self {
+        Self::NULL
+    }
+}
+impl<T: GpuPod> PartialEq for GpuPtr<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.addr == other.addr
+    }
+}
+impl<T: GpuPod> Eq for GpuPtr<T> {}
+impl<T: GpuPod> std::hash::Hash for GpuPtr<T> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.addr.hash(state);
+    }
+}
+impl<T: GpuPod> std::fmt::Debug for GpuPtr<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "GpuPtr<{}>({:#x})",
+            std::any::type_name::<T>(),
+            self.addr
+        )
+    }
+}
+
+/// Typed GPU array: a [`GpuPtr`] plus an element count.
+///
+/// Host-side only; it is not `#[repr(transparent)]` since it carries the length. Put
+/// [`ptr`](Self::ptr) (and the length, if the shader needs it) into GPU structs.
+pub struct GpuSlice<T: GpuPod> {
+    ptr: GpuPtr<T>,
+    len: u64,
+}
+
+impl<T: GpuPod> GpuSlice<T> {
+    /// `len` elements starting at `ptr`.
+    #[inline]
+    pub const fn new(ptr: GpuPtr<T>, len: u64) -> Self {
+        Self { ptr, len }
+    }
+
+    /// Pointer to the first element.
+    #[inline]
+    pub fn ptr(self) -> GpuPtr<T> {
+        self.ptr
+    }
+
+    /// Number of elements.
+    #[inline]
+    pub fn len(self) -> u64 {
+        self.len
+    }
+
+    #[inline]
+    pub fn is_empty(self) -> bool {
+        self.len == 0
+    }
+
+    /// Size of the slice in bytes.
+    #[inline]
+    pub fn byte_size(self) -> u64 {
+        self.len * std::mem::size_of::<T>() as u64
+    }
+
+    /// Pointer to element `index`. `index == len()` (one past the end) is allowed, like a
+    /// Rust slice's end pointer; anything further panics.
+    #[inline]
+    pub fn offset(self, index: u64) -> GpuPtr<T> {
+        assert!(
+            index <= self.len,
+            "GpuSlice index {index} out of bounds (len {})",
+            self.len
+        );
+        self.ptr.offset(index)
+    }
+
+    /// Pointer to element `index`, or `None` if out of bounds.
+    #[inline]
+    pub fn get(self, index: u64) -> Option<GpuPtr<T>> {
+        (index < self.len).then(|| self.ptr.offset(index))
+    }
+
+    /// Sub-slice of `count` elements starting at `start`. Panics if it exceeds the slice.
+    pub fn slice(self, start: u64, count: u64) -> Self {
+        let end = start.checked_add(count);
+        assert!(
+            end.is_some_and(|end| end <= self.len),
+            "GpuSlice range {start}..{start}+{count} out of bounds (len {})",
+            self.len
+        );
+        Self::new(self.ptr.offset(start), count)
+    }
+}
+
+impl<T: GpuPod> Clone for GpuSlice<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<T: GpuPod> Copy for GpuSlice<T> {}
+impl<T: GpuPod> PartialEq for GpuSlice<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.ptr == other.ptr && self.len == other.len
+    }
+}
+impl<T: GpuPod> Eq for GpuSlice<T> {}
+impl<T: GpuPod> std::fmt::Debug for GpuSlice<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "GpuSlice<{}>({:#x}, len {})",
+            std::any::type_name::<T>(),
+            self.ptr.addr,
+            self.len
+        )
+    }
+}
+
 /// Maximum number of bindless textures supported by the RHI.
 pub const MAX_BINDLESS_TEXTURES: u32 = 1_000_000;
 /// Maximum number of bindless samplers supported by the RHI.
@@ -84,6 +275,64 @@ pub enum Format {
     R10G10B10A2Unorm,
     R11G11B10Float,
 
+    // Block-compressed (BC1–BC7; desktop GPUs)
+    Bc1RgbaUnorm,
+    Bc1RgbaSrgb,
+    Bc2RgbaUnorm,
+    Bc2RgbaSrgb,
+    Bc3RgbaUnorm,
+    Bc3RgbaSrgb,
+    Bc4RUnorm,
+    Bc4RSnorm,
+    Bc5RgUnorm,
+    Bc5RgSnorm,
+    Bc6hRgbUfloat,
+    Bc6hRgbSfloat,
+    Bc7RgbaUnorm,
+    Bc7RgbaSrgb,
+
+    // Block-compressed (ETC2 / EAC; mobile and Apple GPUs)
+    Etc2R8G8B8Unorm,
+    Etc2R8G8B8Srgb,
+    Etc2R8G8B8A1Unorm,
+    Etc2R8G8B8A1Srgb,
+    Etc2R8G8B8A8Unorm,
+    Etc2R8G8B8A8Srgb,
+    EacR11Unorm,
+    EacR11Snorm,
+    EacR11G11Unorm,
+    EacR11G11Snorm,
+
+    // Block-compressed (ASTC LDR; mobile and Apple GPUs)
+    Astc4x4Unorm,
+    Astc4x4Srgb,
+    Astc5x4Unorm,
+    Astc5x4Srgb,
+    Astc5x5Unorm,
+    Astc5x5Srgb,
+    Astc6x5Unorm,
+    Astc6x5Srgb,
+    Astc6x6Unorm,
+    Astc6x6Srgb,
+    Astc8x5Unorm,
+    Astc8x5Srgb,
+    Astc8x6Unorm,
+    Astc8x6Srgb,
+    Astc8x8Unorm,
+    Astc8x8Srgb,
+    Astc10x5Unorm,
+    Astc10x5Srgb,
+    Astc10x6Unorm,
+    Astc10x6Srgb,
+    Astc10x8Unorm,
+    Astc10x8Srgb,
+    Astc10x10Unorm,
+    Astc10x10Srgb,
+    Astc12x10Unorm,
+    Astc12x10Srgb,
+    Astc12x12Unorm,
+    Astc12x12Srgb,
+
     // Depth/stencil
     D16Unorm,
     D32Float,
@@ -0,0 +1,250 @@
+//! Staging uploads into `GpuOnly` buffers and textures.
+//!
+//! `GpuOnly` memory is not CPU-mapped, so filling it means copying through a `Default` staging
+//! buffer on the GPU. [`Uploader`] owns that plumbing: each upload is copied into a staging
+//! chunk and a matching copy is recorded, either into the uploader's own command buffer or into
+//! one the caller supplies. `flush` submits the uploader's command buffer and returns an
+//! [`UploadToken`] that completes once every copy recorded so far has executed. Staging chunks
+//! are reused once the submission that read them has retired.
+
+use crate::barrier::StageFlags;
+use crate::command::CommandBuffer;
+use crate::device::Device;
+use crate::error::{RhiError, RhiResult};
+use crate::memory::{BumpAllocator, GpuPod, MemoryType, TransientAllocation};
+use crate::texture::{Texture, TextureCopyRegion, format_block};
+use crate::types::GpuAddress;
+use zerocopy::IntoBytes;
+
+/// Default size of each staging chunk.
+pub const DEFAULT_STAGING_CHUNK_SIZE: u64 = 16 << 20;
+
+/// Staging offset alignment. Covers every texel size, so texture copy sources are always
+/// suitably aligned.
+const STAGING_ALIGNMENT: u64 = 256;
+
+/// Completion token for the copies recorded before a `flush`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct UploadToken {
+    serial: u64,
+}
+
+impl UploadToken {
+    /// True once the uploads have finished executing on the GPU.
+    pub fn is_complete(&self, device: &Device) -> bool {
+        device.queue().completed_serial() >= self.serial
+    }
+
+    /// Block until the uploads have finished executing on the GPU.
+    pub fn wait(&self, device: &Device) {
+        device.queue().wait_for_serial(self.serial);
+    }
+}
+
+struct StagingChunk {
+    arena: BumpAllocator,
+    /// Queue serial that must complete before the chunk may be reset.
+    retire_serial: u64,
+    /// Written since the last `flush`; not reusable until tagged and retired.
+    active: bool,
+}
+
+/// Batches uploads into `GpuOnly` (or any) memory through reusable staging chunks.
+///
+/// Copies recorded into a caller-supplied command buffer must be submitted before the next
+/// `flush`, which tags the staging they read with the latest submission. Dropping the
+/// uploader flushes pending copies, waits for them and frees the staging memory.
+pub struct Uploader<'d> {
+    device: &'d Device,
+    chunk_size: u64,
+    chunks: Vec<StagingChunk>,
+    /// The uploader's own command buffer, created on the first upload after a flush.
+    cmd: Option<CommandBuffer>,
+}
+
+impl<'d> Uploader<'d> {
+    /// An uploader staging through chunks of `chunk_size` bytes (larger uploads get a chunk
+    /// of their own). See [`DEFAULT_STAGING_CHUNK_SIZE`].
+    pub fn new(device: &'d Device, chunk_size: u64) -> Self {
+        Self {
+            device,
+            chunk_size,
+            chunks: Vec::new(),
+            cmd: None,
+        }
+    }
+
+    /// Copy `data` to `dst`, recording the copy into the uploader's command buffer.
+    pub fn upload_buffer<T: GpuPod>(&mut self, dst: GpuAddress, data: &[T]) -> RhiResult<()> {
+        let staging = self.stage(data.as_bytes())?;
+        self.own_cmd()?.memcpy(dst, staging.gpu, staging.size);
+        Ok(())
+    }
+
+    /// Copy tightly packed texels into mip `mip` of array layer `layer` of `texture`,
+    /// recording the copy into the uploader's command buffer. `bytes` must cover the whole
+    /// mip level exactly; otherwise, or for a mip or layer the texture lacks, this returns
+    /// [`RhiError::InvalidArgument`].
+    pub fn upload_texture(
+        &mut self,
+        texture: &Texture,
+        mip: u32,
+        layer: u32,
+        bytes: &[u8],
+    ) -> RhiResult<()> {
+        check_texture_upload(texture, mip, layer, bytes)?;
+        let staging = self.stage(bytes)?;
+        self.own_cmd()?
+            .copy_to_texture_subresource(staging.gpu, texture, mip, layer);
+        Ok(())
+    }
+
+    /// Like [`upload_buffer`](Self::upload_buffer), but record the copy into `cmd`. The
+    /// caller orders it against later reads with a barrier.
+    pub fn record_buffer_upload<T: GpuPod>(
+        &mut self,
+        cmd: &mut CommandBuffer,
+        dst: GpuAddress,
+        data: &[T],
+    ) -> RhiResult<()> {
+        let staging = self.stage(data.as_bytes())?;
+        cmd.memcpy(dst, staging.gpu, staging.size);
+        Ok(())
+    }
+
+    /// Like [`upload_texture`](Self::upload_texture), but record the copy into `cmd`.
+    pub fn record_texture_upload(
+        &mut self,
+        cmd: &mut CommandBuffer,
+        texture: &Texture,
+        mip: u32,
+        layer: u32,
+        bytes: &[u8],
+    ) -> RhiResult<()> {
+        check_texture_upload(texture, mip, layer, bytes)?;
+        let staging = self.stage(bytes)?;
+        cmd.copy_to_texture_subresource(staging.gpu, texture, mip, layer);
+        Ok(())
+    }
+
+    /// Submit the uploader's pending copies (if any) and return a token that completes once
+    /// they, and every copy recorded into caller-supplied command buffers submitted so far,
+    /// have executed.
+    pub fn flush(&mut self) -> RhiResult<UploadToken> {
+        let queue = self.device.queue();
+        if let Some(mut cmd) = self.cmd.take() {
+            cmd.barrier(StageFlags::TRANSFER, StageFlags::ALL_COMMANDS);
+            cmd.end();
+            queue.submit(cmd)?;
+        }
+        let serial = queue.submitted_serial();
+        for chunk in self.chunks.iter_mut().filter(|c| c.active) {
+            chunk.active = false;
+            chunk.retire_serial = serial;
+        }
+        Ok(UploadToken { serial })
+    }
+
+    /// Bytes of staging memory held, in use or not.
+    pub fn staging_capacity(&self) -> u64 {
+        self.chunks.iter().map(|c| c.arena.capacity()).sum()
+    }
+
+    fn own_cmd(&mut self) -> RhiResult<&mut CommandBuffer> {
+        if self.cmd.is_none() {
+            self.cmd = Some(self.device.create_command_buffer()?);
+        }
+        Ok(self.cmd.as_mut().expect("uploader command buffer"))
+    }
+
+    /// Copy `bytes` into staging memory.
+    fn stage(&mut self, bytes: &[u8]) -> RhiResult<TransientAllocation> {
+        let size = bytes.len() as u64;
+        let staging = match self.alloc_staging(size) {
+            Some(staging) => staging,
+            None => {
+                let completed = self.device.queue().completed_serial();
+                let reusable = self.chunks.iter_mut().find(|c| {
+                    !c.active && c.retire_serial <= completed && c.arena.capacity() >= size
+                });
+                let chunk = match reusable {
+                    Some(chunk) => chunk,
+                    None => {
+                        let chunk_size = self.chunk_size.max(size);
+                        let allocation = self.device.malloc_aligned(
+                            chunk_size,
+                            STAGING_ALIGNMENT,
+                            MemoryType::Default,
+                        )?;
+                        self.chunks.push(StagingChunk {
+                            arena: BumpAllocator::new(allocation.into_buffer()),
+                            retire_serial: 0,
+                            active: false,
+                        });
+                        self.chunks.last_mut().expect("staging chunk just pushed")
+                    }
+                };
+                chunk.arena.reset();
+                chunk.active = true;
+                chunk
+                    .arena
+                    .alloc(size, STAGING_ALIGNMENT)
+                    .expect("fresh staging chunk fits the upload")
+            }
+        };
+        staging.upload_slice(bytes)?;
+        Ok(staging)
+    }
+
+    /// Place `size` bytes in a chunk already written since the last flush.
+    fn alloc_staging(&mut self, size: u64) -> Option<TransientAllocation> {
+        self.chunks
+            .iter_mut()
+            .filter(|c| c.active)
+            .find_map(|c| c.arena.alloc(size, STAGING_ALIGNMENT))
+    }
+}
+
+impl Drop for Uploader<'_> {
+    fn drop(&mut self) {
+        if self.flush().is_err() {
+            self.device.wait_idle();
+        }
+        let last_read = self.chunks.iter().map(|c| c.retire_serial).max();
+        if let Some(serial) = last_read {
+            self.device.queue().wait_for_serial(serial);
+        }
+        for chunk in self.chunks.drain(..) {
+            self.device.destroy_buffer(chunk.arena.into_buffer());
+        }
+    }
+}
+
+fn check_texture_upload(texture: &Texture, mip: u32, layer: u32, bytes: &[u8]) -> RhiResult<()> {
+    let desc = texture.desc();
+    if mip >= desc.mip_levels || layer >= desc.layer_count() {
+        return Err(RhiError::InvalidArgument(format!(
+            "texture upload to mip {mip} layer {layer} is outside the texture's {} mips and {} \
+             layers",
+            desc.mip_levels,
+            desc.layer_count()
+        )));
+    }
+    if format_block(desc.format).is_none() {
+        return Err(RhiError::Unsupported(format!(
+            "texture upload to format {:?}",
+            desc.format
+        )));
+    }
+    let region = TextureCopyRegion::subresource(mip, layer).resolve(desc, "upload");
+    if bytes.len() as u64 != region.byte_size {
+        return Err(RhiError::InvalidArgument(format!(
+            "texture upload of {} bytes must cover mip {mip} ({}x{}, {} bytes) exactly",
+            bytes.len(),
+            region.extent[0],
+            region.extent[1],
+            region.byte_size
+        )));
+    }
+    Ok(())
+}
@@ -0,0 +1,368 @@
+//! Headless command bundle tests (timed): a render bundle and a compute bundle, each
+//! recorded once and executed from several command buffers, and a bundle dropped while in
+//! flight.
+
+mod common;
+
+use kiln_rhi::{
+    ColorAttachment, ColorTarget, ComputeBundle, ComputeBundleDesc, ComputeBundleEncoder,
+    ComputePsoDesc, Cull, Format, GraphicsPsoDesc, LoadOp, MemoryType, RenderBundle,
+    RenderBundleDesc, RenderBundleEncoder, RenderPassDesc, RenderTarget, SampleCount, ShaderStage,
+    StageFlags, StoreOp, TextureDesc, TextureDimension, TextureUsage, Topology, gpu_struct,
+};
+
+gpu_struct! {
+    pub struct Root {
+        color: [f32; 4] as "float4",
+    }
+}
+
+gpu_struct! {
+    pub struct Counters {
+        values: GpuPtr<u32>,
+        count: u32 as "uint",
+        _pad: u32 as "uint",
+    }
+}
+
+// Full-screen triangle; the scissor picks which half each draw covers.
+const GFX_BODY: &str = /*slang*/
+    r#"
+struct VOut { float4 pos : SV_Position; };
+
+[shader("vertex")]
+VOut vsMain(uint vid : SV_VertexID)
+{
+    float2 p = float2(float((vid << 1) & 2), float(vid & 2));
+    VOut o;
+    o.pos = float4(p * 2.0 - 1.0, 0.0, 1.0);
+    return o;
+}
+
+[shader("fragment")]
+float4 fsMain(VOut i, uniform Root* r) : SV_Target
+{
+    return r.color;
+}
+"#;
+
+const INCREMENT_BODY: &str = /*slang*/
+    r#"
+[shader("compute")]
+[numthreads(64, 1, 1)]
+void computeMain(uint3 tid : SV_DispatchThreadID, uniform Counters* c)
+{
+    if (tid.x >= c.count)
+        return;
+    c.values[tid.x] += 1u;
+}
+"#;
+
+const SIZE: u32 = 64;
+
+/// Encoders record on worker threads, and bundles are handed back to the submitting one.
+#[test]
+fn bundles_are_send() {
+    fn assert_send<T: Send>() {}
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send::<RenderBundleEncoder>();
+    assert_send::<ComputeBundleEncoder>();
+    assert_send_sync::<RenderBundle>();
+    assert_send_sync::<ComputeBundle>();
+}
+
+/// Record two scissored draws with different roots into one bundle, then execute it in two
+/// command buffers; both must fill the left half red and the right half green.
+#[test]
+fn render_bundle_replays_in_two_command_buffers() {
+    let Some((device, _gpu)) = common::device_or_skip() else {
+        return;
+    };
+
+    let src = format!("{}{}", Root::SLANG, GFX_BODY);
+    let Some(vs) = common::compile_shader_or_skip(&device, &src, "vsMain", ShaderStage::Vertex)
+    else {
+        return;
+    };
+    let Some(fs) = common::compile_shader_or_skip(&device, &src, "fsMain", ShaderStage::Pixel)
+    else {
+        return;
+    };
+    let pso = device
+        .create_graphics_pso(
+            &GraphicsPsoDesc {
+                topology: Topology::TriangleList,
+                color_targets: vec![ColorTarget::new(Format::R8G8B8A8Unorm)],
+                depth_format: None,
+                sample_count: SampleCount::S1,
+                root_constant_size: 16,
+                cull: Cull::None,
+                label: Some("bundle-fill".into()),
+                ..Default::default()
+            },
+            &vs,
+            &fs,
+        )
+        .expect("create_graphics_pso");
+
+    let roots = device
+        .malloc(2 * std::mem::size_of::<Root>() as u64, MemoryType::Default)
+        .expect("roots");
+    roots
+        .upload_slice(&[
+            Root {
+                color: [1.0, 0.0, 0.0, 1.0],
+            },
+            Root {
+                color: [0.0, 1.0, 0.0, 1.0],
+            },
+        ])
+        .expect("upload roots");
+    let red = roots.gpu();
+    let green = roots.gpu().offset(std::mem::size_of::<Root>() as u64);
+
+    let bundle = common::timed("record render bundle · 2 draws", || {
+        let mut encoder = device
+            .create_render_bundle_encoder(&RenderBundleDesc {
+                color_formats: vec![Format::R8G8B8A8Unorm],
+                depth_format: None,
+                label: Some("halves".into()),
+                ..Default::default()
+            })
+            .expect("create_render_bundle_encoder");
+        encoder.set_graphics_pipeline(&pso);
+        encoder.set_viewport(0.0, 0.0, SIZE as f32, SIZE as f32, 0.0, 1.0);
+        encoder.set_scissor(0, 0, SIZE / 2, SIZE);
+        encoder.draw(red, red, 3, 1, 0, 0);
+        encoder.set_scissor((SIZE / 2) as i32, 0, SIZE / 2, SIZE);
+        encoder.draw(green, green, 3, 1, 0, 0);
+        encoder.finish().expect("finish")
+    });
+
+    let tex_desc = TextureDesc {
+        width: SIZE,
+        height: SIZE,
+        depth: 1,
+        mip_levels: 1,
+        array_layers: 1,
+        format: Format::R8G8B8A8Unorm,
+        dimension: TextureDimension::D2,
+        sample_count: SampleCount::S1,
+        usage: TextureUsage::COLOR_ATTACHMENT | TextureUsage::TRANSFER_SRC,
+        label: Some("bundle-rt".into()),
+        export: None,
+    };
+    let sa = device.texture_size_align(&tex_desc).expect("size_align");
+    let tex_mem = device
+        .malloc_aligned(sa.size, sa.align, MemoryType::GpuOnly)
+        .expect("rt mem");
+    let texture = device
+        .create_texture(&tex_desc, tex_mem.gpu())
+        .expect("create_texture");
+    let readback = device
+        .malloc((SIZE * SIZE * 4) as u64, MemoryType::Readback)
+        .expect("readback");
+
+    for run in 0..2 {
+        common::timed("execute render bundle · readback · submit+wait", || {
+            let mut cmd = device.create_command_buffer().expect("cmd");
+            cmd.execute_render_bundles(
+                &RenderPassDesc {
+                    color_attachments: vec![ColorAttachment {
+                        target: RenderTarget::Texture(texture.id()),
+                        load_op: LoadOp::Clear,
+                        store_op: StoreOp::Store,
+                        clear_color: [0.0, 0.0, 0.0, 1.0],
+                        resolve_target: None,
+                    }],
+                    depth_attachment: None,
+                    render_area: [0, 0, SIZE, SIZE],
+                    view_mask: 0,
+                },
+                &[&bundle],
+            );
+            cmd.barrier(StageFlags::RASTER_COLOR_OUT, StageFlags::TRANSFER);
+            cmd.copy_from_texture(readback.gpu(), tex_mem.gpu(), &texture);
+            cmd.barrier(StageFlags::TRANSFER, StageFlags::ALL_COMMANDS);
+            cmd.end();
+            let queue = device.queue();
+            queue.submit(cmd).expect("submit");
+            queue.wait_idle();
+        });
+
+        let pixels = readback.as_slice::<u8>().expect("read readback");
+        let row = (SIZE / 2 * SIZE * 4) as usize;
+        let left = &pixels[row + 4 * (SIZE / 4) as usize..][..4];
+        let right = &pixels[row + 4 * (3 * SIZE / 4) as usize..][..4];
+        assert_eq!(left, [255, 0, 0, 255], "run {run}: left half not red");
+        assert_eq!(right, [0, 255, 0, 255], "run {run}: right half not green");
+        if run == 1 {
+            common::save_rgba_png("render_bundle_halves", SIZE, SIZE, pixels);
+        }
+    }
+
+    drop(bundle);
+    device.free(readback);
+    device.free(roots);
+}
+
+/// One dispatch incrementing every counter, executed twice in one command buffer (with a
+/// barrier between) and once in a second: each counter must read 3.
+#[test]
+fn compute_bundle_replays() {
+    let Some((device, _gpu)) = common::device_or_skip() else {
+        return;
+    };
+
+    let src = format!("{}{}", Counters::SLANG, INCREMENT_BODY);
+    let Some(module) =
+        common::compile_shader_or_skip(&device, &src, "computeMain", ShaderStage::Compute)
+    else {
+        return;
+    };
+    let pso = device
+        .create_compute_pso(
+            &ComputePsoDesc {
+                root_constant_size: 16,
+                threads_per_threadgroup: [64, 1, 1],
+                label: Some("increment".into()),
+            },
+            &module,
+        )
+        .expect("create_compute_pso");
+
+    const N: u32 = 256;
+    let values = device
+        .malloc((N * 4) as u64, MemoryType::Readback)
+        .expect("values");
+    values
+        .upload_slice(&[0u32; N as usize])
+        .expect("zero values");
+    let root = device
+        .malloc(std::mem::size_of::<Counters>() as u64, MemoryType::Default)
+        .expect("root");
+    root.upload(&Counters {
+        values: values.typed().ptr(),
+        count: N,
+        _pad: 0,
+    })
+    .expect("upload root");
+
+    let bundle = common::timed("record compute bundle · 1 dispatch", || {
+        let mut encoder = device
+            .create_compute_bundle_encoder(&ComputeBundleDesc {
+                label: Some("increment".into()),
+            })
+            .expect("create_compute_bundle_encoder");
+        encoder.set_compute_pipeline(&pso);
+        encoder.dispatch(root.gpu(), N.div_ceil(64), 1, 1);
+        encoder.finish().expect("finish")
+    });
+
+    common::timed("execute compute bundle ×3 · 2 submits", || {
+        let queue = device.queue();
+        let mut cmd = device.create_command_buffer().expect("cmd");
+        cmd.execute_compute_bundles(&[&bundle]);
+        cmd.barrier(StageFlags::COMPUTE, StageFlags::COMPUTE);
+        cmd.execute_compute_bundles(&[&bundle]);
+        cmd.barrier(StageFlags::COMPUTE, StageFlags::ALL_COMMANDS);
+        cmd.end();
+        queue.submit(cmd).expect("submit");
+
+        let mut cmd = device.create_command_buffer().expect("cmd");
+        cmd.barrier(StageFlags::ALL_COMMANDS, StageFlags::COMPUTE);
+        cmd.execute_compute_bundles(&[&bundle]);
+        cmd.barrier(StageFlags::COMPUTE, StageFlags::ALL_COMMANDS);
+        cmd.end();
+        queue.submit(cmd).expect("submit");
+        queue.wait_idle();
+    });
+
+    let result = values.as_slice::<u32>().expect("read values");
+    assert!(
+        result.iter().all(|&v| v == 3),
+        "counters not incremented three times: {:?}",
+        &result[..8]
+    );
+
+    drop(bundle);
+    device.free(values);
+    device.free(root);
+}
+
+/// A bundle dropped while a submission executing it is in flight is parked until that
+/// submission retires; one dropped with nothing submitted is released by the next collect.
+#[test]
+fn dropped_bundle_outlives_its_submission() {
+    let Some((device, _gpu)) = common::device_or_skip() else {
+        return;
+    };
+
+    let src = format!("{}{}", Counters::SLANG, INCREMENT_BODY);
+    let Some(module) =
+        common::compile_shader_or_skip(&device, &src, "computeMain", ShaderStage::Compute)
+    else {
+        return;
+    };
+    let pso = device
+        .create_compute_pso(
+            &ComputePsoDesc {
+                root_constant_size: 16,
+                threads_per_threadgroup: [64, 1, 1],
+                label: Some("increment".into()),
+            },
+            &module,
+        )
+        .expect("create_compute_pso");
+
+    const N: u32 = 64;
+    let values = device
+        .malloc((N * 4) as u64, MemoryType::Readback)
+        .expect("values");
+    values
+        .upload_slice(&[0u32; N as usize])
+        .expect("zero values");
+    let root = device
+        .malloc(std::mem::size_of::<Counters>() as u64, MemoryType::Default)
+        .expect("root");
+    root.upload(&Counters {
+        values: values.typed().ptr(),
+        count: N,
+        _pad: 0,
+    })
+    .expect("upload root");
+
+    let record = || {
+        let mut encoder = device
+            .create_compute_bundle_encoder(&ComputeBundleDesc::default())
+            .expect("create_compute_bundle_encoder");
+        encoder.set_compute_pipeline(&pso);
+        encoder.dispatch(root.gpu(), 1, 1, 1);
+        encoder.finish().expect("finish")
+    };
+
+    device.wait_idle();
+    drop(record());
+    assert_eq!(device.collect_garbage(), 1, "unsubmitted bundle released");
+
+    let bundle = record();
+    let mut cmd = device.create_command_buffer().expect("cmd");
+    cmd.execute_compute_bundles(&[&bundle]);
+    cmd.barrier(StageFlags::COMPUTE, StageFlags::ALL_COMMANDS);
+    cmd.end();
+    device.queue().submit(cmd).expect("submit");
+    drop(bundle);
+
+    device.wait_idle();
+    assert_eq!(device.collect_garbage(), 0, "wait_idle released the bundle");
+    assert!(
+        values
+            .as_slice::<u32>()
+            .expect("read values")
+            .iter()
+            .all(|&v| v == 1)
+    );
+
+    device.free(values);
+    device.free(root);
+}
@@ -6,7 +6,9 @@
 
 mod common;
 
-use kiln_rhi::{ComputePsoDesc, GpuAddress, MemoryType, ShaderStage, StageFlags, gpu_struct};
+use kiln_rhi::{
+    ComputePsoDesc, GpuAddress, GpuPtr, GpuSlice, MemoryType, ShaderStage, StageFlags, gpu_struct,
+};
 
 // Shared host/device data contract. `Data::SLANG` is the matching Slang declaration.
 gpu_struct! {
@@ -101,3 +103,75 @@ fn compute_doubles_buffer() {
     device.free(output);
     device.free(data);
 }
+
+/// `GpuPtr<T>` fields emit `T*` in the Slang declaration, keep `GpuAddress`'s wire format,
+/// and do arithmetic in elements.
+#[test]
+fn typed_gpu_pointers() {
+    gpu_struct! {
+        pub struct TypedData {
+            input: GpuPtr<u32>,
+            output: GpuPtr<u32>,
+            count: u32 as "uint",
+            _pad: u32 as "uint",
+        }
+    }
+
+    assert_eq!(
+        TypedData::SLANG,
+        "struct TypedData {\n    uint* input;\n    uint* output;\n    uint count;\n    uint _pad;\n};\n"
+    );
+    assert_eq!(std::mem::size_of::<GpuPtr<TypedData>>(), 8);
+    assert_eq!(
+        std::mem::size_of::<TypedData>(),
+        std::mem::size_of::<Data>(),
+        "typed pointers keep the untyped layout"
+    );
+
+    let base = GpuPtr::<TypedData>::new(GpuAddress(0x1000));
+    assert_eq!(base.offset(2).addr(), GpuAddress(0x1000 + 48));
+    assert_eq!(base.cast::<u32>().offset(2).addr(), GpuAddress(0x1008));
+    assert!(GpuPtr::<u32>::NULL.is_null());
+
+    let slice = GpuSlice::new(base.cast::<u32>(), 8);
+    assert_eq!(slice.len(), 8);
+    assert_eq!(slice.byte_size(), 32);
+    assert_eq!(slice.get(7), Some(slice.ptr().offset(7)));
+    assert_eq!(slice.get(8), None);
+    assert_eq!(
+        slice.offset(8).addr(),
+        GpuAddress(0x1020),
+        "one past the end is allowed"
+    );
+    let tail = slice.slice(6, 2);
+    assert_eq!((tail.ptr().addr(), tail.len()), (GpuAddress(0x1018), 2));
+}
+
+/// `GpuPtr` fields take any pointee type: paths name their last segment and arrays keep
+/// their length in the derived Slang declaration.
+#[test]
+fn gpu_struct_pointer_pointees() {
+    mod scene {
+        kiln_rhi::gpu_struct! {
+            pub struct Sphere {
+                radius: f32 as "float",
+                _pad: u32 as "uint",
+            }
+        }
+    }
+
+    gpu_struct! {
+        pub struct Scene {
+            spheres: GpuPtr<scene::Sphere>,
+            bounds: GpuPtr<[f32; 4]>,
+            weights: GpuPtr<std::primitive::f32>,
+        }
+    }
+
+    assert_eq!(
+        Scene::SLANG,
+        "struct Scene {\n    Sphere* spheres;\n    float[4]* bounds;\n    float* weights;\n};\n"
+    );
+    let bounds = GpuPtr::<[f32; 4]>::new(GpuAddress(0x1000));
+    assert_eq!(bounds.offset(1).addr(), GpuAddress(0x1010));
+}
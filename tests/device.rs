@@ -2,7 +2,10 @@
 
 mod common;
 
-use kiln_rhi::{Device, DeviceDesc};
+use kiln_rhi::{
+    BufferDesc, Device, DeviceDesc, Format, FormatCaps, MemoryType, SampleCount, StageFlags,
+    TextureCompression,
+};
 
 /// Time device creation and report the backend's reported properties.
 #[test]
@@ -61,3 +64,81 @@ fn command_buffer_creation_throughput() {
     });
     device.queue().wait_idle();
 }
+
+/// Formats every GPU supports report the caps the renderer relies on, and compressed families
+/// the device lacks report none.
+#[test]
+fn format_caps_report_baseline_formats() {
+    let Some((device, _gpu)) = common::device_or_skip() else {
+        return;
+    };
+
+    let rgba8 = device.format_caps(Format::R8G8B8A8Unorm);
+    assert!(rgba8.contains(
+        FormatCaps::SAMPLED
+            | FormatCaps::FILTER_LINEAR
+            | FormatCaps::COLOR_ATTACHMENT
+            | FormatCaps::BLEND
+    ));
+    assert!(rgba8.supports_samples(SampleCount::S1));
+    assert!(rgba8.supports_samples(SampleCount::S4));
+
+    let depth = device.format_caps(Format::D32Float);
+    assert!(depth.contains(FormatCaps::DEPTH_STENCIL_ATTACHMENT));
+    assert!(!depth.contains(FormatCaps::COLOR_ATTACHMENT));
+
+    let compression = device.texture_compression();
+    for (family, format) in [
+        (TextureCompression::BC, Format::Bc7RgbaUnorm),
+        (TextureCompression::ETC2, Format::Etc2R8G8B8A8Unorm),
+        (TextureCompression::ASTC, Format::Astc4x4Unorm),
+    ] {
+        let caps = device.format_caps(format);
+        eprintln!("    {format:?}: {caps:?}");
+        assert_eq!(
+            caps.contains(FormatCaps::SAMPLED),
+            compression.contains(family)
+        );
+        assert!(!caps.contains(FormatCaps::COLOR_ATTACHMENT));
+    }
+}
+
+/// Labelled objects and nested debug groups with markers record and submit cleanly.
+#[test]
+fn debug_labels_groups_and_markers() {
+    let Some((device, _gpu)) = common::device_or_skip() else {
+        return;
+    };
+    let desc = BufferDesc {
+        size: 4096,
+        memory: MemoryType::GpuOnly,
+        label: Some("rhi-test-labelled".into()),
+        export: None,
+    };
+    let src = device.create_buffer(&desc).expect("src");
+    let dst = device
+        .create_buffer(&BufferDesc {
+            label: Some("rhi-test-labelled-dst".into()),
+            ..desc
+        })
+        .expect("dst");
+
+    common::timed("debug groups · marker · copy · submit+wait", || {
+        let mut cmd = device.create_command_buffer().expect("cmd");
+        cmd.push_debug_group("frame", [0.2, 0.4, 0.8, 1.0]);
+        cmd.push_debug_group("upload", [0.8, 0.4, 0.2, 1.0]);
+        cmd.insert_debug_marker("before copy");
+        cmd.memcpy(dst.gpu(), src.gpu(), 4096);
+        cmd.barrier(StageFlags::TRANSFER, StageFlags::ALL_COMMANDS);
+        cmd.pop_debug_group();
+        cmd.insert_debug_marker("after upload");
+        cmd.pop_debug_group();
+        cmd.end();
+        let queue = device.queue();
+        queue.submit(cmd).expect("submit");
+        queue.wait_idle();
+    });
+
+    device.destroy_buffer(src);
+    device.destroy_buffer(dst);
+}
@@ -10,8 +10,8 @@ use kiln_rhi::gpu_struct;
 use kiln_rhi::{
     BufferDesc, BumpAllocator, ColorAttachment, ColorTarget, Cull, Device, Format, GpuAddress,
     GraphicsPso, GraphicsPsoDesc, LoadOp, MemoryType, RenderPassDesc, RenderTarget, SampleCount,
-    ShaderModule, ShaderStage, StageFlags, StoreOp, TextureDesc, TextureDimension, TextureUsage,
-    Topology,
+    ShaderModule, ShaderStage, StageFlags, StoreOp, TextureCopyRegion, TextureDesc,
+    TextureDimension, TextureUsage, Topology,
 };
 
 // Shared host/device root: a single colour, used by the pixel shader.
@@ -91,6 +91,7 @@ fn graphics_fullscreen_color() {
         sample_count: SampleCount::S1,
         usage: TextureUsage::COLOR_ATTACHMENT | TextureUsage::TRANSFER_SRC,
         label: Some("rt".into()),
+        export: None,
     };
     let sa = device.texture_size_align(&tex_desc).expect("size_align");
     let tex_mem = device
@@ -122,9 +123,11 @@ fn graphics_fullscreen_color() {
                 load_op: LoadOp::Clear,
                 store_op: StoreOp::Store,
                 clear_color: [0.0, 0.0, 0.0, 1.0],
+                resolve_target: None,
             }],
             depth_attachment: None,
             render_area: [0, 0, SIZE, SIZE],
+            view_mask: 0,
         });
         cmd.set_graphics_pipeline(&pso);
         cmd.set_viewport(0.0, 0.0, SIZE as f32, SIZE as f32, 0.0, 1.0);
@@ -218,6 +221,7 @@ fn render_draw(
         sample_count: SampleCount::S1,
         usage: TextureUsage::COLOR_ATTACHMENT | TextureUsage::TRANSFER_SRC,
         label: Some("rt".into()),
+        export: None,
     };
     let sa = device.texture_size_align(&tex_desc).expect("size_align");
     let tex_mem = device
@@ -237,9 +241,11 @@ fn render_draw(
             load_op: LoadOp::Clear,
             store_op: StoreOp::Store,
             clear_color: [0.0, 0.0, 0.0, 1.0],
+            resolve_target: None,
         }],
         depth_attachment: None,
         render_area: [0, 0, size, size],
+        view_mask: 0,
     });
     cmd.set_graphics_pipeline(pso);
     cmd.set_viewport(0.0, 0.0, size as f32, size as f32, 0.0, 1.0);
@@ -269,6 +275,7 @@ fn test_bump(device: &Device) -> BumpAllocator {
             size: 64 * 1024,
             memory: MemoryType::Default,
             label: Some("test-bump".into()),
+            export: None,
         })
         .expect("create_buffer");
     BumpAllocator::new(buffer)
@@ -582,6 +589,7 @@ fn graphics_root_from_bump_allocator() {
             size: 4096,
             memory: MemoryType::Default,
             label: Some("bump-root".into()),
+            export: None,
         })
         .expect("create_buffer");
     let mut bump = BumpAllocator::new(buffer);
@@ -616,3 +624,397 @@ fn graphics_root_from_bump_allocator() {
 
     device.destroy_buffer(bump.into_buffer());
 }
+
+// ---------------------------------------------------------------------------
+// MSAA resolve: a 4x multisampled target resolved into a single-sampled one at the end
+// of the pass. Pixels on the triangle's diagonal edge are partly covered, so the average
+// lands strictly between the clear colour and the fill colour.
+// ---------------------------------------------------------------------------
+
+const HALF_BODY: &str = /*slang*/
+    r#"
+struct VOut { float4 pos : SV_Position; };
+
+// The lower-left half of NDC, split along the diagonal.
+static const float2 HALF[3] = { float2(-1.0, -1.0), float2(1.0, -1.0), float2(-1.0, 1.0) };
+
+[shader("vertex")]
+VOut vsMain(uint vid : SV_VertexID)
+{
+    VOut o; o.pos = float4(HALF[vid], 0.0, 1.0); return o;
+}
+
+[shader("fragment")]
+float4 fsMain(VOut i) : SV_Target { return float4(1.0, 1.0, 1.0, 1.0); }
+"#;
+
+#[test]
+fn graphics_msaa_resolve_averages_edge_samples() {
+    let Some((device, _gpu)) = common::device_or_skip() else {
+        return;
+    };
+    if !device
+        .format_caps(Format::R8G8B8A8Unorm)
+        .supports_samples(SampleCount::S4)
+    {
+        eprintln!("skipping: no 4x MSAA for RGBA8");
+        return;
+    }
+
+    let Some(vs) =
+        common::compile_shader_or_skip(&device, HALF_BODY, "vsMain", ShaderStage::Vertex)
+    else {
+        return;
+    };
+    let Some(fs) = common::compile_shader_or_skip(&device, HALF_BODY, "fsMain", ShaderStage::Pixel)
+    else {
+        return;
+    };
+    let pso = device
+        .create_graphics_pso(
+            &GraphicsPsoDesc {
+                topology: Topology::TriangleList,
+                color_targets: vec![ColorTarget::new(Format::R8G8B8A8Unorm)],
+                depth_format: None,
+                sample_count: SampleCount::S4,
+                root_constant_size: 16,
+                cull: Cull::None,
+                label: Some("msaa".into()),
+                ..Default::default()
+            },
+            &vs,
+            &fs,
+        )
+        .expect("create_graphics_pso");
+
+    let resolved_desc = TextureDesc {
+        width: SIZE,
+        height: SIZE,
+        depth: 1,
+        mip_levels: 1,
+        array_layers: 1,
+        format: Format::R8G8B8A8Unorm,
+        dimension: TextureDimension::D2,
+        sample_count: SampleCount::S1,
+        usage: TextureUsage::COLOR_ATTACHMENT | TextureUsage::TRANSFER_SRC,
+        label: Some("resolved".into()),
+        export: None,
+    };
+    let msaa_desc = TextureDesc {
+        sample_count: SampleCount::S4,
+        usage: TextureUsage::COLOR_ATTACHMENT,
+        label: Some("msaa".into()),
+        ..resolved_desc.clone()
+    };
+    let mut textures = Vec::new();
+    for desc in [&msaa_desc, &resolved_desc] {
+        let sa = device.texture_size_align(desc).expect("size_align");
+        let mem = device
+            .malloc_aligned(sa.size, sa.align, MemoryType::GpuOnly)
+            .expect("rt mem");
+        let texture = device
+            .create_texture(desc, mem.gpu())
+            .expect("create_texture");
+        textures.push((texture, mem));
+    }
+    let (msaa, resolved) = (&textures[0].0, &textures[1].0);
+    let readback = device
+        .malloc((SIZE * SIZE * 4) as u64, MemoryType::Readback)
+        .expect("readback");
+
+    common::timed("render 4x MSAA triangle + resolve · submit+wait", || {
+        let mut cmd = device.create_command_buffer().expect("cmd");
+        cmd.begin_render_pass(&RenderPassDesc {
+            color_attachments: vec![ColorAttachment {
+                target: RenderTarget::Texture(msaa.id()),
+                load_op: LoadOp::Clear,
+                store_op: StoreOp::Resolve,
+                clear_color: [0.0, 0.0, 0.0, 1.0],
+                resolve_target: Some(RenderTarget::Texture(resolved.id())),
+            }],
+            depth_attachment: None,
+            render_area: [0, 0, SIZE, SIZE],
+            view_mask: 0,
+        });
+        cmd.set_graphics_pipeline(&pso);
+        cmd.set_viewport(0.0, 0.0, SIZE as f32, SIZE as f32, 0.0, 1.0);
+        cmd.set_scissor(0, 0, SIZE, SIZE);
+        cmd.draw(None, None, 3, 1, 0, 0);
+        cmd.end_render_pass();
+
+        cmd.barrier(StageFlags::RASTER_COLOR_OUT, StageFlags::TRANSFER);
+        cmd.copy_texture_to_buffer(resolved, readback.gpu(), &[TextureCopyRegion::default()]);
+        cmd.barrier(StageFlags::TRANSFER, StageFlags::ALL_COMMANDS);
+        cmd.end();
+        let queue = device.queue();
+        queue.submit(cmd).expect("submit");
+        queue.wait_idle();
+    });
+
+    let pixels = readback.as_slice::<u8>().expect("read readback");
+    common::save_rgba_png("graphics_msaa_resolve", SIZE, SIZE, pixels);
+    let red = |x: u32, y: u32| pixels[((y * SIZE + x) * 4) as usize];
+    // Y-up NDC: the lower-left half is the bottom-left of the image.
+    assert_eq!(red(2, SIZE - 3), 255, "deep inside the triangle");
+    assert_eq!(red(SIZE - 3, 2), 0, "deep outside the triangle");
+    let partial = (0..SIZE).filter(|&x| (1..255).contains(&red(x, x))).count();
+    assert!(
+        partial > SIZE as usize / 2,
+        "only {partial} diagonal pixels were averaged"
+    );
+
+    device.free(readback);
+    for (texture, mem) in textures {
+        device.destroy_texture(texture);
+        device.free(mem);
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Subresource targets: every face of both mips of a cube map is cleared to its own colour
+// through `RenderTarget::TextureSubresource`, then each is read back on its own.
+// ---------------------------------------------------------------------------
+
+#[test]
+fn graphics_clear_cube_faces_and_mips() {
+    let Some((device, _gpu)) = common::device_or_skip() else {
+        return;
+    };
+
+    const CUBE: u32 = 16;
+    const MIPS: u32 = 2;
+    let desc = TextureDesc {
+        width: CUBE,
+        height: CUBE,
+        depth: 1,
+        mip_levels: MIPS,
+        array_layers: 1,
+        format: Format::R8G8B8A8Unorm,
+        dimension: TextureDimension::Cube,
+        sample_count: SampleCount::S1,
+        usage: TextureUsage::COLOR_ATTACHMENT | TextureUsage::TRANSFER_SRC,
+        label: Some("probe".into()),
+        export: None,
+    };
+    let sa = device.texture_size_align(&desc).expect("size_align");
+    let mem = device
+        .malloc_aligned(sa.size, sa.align, MemoryType::GpuOnly)
+        .expect("cube mem");
+    let cube = device
+        .create_texture(&desc, mem.gpu())
+        .expect("create_texture");
+
+    // One region per (mip, face), packed back to back in the readback buffer.
+    let mut regions = Vec::new();
+    let mut offset = 0u64;
+    for mip in 0..MIPS {
+        let side = CUBE >> mip;
+        for face in 0..6 {
+            regions.push(TextureCopyRegion {
+                buffer_offset: offset,
+                ..TextureCopyRegion::subresource(mip, face)
+            });
+            offset += (side * side * 4) as u64;
+        }
+    }
+    let readback = device
+        .malloc(offset, MemoryType::Readback)
+        .expect("readback");
+    let expected = |mip: u32, face: u32| [(face * 40) as u8, (mip * 255) as u8, 0, 255];
+
+    common::timed("clear 12 cube subresources · submit+wait", || {
+        let mut cmd = device.create_command_buffer().expect("cmd");
+        for mip in 0..MIPS {
+            let side = CUBE >> mip;
+            for face in 0..6 {
+                let [r, g, b, a] = expected(mip, face).map(|c| c as f32 / 255.0);
+                cmd.begin_render_pass(&RenderPassDesc {
+                    color_attachments: vec![ColorAttachment {
+                        target: RenderTarget::TextureSubresource {
+                            texture: cube.id(),
+                            mip,
+                            layer: face,
+                        },
+                        load_op: LoadOp::Clear,
+                        store_op: StoreOp::Store,
+                        clear_color: [r, g, b, a],
+                        resolve_target: None,
+                    }],
+                    depth_attachment: None,
+                    render_area: [0, 0, side, side],
+                    view_mask: 0,
+                });
+                cmd.end_render_pass();
+            }
+        }
+        cmd.barrier(StageFlags::RASTER_COLOR_OUT, StageFlags::TRANSFER);
+        cmd.copy_texture_to_buffer(&cube, readback.gpu(), &regions);
+        cmd.barrier(StageFlags::TRANSFER, StageFlags::ALL_COMMANDS);
+        cmd.end();
+        let queue = device.queue();
+        queue.submit(cmd).expect("submit");
+        queue.wait_idle();
+    });
+
+    let bytes = readback.as_slice::<u8>().expect("read readback");
+    for region in &regions {
+        let side = CUBE >> region.mip;
+        let start = region.buffer_offset as usize;
+        let texels = &bytes[start..start + (side * side * 4) as usize];
+        let want = expected(region.mip, region.base_layer);
+        assert!(
+            texels.chunks_exact(4).all(|t| t == want),
+            "mip {} face {}: expected {want:?}, first texel {:?}",
+            region.mip,
+            region.base_layer,
+            &texels[..4]
+        );
+    }
+
+    device.free(readback);
+    device.destroy_texture(cube);
+    device.free(mem);
+}
+
+// ---------------------------------------------------------------------------
+// Multiview: one stereo draw with `view_mask = 0b11` fills both layers of a 2D array
+// texture, each with the colour its `SV_ViewID` picks.
+// ---------------------------------------------------------------------------
+
+const VIEW_BODY: &str = /*slang*/
+    r#"
+struct VOut { float4 pos : SV_Position; };
+
+[shader("vertex")]
+VOut vsMain(uint vid : SV_VertexID)
+{
+    float2 p = float2(float((vid << 1) & 2), float(vid & 2));
+    VOut o;
+    o.pos = float4(p * 2.0 - 1.0, 0.0, 1.0);
+    return o;
+}
+
+[shader("fragment")]
+float4 fsMain(VOut i, uint view : SV_ViewID) : SV_Target
+{
+    return view == 0 ? float4(1.0, 0.0, 0.0, 1.0) : float4(0.0, 1.0, 0.0, 1.0);
+}
+"#;
+
+#[test]
+fn graphics_multiview_renders_each_view_to_its_layer() {
+    let Some((device, _gpu)) = common::device_or_skip() else {
+        return;
+    };
+
+    let Some(vs) =
+        common::compile_shader_or_skip(&device, VIEW_BODY, "vsMain", ShaderStage::Vertex)
+    else {
+        return;
+    };
+    let Some(fs) = common::compile_shader_or_skip(&device, VIEW_BODY, "fsMain", ShaderStage::Pixel)
+    else {
+        return;
+    };
+    let pso = match device.create_graphics_pso(
+        &GraphicsPsoDesc {
+            topology: Topology::TriangleList,
+            color_targets: vec![ColorTarget::new(Format::R8G8B8A8Unorm)],
+            depth_format: None,
+            sample_count: SampleCount::S1,
+            root_constant_size: 16,
+            cull: Cull::None,
+            view_mask: 0b11,
+            label: Some("stereo".into()),
+            ..Default::default()
+        },
+        &vs,
+        &fs,
+    ) {
+        Ok(pso) => pso,
+        Err(e) => {
+            eprintln!("skipping: {e}");
+            return;
+        }
+    };
+
+    let desc = TextureDesc {
+        width: SIZE,
+        height: SIZE,
+        depth: 1,
+        mip_levels: 1,
+        array_layers: 2,
+        format: Format::R8G8B8A8Unorm,
+        dimension: TextureDimension::D2Array,
+        sample_count: SampleCount::S1,
+        usage: TextureUsage::COLOR_ATTACHMENT | TextureUsage::TRANSFER_SRC,
+        label: Some("eyes".into()),
+        export: None,
+    };
+    let sa = device.texture_size_align(&desc).expect("size_align");
+    let mem = device
+        .malloc_aligned(sa.size, sa.align, MemoryType::GpuOnly)
+        .expect("rt mem");
+    let eyes = device
+        .create_texture(&desc, mem.gpu())
+        .expect("create_texture");
+    let layer_bytes = (SIZE * SIZE * 4) as u64;
+    let readback = device
+        .malloc(layer_bytes * 2, MemoryType::Readback)
+        .expect("readback");
+
+    common::timed("stereo multiview draw · submit+wait", || {
+        let mut cmd = device.create_command_buffer().expect("cmd");
+        cmd.begin_render_pass(&RenderPassDesc {
+            color_attachments: vec![ColorAttachment {
+                target: RenderTarget::TextureLayers {
+                    texture: eyes.id(),
+                    mip: 0,
+                    base_layer: 0,
+                    layer_count: 2,
+                },
+                load_op: LoadOp::Clear,
+                store_op: StoreOp::Store,
+                clear_color: [0.0, 0.0, 0.0, 1.0],
+                resolve_target: None,
+            }],
+            depth_attachment: None,
+            render_area: [0, 0, SIZE, SIZE],
+            view_mask: 0b11,
+        });
+        cmd.set_graphics_pipeline(&pso);
+        cmd.set_viewport(0.0, 0.0, SIZE as f32, SIZE as f32, 0.0, 1.0);
+        cmd.set_scissor(0, 0, SIZE, SIZE);
+        cmd.draw(None, None, 3, 1, 0, 0);
+        cmd.end_render_pass();
+
+        cmd.barrier(StageFlags::RASTER_COLOR_OUT, StageFlags::TRANSFER);
+        cmd.copy_texture_to_buffer(
+            &eyes,
+            readback.gpu(),
+            &[TextureCopyRegion {
+                layer_count: 2,
+                ..TextureCopyRegion::default()
+            }],
+        );
+        cmd.barrier(StageFlags::TRANSFER, StageFlags::ALL_COMMANDS);
+        cmd.end();
+        let queue = device.queue();
+        queue.submit(cmd).expect("submit");
+        queue.wait_idle();
+    });
+
+    let bytes = readback.as_slice::<u8>().expect("read readback");
+    for (view, want) in [[255, 0, 0, 255], [0, 255, 0, 255]].iter().enumerate() {
+        let layer = &bytes[view * layer_bytes as usize..(view + 1) * layer_bytes as usize];
+        assert!(
+            layer.chunks_exact(4).all(|t| t == want),
+            "view {view}: expected {want:?}, first texel {:?}",
+            &layer[..4]
+        );
+    }
+
+    device.free(readback);
+    device.destroy_texture(eyes);
+    device.free(mem);
+}
@@ -5,7 +5,10 @@
 
 mod common;
 
-use kiln_rhi::{BufferDesc, BumpAllocator, MemoryType};
+use kiln_rhi::{
+    BufferDesc, BumpAllocator, ExternalMemoryHandle, FrameRingAllocator, MemoryType, RhiError,
+    StageFlags,
+};
 
 /// `Default` memory is CPU-mapped GPU memory: a write through the mapped pointer must read
 /// straight back (the dual-pointer model the whole RHI is built on).
@@ -77,6 +80,95 @@ fn host_to_device_pointer_translates_with_offset() {
     device.free(allocation);
 }
 
+/// `device_to_host_pointer` inverts `host_to_device_pointer` across many live allocations,
+/// misses unmapped and freed memory, and stays fast with thousands of entries.
+#[test]
+fn device_to_host_pointer_round_trips() {
+    let Some((device, _gpu)) = common::device_or_skip() else {
+        return;
+    };
+
+    let allocations: Vec<_> = (0..2048)
+        .map(|_| device.malloc(256, MemoryType::Default).expect("malloc"))
+        .collect();
+    let gpu_only = device.malloc(256, MemoryType::GpuOnly).expect("malloc");
+
+    common::timed("pointer round trips · 2048 allocations", || {
+        for allocation in &allocations {
+            let cpu = allocation.cpu().expect("mapped");
+            let inner = allocation.gpu().offset(100);
+            let host = device
+                .device_to_host_pointer(inner)
+                .expect("mapped GPU address should translate to a host pointer");
+            assert_eq!(host, unsafe { cpu.add(100) });
+            assert_eq!(device.host_to_device_pointer(host), Some(inner));
+        }
+    });
+    assert_eq!(device.device_to_host_pointer(gpu_only.gpu()), None);
+
+    let freed = allocations[0].gpu();
+    let freed_cpu = allocations[0].cpu().expect("mapped");
+    for allocation in allocations {
+        device.free(allocation);
+    }
+    assert_eq!(device.device_to_host_pointer(freed), None);
+    assert_eq!(device.host_to_device_pointer(freed_cpu), None);
+    device.free(gpu_only);
+}
+
+/// Memory exported as an opaque fd and imported back into the same device aliases the
+/// original: bytes written through one mapping read back through the other.
+#[cfg(unix)]
+#[test]
+fn exported_memory_imports_as_allocation() {
+    let Some((device, _gpu)) = common::device_or_skip() else {
+        return;
+    };
+
+    const SIZE: u64 = 64 * 1024;
+    let desc = BufferDesc {
+        size: SIZE,
+        memory: MemoryType::Default,
+        label: Some("exported".into()),
+        export: Some(ExternalMemoryHandle::OpaqueFd),
+    };
+    let exported = match device.malloc_with(&desc, 256) {
+        Ok(allocation) => allocation,
+        Err(RhiError::Unsupported(reason)) => {
+            eprintln!("skipping: {reason}");
+            return;
+        }
+        Err(e) => panic!("exportable malloc: {e:?}"),
+    };
+    exported
+        .upload_slice(&[0xC0FF_EE11u32; 16])
+        .expect("upload");
+
+    let imported = common::timed("export_fd + import_memory_fd", || {
+        let fd = exported.export_fd().expect("export_fd");
+        device
+            .import_memory_fd(
+                fd,
+                ExternalMemoryHandle::OpaqueFd,
+                SIZE,
+                MemoryType::Default,
+            )
+            .expect("import_memory_fd")
+    });
+    assert_ne!(imported.gpu(), exported.gpu());
+    assert_eq!(
+        device.host_to_device_pointer(imported.cpu().expect("imported mapping")),
+        Some(imported.gpu())
+    );
+    assert_eq!(
+        &imported.as_slice::<u32>().expect("imported slice")[..16],
+        &[0xC0FF_EE11u32; 16]
+    );
+
+    device.free(imported);
+    device.free(exported);
+}
+
 /// malloc/free throughput — a feel for raw allocation cost.
 #[test]
 fn malloc_free_throughput() {
@@ -107,6 +199,7 @@ fn bump_or_skip(device: &kiln_rhi::Device, size: u64) -> Option<BumpAllocator> {
             size,
             memory: MemoryType::Default,
             label: Some("bump".into()),
+            export: None,
         })
         .expect("create_buffer(Default)");
     Some(BumpAllocator::new(buffer))
@@ -240,3 +333,292 @@ fn bump_reset_reuses_space() {
 
     device.destroy_buffer(bump.into_buffer());
 }
+
+// ---------------------------------------------------------------------------
+// Sub-allocation. Small buffers are carved out of large per-`MemoryType` memory blocks
+// rather than getting one driver allocation each, so thousands of them must not run into
+// the driver's allocation-count limit, and every carved range must still be a well-formed
+// dual pointer.
+// ---------------------------------------------------------------------------
+
+/// Far more small allocations than `maxMemoryAllocationCount` (4096 on many drivers): all
+/// succeed, land at distinct aligned addresses, and keep their CPU writes apart.
+#[test]
+fn many_small_mallocs_share_blocks() {
+    let Some((device, _gpu)) = common::device_or_skip() else {
+        return;
+    };
+
+    const COUNT: usize = 10_000;
+    let allocations: Vec<_> = common::timed("malloc 10k × 256 B (Default)", || {
+        (0..COUNT)
+            .map(|_| device.malloc(256, MemoryType::Default).expect("malloc"))
+            .collect()
+    });
+
+    let mut ranges: Vec<(u64, u64)> = allocations
+        .iter()
+        .map(|a| (a.gpu().0, a.gpu().0 + a.size()))
+        .collect();
+    ranges.sort_unstable();
+    for pair in ranges.windows(2) {
+        assert!(pair[0].1 <= pair[1].0, "sub-allocations must not overlap");
+    }
+
+    for (i, a) in allocations.iter().enumerate() {
+        assert!(
+            a.gpu().is_aligned_to(16),
+            "malloc returns 16-byte aligned memory"
+        );
+        a.upload(&(i as u32)).expect("upload");
+    }
+    for (i, a) in allocations.iter().enumerate() {
+        assert_eq!(
+            a.read::<u32>().expect("read"),
+            i as u32,
+            "allocation {i} clobbered"
+        );
+    }
+
+    common::timed("free 10k", || {
+        for a in allocations {
+            device.free(a);
+        }
+    });
+}
+
+/// `malloc_aligned` honours large alignments for sub-allocated buffers too, and
+/// `host_to_device_pointer` resolves an address inside a carved range.
+#[test]
+fn sub_allocations_honour_alignment_and_translate() {
+    let Some((device, _gpu)) = common::device_or_skip() else {
+        return;
+    };
+
+    // Bump the block cursor off zero so the aligned requests have to pad.
+    let pad = device.malloc(48, MemoryType::Default).expect("pad");
+    for align in [256u64, 4096, 65536] {
+        let a = device
+            .malloc_aligned(1000, align, MemoryType::Default)
+            .expect("malloc_aligned");
+        assert!(
+            a.gpu().is_aligned_to(align),
+            "gpu address {:#x} not aligned to {align}",
+            a.gpu().0
+        );
+        let cpu = a.cpu().expect("Default memory is mapped");
+        assert_eq!(device.host_to_device_pointer(cpu), Some(a.gpu()));
+        device.free(a);
+    }
+    device.free(pad);
+}
+
+// ---------------------------------------------------------------------------
+// Memory statistics. `memory_stats` must track live buffers per `MemoryType` exactly
+// and report at least one heap with a sane budget.
+// ---------------------------------------------------------------------------
+
+#[test]
+fn memory_stats_track_allocations() {
+    let Some((device, _gpu)) = common::device_or_skip() else {
+        return;
+    };
+
+    let before = device.memory_stats();
+    assert!(!before.heaps.is_empty(), "at least one memory heap");
+    for heap in &before.heaps {
+        assert!(heap.budget > 0, "heap budget must be non-zero: {heap:?}");
+    }
+
+    let small = device.malloc(1000, MemoryType::Default).expect("malloc");
+    let large = device
+        .malloc(48 << 20, MemoryType::GpuOnly)
+        .expect("malloc large");
+    let readback = device.malloc(64, MemoryType::Readback).expect("malloc");
+
+    let during = device.memory_stats();
+    let delta = |memory: MemoryType| {
+        (
+            during.of(memory).allocation_count - before.of(memory).allocation_count,
+            during.of(memory).allocated_bytes - before.of(memory).allocated_bytes,
+        )
+    };
+    assert_eq!(delta(MemoryType::Default), (1, 1000));
+    assert_eq!(delta(MemoryType::GpuOnly), (1, 48 << 20));
+    assert_eq!(delta(MemoryType::Readback), (1, 64));
+    assert!(during.gpu_only.largest_allocation >= 48 << 20);
+    assert!(
+        during.default.block_count >= 1,
+        "small Default buffer lives in a shared block"
+    );
+    assert_eq!(
+        during.allocated_bytes() - before.allocated_bytes(),
+        1000 + (48 << 20) + 64
+    );
+
+    device.free(small);
+    device.free(large);
+    device.free(readback);
+
+    let after = device.memory_stats();
+    for memory in [
+        MemoryType::Default,
+        MemoryType::GpuOnly,
+        MemoryType::Readback,
+    ] {
+        assert_eq!(
+            after.of(memory).allocation_count,
+            before.of(memory).allocation_count,
+            "{memory:?} allocations returned"
+        );
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Deferred destruction. Resources handed to `Device::own` are released by the device once
+// every submission made before the handle was dropped has retired, never earlier.
+// ---------------------------------------------------------------------------
+
+/// With nothing submitted there is nothing to wait for: a dropped handle is released by the
+/// next `collect_garbage`, and `into_inner` hands ownership back without parking anything.
+#[test]
+fn owned_drop_without_submissions_releases_on_collect() {
+    let Some((device, _gpu)) = common::device_or_skip() else {
+        return;
+    };
+    let live = |device: &kiln_rhi::Device| device.memory_stats().default.allocation_count;
+    let before = live(&device);
+
+    let owned = device.own(device.malloc(4096, MemoryType::Default).expect("malloc"));
+    owned.upload(&7u32).expect("upload through deref");
+    drop(owned);
+    assert_eq!(live(&device), before + 1, "drop only parks the allocation");
+    assert_eq!(device.collect_garbage(), 1);
+    assert_eq!(live(&device), before);
+
+    let detached = device
+        .own(device.malloc(64, MemoryType::Default).expect("malloc"))
+        .into_inner();
+    assert_eq!(device.collect_garbage(), 0, "into_inner parks nothing");
+    device.free(detached);
+    assert_eq!(live(&device), before);
+}
+
+/// A handle dropped right after submitting a copy that reads it survives until the copy has
+/// retired, and `wait_idle` releases it.
+#[test]
+fn owned_drop_waits_for_submission() {
+    let Some((device, _gpu)) = common::device_or_skip() else {
+        return;
+    };
+    let live = |device: &kiln_rhi::Device| device.memory_stats().default.allocation_count;
+    let before = live(&device);
+
+    let mut src = device.own(device.malloc(1 << 20, MemoryType::Default).expect("src"));
+    let mut dst = device.malloc(1 << 20, MemoryType::Readback).expect("dst");
+    src.as_mut_slice::<u8>().expect("src slice").fill(0x5A);
+    dst.as_mut_slice::<u8>().expect("dst slice").fill(0);
+    let mut cmd = device.create_command_buffer().expect("cmd");
+    cmd.memcpy(dst.gpu(), src.gpu(), 1 << 20);
+    cmd.barrier(StageFlags::TRANSFER, StageFlags::ALL_COMMANDS);
+    cmd.end();
+    device.queue().submit(cmd).expect("submit");
+    drop(src);
+
+    // Whether or not the copy has retired yet, `wait_idle` leaves nothing parked.
+    device.collect_garbage();
+    device.wait_idle();
+    assert_eq!(live(&device), before, "source released after the copy");
+    assert_eq!(device.collect_garbage(), 0);
+    assert!(
+        dst.as_slice::<u8>()
+            .expect("dst")
+            .iter()
+            .all(|&b| b == 0x5A)
+    );
+    device.free(dst);
+}
+
+// ---------------------------------------------------------------------------
+// FrameRingAllocator — one bump region per frame in flight, reclaimed once the GPU has
+// retired that frame's submissions.
+// ---------------------------------------------------------------------------
+
+/// Regions rotate with `begin_frame`, come back reset, and grow rather than fail when full.
+#[test]
+fn frame_ring_rotates_and_grows() {
+    let Some((device, _gpu)) = common::device_or_skip() else {
+        return;
+    };
+
+    let mut ring = FrameRingAllocator::new(&device, 2, 4096).expect("ring");
+    let first = ring.alloc(&device, 256, 256).expect("alloc");
+    assert!(first.gpu.is_aligned_to(256));
+    let big = ring
+        .alloc(&device, 4096, 16)
+        .expect("overflow grows the region");
+    assert!(big.gpu.is_aligned_to(16));
+    assert_eq!(ring.used(), 256 + 4096);
+    assert!(ring.capacity() >= 2 * 4096, "region grew");
+
+    ring.begin_frame(&device);
+    assert_eq!(ring.frame_index(), 1);
+    assert_eq!(ring.used(), 0);
+    assert_eq!(ring.capacity(), 4096, "other regions are untouched");
+
+    ring.begin_frame(&device);
+    assert_eq!(ring.frame_index(), 0);
+    assert_eq!(ring.used(), 0, "region reset on reuse");
+    assert!(ring.capacity() >= 2 * 4096, "growth is kept");
+    let again = ring.alloc(&device, 256, 256).expect("alloc");
+    assert_eq!(
+        again.gpu, first.gpu,
+        "reclaimed region is reused from the start"
+    );
+
+    ring.destroy(&device);
+}
+
+/// Each frame copies its transient data on the GPU; reclaiming a region must not clobber
+/// data an in-flight frame is still reading.
+#[test]
+fn frame_ring_survives_frames_in_flight() {
+    let Some((device, _gpu)) = common::device_or_skip() else {
+        return;
+    };
+
+    const FRAMES: u32 = 8;
+    const WORDS: usize = 1024;
+    let mut ring = FrameRingAllocator::new(&device, 2, (WORDS * 4) as u64).expect("ring");
+    let results: Vec<_> = (0..FRAMES)
+        .map(|_| {
+            device
+                .malloc((WORDS * 4) as u64, MemoryType::Readback)
+                .expect("dst")
+        })
+        .collect();
+
+    for (frame, dst) in results.iter().enumerate() {
+        let src = ring.alloc(&device, (WORDS * 4) as u64, 16).expect("alloc");
+        src.upload_slice(&[frame as u32; WORDS]).expect("upload");
+        let mut cmd = device.create_command_buffer().expect("cmd");
+        cmd.memcpy(dst.gpu(), src.gpu, (WORDS * 4) as u64);
+        cmd.barrier(StageFlags::TRANSFER, StageFlags::ALL_COMMANDS);
+        cmd.end();
+        device.queue().submit(cmd).expect("submit");
+        ring.begin_frame(&device);
+    }
+    device.wait_idle();
+
+    for (frame, dst) in results.into_iter().enumerate() {
+        assert!(
+            dst.as_slice::<u32>()
+                .expect("dst")
+                .iter()
+                .all(|&w| w == frame as u32),
+            "frame {frame} data clobbered"
+        );
+        device.free(dst);
+    }
+    ring.destroy(&device);
+}
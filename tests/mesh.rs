@@ -71,6 +71,7 @@ fn mesh_fullscreen_color() {
             support_dual_source_blending: false,
             blendstate: None,
             root_constant_size: 16,
+            view_mask: 0,
             label: Some("mesh".into()),
         },
         &ms,
@@ -94,6 +95,7 @@ fn mesh_fullscreen_color() {
         sample_count: SampleCount::S1,
         usage: TextureUsage::COLOR_ATTACHMENT | TextureUsage::TRANSFER_SRC,
         label: Some("rt".into()),
+        export: None,
     };
     let sa = device.texture_size_align(&tex_desc).expect("size_align");
     let tex_mem = device
@@ -124,9 +126,11 @@ fn mesh_fullscreen_color() {
                 load_op: LoadOp::Clear,
                 store_op: StoreOp::Store,
                 clear_color: [0.0, 0.0, 0.0, 1.0],
+                resolve_target: None,
             }],
             depth_attachment: None,
             render_area: [0, 0, SIZE, SIZE],
+            view_mask: 0,
         });
         cmd.set_meshlet_pipeline(&pso);
         cmd.set_viewport(0.0, 0.0, SIZE as f32, SIZE as f32, 0.0, 1.0);
@@ -190,6 +194,7 @@ fn make_meshlet_pso(
             support_dual_source_blending: false,
             blendstate: None,
             root_constant_size,
+            view_mask: 0,
             label: Some(label.into()),
         },
         ms,
@@ -226,6 +231,7 @@ fn render_meshlets(
         sample_count: SampleCount::S1,
         usage: TextureUsage::COLOR_ATTACHMENT | TextureUsage::TRANSFER_SRC,
         label: Some("rt".into()),
+        export: None,
     };
     let sa = device.texture_size_align(&tex_desc).expect("size_align");
     let tex_mem = device
@@ -245,9 +251,11 @@ fn render_meshlets(
             load_op: LoadOp::Clear,
             store_op: StoreOp::Store,
             clear_color: [0.0, 0.0, 0.0, 1.0],
+            resolve_target: None,
         }],
         depth_attachment: None,
         render_area: [0, 0, size, size],
+        view_mask: 0,
     });
     cmd.set_meshlet_pipeline(pso);
     cmd.set_viewport(0.0, 0.0, size as f32, size as f32, 0.0, 1.0);
@@ -277,6 +285,7 @@ fn test_bump(device: &Device) -> BumpAllocator {
             size: 64 * 1024,
             memory: MemoryType::Default,
             label: Some("test-bump".into()),
+            export: None,
         })
         .expect("create_buffer");
     BumpAllocator::new(buffer)
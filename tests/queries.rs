@@ -0,0 +1,338 @@
+//! Headless GPU query tests (timed): timestamps, the scoped profiler, occlusion and
+//! pipeline statistics.
+
+mod common;
+
+use kiln_rhi::{
+    ColorAttachment, ColorTarget, ComputePsoDesc, Cull, Device, Format, GpuProfiler,
+    GraphicsPsoDesc, LoadOp, MemoryType, PipelineStatistics, QUERY_RESULT_SIZE, QueryPool,
+    QueryPoolDesc, QueryType, RenderPassDesc, RenderTarget, RhiError, SampleCount, ShaderStage,
+    StageFlags, StoreOp, TextureDesc, TextureDimension, TextureUsage, Topology,
+};
+
+const COPY_SIZE: u64 = 16 << 20;
+
+/// Create a query pool, or `None` (after saying why) if the device lacks its type.
+fn query_pool_or_skip(device: &Device, ty: QueryType, count: u32) -> Option<QueryPool> {
+    match device.create_query_pool(&QueryPoolDesc {
+        ty,
+        count,
+        label: Some(format!("rhi-test-{ty:?}")),
+    }) {
+        Ok(pool) => Some(pool),
+        Err(RhiError::Unsupported(reason)) => {
+            eprintln!("skipping: {reason}");
+            None
+        }
+        Err(e) => panic!("create_query_pool: {e}"),
+    }
+}
+
+/// Bracket a 16 MiB copy with two timestamps, resolve them into `Readback` memory, and check
+/// the clock moved forward by a plausible amount.
+#[test]
+fn timestamps_bracket_a_copy() {
+    let Some((device, _gpu)) = common::device_or_skip() else {
+        return;
+    };
+    let Some(pool) = query_pool_or_skip(&device, QueryType::Timestamp, 2) else {
+        return;
+    };
+    let src = device.malloc(COPY_SIZE, MemoryType::GpuOnly).expect("src");
+    let dst = device.malloc(COPY_SIZE, MemoryType::GpuOnly).expect("dst");
+    let results = device
+        .malloc(2 * QUERY_RESULT_SIZE, MemoryType::Readback)
+        .expect("readback");
+
+    common::timed(
+        "timestamp · copy 16 MiB · timestamp → resolve · submit+wait",
+        || {
+            let mut cmd = device.create_command_buffer().expect("cmd");
+            cmd.write_timestamp(StageFlags::ALL_COMMANDS, &pool, 0);
+            cmd.memcpy(dst.gpu(), src.gpu(), COPY_SIZE);
+            cmd.write_timestamp(StageFlags::TRANSFER, &pool, 1);
+            cmd.resolve_queries(&pool, 0..2, results.gpu());
+            cmd.barrier(StageFlags::TRANSFER, StageFlags::ALL_COMMANDS);
+            cmd.end();
+            let queue = device.queue();
+            queue.submit(cmd).expect("submit");
+            queue.wait_idle();
+        },
+    );
+
+    let ticks = results.as_slice::<u64>().expect("results slice");
+    let period = device.timestamp_period();
+    assert!(period > 0.0, "timestamp period {period}");
+    assert!(ticks[1] > ticks[0], "timestamps {ticks:?} did not advance");
+    let gpu_ns = (ticks[1] - ticks[0]) as f64 * period;
+    assert!(
+        gpu_ns < 1e10,
+        "a 16 MiB copy took {gpu_ns} ns by the GPU clock"
+    );
+    eprintln!("  GPU time of the copy: {:.1} µs", gpu_ns / 1e3);
+
+    device.free(src);
+    device.free(dst);
+    device.free(results);
+}
+
+/// Time nested scopes over three frames and check the profiler reports the latest finished
+/// frame with outer scopes spanning their inner ones.
+#[test]
+fn profiler_times_nested_scopes() {
+    let Some((device, _gpu)) = common::device_or_skip() else {
+        return;
+    };
+    let src = device.malloc(COPY_SIZE, MemoryType::GpuOnly).expect("src");
+    let dst = device.malloc(COPY_SIZE, MemoryType::GpuOnly).expect("dst");
+    let mut profiler = GpuProfiler::new(&device, 8);
+
+    for _ in 0..3 {
+        let mut cmd = device.create_command_buffer().expect("cmd");
+        match profiler.begin_frame(&mut cmd) {
+            Ok(()) => {}
+            Err(RhiError::Unsupported(reason)) => {
+                eprintln!("skipping: {reason}");
+                return;
+            }
+            Err(e) => panic!("begin_frame: {e}"),
+        }
+        {
+            let mut frame = cmd.profile_scope("frame");
+            {
+                let mut copy = frame.profile_scope("copy");
+                copy.memcpy(dst.gpu(), src.gpu(), COPY_SIZE);
+                copy.barrier(StageFlags::TRANSFER, StageFlags::TRANSFER);
+            }
+            let mut copy_back = frame.profile_scope("copy back");
+            copy_back.memcpy(src.gpu(), dst.gpu(), COPY_SIZE);
+        }
+        profiler.end_frame(&mut cmd);
+        cmd.end();
+        device.queue().submit(cmd).expect("submit");
+    }
+    device.queue().wait_idle();
+
+    let timings = profiler.timings();
+    let layout: Vec<_> = timings
+        .iter()
+        .map(|scope| (scope.name.as_str(), scope.depth))
+        .collect();
+    assert_eq!(layout, [("frame", 0), ("copy", 1), ("copy back", 1)]);
+    assert!(
+        timings[0].gpu_time >= timings[1].gpu_time.max(timings[2].gpu_time),
+        "outer scope shorter than its inner ones: {timings:?}"
+    );
+    for scope in timings {
+        eprintln!(
+            "  {:indent$}{}: {:?}",
+            "",
+            scope.name,
+            scope.gpu_time,
+            indent = scope.depth * 2
+        );
+    }
+
+    drop(profiler);
+    device.free(src);
+    device.free(dst);
+}
+
+// Two triangles covering the top-left NDC quadrant: 32x32 of the 64x64 target's pixels.
+const QUAD_BODY: &str = /*slang*/
+    r#"
+struct VOut { float4 pos : SV_Position; };
+
+static const float2 QUAD[6] = {
+    float2(-1.0, 0.0), float2(0.0, 0.0), float2(-1.0, 1.0),
+    float2(-1.0, 1.0), float2(0.0, 0.0), float2( 0.0, 1.0),
+};
+
+[shader("vertex")]
+VOut vsMain(uint vid : SV_VertexID)
+{
+    VOut o; o.pos = float4(QUAD[vid], 0.0, 1.0); return o;
+}
+
+[shader("fragment")]
+float4 fsMain(VOut i) : SV_Target { return float4(1.0, 1.0, 1.0, 1.0); }
+"#;
+
+const SIZE: u32 = 64;
+
+/// Count the samples of a quarter-screen quad with precise and binary occlusion queries,
+/// plus an empty query, resolving all of them into one `Readback` buffer.
+#[test]
+fn occlusion_queries_count_covered_samples() {
+    let Some((device, _gpu)) = common::device_or_skip() else {
+        return;
+    };
+    let Some(vs) =
+        common::compile_shader_or_skip(&device, QUAD_BODY, "vsMain", ShaderStage::Vertex)
+    else {
+        return;
+    };
+    let Some(fs) = common::compile_shader_or_skip(&device, QUAD_BODY, "fsMain", ShaderStage::Pixel)
+    else {
+        return;
+    };
+    let Some(precise) = query_pool_or_skip(&device, QueryType::Occlusion, 2) else {
+        return;
+    };
+    let Some(binary) = query_pool_or_skip(&device, QueryType::BinaryOcclusion, 1) else {
+        return;
+    };
+    let pso = device
+        .create_graphics_pso(
+            &GraphicsPsoDesc {
+                topology: Topology::TriangleList,
+                color_targets: vec![ColorTarget::new(Format::R8G8B8A8Unorm)],
+                depth_format: None,
+                sample_count: SampleCount::S1,
+                root_constant_size: 16,
+                cull: Cull::None,
+                label: Some("occluded-quad".into()),
+                ..Default::default()
+            },
+            &vs,
+            &fs,
+        )
+        .expect("create_graphics_pso");
+    let tex_desc = TextureDesc {
+        width: SIZE,
+        height: SIZE,
+        depth: 1,
+        mip_levels: 1,
+        array_layers: 1,
+        format: Format::R8G8B8A8Unorm,
+        dimension: TextureDimension::D2,
+        sample_count: SampleCount::S1,
+        usage: TextureUsage::COLOR_ATTACHMENT,
+        label: Some("rt".into()),
+        export: None,
+    };
+    let sa = device.texture_size_align(&tex_desc).expect("size_align");
+    let tex_mem = device
+        .malloc_aligned(sa.size, sa.align, MemoryType::GpuOnly)
+        .expect("rt mem");
+    let texture = device
+        .create_texture(&tex_desc, tex_mem.gpu())
+        .expect("create_texture");
+    let results = device
+        .malloc(3 * QUERY_RESULT_SIZE, MemoryType::Readback)
+        .expect("readback");
+
+    common::timed("occlusion-queried draws · resolve · submit+wait", || {
+        let mut cmd = device.create_command_buffer().expect("cmd");
+        cmd.begin_render_pass(&RenderPassDesc {
+            color_attachments: vec![ColorAttachment {
+                target: RenderTarget::Texture(texture.id()),
+                load_op: LoadOp::Clear,
+                store_op: StoreOp::Store,
+                clear_color: [0.0, 0.0, 0.0, 1.0],
+                resolve_target: None,
+            }],
+            depth_attachment: None,
+            render_area: [0, 0, SIZE, SIZE],
+            view_mask: 0,
+        });
+        cmd.set_graphics_pipeline(&pso);
+        cmd.set_viewport(0.0, 0.0, SIZE as f32, SIZE as f32, 0.0, 1.0);
+        cmd.set_scissor(0, 0, SIZE, SIZE);
+        cmd.begin_query(&precise, 0);
+        cmd.draw(None, None, 6, 1, 0, 0);
+        cmd.end_query(&precise, 0);
+        cmd.begin_query(&precise, 1);
+        cmd.end_query(&precise, 1);
+        cmd.begin_query(&binary, 0);
+        cmd.draw(None, None, 6, 1, 0, 0);
+        cmd.end_query(&binary, 0);
+        cmd.end_render_pass();
+        cmd.resolve_queries(&precise, 0..2, results.gpu());
+        cmd.resolve_queries(&binary, 0..1, results.gpu().offset(2 * QUERY_RESULT_SIZE));
+        cmd.barrier(StageFlags::TRANSFER, StageFlags::ALL_COMMANDS);
+        cmd.end();
+        let queue = device.queue();
+        queue.submit(cmd).expect("submit");
+        queue.wait_idle();
+    });
+
+    let samples = results.as_slice::<u64>().expect("results slice");
+    assert_eq!(samples[0], (SIZE / 2 * SIZE / 2) as u64, "quad samples");
+    assert_eq!(samples[1], 0, "samples of an empty query");
+    assert_ne!(samples[2], 0, "binary query of a visible quad");
+
+    device.destroy_texture(texture);
+    device.free(tex_mem);
+    device.free(results);
+}
+
+const COUNT_BODY: &str = /*slang*/
+    r#"
+[shader("compute")]
+[numthreads(64, 1, 1)]
+void computeMain(uint3 tid : SV_DispatchThreadID, uniform uint* output)
+{
+    output[tid.x] = tid.x;
+}
+"#;
+
+/// Count the invocations of a 1024-thread dispatch with a pipeline-statistics query.
+#[test]
+fn pipeline_statistics_count_compute_invocations() {
+    let Some((device, _gpu)) = common::device_or_skip() else {
+        return;
+    };
+    let Some(pool) = query_pool_or_skip(
+        &device,
+        QueryType::PipelineStatistics(PipelineStatistics::COMPUTE_INVOCATIONS),
+        1,
+    ) else {
+        return;
+    };
+    let Some(module) =
+        common::compile_shader_or_skip(&device, COUNT_BODY, "computeMain", ShaderStage::Compute)
+    else {
+        return;
+    };
+    let pso = device
+        .create_compute_pso(
+            &ComputePsoDesc {
+                root_constant_size: 16,
+                threads_per_threadgroup: [64, 1, 1],
+                label: Some("count".into()),
+            },
+            &module,
+        )
+        .expect("create_compute_pso");
+    const N: u32 = 1024;
+    let out = device
+        .malloc((N * 4) as u64, MemoryType::GpuOnly)
+        .expect("out");
+    let results = device
+        .malloc(pool.result_size(), MemoryType::Readback)
+        .expect("readback");
+
+    common::timed(
+        "statistics-queried dispatch · resolve · submit+wait",
+        || {
+            let mut cmd = device.create_command_buffer().expect("cmd");
+            cmd.set_compute_pipeline(&pso);
+            cmd.begin_query(&pool, 0);
+            cmd.dispatch(out.gpu(), N / 64, 1, 1);
+            cmd.end_query(&pool, 0);
+            cmd.resolve_queries(&pool, 0..1, results.gpu());
+            cmd.barrier(StageFlags::TRANSFER, StageFlags::ALL_COMMANDS);
+            cmd.end();
+            let queue = device.queue();
+            queue.submit(cmd).expect("submit");
+            queue.wait_idle();
+        },
+    );
+
+    let stats = results.as_slice::<u64>().expect("results slice");
+    assert_eq!(stats, [N as u64], "compute invocations");
+
+    device.free(out);
+    device.free(results);
+}
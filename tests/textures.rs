@@ -3,8 +3,11 @@
 mod common;
 
 use kiln_rhi::{
-    ALL_LAYERS, ALL_MIPS, AddressMode, FilterMode, Format, GpuViewDesc, MemoryType, SampleCount,
-    SamplerDesc, StageFlags, TextureDesc, TextureDimension, TextureUsage,
+    ALL_LAYERS, ALL_MIPS, AddressMode, ClearValue, DEFAULT_READBACK_CHUNK_SIZE,
+    DEFAULT_STAGING_CHUNK_SIZE, FilterMode, Format, GpuViewDesc, MemoryType, Readback, RhiError,
+    SampleCount, SamplerDesc, StageFlags, TextureCompression, TextureCopyRegion, TextureDesc,
+    TextureDimension, TextureRegion, TextureSubresourceRange, TextureUsage, TransientTexturePool,
+    Uploader, format_block,
 };
 
 const W: u32 = 64;
@@ -26,6 +29,7 @@ fn test_texture_desc() -> TextureDesc {
             | TextureUsage::TRANSFER_SRC
             | TextureUsage::TRANSFER_DST,
         label: Some("rhi-test-tex".into()),
+        export: None,
     }
 }
 
@@ -106,6 +110,65 @@ fn sampler_creation() {
     });
 }
 
+/// A destroyed texture's, view's or sampler's id stays retiring until the submissions made
+/// before the destroy have finished, then the next creation reuses it.
+#[test]
+fn bindless_ids_are_recycled_after_retirement() {
+    let Some((device, _gpu)) = common::device_or_skip() else {
+        return;
+    };
+    let desc = test_texture_desc();
+    let size_align = device.texture_size_align(&desc).expect("size_align");
+    let mem = device
+        .malloc_aligned(size_align.size, size_align.align, MemoryType::GpuOnly)
+        .expect("texture backing");
+    let baseline = device.descriptor_heap_stats();
+
+    let texture = device
+        .create_texture(&desc, mem.gpu())
+        .expect("create_texture");
+    let view = device
+        .texture_view_descriptor(&texture, &GpuViewDesc::default())
+        .expect("view");
+    let sampler = device
+        .create_sampler(&SamplerDesc::default())
+        .expect("create_sampler");
+    let (texture_id, sampler_id) = (texture.id(), sampler.id());
+    let live = device.descriptor_heap_stats();
+    assert_eq!(live.textures.live, baseline.textures.live + 2);
+    assert_eq!(live.samplers.live, baseline.samplers.live + 1);
+
+    let mut cmd = device.create_command_buffer().expect("cmd");
+    cmd.end();
+    device.queue().submit(cmd).expect("submit");
+    device.destroy_texture_view(view);
+    device.destroy_texture(texture);
+    device.destroy_sampler(sampler);
+    let freed = device.descriptor_heap_stats();
+    assert_eq!(freed.textures.live, baseline.textures.live);
+    assert_eq!(freed.samplers.live, baseline.samplers.live);
+
+    device.wait_idle();
+    let texture = device
+        .create_texture(&desc, mem.gpu())
+        .expect("create_texture again");
+    let sampler = device
+        .create_sampler(&SamplerDesc::default())
+        .expect("create_sampler again");
+    let reused = device.descriptor_heap_stats();
+    assert!(
+        [texture_id, view].contains(&texture.id()),
+        "texture id not recycled"
+    );
+    assert_eq!(sampler.id(), sampler_id, "sampler id not recycled");
+    assert_eq!(reused.textures.high_water, live.textures.high_water);
+    assert_eq!(reused.samplers.high_water, live.samplers.high_water);
+
+    device.destroy_sampler(sampler);
+    device.destroy_texture(texture);
+    device.free(mem);
+}
+
 /// Upload a pattern into a texture and read it straight back out — exercises both
 /// `copy_to_texture` and `copy_from_texture` with a GPU round-trip and CPU verification.
 #[test]
@@ -159,3 +222,604 @@ fn texture_copy_roundtrip() {
     device.free(src);
     device.free(dst);
 }
+
+/// Write a padded sub-rectangle into the second array layer through a `TextureCopyRegion`,
+/// then read the whole layer back to check the rectangle landed and nothing else changed.
+#[test]
+fn texture_region_copy_roundtrip() {
+    let Some((device, _gpu)) = common::device_or_skip() else {
+        return;
+    };
+    let desc = TextureDesc {
+        array_layers: 2,
+        ..test_texture_desc()
+    };
+    let size_align = device.texture_size_align(&desc).expect("size_align");
+    let mem = device
+        .malloc_aligned(size_align.size, size_align.align, MemoryType::GpuOnly)
+        .expect("texture backing");
+    let texture = device
+        .create_texture(&desc, mem.gpu())
+        .expect("create_texture");
+
+    const RECT_OFFSET: [u32; 2] = [8, 4];
+    const RECT_W: u32 = 16;
+    const RECT_H: u32 = 8;
+    const ROW_PITCH: u32 = 256;
+    const RECT_BUFFER_OFFSET: u64 = 512;
+
+    let layer_bytes = (W as usize) * (H as usize) * BPP;
+    let zeros = device
+        .malloc(2 * layer_bytes as u64, MemoryType::Default)
+        .expect("zeros");
+    zeros
+        .upload_slice(&vec![0u8; 2 * layer_bytes])
+        .expect("zero fill");
+    let rect_bytes = RECT_BUFFER_OFFSET as usize + (ROW_PITCH * RECT_H) as usize;
+    let mut src = device
+        .malloc(rect_bytes as u64, MemoryType::Default)
+        .expect("upload");
+    for (i, b) in src
+        .as_mut_slice::<u8>()
+        .expect("src slice")
+        .iter_mut()
+        .enumerate()
+    {
+        *b = (i as u8).wrapping_mul(13).wrapping_add(1);
+    }
+    let dst = device
+        .malloc(layer_bytes as u64, MemoryType::Readback)
+        .expect("readback");
+
+    common::timed("region upload→texture→readback · submit+wait", || {
+        let mut cmd = device.create_command_buffer().expect("cmd");
+        cmd.copy_buffer_to_texture(
+            zeros.gpu(),
+            &texture,
+            &[TextureCopyRegion {
+                layer_count: 2,
+                ..TextureCopyRegion::subresource(0, 0)
+            }],
+        );
+        cmd.barrier(StageFlags::TRANSFER, StageFlags::TRANSFER);
+        cmd.copy_buffer_to_texture(
+            src.gpu(),
+            &texture,
+            &[TextureCopyRegion {
+                buffer_offset: RECT_BUFFER_OFFSET,
+                buffer_row_pitch: ROW_PITCH,
+                offset: [RECT_OFFSET[0], RECT_OFFSET[1], 0],
+                extent: [RECT_W, RECT_H, 1],
+                ..TextureCopyRegion::subresource(0, 1)
+            }],
+        );
+        cmd.barrier(StageFlags::TRANSFER, StageFlags::TRANSFER);
+        cmd.copy_texture_to_buffer(&texture, dst.gpu(), &[TextureCopyRegion::subresource(0, 1)]);
+        cmd.barrier(StageFlags::TRANSFER, StageFlags::ALL_COMMANDS);
+        cmd.end();
+        let queue = device.queue();
+        queue.submit(cmd).expect("submit");
+        queue.wait_idle();
+    });
+
+    let src_bytes = src.as_slice::<u8>().expect("src slice");
+    for (i, &b) in dst.as_slice::<u8>().expect("dst slice").iter().enumerate() {
+        let x = (i / BPP) as u32 % W;
+        let y = (i / BPP) as u32 / W;
+        let in_rect = (RECT_OFFSET[0]..RECT_OFFSET[0] + RECT_W).contains(&x)
+            && (RECT_OFFSET[1]..RECT_OFFSET[1] + RECT_H).contains(&y);
+        let expected = if in_rect {
+            let row = (y - RECT_OFFSET[1]) as usize;
+            let column = (x - RECT_OFFSET[0]) as usize * BPP + i % BPP;
+            src_bytes[RECT_BUFFER_OFFSET as usize + row * ROW_PITCH as usize + column]
+        } else {
+            0
+        };
+        assert_eq!(b, expected, "texel ({x}, {y}) byte {} mismatch", i % BPP);
+    }
+
+    device.free(zeros);
+    device.free(src);
+    device.free(dst);
+}
+
+/// Stage a buffer and a texture into `GpuOnly` memory through the `Uploader`, then read both
+/// back to verify the copies landed.
+#[test]
+fn uploader_fills_gpu_only_resources() {
+    let Some((device, _gpu)) = common::device_or_skip() else {
+        return;
+    };
+    let desc = test_texture_desc();
+    let size_align = device.texture_size_align(&desc).expect("size_align");
+    let mem = device
+        .malloc_aligned(size_align.size, size_align.align, MemoryType::GpuOnly)
+        .expect("texture backing");
+    let texture = device
+        .create_texture(&desc, mem.gpu())
+        .expect("create_texture");
+
+    let values: Vec<u32> = (0..4096u32)
+        .map(|i| i.wrapping_mul(2_654_435_761))
+        .collect();
+    let buffer_bytes = (values.len() * 4) as u64;
+    let buffer = device
+        .malloc(buffer_bytes, MemoryType::GpuOnly)
+        .expect("gpu-only buffer");
+    let texels: Vec<u8> = (0..(W as usize) * (H as usize) * BPP)
+        .map(|i| (i as u8).wrapping_mul(7).wrapping_add(3))
+        .collect();
+
+    // Small chunks force the uploads into separate staging chunks.
+    let mut uploader = Uploader::new(&device, 4096);
+    assert!(matches!(
+        uploader.upload_texture(&texture, 0, 0, &texels[1..]),
+        Err(RhiError::InvalidArgument(_))
+    ));
+    assert!(matches!(
+        uploader.upload_texture(&texture, desc.mip_levels, 0, &texels),
+        Err(RhiError::InvalidArgument(_))
+    ));
+    let token = common::timed("uploader · buffer + texture · flush+wait", || {
+        uploader
+            .upload_buffer(buffer.gpu(), &values)
+            .expect("upload_buffer");
+        uploader
+            .upload_texture(&texture, 0, 0, &texels)
+            .expect("upload_texture");
+        let token = uploader.flush().expect("flush");
+        token.wait(&device);
+        token
+    });
+    assert!(token.is_complete(&device));
+    assert!(uploader.staging_capacity() >= buffer_bytes + texels.len() as u64);
+
+    let buffer_readback = device
+        .malloc(buffer_bytes, MemoryType::Readback)
+        .expect("buffer readback");
+    let texture_readback = device
+        .malloc(texels.len() as u64, MemoryType::Readback)
+        .expect("texture readback");
+    let mut cmd = device.create_command_buffer().expect("cmd");
+    cmd.memcpy(buffer_readback.gpu(), buffer.gpu(), buffer_bytes);
+    cmd.copy_from_texture(texture_readback.gpu(), mem.gpu(), &texture);
+    cmd.barrier(StageFlags::TRANSFER, StageFlags::ALL_COMMANDS);
+    cmd.end();
+    device.queue().submit(cmd).expect("submit");
+    device.queue().wait_idle();
+
+    assert_eq!(
+        buffer_readback.as_slice::<u32>().expect("buffer slice"),
+        &values[..]
+    );
+    assert_eq!(
+        texture_readback.as_slice::<u8>().expect("texture slice"),
+        &texels[..]
+    );
+
+    drop(uploader);
+    device.destroy_texture(texture);
+    device.free(mem);
+    device.free(buffer_readback);
+    device.free(texture_readback);
+    device.free(buffer);
+}
+
+/// Upload opaque blocks to a block-compressed texture whose size is not a multiple of the block
+/// size, then read them back: copies count whole blocks, including the partial edge ones.
+#[test]
+fn compressed_texture_copy_roundtrip() {
+    let Some((device, _gpu)) = common::device_or_skip() else {
+        return;
+    };
+    let compression = device.texture_compression();
+    let format = if compression.contains(TextureCompression::BC) {
+        Format::Bc7RgbaUnorm
+    } else if compression.contains(TextureCompression::ASTC) {
+        Format::Astc4x4Unorm
+    } else if compression.contains(TextureCompression::ETC2) {
+        Format::Etc2R8G8B8A8Unorm
+    } else {
+        eprintln!("skipping: no block-compressed formats supported");
+        return;
+    };
+    let block = format_block(format).expect("compressed block");
+    assert_eq!((block.width, block.height, block.bytes), (4, 4, 16));
+
+    let desc = TextureDesc {
+        width: 18,
+        height: 10,
+        format,
+        usage: TextureUsage::SAMPLED | TextureUsage::TRANSFER_SRC | TextureUsage::TRANSFER_DST,
+        ..test_texture_desc()
+    };
+    let size_align = device.texture_size_align(&desc).expect("size_align");
+    let mem = device
+        .malloc_aligned(size_align.size, size_align.align, MemoryType::GpuOnly)
+        .expect("texture backing");
+    let texture = device
+        .create_texture(&desc, mem.gpu())
+        .expect("create_texture");
+
+    // 5x3 blocks; the copy treats them as opaque bytes.
+    let (blocks_x, blocks_y) = block.blocks(desc.width, desc.height);
+    let blocks: Vec<u8> = (0..(blocks_x * blocks_y * block.bytes) as usize)
+        .map(|i| (i as u8).wrapping_mul(29).wrapping_add(11))
+        .collect();
+
+    let mut uploader = Uploader::new(&device, DEFAULT_STAGING_CHUNK_SIZE);
+    uploader
+        .upload_texture(&texture, 0, 0, &blocks)
+        .expect("upload_texture");
+    uploader.flush().expect("flush").wait(&device);
+
+    let readback = Readback::new(&device, DEFAULT_READBACK_CHUNK_SIZE);
+    let mut cmd = device.create_command_buffer().expect("cmd");
+    let handle = readback
+        .read_texture(&mut cmd, &texture, 0, 0)
+        .expect("read_texture");
+    cmd.end();
+    common::timed("compressed upload→readback · submit+wait", || {
+        device.queue().submit(cmd).expect("submit");
+        assert_eq!(handle.wait(), &blocks[..]);
+    });
+
+    drop(handle);
+    drop(readback);
+    drop(uploader);
+    device.destroy_texture(texture);
+    device.free(mem);
+}
+
+/// `generate_mips` fills every face of an sRGB cube: a black/white checkerboard averages to
+/// mid-grey in linear space (188, not 128, once re-encoded), and a solid face stays solid.
+#[test]
+fn generate_mips_filters_srgb_cube_faces() {
+    let Some((device, _gpu)) = common::device_or_skip() else {
+        return;
+    };
+    let desc = TextureDesc {
+        width: 8,
+        height: 8,
+        mip_levels: 4,
+        format: Format::R8G8B8A8Srgb,
+        dimension: TextureDimension::Cube,
+        usage: TextureUsage::SAMPLED | TextureUsage::TRANSFER_SRC | TextureUsage::TRANSFER_DST,
+        ..test_texture_desc()
+    };
+    let size_align = device.texture_size_align(&desc).expect("size_align");
+    let mem = device
+        .malloc_aligned(size_align.size, size_align.align, MemoryType::GpuOnly)
+        .expect("texture backing");
+    let texture = device
+        .create_texture(&desc, mem.gpu())
+        .expect("create_texture");
+
+    let face_texels = |face: u32| -> Vec<u8> {
+        (0..64)
+            .flat_map(|i| {
+                let v = if face % 2 == 1 {
+                    77
+                } else if (i % 8 + i / 8) % 2 == 0 {
+                    0
+                } else {
+                    255
+                };
+                [v, v, v, 255]
+            })
+            .collect()
+    };
+    let mut uploader = Uploader::new(&device, DEFAULT_STAGING_CHUNK_SIZE);
+    for face in 0..6 {
+        uploader
+            .upload_texture(&texture, 0, face, &face_texels(face))
+            .expect("upload_texture");
+    }
+    uploader.flush().expect("flush").wait(&device);
+
+    let readback = Readback::new(&device, DEFAULT_READBACK_CHUNK_SIZE);
+    let mut cmd = device.create_command_buffer().expect("cmd");
+    cmd.generate_mips(&texture);
+    cmd.barrier(StageFlags::TRANSFER, StageFlags::TRANSFER);
+    let handles: Vec<_> = (0..6)
+        .map(|face| {
+            readback
+                .read_texture(&mut cmd, &texture, 3, face)
+                .expect("read_texture")
+        })
+        .collect();
+    cmd.end();
+    common::timed("generate_mips · cube 8x8 sRGB", || {
+        device.queue().submit(cmd).expect("submit");
+        handles[5].wait();
+    });
+
+    for (face, handle) in handles.iter().enumerate() {
+        let texel = handle.wait();
+        let expected = if face % 2 == 1 { 77 } else { 188 };
+        for &channel in &texel[..3] {
+            assert!(
+                channel.abs_diff(expected) <= 3,
+                "face {face}: mip 3 texel {texel:?}, expected ~{expected}"
+            );
+        }
+        assert_eq!(texel[3], 255, "face {face}: alpha");
+    }
+
+    drop(handles);
+    drop(readback);
+    drop(uploader);
+    device.destroy_texture(texture);
+    device.free(mem);
+}
+
+/// Targets with disjoint pass lifetimes share memory in a `TransientTexturePool`; an overlapping
+/// one gets its own range. Rebuilding with the same declarations keeps the textures.
+#[test]
+fn transient_textures_alias_disjoint_lifetimes() {
+    let Some((device, _gpu)) = common::device_or_skip() else {
+        return;
+    };
+    let target = TextureDesc {
+        usage: TextureUsage::SAMPLED | TextureUsage::STORAGE | TextureUsage::COLOR_ATTACHMENT,
+        label: Some("rhi-test-transient".into()),
+        export: None,
+        ..test_texture_desc()
+    };
+
+    let mut pool = TransientTexturePool::new();
+    let declare = |pool: &mut TransientTexturePool| {
+        (
+            pool.declare(target.clone(), 0, 1),
+            pool.declare(target.clone(), 1, 2),
+            pool.declare(target.clone(), 2, 3),
+        )
+    };
+    let (a, b, c) = declare(&mut pool);
+    common::timed("transient pool · build", || {
+        pool.build(&device).expect("build")
+    });
+
+    assert_eq!(pool.texture(a).gpu(), pool.texture(c).gpu());
+    assert_ne!(pool.texture(a).gpu(), pool.texture(b).gpu());
+    assert!(pool.memory_size() < pool.unaliased_size());
+
+    let ids = [a, b, c].map(|t| pool.texture(t).id());
+    let (a, b, c) = declare(&mut pool);
+    pool.build(&device).expect("rebuild");
+    assert_eq!([a, b, c].map(|t| pool.texture(t).id()), ids);
+
+    let mut cmd = device.create_command_buffer().expect("cmd");
+    for pass in 0..4 {
+        pool.begin_pass(&mut cmd, pass);
+    }
+    cmd.end();
+    device.queue().submit(cmd).expect("submit");
+
+    drop(pool);
+    device.wait_idle();
+    assert_eq!(device.collect_garbage(), 0);
+}
+
+/// Clear a storage-only array texture outside any render pass: the whole texture first, then
+/// one mip of one layer again with another value, and read back every subresource.
+#[test]
+fn clear_texture_covers_the_requested_range() {
+    let Some((device, _gpu)) = common::device_or_skip() else {
+        return;
+    };
+    let desc = TextureDesc {
+        mip_levels: 2,
+        array_layers: 2,
+        dimension: TextureDimension::D2Array,
+        usage: TextureUsage::STORAGE | TextureUsage::TRANSFER_SRC | TextureUsage::TRANSFER_DST,
+        ..test_texture_desc()
+    };
+    let size_align = device.texture_size_align(&desc).expect("size_align");
+    let mem = device
+        .malloc_aligned(size_align.size, size_align.align, MemoryType::GpuOnly)
+        .expect("texture backing");
+    let texture = device
+        .create_texture(&desc, mem.gpu())
+        .expect("create_texture");
+
+    // Mip 0 of both layers, then mip 1 of both layers, back to back.
+    let mut regions = Vec::new();
+    let mut offset = 0;
+    for mip in 0..2 {
+        for layer in 0..2 {
+            regions.push(TextureCopyRegion {
+                buffer_offset: offset,
+                ..TextureCopyRegion::subresource(mip, layer)
+            });
+            offset += ((W >> mip) * (H >> mip)) as u64 * BPP as u64;
+        }
+    }
+    let dst = device
+        .malloc(offset, MemoryType::Readback)
+        .expect("readback");
+
+    common::timed("clear_texture ×2 → readback · submit+wait", || {
+        let mut cmd = device.create_command_buffer().expect("cmd");
+        cmd.clear_texture(
+            &texture,
+            TextureSubresourceRange::default(),
+            ClearValue::Color([0.0, 0.2, 1.0, 1.0]),
+        );
+        cmd.barrier(StageFlags::TRANSFER, StageFlags::TRANSFER);
+        cmd.clear_texture(
+            &texture,
+            TextureSubresourceRange {
+                base_mip: 1,
+                mip_count: 1,
+                base_layer: 1,
+                layer_count: 1,
+            },
+            ClearValue::Color([1.0, 0.0, 0.0, 0.0]),
+        );
+        cmd.barrier(StageFlags::TRANSFER, StageFlags::TRANSFER);
+        cmd.copy_texture_to_buffer(&texture, dst.gpu(), &regions);
+        cmd.barrier(StageFlags::TRANSFER, StageFlags::ALL_COMMANDS);
+        cmd.end();
+        let queue = device.queue();
+        queue.submit(cmd).expect("submit");
+        queue.wait_idle();
+    });
+
+    let bytes = dst.as_slice::<u8>().expect("dst slice");
+    for region in &regions {
+        let len = ((W >> region.mip) * (H >> region.mip)) as usize * BPP;
+        let start = region.buffer_offset as usize;
+        let want: [u8; 4] = if (region.mip, region.base_layer) == (1, 1) {
+            [255, 0, 0, 0]
+        } else {
+            [0, 51, 255, 255]
+        };
+        let texels = &bytes[start..start + len];
+        assert!(
+            texels.chunks_exact(4).all(|t| t == want),
+            "mip {} layer {}: expected {want:?}, first texel {:?}",
+            region.mip,
+            region.base_layer,
+            &texels[..4]
+        );
+    }
+
+    device.free(dst);
+    device.destroy_texture(texture);
+    device.free(mem);
+}
+
+/// Copy a rectangle of a patterned texture into layer 0 of another, and blit its top-left
+/// quarter up to the whole of layer 1 with nearest filtering, then read both layers back.
+#[test]
+fn copy_texture_and_blit_texture_between_textures() {
+    let Some((device, _gpu)) = common::device_or_skip() else {
+        return;
+    };
+    let src_desc = test_texture_desc();
+    let dst_desc = TextureDesc {
+        array_layers: 2,
+        dimension: TextureDimension::D2Array,
+        ..test_texture_desc()
+    };
+    let make = |desc: &TextureDesc| {
+        let size_align = device.texture_size_align(desc).expect("size_align");
+        let mem = device
+            .malloc_aligned(size_align.size, size_align.align, MemoryType::GpuOnly)
+            .expect("texture backing");
+        let texture = device
+            .create_texture(desc, mem.gpu())
+            .expect("create_texture");
+        (mem, texture)
+    };
+    let (src_mem, src_texture) = make(&src_desc);
+    let (dst_mem, dst_texture) = make(&dst_desc);
+
+    const RECT_OFFSET: [u32; 3] = [8, 4, 0];
+    const RECT_EXTENT: [u32; 3] = [16, 8, 1];
+    const COPY_OFFSET: [u32; 3] = [40, 48, 0];
+
+    let layer_bytes = (W as usize) * (H as usize) * BPP;
+    let mut pattern = device
+        .malloc(layer_bytes as u64, MemoryType::Default)
+        .expect("upload");
+    for (i, b) in pattern
+        .as_mut_slice::<u8>()
+        .expect("pattern slice")
+        .iter_mut()
+        .enumerate()
+    {
+        *b = (i as u8).wrapping_mul(29).wrapping_add(3);
+    }
+    let dst = device
+        .malloc(2 * layer_bytes as u64, MemoryType::Readback)
+        .expect("readback");
+
+    common::timed(
+        "copy_texture + blit_texture → readback · submit+wait",
+        || {
+            let mut cmd = device.create_command_buffer().expect("cmd");
+            cmd.copy_buffer_to_texture(
+                pattern.gpu(),
+                &src_texture,
+                &[TextureCopyRegion::subresource(0, 0)],
+            );
+            cmd.clear_texture(
+                &dst_texture,
+                TextureSubresourceRange::default(),
+                ClearValue::Color([0.0; 4]),
+            );
+            cmd.barrier(StageFlags::TRANSFER, StageFlags::TRANSFER);
+            cmd.copy_texture(
+                &src_texture,
+                TextureRegion {
+                    offset: RECT_OFFSET,
+                    extent: RECT_EXTENT,
+                    ..TextureRegion::default()
+                },
+                &dst_texture,
+                TextureRegion {
+                    offset: COPY_OFFSET,
+                    ..TextureRegion::default()
+                },
+            );
+            cmd.blit_texture(
+                &src_texture,
+                TextureRegion {
+                    extent: [W / 2, H / 2, 1],
+                    ..TextureRegion::default()
+                },
+                &dst_texture,
+                TextureRegion::subresource(0, 1),
+                FilterMode::Nearest,
+            );
+            cmd.barrier(StageFlags::TRANSFER, StageFlags::TRANSFER);
+            cmd.copy_texture_to_buffer(
+                &dst_texture,
+                dst.gpu(),
+                &[TextureCopyRegion {
+                    layer_count: 2,
+                    ..TextureCopyRegion::subresource(0, 0)
+                }],
+            );
+            cmd.barrier(StageFlags::TRANSFER, StageFlags::ALL_COMMANDS);
+            cmd.end();
+            let queue = device.queue();
+            queue.submit(cmd).expect("submit");
+            queue.wait_idle();
+        },
+    );
+
+    let pattern_bytes = pattern.as_slice::<u8>().expect("pattern slice");
+    let texel = |x: u32, y: u32| {
+        let start = ((y * W + x) as usize) * BPP;
+        &pattern_bytes[start..start + BPP]
+    };
+    let bytes = dst.as_slice::<u8>().expect("dst slice");
+    for (i, got) in bytes.chunks_exact(BPP).enumerate() {
+        let layer = i / (W * H) as usize;
+        let x = (i % (W * H) as usize) as u32 % W;
+        let y = (i % (W * H) as usize) as u32 / W;
+        let expected: &[u8] = if layer == 1 {
+            texel(x / 2, y / 2)
+        } else if (COPY_OFFSET[0]..COPY_OFFSET[0] + RECT_EXTENT[0]).contains(&x)
+            && (COPY_OFFSET[1]..COPY_OFFSET[1] + RECT_EXTENT[1]).contains(&y)
+        {
+            texel(
+                x - COPY_OFFSET[0] + RECT_OFFSET[0],
+                y - COPY_OFFSET[1] + RECT_OFFSET[1],
+            )
+        } else {
+            &[0; BPP]
+        };
+        assert_eq!(got, expected, "layer {layer} texel ({x}, {y}) mismatch");
+    }
+
+    device.free(pattern);
+    device.free(dst);
+    device.destroy_texture(src_texture);
+    device.destroy_texture(dst_texture);
+    device.free(src_mem);
+    device.free(dst_mem);
+}
@@ -0,0 +1,117 @@
+//! Headless multi-threaded recording tests (timed): command buffers recorded on worker
+//! threads and submitted together with `Queue::submit_batch`.
+
+mod common;
+
+use kiln_rhi::{CommandBuffer, Device, GraphicsPso, MemoryType, Queue, StageFlags};
+
+/// The device is shared by reference across recording threads, and finished command buffers
+/// are moved back to the submitting one.
+#[test]
+fn device_is_sync_and_command_buffers_are_send() {
+    fn assert_sync<T: Send + Sync>() {}
+    fn assert_send<T: Send>() {}
+    assert_sync::<Device>();
+    assert_sync::<Queue>();
+    assert_sync::<GraphicsPso>();
+    assert_send::<CommandBuffer>();
+}
+
+/// Each of several threads records many small fills into its own slice of one buffer; the
+/// command buffers are submitted in one batch and every slice must hold its thread's pattern.
+#[test]
+fn parallel_recording_submit_batch() {
+    let Some((device, _gpu)) = common::device_or_skip() else {
+        return;
+    };
+
+    const THREADS: u32 = 4;
+    const FILLS: u64 = 256;
+    const CHUNK: u64 = 256;
+    const SLICE: u64 = FILLS * CHUNK;
+
+    let dst = device
+        .malloc(THREADS as u64 * SLICE, MemoryType::Readback)
+        .expect("dst");
+
+    for frame in 0..3u32 {
+        common::timed("record 4 threads × 256 fills · submit_batch+wait", || {
+            let cmds: Vec<CommandBuffer> = std::thread::scope(|s| {
+                let workers: Vec<_> = (0..THREADS)
+                    .map(|t| {
+                        let device = &device;
+                        let slice = dst.gpu().offset(t as u64 * SLICE);
+                        s.spawn(move || {
+                            let mut cmd = device.create_command_buffer().expect("cmd");
+                            for i in 0..FILLS {
+                                cmd.fill(slice.offset(i * CHUNK), CHUNK, pattern(frame, t));
+                            }
+                            cmd.barrier(StageFlags::TRANSFER, StageFlags::ALL_COMMANDS);
+                            cmd.end();
+                            cmd
+                        })
+                    })
+                    .collect();
+                workers
+                    .into_iter()
+                    .map(|w| w.join().expect("recording thread panicked"))
+                    .collect()
+            });
+            let queue = device.queue();
+            queue.submit_batch(cmds).expect("submit_batch");
+            queue.wait_idle();
+        });
+
+        let words = dst.as_slice::<u32>().expect("dst slice");
+        for t in 0..THREADS {
+            let slice = &words[(t as u64 * SLICE / 4) as usize..][..(SLICE / 4) as usize];
+            assert!(
+                slice.iter().all(|&w| w == pattern(frame, t)),
+                "frame {frame}: slice of thread {t} not filled"
+            );
+        }
+    }
+
+    device.free(dst);
+}
+
+/// Command buffers of a batch run in order: the second copies what the first filled.
+#[test]
+fn submit_batch_executes_in_order() {
+    let Some((device, _gpu)) = common::device_or_skip() else {
+        return;
+    };
+
+    const SIZE: u64 = 4096;
+    let scratch = device.malloc(SIZE, MemoryType::GpuOnly).expect("scratch");
+    let dst = device.malloc(SIZE, MemoryType::Readback).expect("dst");
+
+    let mut fill = device.create_command_buffer().expect("cmd");
+    fill.fill(scratch.gpu(), SIZE, 0xC0FF_EE00);
+    fill.barrier(StageFlags::TRANSFER, StageFlags::ALL_COMMANDS);
+    fill.end();
+
+    let mut copy = device.create_command_buffer().expect("cmd");
+    copy.barrier(StageFlags::ALL_COMMANDS, StageFlags::TRANSFER);
+    copy.memcpy(dst.gpu(), scratch.gpu(), SIZE);
+    copy.barrier(StageFlags::TRANSFER, StageFlags::ALL_COMMANDS);
+    copy.end();
+
+    let queue = device.queue();
+    queue.submit_batch(vec![fill, copy]).expect("submit_batch");
+    queue.wait_idle();
+
+    assert!(
+        dst.as_slice::<u32>()
+            .expect("dst slice")
+            .iter()
+            .all(|&w| w == 0xC0FF_EE00)
+    );
+
+    device.free(dst);
+    device.free(scratch);
+}
+
+fn pattern(frame: u32, thread: u32) -> u32 {
+    0x5A00_0000 | (frame << 8) | thread
+}
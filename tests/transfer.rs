@@ -2,7 +2,13 @@
 
 mod common;
 
-use kiln_rhi::{MemoryType, StageFlags};
+use std::future::Future;
+use std::pin::pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::task::{Context, Poll, Wake, Waker};
+
+use kiln_rhi::{MemoryType, Readback, StageFlags};
 
 /// Write a pattern into a CPU-mapped `Default` buffer, GPU-copy it into a `Readback`
 /// buffer, and verify the bytes came through. Reports the full submit→wait latency.
@@ -71,3 +77,167 @@ fn gpu_memcpy_size_sweep() {
         device.free(dst);
     }
 }
+
+/// GPU copies between small allocations, which share memory blocks, resolve to the right
+/// ranges: neighbouring allocations around the destination must stay untouched.
+#[test]
+fn gpu_memcpy_between_sub_allocations() {
+    let Some((device, _gpu)) = common::device_or_skip() else {
+        return;
+    };
+
+    let mut src = device.malloc(1024, MemoryType::Default).expect("src");
+    let mut before = device.malloc(256, MemoryType::Readback).expect("before");
+    let dst = device.malloc(1024, MemoryType::Readback).expect("dst");
+    let mut after = device.malloc(256, MemoryType::Readback).expect("after");
+
+    src.as_mut_slice::<u8>().expect("src slice").fill(0xA5);
+    before.as_mut_slice::<u8>().expect("before slice").fill(0);
+    after.as_mut_slice::<u8>().expect("after slice").fill(0);
+    let mut cmd = device.create_command_buffer().expect("cmd");
+    cmd.memcpy(dst.gpu(), src.gpu(), 1024);
+    cmd.barrier(StageFlags::TRANSFER, StageFlags::ALL_COMMANDS);
+    cmd.end();
+    let queue = device.queue();
+    queue.submit(cmd).expect("submit");
+    queue.wait_idle();
+
+    assert!(
+        dst.as_slice::<u8>()
+            .expect("dst")
+            .iter()
+            .all(|&b| b == 0xA5)
+    );
+    for neighbour in [&before, &after] {
+        assert!(
+            neighbour
+                .as_slice::<u8>()
+                .expect("neighbour")
+                .iter()
+                .all(|&b| b == 0),
+            "copy spilled into a neighbouring sub-allocation"
+        );
+    }
+
+    device.free(src);
+    device.free(before);
+    device.free(dst);
+    device.free(after);
+}
+
+/// Copy GPU data into a `Readback` ring and collect it without a device-wide wait: once
+/// through `wait`, once by driving the handle as a future that `collect` wakes.
+#[test]
+fn async_readback_resolves_after_submission() {
+    let Some((device, _gpu)) = common::device_or_skip() else {
+        return;
+    };
+
+    const COUNT: usize = 1024;
+    let mut src = device
+        .malloc((COUNT * 4) as u64, MemoryType::Default)
+        .expect("src");
+    for (i, v) in src
+        .as_mut_slice::<u32>()
+        .expect("src slice")
+        .iter_mut()
+        .enumerate()
+    {
+        *v = (i as u32).wrapping_mul(2_654_435_761);
+    }
+    let expected = src.as_slice::<u32>().expect("src slice").to_vec();
+
+    let readback = Readback::new(&device, 4096);
+    let mut cmd = device.create_command_buffer().expect("cmd");
+    let first = readback
+        .read_buffer::<u32>(&mut cmd, src.gpu(), COUNT)
+        .expect("read_buffer");
+    let second = readback
+        .read_buffer::<u32>(&mut cmd, src.gpu(), COUNT)
+        .expect("read_buffer");
+    cmd.barrier(StageFlags::TRANSFER, StageFlags::ALL_COMMANDS);
+    cmd.end();
+    assert!(!first.is_submitted());
+    assert!(first.try_get().is_none());
+
+    common::timed("readback 4 KiB · submit+wait", || {
+        device.queue().submit(cmd).expect("submit");
+        assert!(first.is_submitted());
+        assert_eq!(first.wait(), &expected[..]);
+    });
+
+    let flag = Arc::new(WakeFlag(AtomicBool::new(false)));
+    let waker = Waker::from(flag.clone());
+    let mut cx = Context::from_waker(&waker);
+    let mut future = pin!(second);
+    let data = match future.as_mut().poll(&mut cx) {
+        Poll::Ready(data) => data,
+        Poll::Pending => {
+            device.queue().wait_idle();
+            assert_eq!(readback.collect(), 1);
+            assert!(
+                flag.0.load(Ordering::Acquire),
+                "collect wakes the retired read"
+            );
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(data) => data,
+                Poll::Pending => panic!("readback still pending after it was woken"),
+            }
+        }
+    };
+    assert_eq!(&*data, &expected[..]);
+
+    drop(data);
+    drop(first);
+    drop(readback);
+    device.free(src);
+}
+
+/// A waker that records whether it was woken.
+struct WakeFlag(AtomicBool);
+
+impl Wake for WakeFlag {
+    fn wake(self: Arc<Self>) {
+        self.0.store(true, Ordering::Release);
+    }
+}
+
+/// Fill a buffer larger than one staging chunk with a non-uniform pattern, zero a window of
+/// it with a byte-uniform fill, then patch inside the window with an inline update.
+#[test]
+fn gpu_fill_and_update() {
+    let Some((device, _gpu)) = common::device_or_skip() else {
+        return;
+    };
+
+    const SIZE: u64 = 3 << 20;
+    const PATTERN: u32 = 0xDEAD_BEEF;
+    let dst = device.malloc(SIZE, MemoryType::Readback).expect("dst");
+    let patch: Vec<u8> = (1..=16).collect();
+
+    common::timed("fill 3 MiB + fill + update · record+submit+wait", || {
+        let mut cmd = device.create_command_buffer().expect("cmd");
+        cmd.fill(dst.gpu(), SIZE, PATTERN);
+        cmd.barrier(StageFlags::TRANSFER, StageFlags::TRANSFER);
+        cmd.fill(dst.gpu().offset(64), 256, 0);
+        cmd.barrier(StageFlags::TRANSFER, StageFlags::TRANSFER);
+        cmd.update(dst.gpu().offset(128), &patch);
+        cmd.barrier(StageFlags::TRANSFER, StageFlags::ALL_COMMANDS);
+        cmd.end();
+        let queue = device.queue();
+        queue.submit(cmd).expect("submit");
+        queue.wait_idle();
+    });
+
+    let pattern = PATTERN.to_le_bytes();
+    for (i, &b) in dst.as_slice::<u8>().expect("dst slice").iter().enumerate() {
+        let expected = match i {
+            128..144 => patch[i - 128],
+            64..320 => 0,
+            _ => pattern[i % 4],
+        };
+        assert_eq!(b, expected, "byte {i} mismatch");
+    }
+
+    device.free(dst);
+}